@@ -0,0 +1,65 @@
+#[macro_use]
+extern crate criterion;
+use criterion::{black_box, Criterion};
+
+use glib::prelude::*;
+
+const NUM_ROWS: usize = 50;
+const LABELS_PER_ROW: usize = 20;
+
+fn make_document() -> String {
+    let mut svg = String::from(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="1000" height="1000">
+  <style>
+    .label { font-size: 8px; letter-spacing: 0.5px; text-decoration: underline; }
+  </style>
+"#,
+    );
+
+    for row in 0..NUM_ROWS {
+        for column in 0..LABELS_PER_ROW {
+            svg.push_str(&format!(
+                "  <text class=\"label\" x=\"{}\" y=\"{}\">label {}</text>\n",
+                column * 50,
+                (row + 1) * 20,
+                row * LABELS_PER_ROW + column
+            ));
+        }
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+fn bench_text_labels(c: &mut Criterion) {
+    let svg = make_document();
+
+    let bytes = glib::Bytes::from_owned(svg.into_bytes());
+    let stream = gio::MemoryInputStream::from_bytes(&bytes);
+    let handle = librsvg::Loader::new()
+        .read_stream(
+            &stream.upcast::<gio::InputStream>(),
+            None::<&gio::File>,
+            None::<&gio::Cancellable>,
+        )
+        .unwrap();
+
+    let surface = cairo::ImageSurface::create(cairo::Format::ARgb32, 1000, 1000).unwrap();
+    let cr = cairo::Context::new(&surface).unwrap();
+    let viewport = cairo::Rectangle {
+        x: 0.0,
+        y: 0.0,
+        width: 1000.0,
+        height: 1000.0,
+    };
+
+    c.bench_function("render many uniformly styled labels", |b| {
+        b.iter(|| {
+            let renderer = librsvg::CairoRenderer::new(black_box(&handle));
+            renderer.render_document(&cr, &viewport).unwrap();
+        })
+    });
+}
+
+criterion_group!(benches, bench_text_labels);
+criterion_main!(benches);
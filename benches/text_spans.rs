@@ -0,0 +1,68 @@
+#[macro_use]
+extern crate criterion;
+use criterion::{black_box, Criterion};
+
+use glib::prelude::*;
+
+const NUM_LINES: usize = 100;
+const SPANS_PER_LINE: usize = 20;
+
+fn make_document() -> String {
+    let mut svg = String::from(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="1000" height="1000">
+"#,
+    );
+
+    for line in 0..NUM_LINES {
+        svg.push_str(&format!(
+            "  <text x=\"0\" y=\"{}\" font-size=\"8\">",
+            (line + 1) * 10
+        ));
+
+        for span in 0..SPANS_PER_LINE {
+            svg.push_str(&format!(
+                "<tspan dx=\"1%\" baseline-shift=\"{}\">span {}</tspan>",
+                span % 3,
+                span
+            ));
+        }
+
+        svg.push_str("</text>\n");
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+fn bench_text_spans(c: &mut Criterion) {
+    let svg = make_document();
+
+    let bytes = glib::Bytes::from_owned(svg.into_bytes());
+    let stream = gio::MemoryInputStream::from_bytes(&bytes);
+    let handle = librsvg::Loader::new()
+        .read_stream(
+            &stream.upcast::<gio::InputStream>(),
+            None::<&gio::File>,
+            None::<&gio::Cancellable>,
+        )
+        .unwrap();
+
+    let surface = cairo::ImageSurface::create(cairo::Format::ARgb32, 1000, 1000).unwrap();
+    let cr = cairo::Context::new(&surface).unwrap();
+    let viewport = cairo::Rectangle {
+        x: 0.0,
+        y: 0.0,
+        width: 1000.0,
+        height: 1000.0,
+    };
+
+    c.bench_function("render many text spans", |b| {
+        b.iter(|| {
+            let renderer = librsvg::CairoRenderer::new(black_box(&handle));
+            renderer.render_document(&cr, &viewport).unwrap();
+        })
+    });
+}
+
+criterion_group!(benches, bench_text_spans);
+criterion_main!(benches);
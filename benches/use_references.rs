@@ -0,0 +1,117 @@
+#[macro_use]
+extern crate criterion;
+use criterion::{black_box, Criterion};
+
+use glib::prelude::*;
+
+const NUM_USES: usize = 5000;
+
+const NUM_NESTED_USES: usize = 500;
+const NESTING_DEPTH: usize = 10;
+
+fn make_document() -> String {
+    let mut svg = String::from(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" width="100" height="100">
+  <defs>
+"#,
+    );
+
+    for i in 0..NUM_USES {
+        svg.push_str(&format!(
+            "    <rect id=\"r{}\" width=\"1\" height=\"1\"/>\n",
+            i
+        ));
+    }
+
+    svg.push_str("  </defs>\n");
+
+    for i in 0..NUM_USES {
+        svg.push_str(&format!(
+            "  <use xlink:href=\"#r{}\" x=\"{}\" y=\"{}\"/>\n",
+            i,
+            i % 100,
+            (i / 100) % 100
+        ));
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+// A deeply nested symbol, instanced many times from the same context, so that
+// the cascade of the instanced subtree can be shared instead of recomputed.
+fn make_nested_document() -> String {
+    let mut svg = String::from(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" width="100" height="100">
+  <defs>
+    <g id="nested">
+"#,
+    );
+
+    for i in 0..NESTING_DEPTH {
+        svg.push_str(&format!(
+            "<g stroke-width=\"{}\"><rect width=\"1\" height=\"1\"/>\n",
+            i
+        ));
+    }
+
+    for _ in 0..NESTING_DEPTH {
+        svg.push_str("</g>\n");
+    }
+
+    svg.push_str("    </g>\n  </defs>\n");
+
+    for i in 0..NUM_NESTED_USES {
+        svg.push_str(&format!(
+            "  <use xlink:href=\"#nested\" x=\"{}\" y=\"{}\"/>\n",
+            i % 100,
+            (i / 100) % 100
+        ));
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+fn load_handle(svg: String) -> librsvg::SvgHandle {
+    let bytes = glib::Bytes::from_owned(svg.into_bytes());
+    let stream = gio::MemoryInputStream::from_bytes(&bytes);
+    librsvg::Loader::new()
+        .read_stream(
+            &stream.upcast::<gio::InputStream>(),
+            None::<&gio::File>,
+            None::<&gio::Cancellable>,
+        )
+        .unwrap()
+}
+
+fn bench_document(c: &mut Criterion, name: &str, svg: String) {
+    let handle = load_handle(svg);
+
+    let surface = cairo::ImageSurface::create(cairo::Format::ARgb32, 100, 100).unwrap();
+    let cr = cairo::Context::new(&surface).unwrap();
+    let viewport = cairo::Rectangle {
+        x: 0.0,
+        y: 0.0,
+        width: 100.0,
+        height: 100.0,
+    };
+
+    c.bench_function(name, |b| {
+        b.iter(|| {
+            let renderer = librsvg::CairoRenderer::new(black_box(&handle));
+            renderer.render_document(&cr, &viewport).unwrap();
+        })
+    });
+}
+
+fn bench_use_references(c: &mut Criterion) {
+    bench_document(c, "render thousands of use references", make_document());
+}
+
+fn bench_nested_use_references(c: &mut Criterion) {
+    bench_document(c, "render nested symbol instances", make_nested_document());
+}
+
+criterion_group!(benches, bench_use_references, bench_nested_use_references);
+criterion_main!(benches);
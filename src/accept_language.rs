@@ -3,6 +3,7 @@
 use language_tags::{LanguageTag, ParseError};
 use locale_config::{LanguageRange, Locale};
 
+use std::cmp::Reverse;
 use std::error;
 use std::fmt;
 use std::str::FromStr;
@@ -245,6 +246,55 @@ impl UserLanguage {
             }
         }
     }
+
+    /// Scores how well the best of `tags` matches the user's languages.
+    ///
+    /// Returns `None` if none of the `tags` match.  Higher scores are better matches:
+    /// first, a match for a language that the user prefers more wins; then, between
+    /// matches for the same language, the tag that is closest to the user's tag wins.
+    /// So `en-GB` is a better match than `en` for someone who prefers British English,
+    /// and `en` is a better match than `en-GB` for someone who prefers plain `en`.
+    pub fn match_score(&self, tags: &LanguageTags) -> Option<(f32, Reverse<usize>)> {
+        // Earlier tags from the environment are preferred, just like higher weights.
+        let preferences: Vec<(&LanguageTag, f32)> = match *self {
+            UserLanguage::LanguageTags(ref language_tags) => language_tags
+                .iter()
+                .enumerate()
+                .map(|(i, tag)| (tag, -(i as f32)))
+                .collect(),
+
+            UserLanguage::AcceptLanguage(ref accept_language) => accept_language.iter().collect(),
+        };
+
+        let matches = |user_tag: &LanguageTag, tag: &LanguageTag| match *self {
+            UserLanguage::LanguageTags(_) => user_tag.matches(tag),
+            UserLanguage::AcceptLanguage(_) => tag.matches(user_tag),
+        };
+
+        let mut best = None;
+
+        // One of the tags is a prefix of the other when they match, so they differ by
+        // the number of subtags that only the longer one has.
+        let num_subtags = |tag: &LanguageTag| tag.as_str().split('-').count();
+
+        for tag in tags.iter() {
+            for &(user_tag, preference) in &preferences {
+                if !matches(user_tag, tag) {
+                    continue;
+                }
+
+                let distance = num_subtags(tag).max(num_subtags(user_tag))
+                    - num_subtags(tag).min(num_subtags(user_tag));
+                let score = Some((preference, Reverse(distance)));
+
+                if score > best {
+                    best = score;
+                }
+            }
+        }
+
+        best
+    }
 }
 
 /// Gets the user's preferred locale from the environment and
@@ -495,4 +545,37 @@ mod tests {
 
         assert!(iter.next().is_none());
     }
+
+    fn tags(tags: &[&str]) -> LanguageTags {
+        LanguageTags::from(
+            tags.iter()
+                .map(|s| LanguageTag::parse(s).unwrap())
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn scores_language_matches() {
+        let user = UserLanguage::LanguageTags(tags(&["en-GB", "en", "de"]));
+
+        assert!(user.match_score(&tags(&["fr"])).is_none());
+        assert!(user.match_score(&tags(&["en-GB"])) > user.match_score(&tags(&["en"])));
+        assert!(user.match_score(&tags(&["en"])) > user.match_score(&tags(&["de"])));
+        assert_eq!(
+            user.match_score(&tags(&["de", "en-GB"])),
+            user.match_score(&tags(&["en-GB"]))
+        );
+
+        // An exact match wins over a more specific tag in the user's language
+        let user = UserLanguage::LanguageTags(tags(&["en"]));
+
+        assert!(user.match_score(&tags(&["en-GB"])).is_some());
+        assert!(user.match_score(&tags(&["en"])) > user.match_score(&tags(&["en-GB"])));
+
+        let user = UserLanguage::AcceptLanguage(AcceptLanguage::parse("en-GB, de;q=0.5").unwrap());
+
+        assert!(user.match_score(&tags(&["fr"])).is_none());
+        assert!(user.match_score(&tags(&["en-GB"])) > user.match_score(&tags(&["en"])));
+        assert!(user.match_score(&tags(&["en"])) > user.match_score(&tags(&["de"])));
+    }
 }
@@ -6,8 +6,11 @@
 
 pub use crate::{
     accept_language::{AcceptLanguage, Language, UserLanguage},
+    cond::{ConditionResult, ConditionalElement},
     error::{ImplementationLimit, LoadingError, RenderingError},
-    length::{LengthUnit, RsvgLength as Length},
+    length::{RsvgLength as Length, RsvgUnit as LengthUnit},
+    text::StringMetrics,
+    text_options::{FontHinting, FontSubstitution, MissingGlyphs, TextExtents, UsedFont},
 };
 
 use url::Url;
@@ -18,11 +21,16 @@ use gio::prelude::*; // Re-exposes glib's prelude as well
 use gio::Cancellable;
 
 use crate::{
+    cond::Extensions,
     dpi::Dpi,
     handle::{Handle, LoadOptions},
+    text::{self, TextCharacters},
+    text_options::TextOptions,
     url_resolver::UrlResolver,
 };
 
+use std::rc::Rc;
+
 /// Builder for loading an [`SvgHandle`].
 ///
 /// This is the starting point for using librsvg.  This struct
@@ -35,6 +43,9 @@ use crate::{
 pub struct Loader {
     unlimited_size: bool,
     keep_image_data: bool,
+    extensions: Extensions,
+    best_language_match: bool,
+    document_fonts: bool,
 }
 
 impl Loader {
@@ -48,6 +59,9 @@ impl Loader {
     /// surfaces that support including image data in compressed
     /// formats, like PDF.
     ///
+    /// * [`document_fonts`](#method.with_document_fonts) defaults to `false`, as the
+    /// fonts of untrusted documents would get loaded into Fontconfig and FreeType.
+    ///
     /// # Example:
     ///
     /// ```
@@ -122,6 +136,73 @@ impl Loader {
         self
     }
 
+    /// Registers an extension for the `requiredExtensions` attribute.
+    ///
+    /// Elements that require an extension are only rendered if all of their
+    /// required extensions are registered, and their `predicate` returns `true`.
+    /// The predicate is called every time such an element is about to be rendered, so
+    /// it can report a capability that changes over time.  No extensions are
+    /// registered by default.
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// let svg_handle = librsvg::Loader::new()
+    ///     .with_extension("http://example.com/my-extension", || true)
+    ///     .read_path("example.svg")
+    ///     .unwrap();
+    /// ```
+    pub fn with_extension<F>(mut self, uri: &str, predicate: F) -> Self
+    where
+        F: Fn() -> bool + 'static,
+    {
+        self.extensions.register(uri, Rc::new(predicate));
+        self
+    }
+
+    /// Makes `<switch>` elements pick the child that best matches the user's languages.
+    ///
+    /// Per the SVG specification, a `<switch>` renders its first child whose conditions
+    /// pass, so the document's authors must put the most specific languages first.
+    /// With this set to `true`, a `<switch>` instead compares the `systemLanguage` of
+    /// all the children whose conditions pass, and picks the one that matches the
+    /// user's most preferred language; for example, a child for `en-GB` over one for
+    /// `en` if British English is preferred.  Children without a `systemLanguage`, or
+    /// with `systemLanguage="*"`, only get picked if no language matches.  Between
+    /// equally good matches, the first child wins.
+    ///
+    /// This defaults to `false`.
+    pub fn with_best_language_match(mut self, best: bool) -> Self {
+        self.best_language_match = best;
+        self
+    }
+
+    /// Loads the fonts of the document's `@font-face` rules.
+    ///
+    /// The fonts of `url()` sources get read while the document loads, from files next to
+    /// the document or from `data:` URLs, and WOFF and WOFF 2.0 fonts get decoded.  This
+    /// has a cost for each document: the fonts get written to temporary files, and
+    /// Fontconfig scans the system's fonts again for the font map of the document's
+    /// handle.  The fonts come from the document, so they get parsed by Fontconfig and
+    /// FreeType as well.
+    ///
+    /// Set this to `true` only for documents that you trust.  With the default of
+    /// `false`, the text in the document uses the installed fonts, and the fonts added
+    /// with [`SvgHandle::add_font_data`].
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// let svg_handle = librsvg::Loader::new()
+    ///     .with_document_fonts(true)
+    ///     .read_path("example.svg")
+    ///     .unwrap();
+    /// ```
+    pub fn with_document_fonts(mut self, load: bool) -> Self {
+        self.document_fonts = load;
+        self
+    }
+
     /// Reads an SVG document from `path`.
     ///
     /// # Example:
@@ -196,7 +277,10 @@ impl Loader {
 
         let load_options = LoadOptions::new(UrlResolver::new(base_url))
             .with_unlimited_size(self.unlimited_size)
-            .keep_image_data(self.keep_image_data);
+            .keep_image_data(self.keep_image_data)
+            .with_extensions(self.extensions)
+            .with_best_language_match(self.best_language_match)
+            .with_document_fonts(self.document_fonts);
 
         Ok(SvgHandle(Handle::from_stream(
             &load_options,
@@ -239,6 +323,44 @@ impl SvgHandle {
     pub fn set_stylesheet(&mut self, css: &str) -> Result<(), LoadingError> {
         self.0.set_stylesheet(css)
     }
+
+    /// Adds a TrueType, OpenType, WOFF, or WOFF 2.0 font for the document's text from
+    /// its `data`.
+    ///
+    /// The font's family can then be used in the `font-family` property, just like the
+    /// families of the fonts installed in the system.  This is the programmatic
+    /// counterpart to embedding a font in the document itself.
+    ///
+    /// Renderers that use [`CairoRenderer::with_font_map`] only see the fonts in their
+    /// own font map, not the ones added here.
+    ///
+    /// Returns [`LoadingError::BadFont`] if the `data` is not a font.
+    pub fn add_font_data(&mut self, data: &[u8]) -> Result<(), LoadingError> {
+        self.0.add_font_data(data)
+    }
+
+    /// Returns the text of an element's `<title>`.
+    ///
+    /// Element IDs should look like an URL fragment identifier; for example, pass
+    /// `Some("#foo")` to get the title of the element that has an `id="foo"` attribute.
+    /// Pass `None` to get the title of the whole document, i.e. the `<title>` child of
+    /// the toplevel `<svg>` element.
+    ///
+    /// Returns `Ok(None)` if the element does not have a `<title>` child.
+    pub fn title_for_element(&self, id: Option<&str>) -> Result<Option<String>, RenderingError> {
+        self.0.get_title(id)
+    }
+
+    /// Returns the text of an element's `<desc>`.
+    ///
+    /// This works like [`title_for_element`](#method.title_for_element), but for the
+    /// `<desc>` element, which holds a longer description of an element.
+    pub fn description_for_element(
+        &self,
+        id: Option<&str>,
+    ) -> Result<Option<String>, RenderingError> {
+        self.0.get_desc(id)
+    }
 }
 
 /// Can render an `SvgHandle` to a Cairo context.
@@ -246,6 +368,7 @@ pub struct CairoRenderer<'a> {
     handle: &'a SvgHandle,
     dpi: Dpi,
     user_language: UserLanguage,
+    text_options: TextOptions,
     is_testing: bool,
 }
 
@@ -290,6 +413,10 @@ impl<'a> CairoRenderer<'a> {
             handle,
             dpi: Dpi::new(DEFAULT_DPI_X, DEFAULT_DPI_Y),
             user_language: UserLanguage::new(&Language::FromEnvironment),
+            text_options: TextOptions {
+                font_map: handle.0.font_map(),
+                ..TextOptions::default()
+            },
             is_testing: false,
         }
     }
@@ -299,6 +426,9 @@ impl<'a> CairoRenderer<'a> {
     /// If an SVG document has physical units like `5cm`, they must be resolved
     /// to pixel-based values.  The default pixel density is 96 DPI in
     /// both dimensions.
+    ///
+    /// This also applies to font sizes in physical units like `12pt`; since a font
+    /// size is not horizontal nor vertical, it uses a combination of both DPI values.
     pub fn with_dpi(self, dpi_x: f64, dpi_y: f64) -> Self {
         assert!(dpi_x > 0.0);
         assert!(dpi_y > 0.0);
@@ -328,6 +458,89 @@ impl<'a> CairoRenderer<'a> {
         }
     }
 
+    /// Configures a function to be called when a font is not available.
+    ///
+    /// When an element's `font-family` names fonts that are not installed, Pango
+    /// silently substitutes a similar font.  The `callback` gets called with the
+    /// requested and actual font families each time this happens, so that applications
+    /// can tell authors why their text looks different than expected.
+    ///
+    /// Generic families like `sans-serif` are never reported as being substituted.
+    pub fn with_font_substitution_callback<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&FontSubstitution) + 'static,
+    {
+        self.text_options.font_substitution_callback = Some(Rc::new(callback));
+        self
+    }
+
+    /// Configures a function to be called when text has characters without a glyph.
+    ///
+    /// If none of the available fonts can render a character, Pango draws a placeholder
+    /// box instead, so the rendering does not show the actual text.  The `callback` gets
+    /// called with the `font-family` of the text and the characters that had no glyph,
+    /// once for each text span that has such characters, so that applications can warn
+    /// authors to pick a font that covers their text.
+    pub fn with_missing_glyphs_callback<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&MissingGlyphs) + 'static,
+    {
+        self.text_options.missing_glyphs_callback = Some(Rc::new(callback));
+        self
+    }
+
+    /// Selects which extents of the text make up the bounding box of text elements.
+    ///
+    /// By default, the bounding box of a text element covers only its painted glyphs,
+    /// i.e. [`TextExtents::Ink`].  With [`TextExtents::Logical`], it spans the font's full
+    /// ascent and descent and the glyphs' advances instead, which is useful when text
+    /// needs to be laid out next to other content.
+    pub fn with_text_extents(mut self, text_extents: TextExtents) -> Self {
+        self.text_options.text_extents = text_extents;
+        self
+    }
+
+    /// Selects whether to hint the glyph outlines of text.
+    ///
+    /// By default, librsvg uses the platform's hinting and antialiasing settings, so
+    /// the rendered text may differ slightly among machines.  Use [`FontHinting::None`]
+    /// to get the same text metrics and pixels everywhere.
+    pub fn with_font_hinting(mut self, font_hinting: FontHinting) -> Self {
+        self.text_options.font_hinting = font_hinting;
+        self
+    }
+
+    /// Uses a specific font map to find the fonts for text elements.
+    ///
+    /// By default, librsvg uses the default `PangoCairoFontMap`, which finds the fonts
+    /// installed in the system.  Applications that need control over which fonts are
+    /// available, for example to get reproducible output, can create their own font map
+    /// with only the fonts they want.
+    ///
+    /// The font map replaces the one that holds the fonts added with
+    /// [`SvgHandle::add_font_data`] and the ones from `@font-face` rules in the document,
+    /// so text only gets rendered with the fonts in `font_map`.
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use glib::Cast;
+    ///
+    /// let handle = librsvg::Loader::new()
+    ///     .read_path("example.svg")
+    ///     .unwrap();
+    ///
+    /// let font_map = pangocairo::FontMap::new()
+    ///     .and_then(|font_map| font_map.downcast::<pangocairo::FontMap>().ok())
+    ///     .unwrap();
+    ///
+    /// let renderer = librsvg::CairoRenderer::new(&handle).with_font_map(&font_map);
+    /// ```
+    pub fn with_font_map(mut self, font_map: &pangocairo::FontMap) -> Self {
+        self.text_options.font_map = Some(font_map.clone().upcast());
+        self
+    }
+
     /// Queries the `width`, `height`, and `viewBox` attributes in an SVG document.
     ///
     /// If you are calling this function to compute a scaling factor to render the SVG,
@@ -376,9 +589,14 @@ impl<'a> CairoRenderer<'a> {
         cr: &cairo::Context,
         viewport: &cairo::Rectangle,
     ) -> Result<(), RenderingError> {
-        self.handle
-            .0
-            .render_document(cr, viewport, &self.user_language, self.dpi, self.is_testing)
+        self.handle.0.render_document(
+            cr,
+            viewport,
+            &self.user_language,
+            self.dpi,
+            &self.text_options,
+            self.is_testing,
+        )
     }
 
     /// Computes the (ink_rect, logical_rect) of an SVG element, as if
@@ -412,7 +630,14 @@ impl<'a> CairoRenderer<'a> {
     ) -> Result<(cairo::Rectangle, cairo::Rectangle), RenderingError> {
         self.handle
             .0
-            .get_geometry_for_layer(id, viewport, &self.user_language, self.dpi, self.is_testing)
+            .get_geometry_for_layer(
+                id,
+                viewport,
+                &self.user_language,
+                self.dpi,
+                &self.text_options,
+                self.is_testing,
+            )
             .map(|(i, l)| (i, l))
     }
 
@@ -447,6 +672,7 @@ impl<'a> CairoRenderer<'a> {
             viewport,
             &self.user_language,
             self.dpi,
+            &self.text_options,
             self.is_testing,
         )
     }
@@ -487,10 +713,266 @@ impl<'a> CairoRenderer<'a> {
     ) -> Result<(cairo::Rectangle, cairo::Rectangle), RenderingError> {
         self.handle
             .0
-            .get_geometry_for_element(id, &self.user_language, self.dpi, self.is_testing)
+            .get_geometry_for_element(
+                id,
+                &self.user_language,
+                self.dpi,
+                &self.text_options,
+                self.is_testing,
+            )
             .map(|(i, l)| (i, l))
     }
 
+    /// Computes the bounding box of an element where it is placed in the document
+    ///
+    /// Unlike `geometry_for_element`, the element is not measured by itself: the
+    /// transforms of its ancestors are taken into account.  The resulting ink and logical
+    /// rectangles are in the user space of the element's nearest viewport, that is, of
+    /// the closest `<svg>` or `<symbol>` around it.  For an element in the toplevel SVG,
+    /// these are the coordinates of its `viewBox`.
+    ///
+    /// Nothing gets rendered for this.  Elements that are not rendered at all, like the
+    /// ones inside `<defs>`, return empty rectangles.  The `id` must be a plain fragment
+    /// identifier like `#foo`.
+    pub fn bounding_box_for_element(
+        &self,
+        id: &str,
+    ) -> Result<(cairo::Rectangle, cairo::Rectangle), RenderingError> {
+        self.handle.0.get_bbox_in_viewport_for_element(
+            id,
+            &self.user_language,
+            self.dpi,
+            &self.text_options,
+            self.is_testing,
+        )
+    }
+
+    /// Computes the outlines of a single SVG element's shapes and text
+    ///
+    /// Text gets laid out and positioned just as it would be for rendering, but its glyphs
+    /// are returned as vector outlines instead of being painted.  This is useful when text
+    /// needs to be turned into paths, for example for engraving or for rendering without
+    /// depending on the available fonts.  No rendering takes place.
+    ///
+    /// Like with `geometry_for_element`, the element is processed as if it were rendered
+    /// under an identity transformation by itself; its own `transform` is taken into
+    /// account.  Stroke widths, fills and other painting properties are ignored.
+    ///
+    /// Element IDs should look like an URL fragment identifier; for example, pass
+    /// `Some("#foo")` to get the outlines of the element that has an `id="foo"`
+    /// attribute.  Pass `None` to get the outlines of the whole document.
+    pub fn outline_for_element(&self, id: Option<&str>) -> Result<cairo::Path, RenderingError> {
+        self.handle.0.get_outline_for_element(
+            id,
+            &self.user_language,
+            self.dpi,
+            &self.text_options,
+            self.is_testing,
+        )
+    }
+
+    /// Computes the outlines of a single SVG element's shapes and text as SVG path data
+    ///
+    /// This is like `outline_for_element`, but the outlines are returned as a string
+    /// suitable for the `d` attribute of a `<path>` element, made of `M`, `L`, `C`, and
+    /// `Z` commands with absolute coordinates.  This can be used to "flatten" text to paths,
+    /// so that it renders the same where its fonts are not available.
+    pub fn outline_path_data_for_element(
+        &self,
+        id: Option<&str>,
+    ) -> Result<String, RenderingError> {
+        self.handle.0.get_outline_path_data_for_element(
+            id,
+            &self.user_language,
+            self.dpi,
+            &self.text_options,
+            self.is_testing,
+        )
+    }
+
+    /// Computes the clickable regions of text inside `<a>` links
+    ///
+    /// The document is laid out as if it were rendered with `render_document` to the
+    /// same `viewport`, but nothing gets painted.  Each line of text inside a link
+    /// produces one rectangle, paired with the link's target.  Interactive viewers can
+    /// use these to find out which link is under the pointer.
+    pub fn link_regions(
+        &self,
+        viewport: &cairo::Rectangle,
+    ) -> Result<Vec<(cairo::Rectangle, String)>, RenderingError> {
+        self.handle.0.get_link_regions(
+            viewport,
+            &self.user_language,
+            self.dpi,
+            &self.text_options,
+            self.is_testing,
+        )
+    }
+
+    /// Finds the fonts that the document's text gets rendered with
+    ///
+    /// The document is laid out as if it were rendered with `render_document` to the
+    /// same `viewport`, but nothing gets painted.  The result has each font face that
+    /// Pango picked for the text once, after substitutions and fallbacks, in the order
+    /// in which they are first used.  This is useful to find out which fonts need to
+    /// be shipped or licensed along with a document.
+    ///
+    /// Text that only appears inside patterns or masks is not laid out, so its fonts
+    /// are not included.
+    pub fn used_fonts(&self, viewport: &cairo::Rectangle) -> Result<Vec<UsedFont>, RenderingError> {
+        self.handle.0.get_used_fonts(
+            viewport,
+            &self.user_language,
+            self.dpi,
+            &self.text_options,
+            self.is_testing,
+        )
+    }
+
+    /// Tells how the conditional processing attributes of each element evaluate.
+    ///
+    /// The result has an entry for every element that has `requiredFeatures`,
+    /// `requiredExtensions`, or `systemLanguage` attributes, in document order, with
+    /// the reasons why each condition passed or failed for this renderer's
+    /// [language](#method.with_language).  This is useful to find out why a `<switch>`
+    /// picked a particular child.
+    pub fn conditional_elements(&self) -> Vec<ConditionalElement> {
+        self.handle.0.get_conditional_elements(&self.user_language)
+    }
+
+    /// Counts the addressable characters of a text element
+    ///
+    /// This is like `getNumberOfChars` from the SVG DOM.  Characters get counted as in
+    /// `start_position_of_char`, so the valid indices for it and the other per-character
+    /// queries go from 0 up to this number.
+    pub fn number_of_chars(&self, id: &str) -> Result<usize, RenderingError> {
+        Ok(self.text_characters(id)?.number_of_chars())
+    }
+
+    /// Computes where the glyph for a character of a text element starts
+    ///
+    /// This is like `getStartPositionOfChar` from the SVG DOM.  The `index` counts the
+    /// addressable characters of the element's text content: grapheme clusters, not
+    /// including white space that was collapsed.  The result is the point on the
+    /// baseline at the start of the glyph, in the element's user space.
+    ///
+    /// Returns `Ok(None)` if the element has no character at `index`.  The `id` must be
+    /// a plain fragment identifier like `#foo`.
+    pub fn start_position_of_char(
+        &self,
+        id: &str,
+        index: usize,
+    ) -> Result<Option<(f64, f64)>, RenderingError> {
+        Ok(self.text_characters(id)?.start_position_of_char(index))
+    }
+
+    /// Computes the total advance of the characters in a text element
+    ///
+    /// This is like `getComputedTextLength` from the SVG DOM.  The result includes
+    /// letter and word spacing, and the adjustments from the `textLength` attribute,
+    /// in the element's user space.  Shifts from `dx` and `dy` are not advances, so
+    /// they are not included.
+    pub fn computed_text_length(&self, id: &str) -> Result<f64, RenderingError> {
+        Ok(self.text_characters(id)?.computed_text_length())
+    }
+
+    /// Computes the total advance of a range of characters in a text element
+    ///
+    /// This is like `getSubStringLength` from the SVG DOM.  The range starts at the
+    /// addressable character `charnum` and has `nchars` characters, or less if the text
+    /// ends before.  Characters get counted as in `start_position_of_char`.
+    ///
+    /// Returns `Ok(None)` if the element has no character at `charnum`.
+    pub fn sub_string_length(
+        &self,
+        id: &str,
+        charnum: usize,
+        nchars: usize,
+    ) -> Result<Option<f64>, RenderingError> {
+        Ok(self.text_characters(id)?.sub_string_length(charnum, nchars))
+    }
+
+    /// Computes the extents of a character in a text element
+    ///
+    /// This is like `getExtentOfChar` from the SVG DOM.  The rectangle covers the
+    /// character's advance and the height of its line, in the element's user space.  If
+    /// the glyph is rotated with the `rotate` attribute, this is the bounding box of the
+    /// rotated cell.  Characters get counted as in `start_position_of_char`.
+    ///
+    /// Returns `Ok(None)` if the element has no character at `index`.
+    pub fn extent_of_char(
+        &self,
+        id: &str,
+        index: usize,
+    ) -> Result<Option<cairo::Rectangle>, RenderingError> {
+        Ok(self
+            .text_characters(id)?
+            .extent_of_char(index)
+            .map(cairo::Rectangle::from))
+    }
+
+    /// Computes the rectangles that cover a range of characters in a text element
+    ///
+    /// This is for drawing the highlight of a text selection.  The range goes from the
+    /// addressable character `start` up to, but not including, `end`; characters get
+    /// counted as in `start_position_of_char`.  There is one rectangle for each run of
+    /// selected characters on a line, so a selection that crosses lines, chunks, or
+    /// changes of direction gets several of them.  The rectangles cover the height of
+    /// their line, and are in the element's user space.
+    ///
+    /// Returns an empty list if the range has no characters.
+    pub fn selection_extents(
+        &self,
+        id: &str,
+        start: usize,
+        end: usize,
+    ) -> Result<Vec<cairo::Rectangle>, RenderingError> {
+        Ok(self
+            .text_characters(id)?
+            .selection_extents(start, end)
+            .into_iter()
+            .map(cairo::Rectangle::from)
+            .collect())
+    }
+
+    /// Computes the rotation of a character's glyph in a text element
+    ///
+    /// This is like `getRotationOfChar` from the SVG DOM.  The result is in degrees, and
+    /// comes from the `rotate` attribute; glyphs that are not rotated return 0.
+    /// Characters get counted as in `start_position_of_char`.
+    ///
+    /// Returns `Ok(None)` if the element has no character at `index`.
+    pub fn rotation_of_char(&self, id: &str, index: usize) -> Result<Option<f64>, RenderingError> {
+        Ok(self.text_characters(id)?.rotation_of_char(index))
+    }
+
+    /// Finds the character of a text element at a point
+    ///
+    /// This is like `getCharNumAtPosition` from the SVG DOM, with `Ok(None)` in place of
+    /// -1 for when there is no character at the point.  The point is in the element's user
+    /// space, and gets tested against the cells from `extent_of_char` before they are
+    /// rotated into their bounding boxes.  If several cells contain the point, this
+    /// returns the character that is drawn last.  Characters get counted as in
+    /// `start_position_of_char`.
+    pub fn char_num_at_position(
+        &self,
+        id: &str,
+        x: f64,
+        y: f64,
+    ) -> Result<Option<usize>, RenderingError> {
+        Ok(self.text_characters(id)?.char_num_at_position(x, y))
+    }
+
+    fn text_characters(&self, id: &str) -> Result<TextCharacters, RenderingError> {
+        self.handle.0.get_text_characters(
+            id,
+            &self.user_language,
+            self.dpi,
+            &self.text_options,
+            self.is_testing,
+        )
+    }
+
     /// Renders a single SVG element to a given viewport
     ///
     /// This function can be used to extract individual element subtrees and render them,
@@ -519,6 +1001,7 @@ impl<'a> CairoRenderer<'a> {
             element_viewport,
             &self.user_language,
             self.dpi,
+            &self.text_options,
             self.is_testing,
         )
     }
@@ -531,3 +1014,34 @@ impl<'a> CairoRenderer<'a> {
         }
     }
 }
+
+/// Measures a string as librsvg would lay it out in a `<text>` element.
+///
+/// The `style` has CSS declarations for the text, like the value of a `style`
+/// attribute, for example, `"font-family: Sans; font-size: 20px"`.  The properties that
+/// it does not mention have their initial values, and invalid declarations are ignored.
+/// Lengths get resolved at 96 DPI, so the returned metrics are in pixels.
+///
+/// The string gets laid out with a Pango context of its own, created from the
+/// `font_map`.
+///
+/// # Example:
+///
+/// ```
+/// use glib::Cast;
+///
+/// let font_map = pangocairo::FontMap::default()
+///     .and_then(|font_map| font_map.downcast::<pangocairo::FontMap>().ok())
+///     .unwrap();
+/// let metrics = librsvg::measure_string(&font_map, "font-size: 20px", "Hello");
+///
+/// assert!(metrics.width > 0.0);
+/// ```
+pub fn measure_string(font_map: &pangocairo::FontMap, style: &str, text: &str) -> StringMetrics {
+    text::measure_string_with_style(
+        font_map.upcast_ref(),
+        style,
+        text,
+        Dpi::new(DEFAULT_DPI_X, DEFAULT_DPI_Y),
+    )
+}
@@ -0,0 +1,452 @@
+//! Fonts that applications provide from memory, in addition to the system's fonts.
+//!
+//! Fontconfig can only add application fonts from files, so the font data gets written
+//! to a temporary file that lives as long as the [`AppFonts`].  Fontconfig and Pango's
+//! Fontconfig font map are not bound in gtk-rs, so this has a hand-written binding to
+//! the few functions that we need.
+//!
+//! Fonts from the `@font-face` rules of a document get added in the same way, with the
+//! family name of their rule added to the ones in the font itself.
+//!
+//! On Windows, librsvg can be built without Fontconfig and PangoFT2.  Then adding fonts
+//! from memory is not supported, and [`AppFonts::new`] always returns `None`.
+
+#[cfg(all(system_deps_have_fontconfig, system_deps_have_pangoft2))]
+pub use self::fontconfig::AppFonts;
+
+#[cfg(not(all(system_deps_have_fontconfig, system_deps_have_pangoft2)))]
+pub use self::unsupported::AppFonts;
+
+#[cfg(all(system_deps_have_fontconfig, system_deps_have_pangoft2))]
+mod fontconfig {
+    #![allow(non_camel_case_types)]
+
+    use glib::translate::*;
+    use std::ffi::{CStr, CString};
+    use std::fs::{self, OpenOptions};
+    use std::io::{self, Write};
+    use std::path::PathBuf;
+    use std::process;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use crate::error::LoadingError;
+    use crate::font_face::{FontFace, FontFaceSource};
+    use crate::io::{self as rsvg_io, BinaryData};
+    use crate::url_resolver::UrlResolver;
+    use crate::woff;
+    use crate::woff2;
+
+    #[cfg(unix)]
+    use std::os::unix::fs::OpenOptionsExt;
+
+    type FcBool = libc::c_int;
+
+    #[repr(C)]
+    struct FcPattern {
+        _private: [u8; 0],
+    }
+
+    #[repr(C)]
+    struct FcConfig {
+        _private: [u8; 0],
+    }
+
+    #[repr(C)]
+    struct FcObjectSet {
+        _private: [u8; 0],
+    }
+
+    #[repr(C)]
+    struct FcFontSet {
+        nfont: libc::c_int,
+        _sfont: libc::c_int,
+        fonts: *mut *mut FcPattern,
+    }
+
+    /// The `FcSetApplication` value of the `FcSetName` enum.
+    const FC_SET_APPLICATION: libc::c_int = 1;
+
+    /// The `FcResultMatch` value of the `FcResult` enum.
+    const FC_RESULT_MATCH: libc::c_int = 0;
+
+    // Names of the pattern properties that we use.
+    const FC_FAMILY: &[u8] = b"family\0";
+    const FC_FULLNAME: &[u8] = b"fullname\0";
+    const FC_POSTSCRIPT_NAME: &[u8] = b"postscriptname\0";
+    const FC_FILE: &[u8] = b"file\0";
+    const FC_INDEX: &[u8] = b"index\0";
+
+    extern "C" {
+        fn FcInitLoadConfigAndFonts() -> *mut FcConfig;
+
+        fn FcConfigDestroy(config: *mut FcConfig);
+
+        fn FcConfigAppFontAddFile(config: *mut FcConfig, file: *const libc::c_char) -> FcBool;
+
+        fn FcConfigGetFonts(config: *mut FcConfig, set: libc::c_int) -> *mut FcFontSet;
+
+        fn FcPatternCreate() -> *mut FcPattern;
+
+        fn FcPatternDestroy(pattern: *mut FcPattern);
+
+        fn FcPatternAddString(
+            pattern: *mut FcPattern,
+            object: *const libc::c_char,
+            s: *const libc::c_char,
+        ) -> FcBool;
+
+        fn FcPatternGetString(
+            pattern: *const FcPattern,
+            object: *const libc::c_char,
+            n: libc::c_int,
+            s: *mut *const libc::c_char,
+        ) -> libc::c_int;
+
+        fn FcPatternGetInteger(
+            pattern: *const FcPattern,
+            object: *const libc::c_char,
+            n: libc::c_int,
+            i: *mut libc::c_int,
+        ) -> libc::c_int;
+
+        fn FcObjectSetCreate() -> *mut FcObjectSet;
+
+        fn FcObjectSetAdd(os: *mut FcObjectSet, object: *const libc::c_char) -> FcBool;
+
+        fn FcObjectSetDestroy(os: *mut FcObjectSet);
+
+        fn FcFontList(
+            config: *mut FcConfig,
+            pattern: *mut FcPattern,
+            os: *mut FcObjectSet,
+        ) -> *mut FcFontSet;
+
+        fn FcFontSetDestroy(set: *mut FcFontSet);
+
+        fn pango_fc_font_map_set_config(
+            font_map: *mut pango::ffi::PangoFontMap,
+            config: *mut FcConfig,
+        );
+
+        fn pango_fc_font_map_config_changed(font_map: *mut pango::ffi::PangoFontMap);
+    }
+
+    /// Fonts loaded from memory, and a font map that finds them along with the system's fonts.
+    pub struct AppFonts {
+        config: *mut FcConfig,
+        font_map: pango::FontMap,
+        files: Vec<PathBuf>,
+    }
+
+    impl AppFonts {
+        /// Creates a font map with the system's fonts.
+        ///
+        /// Returns `None` if Cairo does not support FreeType fonts on this platform.
+        pub fn new() -> Option<AppFonts> {
+            let font_map = pangocairo::FontMap::for_font_type(cairo::FontType::FontTypeFt)?;
+
+            unsafe {
+                let config = FcInitLoadConfigAndFonts();
+                if config.is_null() {
+                    return None;
+                }
+
+                pango_fc_font_map_set_config(font_map.to_glib_none().0, config);
+
+                Some(AppFonts {
+                    config,
+                    font_map,
+                    files: Vec::new(),
+                })
+            }
+        }
+
+        /// The font map to lay out text with.
+        pub fn font_map(&self) -> &pango::FontMap {
+            &self.font_map
+        }
+
+        /// Adds a TrueType, OpenType, WOFF, or WOFF 2.0 font from its `data`.
+        ///
+        /// Its family becomes available to the text in the document, just like the
+        /// families of the fonts installed in the system.
+        pub fn add_font_data(&mut self, data: &[u8]) -> Result<(), LoadingError> {
+            self.add_font(data, None)
+        }
+
+        /// Adds the font of a `@font-face` rule, which can then be used with the rule's
+        /// family name.
+        ///
+        /// The sources of the rule get tried in order, until one of them loads.
+        pub fn add_font_face(&mut self, face: &FontFace) -> Result<(), LoadingError> {
+            for source in face.sources.iter().filter(|s| s.has_supported_format()) {
+                let res = match *source {
+                    FontFaceSource::Local(ref name) => self.add_local_font(name, &face.family),
+
+                    FontFaceSource::Url(ref href, _) => acquire_font_data(href, &face.url_resolver)
+                        .and_then(|data| self.add_font(&data, Some(face.family.as_str()))),
+                };
+
+                match res {
+                    Ok(()) => return Ok(()),
+                    Err(e) => rsvg_log!("could not load font for \"{}\": {}", face.family, e),
+                }
+            }
+
+            Err(LoadingError::BadFont)
+        }
+
+        /// Adds a font from its `data`, with an extra `family` name if given.
+        fn add_font(&mut self, data: &[u8], family: Option<&str>) -> Result<(), LoadingError> {
+            let sfnt;
+
+            let data = if woff::is_woff(data) {
+                sfnt = woff::decode_woff(data).map_err(|_| LoadingError::BadFont)?;
+                &sfnt[..]
+            } else if woff::is_woff2(data) {
+                sfnt = woff2::decode_woff2(data).map_err(|_| LoadingError::BadFont)?;
+                &sfnt[..]
+            } else {
+                data
+            };
+
+            let path = write_temp_font(data).map_err(|e| LoadingError::Io(e.to_string()))?;
+
+            let path_cstring = CString::new(path.to_string_lossy().as_bytes())
+                .map_err(|_| LoadingError::Io(String::from("invalid temporary file name")))?;
+
+            unsafe {
+                // Fontconfig succeeds for files that it does not recognize as fonts; it
+                // just adds nothing.
+                let before = self.num_fonts();
+                let added = FcConfigAppFontAddFile(self.config, path_cstring.as_ptr()) != 0
+                    && self.num_fonts() > before;
+
+                if !added {
+                    let _ = fs::remove_file(&path);
+                    return Err(LoadingError::BadFont);
+                }
+
+                self.files.push(path);
+
+                if let Some(family) = family {
+                    self.add_family_name(before, None, family)?;
+                }
+
+                pango_fc_font_map_config_changed(self.font_map.to_glib_none().0);
+            }
+
+            Ok(())
+        }
+
+        /// Adds an installed font, found by its full name or PostScript `name`, with an
+        /// extra `family` name.
+        ///
+        /// The font's file gets added as an application font, so that only the face
+        /// with that name gets the extra family name.
+        fn add_local_font(&mut self, name: &str, family: &str) -> Result<(), LoadingError> {
+            let name = CString::new(name).map_err(|_| LoadingError::BadFont)?;
+
+            unsafe {
+                let (file, index) = self.find_local_font(&name).ok_or_else(|| {
+                    LoadingError::Other(format!("no installed font is named {:?}", name))
+                })?;
+
+                // Like in add_font(), Fontconfig may succeed without adding anything.
+                let before = self.num_fonts();
+
+                if FcConfigAppFontAddFile(self.config, file.as_ptr()) == 0
+                    || self.num_fonts() <= before
+                {
+                    return Err(LoadingError::BadFont);
+                }
+
+                self.add_family_name(before, Some(index), family)?;
+
+                pango_fc_font_map_config_changed(self.font_map.to_glib_none().0);
+            }
+
+            Ok(())
+        }
+
+        /// Finds the file and the face index of the font with a full name or PostScript
+        /// name, like the `local()` sources of `@font-face` rules do.
+        unsafe fn find_local_font(&self, name: &CStr) -> Option<(CString, libc::c_int)> {
+            for object in &[FC_FULLNAME, FC_POSTSCRIPT_NAME] {
+                let pattern = FcPatternCreate();
+                let object_set = FcObjectSetCreate();
+
+                FcPatternAddString(pattern, object.as_ptr() as *const _, name.as_ptr());
+                FcObjectSetAdd(object_set, FC_FILE.as_ptr() as *const _);
+                FcObjectSetAdd(object_set, FC_INDEX.as_ptr() as *const _);
+
+                let fonts = FcFontList(self.config, pattern, object_set);
+
+                FcObjectSetDestroy(object_set);
+                FcPatternDestroy(pattern);
+
+                if fonts.is_null() {
+                    continue;
+                }
+
+                let found = if (*fonts).nfont > 0 {
+                    file_and_index(*(*fonts).fonts)
+                } else {
+                    None
+                };
+
+                FcFontSetDestroy(fonts);
+
+                if found.is_some() {
+                    return found;
+                }
+            }
+
+            None
+        }
+
+        /// Adds a family name to the application fonts from index `first` on, or just to
+        /// the ones for the face at `index` in their file.
+        ///
+        /// The fonts keep the family names from their own name table, but Fontconfig
+        /// matches any of them.
+        unsafe fn add_family_name(
+            &self,
+            first: libc::c_int,
+            index: Option<libc::c_int>,
+            family: &str,
+        ) -> Result<(), LoadingError> {
+            let family = CString::new(family).map_err(|_| LoadingError::BadFont)?;
+            let set = FcConfigGetFonts(self.config, FC_SET_APPLICATION);
+
+            if set.is_null() {
+                return Err(LoadingError::BadFont);
+            }
+
+            for i in first..(*set).nfont {
+                let pattern = *(*set).fonts.offset(i as isize);
+
+                if index.is_some() && file_and_index(pattern).map(|(_, i)| i) != index {
+                    continue;
+                }
+
+                if FcPatternAddString(pattern, FC_FAMILY.as_ptr() as *const _, family.as_ptr()) == 0
+                {
+                    return Err(LoadingError::OutOfMemory(String::from(
+                        "could not name font",
+                    )));
+                }
+            }
+
+            Ok(())
+        }
+
+        unsafe fn num_fonts(&self) -> libc::c_int {
+            let set = FcConfigGetFonts(self.config, FC_SET_APPLICATION);
+
+            if set.is_null() {
+                0
+            } else {
+                (*set).nfont
+            }
+        }
+    }
+
+    impl Drop for AppFonts {
+        fn drop(&mut self) {
+            unsafe {
+                FcConfigDestroy(self.config);
+            }
+
+            for path in &self.files {
+                let _ = fs::remove_file(path);
+            }
+        }
+    }
+
+    /// Gets the file name and the face index of a font's pattern.
+    unsafe fn file_and_index(pattern: *const FcPattern) -> Option<(CString, libc::c_int)> {
+        let mut file = std::ptr::null();
+        let mut index = 0;
+
+        if FcPatternGetString(pattern, FC_FILE.as_ptr() as *const _, 0, &mut file)
+            == FC_RESULT_MATCH
+            && FcPatternGetInteger(pattern, FC_INDEX.as_ptr() as *const _, 0, &mut index)
+                == FC_RESULT_MATCH
+        {
+            Some((CStr::from_ptr(file).to_owned(), index))
+        } else {
+            None
+        }
+    }
+
+    /// Loads the data of a font from the `url()` of a `@font-face` source.
+    fn acquire_font_data(href: &str, url_resolver: &UrlResolver) -> Result<Vec<u8>, LoadingError> {
+        let aurl = url_resolver
+            .resolve_href(href)
+            .map_err(|_| LoadingError::BadUrl)?;
+
+        let BinaryData { data, .. } = rsvg_io::acquire_data(&aurl, None)?;
+
+        Ok(data)
+    }
+
+    /// Writes font data to a new file in the temporary directory, and returns its path.
+    ///
+    /// The file gets created only if it did not exist, and on Unix only its owner can read
+    /// it, so other users can neither replace the font nor read it.
+    fn write_temp_font(data: &[u8]) -> io::Result<PathBuf> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        loop {
+            let n = COUNTER.fetch_add(1, Ordering::SeqCst);
+            let path = std::env::temp_dir().join(format!("librsvg-font-{}-{}", process::id(), n));
+
+            let mut options = OpenOptions::new();
+            options.write(true).create_new(true);
+
+            #[cfg(unix)]
+            options.mode(0o600);
+
+            let mut file = match options.open(&path) {
+                Ok(file) => file,
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e),
+            };
+
+            if let Err(e) = file.write_all(data) {
+                let _ = fs::remove_file(&path);
+                return Err(e);
+            }
+
+            return Ok(path);
+        }
+    }
+}
+
+#[cfg(not(all(system_deps_have_fontconfig, system_deps_have_pangoft2)))]
+mod unsupported {
+    use crate::error::LoadingError;
+    use crate::font_face::FontFace;
+
+    /// Without Fontconfig there is no way to add fonts from memory, so this can't exist.
+    pub enum AppFonts {}
+
+    impl AppFonts {
+        pub fn new() -> Option<AppFonts> {
+            None
+        }
+
+        pub fn font_map(&self) -> &pango::FontMap {
+            match *self {}
+        }
+
+        pub fn add_font_data(&mut self, _data: &[u8]) -> Result<(), LoadingError> {
+            match *self {}
+        }
+
+        pub fn add_font_face(&mut self, _face: &FontFace) -> Result<(), LoadingError> {
+            match *self {}
+        }
+    }
+}
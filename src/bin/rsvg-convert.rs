@@ -21,12 +21,10 @@ mod windows_imports {
 use self::windows_imports::*;
 
 use librsvg::rsvg_convert_only::{
-    CssLength, Dpi, Horizontal, LegacySize, Length, Normalize, NormalizeParams, PathOrUrl, ULength,
-    Validate, Vertical,
-};
-use librsvg::{
-    AcceptLanguage, CairoRenderer, Color, Language, LengthUnit, Loader, Parse, RenderingError,
+    CssLength, Dpi, Horizontal, LegacySize, Length, LengthUnit, Normalize, NormalizeParams,
+    PathOrUrl, ULength, Validate, Vertical,
 };
+use librsvg::{AcceptLanguage, CairoRenderer, Color, Language, Loader, Parse, RenderingError};
 use once_cell::unsync::OnceCell;
 use std::ops::Deref;
 use std::path::PathBuf;
@@ -1002,7 +1000,7 @@ fn is_absolute_unit(u: LengthUnit) -> bool {
     use LengthUnit::*;
 
     match u {
-        Percent | Em | Ex => false,
+        Percent | Em | Ex | Ch | Rem => false,
         Px | In | Cm | Mm | Pt | Pc => true,
     }
 }
@@ -3,9 +3,13 @@
 #[allow(unused_imports, deprecated)]
 use std::ascii::AsciiExt;
 
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::rc::Rc;
 use std::str::FromStr;
 
 use language_tags::LanguageTag;
+use markup5ever::{expanded_name, local_name, namespace_url, ns, QualName};
 
 use crate::accept_language::{LanguageTags, UserLanguage};
 use crate::error::*;
@@ -13,41 +17,91 @@ use crate::error::*;
 // No extensions at the moment.
 static IMPLEMENTED_EXTENSIONS: &[&str] = &[];
 
+/// Extensions that the application registered for `requiredExtensions`.
+///
+/// Each extension has a predicate that gets called every time an element that
+/// requires the extension is about to be rendered, so that an application can
+/// report capabilities that change over time.  The registry is empty by default.
+#[derive(Clone, Default)]
+pub struct Extensions(HashMap<String, Rc<dyn Fn() -> bool>>);
+
+impl Extensions {
+    /// Registers an extension by its URI, replacing any earlier one with the same URI.
+    pub fn register(&mut self, uri: &str, predicate: Rc<dyn Fn() -> bool>) {
+        self.0.insert(uri.to_string(), predicate);
+    }
+
+    /// Whether an extension is supported, either by librsvg or by the application.
+    pub fn is_supported(&self, uri: &str) -> bool {
+        IMPLEMENTED_EXTENSIONS.binary_search(&uri).is_ok()
+            || self
+                .0
+                .get(uri)
+                .map(|predicate| predicate())
+                .unwrap_or(false)
+    }
+}
+
 #[derive(Debug, PartialEq)]
-pub struct RequiredExtensions(pub bool);
+pub struct RequiredExtensions(Vec<String>);
 
 impl RequiredExtensions {
     /// Parse a requiredExtensions attribute.
     ///
+    /// The extensions are only looked up in [`RequiredExtensions::eval`], since the
+    /// predicates of the registered ones can give different results over time.
+    ///
     /// http://www.w3.org/TR/SVG/struct.html#RequiredExtensionsAttribute
     pub fn from_attribute(s: &str) -> Result<RequiredExtensions, ValueErrorKind> {
         Ok(RequiredExtensions(
-            s.split_whitespace()
-                .all(|f| IMPLEMENTED_EXTENSIONS.binary_search(&f).is_ok()),
+            s.split_whitespace().map(String::from).collect(),
         ))
     }
 
     /// Evaluate a requiredExtensions value for conditional processing.
-    pub fn eval(&self) -> bool {
-        self.0
+    pub fn eval(&self, extensions: &Extensions) -> bool {
+        self.0.iter().all(|uri| extensions.is_supported(uri))
+    }
+
+    fn explain(&self, extensions: &Extensions) -> (bool, String) {
+        let unsupported: Vec<&str> = self
+            .0
+            .iter()
+            .map(String::as_str)
+            .filter(|uri| !extensions.is_supported(uri))
+            .collect();
+
+        if unsupported.is_empty() {
+            (true, String::from("all the extensions are supported"))
+        } else {
+            (
+                false,
+                format!("unsupported extensions: {}", unsupported.join(" ")),
+            )
+        }
     }
 }
 
 // Keep these sorted alphabetically for binary_search.
 static IMPLEMENTED_FEATURES: &[&str] = &[
+    "http://www.w3.org/TR/SVG11/feature#BasicClip",
     "http://www.w3.org/TR/SVG11/feature#BasicFilter",
     "http://www.w3.org/TR/SVG11/feature#BasicGraphicsAttribute",
     "http://www.w3.org/TR/SVG11/feature#BasicPaintAttribute",
     "http://www.w3.org/TR/SVG11/feature#BasicStructure",
     "http://www.w3.org/TR/SVG11/feature#BasicText",
+    "http://www.w3.org/TR/SVG11/feature#Clip",
     "http://www.w3.org/TR/SVG11/feature#ConditionalProcessing",
     "http://www.w3.org/TR/SVG11/feature#ContainerAttribute",
+    "http://www.w3.org/TR/SVG11/feature#CoreAttribute",
     "http://www.w3.org/TR/SVG11/feature#Filter",
     "http://www.w3.org/TR/SVG11/feature#Gradient",
+    "http://www.w3.org/TR/SVG11/feature#GraphicsAttribute",
     "http://www.w3.org/TR/SVG11/feature#Image",
     "http://www.w3.org/TR/SVG11/feature#Marker",
     "http://www.w3.org/TR/SVG11/feature#Mask",
     "http://www.w3.org/TR/SVG11/feature#OpacityAttribute",
+    "http://www.w3.org/TR/SVG11/feature#PaintAttribute",
     "http://www.w3.org/TR/SVG11/feature#Pattern",
     "http://www.w3.org/TR/SVG11/feature#SVG",
     "http://www.w3.org/TR/SVG11/feature#SVG-static",
@@ -58,6 +112,25 @@ static IMPLEMENTED_FEATURES: &[&str] = &[
     "org.w3c.svg.static", // deprecated SVG 1.0 feature string
 ];
 
+/// SVG 2 removed feature strings; some documents still use them with an `SVG2` path.
+static SVG2_FEATURE_PREFIX: &str = "http://www.w3.org/TR/SVG2/feature#";
+
+/// Whether a feature string names something that we implement.
+///
+/// SVG 2 removed `requiredFeatures`, and browsers now consider it to be always true.
+/// We are stricter: a feature is only supported if it is in `IMPLEMENTED_FEATURES`, so
+/// documents that test for animation, scripting, or SVG fonts still get their fallback
+/// content.  A feature string with the SVG 2 path is evaluated like the SVG 1.1 feature
+/// with the same name.
+fn is_implemented_feature(feature: &str) -> bool {
+    if let Some(name) = feature.strip_prefix(SVG2_FEATURE_PREFIX) {
+        let svg11 = format!("http://www.w3.org/TR/SVG11/feature#{}", name);
+        IMPLEMENTED_FEATURES.binary_search(&svg11.as_str()).is_ok()
+    } else {
+        IMPLEMENTED_FEATURES.binary_search(&feature).is_ok()
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub struct RequiredFeatures(pub bool);
 
@@ -66,8 +139,7 @@ impl RequiredFeatures {
     // http://www.w3.org/TR/SVG/struct.html#RequiredFeaturesAttribute
     pub fn from_attribute(s: &str) -> Result<RequiredFeatures, ValueErrorKind> {
         Ok(RequiredFeatures(
-            s.split_whitespace()
-                .all(|f| IMPLEMENTED_FEATURES.binary_search(&f).is_ok()),
+            s.split_whitespace().all(is_implemented_feature),
         ))
     }
 
@@ -75,10 +147,31 @@ impl RequiredFeatures {
     pub fn eval(&self) -> bool {
         self.0
     }
+
+    fn explain(s: &str) -> (bool, String) {
+        let unsupported: Vec<&str> = s
+            .split_whitespace()
+            .filter(|f| !is_implemented_feature(f))
+            .collect();
+
+        if unsupported.is_empty() {
+            (true, String::from("all the features are supported"))
+        } else {
+            (
+                false,
+                format!("unsupported features: {}", unsupported.join(" ")),
+            )
+        }
+    }
 }
 
 #[derive(Debug, PartialEq)]
-pub struct SystemLanguage(LanguageTags);
+pub enum SystemLanguage {
+    /// `systemLanguage="*"`, for a catch-all branch of a `<switch>`.
+    Any,
+
+    LanguageTags(LanguageTags),
+}
 
 impl SystemLanguage {
     /// Parse a `systemLanguage` attribute and match it against a given `Locale`
@@ -94,12 +187,21 @@ impl SystemLanguage {
     /// Normally, calling code will pass `&Locale::current()` for the
     /// `locale` attribute; this is the user's current locale.
     ///
+    /// As an extension, a `*` in the list matches any locale.  An empty
+    /// attribute is still an error.
+    ///
     /// [`systemLanguage`]: https://www.w3.org/TR/SVG/struct.html#ConditionalProcessingSystemLanguageAttribute
     /// [BCP47]: http://www.ietf.org/rfc/bcp/bcp47.txt
     pub fn from_attribute(s: &str) -> Result<SystemLanguage, ValueErrorKind> {
+        let mut any = false;
+
         let attribute_tags = s
             .split(',')
             .map(str::trim)
+            .filter(|s| {
+                any |= *s == "*";
+                *s != "*"
+            })
             .map(|s| {
                 LanguageTag::from_str(s).map_err(|e| {
                     ValueErrorKind::parse_error(&format!("invalid language tag: \"{}\"", e))
@@ -107,15 +209,116 @@ impl SystemLanguage {
             })
             .collect::<Result<Vec<LanguageTag>, _>>()?;
 
-        Ok(SystemLanguage(LanguageTags::from(attribute_tags)))
+        if any {
+            Ok(SystemLanguage::Any)
+        } else {
+            Ok(SystemLanguage::LanguageTags(LanguageTags::from(
+                attribute_tags,
+            )))
+        }
     }
 
     /// Evaluate a systemLanguage value for conditional processing.
     pub fn eval(&self, user_language: &UserLanguage) -> bool {
-        user_language.any_matches(&self.0)
+        match *self {
+            SystemLanguage::Any => true,
+            SystemLanguage::LanguageTags(ref tags) => user_language.any_matches(tags),
+        }
+    }
+
+    /// Scores how well this matches the user's languages; see [`UserLanguage::match_score`].
+    ///
+    /// A `*` does not match any language in particular, so it gets no score.
+    pub fn match_score(&self, user_language: &UserLanguage) -> Option<(f32, Reverse<usize>)> {
+        match *self {
+            SystemLanguage::Any => None,
+            SystemLanguage::LanguageTags(ref tags) => user_language.match_score(tags),
+        }
+    }
+
+    fn explain(&self, user_language: &UserLanguage) -> (bool, String) {
+        match *self {
+            SystemLanguage::Any => (true, String::from("* matches any language")),
+
+            SystemLanguage::LanguageTags(_) if self.eval(user_language) => (
+                true,
+                String::from("one of the languages matches the user's languages"),
+            ),
+
+            SystemLanguage::LanguageTags(_) => (
+                false,
+                String::from("none of the languages match the user's languages"),
+            ),
+        }
     }
 }
 
+/// The result of one conditional processing attribute of an element.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConditionResult {
+    /// Name of the attribute, like `systemLanguage`.
+    pub attribute: String,
+
+    /// Value of the attribute, as written in the document.
+    pub value: String,
+
+    /// Whether the condition passed.
+    pub passed: bool,
+
+    /// Why the condition passed or failed, in English.
+    pub reason: String,
+}
+
+/// The conditional processing attributes of an element, and how they evaluated.
+///
+/// An element gets rendered only if all of its `conditions` passed.  Inside a
+/// `<switch>`, the first child whose conditions all pass is the one that gets rendered.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConditionalElement {
+    /// The local name of the element, like `rect`.
+    pub element_name: String,
+
+    /// The `id` of the element, if it has one.
+    pub id: Option<String>,
+
+    /// One result for each conditional processing attribute, in document order.
+    pub conditions: Vec<ConditionResult>,
+}
+
+/// Evaluates a conditional processing attribute, and tells why it passed or failed.
+///
+/// Returns `None` if `attr` is not a conditional processing attribute.  Values that
+/// cannot be parsed make the condition fail, since they put the element in error.
+pub fn explain_condition(
+    attr: &QualName,
+    value: &str,
+    user_language: &UserLanguage,
+    extensions: &Extensions,
+) -> Option<ConditionResult> {
+    let result = match attr.expanded() {
+        expanded_name!("", "requiredExtensions") => {
+            RequiredExtensions::from_attribute(value).map(|r| r.explain(extensions))
+        }
+
+        expanded_name!("", "requiredFeatures") => Ok(RequiredFeatures::explain(value)),
+
+        expanded_name!("", "systemLanguage") => {
+            SystemLanguage::from_attribute(value).map(|s| s.explain(user_language))
+        }
+
+        _ => return None,
+    };
+
+    let (passed, reason) = result.unwrap_or_else(|e| (false, e.to_string()));
+
+    Some(ConditionResult {
+        attribute: attr.local.to_string(),
+        value: value.to_string(),
+        passed,
+        reason,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -124,11 +327,38 @@ mod tests {
     #[test]
     fn required_extensions() {
         assert_eq!(
-            RequiredExtensions::from_attribute("http://test.org/NotExisting/1.0").unwrap(),
-            RequiredExtensions(false)
+            RequiredExtensions::from_attribute("http://test.org/NotExisting/1.0")
+                .unwrap()
+                .eval(&Extensions::default()),
+            false
         );
     }
 
+    #[test]
+    fn registered_extensions() {
+        use std::cell::Cell;
+
+        let supported = Rc::new(Cell::new(true));
+
+        let mut extensions = Extensions::default();
+        let s = supported.clone();
+        extensions.register("http://example.com/ext", Rc::new(move || s.get()));
+        extensions.register("http://example.com/always", Rc::new(|| true));
+
+        let required =
+            RequiredExtensions::from_attribute("http://example.com/ext http://example.com/always")
+                .unwrap();
+        assert!(required.eval(&extensions));
+
+        supported.set(false);
+        assert!(!required.eval(&extensions));
+
+        let unregistered =
+            RequiredExtensions::from_attribute("http://example.com/always http://example.com/no")
+                .unwrap();
+        assert!(!unregistered.eval(&extensions));
+    }
+
     #[test]
     fn required_features() {
         assert_eq!(
@@ -162,6 +392,37 @@ mod tests {
         );
     }
 
+    #[test]
+    fn implemented_features_are_sorted() {
+        assert!(IMPLEMENTED_FEATURES.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn feature_strings() {
+        for feature in &[
+            "http://www.w3.org/TR/SVG11/feature#Shape",
+            "http://www.w3.org/TR/SVG11/feature#Clip",
+            "http://www.w3.org/TR/SVG11/feature#CoreAttribute",
+            "http://www.w3.org/TR/SVG2/feature#Shape",
+            "http://www.w3.org/TR/SVG2/feature#Filter",
+            "org.w3c.svg.static",
+        ] {
+            assert!(is_implemented_feature(feature), "{}", feature);
+        }
+
+        for feature in &[
+            "http://www.w3.org/TR/SVG11/feature#Animation",
+            "http://www.w3.org/TR/SVG11/feature#Script",
+            "http://www.w3.org/TR/SVG11/feature#Font",
+            "http://www.w3.org/TR/SVG2/feature#Animation",
+            "http://www.w3.org/TR/SVG2/feature#",
+            "http://www.w3.org/TR/SVG12/feature#Shape",
+            "org.w3c.svg.dynamic",
+        ] {
+            assert!(!is_implemented_feature(feature), "{}", feature);
+        }
+    }
+
     #[test]
     fn system_language() {
         let locale = Locale::new("de,en-US").unwrap();
@@ -227,4 +488,51 @@ mod tests {
             true
         );
     }
+
+    #[test]
+    fn explains_system_language_mismatch() {
+        let locale = Locale::new("de").unwrap();
+        let user_language = UserLanguage::LanguageTags(LanguageTags::from_locale(&locale).unwrap());
+        let attr = QualName::new(None, ns!(), local_name!("systemLanguage"));
+
+        assert_eq!(
+            explain_condition(&attr, "fr, en", &user_language, &Extensions::default()),
+            Some(ConditionResult {
+                attribute: String::from("systemLanguage"),
+                value: String::from("fr, en"),
+                passed: false,
+                reason: String::from("none of the languages match the user's languages"),
+            })
+        );
+
+        let result = explain_condition(&attr, "", &user_language, &Extensions::default()).unwrap();
+        assert!(!result.passed);
+        assert!(result.reason.starts_with("parse error"));
+
+        let other = QualName::new(None, ns!(), local_name!("fill"));
+        assert!(explain_condition(&other, "red", &user_language, &Extensions::default()).is_none());
+    }
+
+    #[test]
+    fn system_language_wildcard() {
+        let mut user_languages = vec![UserLanguage::LanguageTags(LanguageTags::empty())];
+        for locale in &["de,en-US", "fr", "ja-JP"] {
+            let locale = Locale::new(locale).unwrap();
+            user_languages.push(UserLanguage::LanguageTags(
+                LanguageTags::from_locale(&locale).unwrap(),
+            ));
+        }
+
+        for user_language in &user_languages {
+            assert!(SystemLanguage::from_attribute("*")
+                .unwrap()
+                .eval(user_language));
+
+            assert!(SystemLanguage::from_attribute("fr-CA, *")
+                .unwrap()
+                .eval(user_language));
+        }
+
+        assert!(SystemLanguage::from_attribute("*, 12345").is_err());
+    }
 }
@@ -24,8 +24,8 @@
 //! Let's look at each rule:
 //!
 //! `@import` is an **at-rule**.  This rule has a prelude, but no block.
-//! There are other at-rules like `@media` and some of them may have a block,
-//! but librsvg doesn't support those yet.
+//! There are other at-rules like `@media` and some of them may have a block;
+//! librsvg only supports `@font-face` among those.
 //!
 //! The prelude of the following rule is `foo, .bar`.
 //! It is a **selector list** with two **selectors**, one for
@@ -88,8 +88,9 @@ use std::fmt;
 use std::str;
 
 use crate::error::*;
+use crate::font_face::{self, FontFace, FontFaceSource};
 use crate::io::{self, BinaryData};
-use crate::node::{Node, NodeBorrow, NodeCascade};
+use crate::node::{Node, NodeBorrow, NodeCascade, NodeSiblings};
 use crate::properties::{parse_property, ComputedValues, ParsedProperty};
 use crate::url_resolver::UrlResolver;
 
@@ -145,6 +146,33 @@ impl<'i> AtRuleParser<'i> for DeclParser {
     type Error = ValueErrorKind;
 }
 
+/// Parses a list of declarations, like the contents of a `style` attribute.
+///
+/// Invalid declarations are logged and ignored.
+pub fn parse_declarations(declarations: &str) -> Vec<Declaration> {
+    let mut input = ParserInput::new(declarations);
+    let mut parser = Parser::new(&mut input);
+
+    parse_declaration_list(&mut parser)
+}
+
+/// Parses a list of declarations, skipping the invalid ones.
+///
+/// Unknown properties, like vendor-prefixed or newer ones that librsvg doesn't
+/// support yet, and properties with invalid values get logged and ignored;
+/// the rest of the declarations still apply.
+fn parse_declaration_list(parser: &mut Parser<'_, '_>) -> Vec<Declaration> {
+    DeclarationListParser::new(parser, DeclParser)
+        .filter_map(|r| match r {
+            Ok(decl) => Some(decl),
+            Err(e) => {
+                rsvg_log!("Invalid declaration; ignoring: {:?}", e);
+                None
+            }
+        })
+        .collect()
+}
+
 /// Dummy struct to implement cssparser::QualifiedRuleParser and
 /// cssparser::AtRuleParser
 pub struct RuleParser;
@@ -169,12 +197,22 @@ pub struct QualifiedRule {
 
 /// Prelude of at-rule used in the AtRuleParser.
 pub enum AtRulePrelude {
-    Import(String),
+    /// URL of the stylesheet, and the media query list after it.
+    Import(String, String),
+}
+
+/// Prelude of an at-rule with a block; `@font-face` does not have anything in it.
+pub enum AtRuleBlockPrelude {
+    FontFace,
 }
 
 /// A CSS at-rule (or ruleset)
 pub enum AtRule {
-    Import(String),
+    /// URL of the stylesheet, and the media query list after it.
+    Import(String, String),
+
+    /// Family name and sources of a font.
+    FontFace(String, Vec<FontFaceSource>),
 }
 
 /// A CSS rule (or ruleset)
@@ -192,15 +230,24 @@ impl<'i> selectors::Parser<'i> for RuleParser {
         Some(ns!(svg))
     }
 
+    /// Resolves the prefix in selectors like `[xlink|href]`.
+    ///
+    /// We don't support `@namespace` rules, so we recognize the prefixes that SVG
+    /// documents conventionally declare for the namespaces we know about.  The
+    /// XML parser has already resolved the prefixes in the document itself, so an
+    /// attribute like `xlink:href` is matched by its namespace, not its prefix.
     fn namespace_for_prefix(
         &self,
-        _prefix: &<Self::Impl as SelectorImpl>::NamespacePrefix,
+        prefix: &<Self::Impl as SelectorImpl>::NamespacePrefix,
     ) -> Option<<Self::Impl as SelectorImpl>::NamespaceUrl> {
-        // FIXME: Do we need to keep a lookup table extracted from libxml2's
-        // XML namespaces?
-        //
-        // Or are CSS namespaces completely different, declared elsewhere?
-        None
+        match &**prefix {
+            "svg" => Some(ns!(svg)),
+            "xlink" => Some(ns!(xlink)),
+            "xml" => Some(ns!(xml)),
+            "xmlns" => Some(ns!(xmlns)),
+            "html" => Some(ns!(html)),
+            _ => None,
+        }
     }
 }
 
@@ -241,15 +288,7 @@ impl<'i> QualifiedRuleParser<'i> for RuleParser {
         _location: SourceLocation,
         input: &mut Parser<'i, 't>,
     ) -> Result<Self::QualifiedRule, cssparser::ParseError<'i, Self::Error>> {
-        let declarations = DeclarationListParser::new(input, DeclParser)
-            .filter_map(|r| match r {
-                Ok(decl) => Some(decl),
-                Err(e) => {
-                    rsvg_log!("Invalid declaration; ignoring: {:?}", e);
-                    None
-                }
-            })
-            .collect();
+        let declarations = parse_declaration_list(input);
 
         Ok(Rule::QualifiedRule(QualifiedRule {
             selectors: prelude,
@@ -260,9 +299,9 @@ impl<'i> QualifiedRuleParser<'i> for RuleParser {
 
 // Required by `cssparser::RuleListParser`.
 //
-// This only handles the `@import` at-rule.
+// This only handles the `@import` and `@font-face` at-rules.
 impl<'i> AtRuleParser<'i> for RuleParser {
-    type PreludeBlock = ();
+    type PreludeBlock = AtRuleBlockPrelude;
     type PreludeNoBlock = AtRulePrelude;
     type AtRule = Rule;
     type Error = ParseErrorKind<'i>;
@@ -278,9 +317,18 @@ impl<'i> AtRuleParser<'i> for RuleParser {
     > {
         match_ignore_ascii_case! { &name,
             "import" => {
-                // FIXME: at the moment we ignore media queries
                 let url = input.expect_url_or_string()?.as_ref().to_owned();
-                Ok(AtRuleType::WithoutBlock(AtRulePrelude::Import(url)))
+
+                let start = input.position();
+                while input.next().is_ok() {}
+                let media = input.slice_from(start).trim().to_owned();
+
+                Ok(AtRuleType::WithoutBlock(AtRulePrelude::Import(url, media)))
+            },
+
+            "font-face" => {
+                input.expect_exhausted()?;
+                Ok(AtRuleType::WithBlock(AtRuleBlockPrelude::FontFace))
             },
 
             _ => Err(input.new_error(BasicParseErrorKind::AtRuleInvalid(name))),
@@ -292,8 +340,22 @@ impl<'i> AtRuleParser<'i> for RuleParser {
         prelude: Self::PreludeNoBlock,
         _location: SourceLocation,
     ) -> Self::AtRule {
-        let AtRulePrelude::Import(url) = prelude;
-        Rule::AtRule(AtRule::Import(url))
+        let AtRulePrelude::Import(url, media) = prelude;
+        Rule::AtRule(AtRule::Import(url, media))
+    }
+
+    fn parse_block<'t>(
+        &mut self,
+        prelude: Self::PreludeBlock,
+        _location: SourceLocation,
+        input: &mut Parser<'i, 't>,
+    ) -> Result<Self::AtRule, cssparser::ParseError<'i, Self::Error>> {
+        let AtRuleBlockPrelude::FontFace = prelude;
+
+        match font_face::parse_font_face_block(input) {
+            Ok((family, sources)) => Ok(Rule::AtRule(AtRule::FontFace(family, sources))),
+            Err(_) => Err(input.new_error(BasicParseErrorKind::AtRuleBodyInvalid)),
+        }
     }
 }
 
@@ -417,32 +479,12 @@ impl selectors::Element for RsvgElement {
 
     /// Skips non-element nodes
     fn prev_sibling_element(&self) -> Option<Self> {
-        let mut sibling = self.0.previous_sibling();
-
-        while let Some(ref sib) = sibling {
-            if sib.is_element() {
-                return sibling.map(|n| n.into());
-            }
-
-            sibling = sib.previous_sibling();
-        }
-
-        None
+        self.0.previous_element_sibling().map(|n| n.into())
     }
 
     /// Skips non-element nodes
     fn next_sibling_element(&self) -> Option<Self> {
-        let mut sibling = self.0.next_sibling();
-
-        while let Some(ref sib) = sibling {
-            if sib.is_element() {
-                return sibling.map(|n| n.into());
-            }
-
-            sibling = sib.next_sibling();
-        }
-
-        None
+        self.0.next_element_sibling().map(|n| n.into())
     }
 
     fn is_html_element_in_html_document(&self) -> bool {
@@ -484,6 +526,9 @@ impl selectors::Element for RsvgElement {
             })
             .map(|(_, value)| {
                 // we have one; does the attribute's value match the expected operation?
+                //
+                // The operation knows whether the selector had the `i` flag for
+                // case-insensitive matching; otherwise values are case-sensitive.
                 operation.eval_str(value)
             })
             .unwrap_or(false)
@@ -522,6 +567,8 @@ impl selectors::Element for RsvgElement {
         false
     }
 
+    /// We always match in `QuirksMode::NoQuirks`, so `case_sensitivity` is
+    /// case-sensitive, as XML ids are.
     fn has_id(&self, id: &LocalName, case_sensitivity: CaseSensitivity) -> bool {
         self.0
             .borrow_element()
@@ -592,6 +639,7 @@ pub enum Origin {
 pub struct Stylesheet {
     origin: Origin,
     qualified_rules: Vec<QualifiedRule>,
+    font_faces: Vec<FontFace>,
 }
 
 /// A match during the selector matching process
@@ -603,6 +651,12 @@ pub struct Stylesheet {
 /// This type implements `Ord` so a list of `Match` can be sorted.
 /// That implementation does ordering based on origin and specificity
 /// as per https://www.w3.org/TR/CSS22/cascade.html#cascading-order
+///
+/// The universal selector `*` has a specificity of zero, so any rule
+/// with a type, class, or id selector overrides it.  Matches with the
+/// same origin and specificity keep their order in the stylesheet, since
+/// sorting is stable.  The document's stylesheets are in the order of
+/// their `<style>` elements, so later ones win as well.
 struct Match<'a> {
     specificity: u32,
     origin: Origin,
@@ -637,6 +691,7 @@ impl Stylesheet {
         Stylesheet {
             origin,
             qualified_rules: Vec::new(),
+            font_faces: Vec::new(),
         }
     }
 
@@ -656,7 +711,7 @@ impl Stylesheet {
         origin: Origin,
     ) -> Result<Self, LoadingError> {
         let mut stylesheet = Stylesheet::new(origin);
-        stylesheet.load(href, url_resolver)?;
+        stylesheet.load(href, url_resolver, &mut Vec::new())?;
         Ok(stylesheet)
     }
 
@@ -665,6 +720,20 @@ impl Stylesheet {
     /// The `base_url` is required for `@import` rules, so that librsvg
     /// can determine if the requested path is allowed.
     pub fn parse(&mut self, buf: &str, url_resolver: &UrlResolver) -> Result<(), LoadingError> {
+        self.parse_importing(buf, url_resolver, &mut Vec::new())
+    }
+
+    /// Parses a stylesheet, with the rules of imported stylesheets in place of the
+    /// `@import` rules.
+    ///
+    /// `importing` has the URLs of the stylesheets whose imports are being loaded, so
+    /// that a stylesheet which ends up importing itself can be caught.
+    fn parse_importing(
+        &mut self,
+        buf: &str,
+        url_resolver: &UrlResolver,
+        importing: &mut Vec<String>,
+    ) -> Result<(), LoadingError> {
         let mut input = ParserInput::new(buf);
         let mut parser = Parser::new(&mut input);
 
@@ -677,10 +746,19 @@ impl Stylesheet {
                 }
             })
             .for_each(|rule| match rule {
-                Rule::AtRule(AtRule::Import(url)) => {
-                    // ignore invalid imports
-                    let _ = self.load(&url, &url_resolver);
+                Rule::AtRule(AtRule::Import(url, media)) => {
+                    if media_list_matches(&media) {
+                        // ignore invalid imports
+                        let _ = self.load(&url, &url_resolver, importing);
+                    } else {
+                        rsvg_log!("ignoring import of \"{}\" for media \"{}\"", url, media);
+                    }
                 }
+                Rule::AtRule(AtRule::FontFace(family, sources)) => self.font_faces.push(FontFace {
+                    family,
+                    sources,
+                    url_resolver: url_resolver.clone(),
+                }),
                 Rule::QualifiedRule(qr) => self.qualified_rules.push(qr),
             });
 
@@ -688,11 +766,21 @@ impl Stylesheet {
     }
 
     /// Parses a stylesheet referenced by an URL
-    fn load(&mut self, href: &str, url_resolver: &UrlResolver) -> Result<(), LoadingError> {
+    fn load(
+        &mut self,
+        href: &str,
+        url_resolver: &UrlResolver,
+        importing: &mut Vec<String>,
+    ) -> Result<(), LoadingError> {
         let aurl = url_resolver
             .resolve_href(href)
             .map_err(|_| LoadingError::BadUrl)?;
 
+        if importing.iter().any(|url| url == aurl.as_str()) {
+            rsvg_log!("\"{}\" imports itself; ignoring", aurl);
+            return Err(LoadingError::BadCss);
+        }
+
         io::acquire_data(&aurl, None)
             .map_err(LoadingError::from)
             .and_then(|data| {
@@ -717,7 +805,21 @@ impl Stylesheet {
                     LoadingError::BadCss
                 })
             })
-            .and_then(|utf8| self.parse(&utf8, &UrlResolver::new(Some((*aurl).clone()))))
+            .and_then(|utf8| {
+                importing.push(aurl.as_str().to_string());
+                let res = self.parse_importing(
+                    &utf8,
+                    &UrlResolver::new(Some((*aurl).clone())),
+                    importing,
+                );
+                importing.pop();
+                res
+            })
+    }
+
+    /// The `@font-face` rules of the stylesheet and of the ones it imports.
+    pub fn font_faces(&self) -> &[FontFace] {
+        &self.font_faces
     }
 
     /// Appends the style declarations that match a specified node to a given vector
@@ -753,10 +855,63 @@ impl Stylesheet {
     }
 }
 
+/// Whether a media query list applies to librsvg's rendering.
+///
+/// This is for the `media` attribute of `<style>` elements.  Librsvg renders for the
+/// `screen` media type, so only queries for `all` or `screen` match, optionally with
+/// `only` or negated with `not`.  Media features like `(min-width: 10cm)` are not
+/// supported, so queries with them never match.  An empty list matches everything.
+///
+/// https://www.w3.org/TR/mediaqueries-4/#media
+pub fn media_list_matches(media: &str) -> bool {
+    if media.trim().is_empty() {
+        return true;
+    }
+
+    media.split(',').any(media_query_matches)
+}
+
+fn media_query_matches(query: &str) -> bool {
+    let query = query.to_ascii_lowercase();
+    let mut words = query.split_whitespace().peekable();
+
+    let negated = match words.peek() {
+        Some(&"not") => {
+            words.next();
+            true
+        }
+
+        Some(&"only") => {
+            words.next();
+            false
+        }
+
+        _ => false,
+    };
+
+    let matches = match (words.next(), words.next()) {
+        (Some("all"), None) | (Some("screen"), None) => true,
+        (Some(_), None) => false,
+        _ => return false,
+    };
+
+    matches != negated
+}
+
 fn is_text_css(mime_type: &Mime) -> bool {
     mime_type.type_ == "text" && mime_type.subtype == "css"
 }
 
+/// The `!important` matches from an origin, sorted by specificity
+fn important_matches<'a>(
+    matches: &'a [Match<'a>],
+    origin: Origin,
+) -> impl Iterator<Item = &'a Match<'a>> {
+    matches
+        .iter()
+        .filter(move |m| m.declaration.important && m.origin == origin)
+}
+
 /// Runs the CSS cascade on the specified tree from all the stylesheets
 pub fn cascade(
     root: &mut Node,
@@ -786,14 +941,45 @@ pub fn cascade(
 
         matches.as_mut_slice().sort();
 
-        for m in matches {
-            node.borrow_element_mut()
-                .apply_style_declaration(m.declaration, m.origin);
+        let style_attribute = node.borrow_element().style_attribute_declarations();
+
+        let mut element = node.borrow_element_mut();
+
+        // A document gets cascaded again when its user stylesheet changes; the
+        // declarations and the !important flags of the previous cascade must not stay.
+        element.reset_style_declarations();
+
+        // Normal declarations go from the lowest to the highest origin, by specificity.
+        // The style attribute is the most specific of the author declarations.
+        for m in matches.iter().filter(|m| !m.declaration.important) {
+            element.apply_style_declaration(m.declaration, m.origin);
+        }
+
+        for decl in style_attribute.iter().filter(|d| !d.important) {
+            element.apply_style_declaration(decl, Origin::Author);
         }
 
-        node.borrow_element_mut().set_style_attribute();
+        // Important declarations go in the reverse order of origins, so that the user's
+        // and the user agent's !important declarations override the author's.
+        //
+        // https://www.w3.org/TR/css-cascade-3/#cascade-origin
+        for m in important_matches(&matches, Origin::Author) {
+            element.apply_style_declaration(m.declaration, m.origin);
+        }
+
+        for decl in style_attribute.iter().filter(|d| d.important) {
+            element.apply_style_declaration(decl, Origin::Author);
+        }
+
+        for m in important_matches(&matches, Origin::User)
+            .chain(important_matches(&matches, Origin::UserAgent))
+        {
+            element.apply_style_declaration(m.declaration, m.origin);
+        }
     }
 
+    root.borrow_element_mut().specify_root_font_size();
+
     let values = ComputedValues::default();
     root.cascade(&values);
 }
@@ -804,6 +990,8 @@ mod tests {
     use selectors::Element;
 
     use crate::document::Document;
+    use crate::property_defs::{Opacity, Overflow};
+    use crate::unit_interval::UnitInterval;
 
     #[test]
     fn impl_element() {
@@ -880,4 +1068,417 @@ mod tests {
         assert!(d.is_empty());
         assert!(!a.is_empty());
     }
+
+    fn opacity(document: &Document, id: &str) -> f64 {
+        let node = document.lookup_internal_node(id).unwrap();
+        let values = node.borrow_element().get_computed_values().clone();
+        let Opacity(UnitInterval(opacity)) = values.opacity();
+        opacity
+    }
+
+    #[test]
+    fn important_declarations_override_normal_ones() {
+        let document = Document::load_from_bytes(
+            br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg">
+  <style>
+    #a { opacity: 0.5 !important; }
+    #b { opacity: 0.1 !important; }
+    #c { opacity: 0.1; }
+    #d { opacity: 0.5 !important; }
+    rect { opacity: 0.1 !important; }
+  </style>
+  <rect id="a" style="opacity: 0.1"/>
+  <rect id="b" style="opacity: 0.5 !important"/>
+  <rect id="c" style="opacity: 0.5"/>
+  <rect id="d"/>
+</svg>
+"#,
+        );
+
+        // A selector's !important beats a normal declaration in the style attribute
+        assert_eq!(opacity(&document, "a"), 0.5);
+
+        // Between !important declarations, the style attribute is the most specific
+        assert_eq!(opacity(&document, "b"), 0.5);
+
+        // Between normal declarations, too
+        assert_eq!(opacity(&document, "c"), 0.5);
+
+        // Between !important selectors, the most specific one wins
+        assert_eq!(opacity(&document, "d"), 0.5);
+    }
+
+    #[test]
+    fn user_important_declarations_override_author_important_ones() {
+        let mut document = Document::load_from_bytes(
+            br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg">
+  <style>
+    #a { opacity: 0.1 !important; }
+    #b { opacity: 0.5; }
+  </style>
+  <rect id="a" style="opacity: 0.1 !important"/>
+  <rect id="b"/>
+</svg>
+"#,
+        );
+
+        let user = Stylesheet::from_data(
+            "rect { opacity: 0.5 !important; } #b { opacity: 0.1; }",
+            &UrlResolver::new(None),
+            Origin::User,
+        )
+        .unwrap();
+
+        document.cascade(&[user]);
+
+        assert_eq!(opacity(&document, "a"), 0.5);
+
+        // Normal declarations from the author override the user's
+        assert_eq!(opacity(&document, "b"), 0.5);
+    }
+
+    #[test]
+    fn compound_class_selectors_require_all_classes() {
+        let document = Document::load_from_bytes(
+            br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg">
+  <style>
+    .warning.bold { opacity: 0.5; }
+  </style>
+  <rect id="a" class="warning bold"/>
+  <rect id="b" class="bold other warning"/>
+  <rect id="c" class="warning"/>
+  <rect id="d" class="bold"/>
+</svg>
+"#,
+        );
+
+        assert_eq!(opacity(&document, "a"), 0.5);
+        assert_eq!(opacity(&document, "b"), 0.5);
+        assert_eq!(opacity(&document, "c"), 1.0);
+        assert_eq!(opacity(&document, "d"), 1.0);
+    }
+
+    #[test]
+    fn universal_selector_has_the_lowest_specificity() {
+        let document = Document::load_from_bytes(
+            br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg">
+  <style>
+    rect { opacity: 0.5; }
+    * { opacity: 0.1; }
+  </style>
+  <rect id="a"/>
+  <circle id="b"/>
+</svg>
+"#,
+        );
+
+        assert_eq!(opacity(&document, "a"), 0.5);
+        assert_eq!(opacity(&document, "b"), 0.1);
+    }
+
+    #[test]
+    fn later_style_elements_win_at_equal_specificity() {
+        let document = Document::load_from_bytes(
+            br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg">
+  <style>
+    rect { opacity: 0.5; }
+    circle { opacity: 0.5; }
+  </style>
+  <rect id="a"/>
+  <circle id="b"/>
+  <style>
+    rect { opacity: 0.1; }
+  </style>
+</svg>
+"#,
+        );
+
+        assert_eq!(opacity(&document, "a"), 0.1);
+        assert_eq!(opacity(&document, "b"), 0.5);
+    }
+
+    #[test]
+    fn matches_namespaced_attribute_selectors() {
+        let document = Document::load_from_bytes(
+            br##"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" xmlns:l="http://www.w3.org/1999/xlink">
+  <style>
+    [xlink|href] { opacity: 0.5; }
+    [href] { opacity: 0.1; }
+    [xlink|href="#b"] { opacity: 0.2; }
+  </style>
+  <rect id="a"/>
+  <use id="b" l:href="#a"/>
+  <use id="c" href="#a"/>
+  <use id="d" l:href="#b"/>
+</svg>
+"##,
+        );
+
+        assert_eq!(opacity(&document, "a"), 1.0);
+
+        // The document's prefix doesn't matter; the attribute's namespace does
+        assert_eq!(opacity(&document, "b"), 0.5);
+
+        // An unprefixed attribute selector only matches attributes without a namespace
+        assert_eq!(opacity(&document, "c"), 0.1);
+
+        assert_eq!(opacity(&document, "d"), 0.2);
+    }
+
+    #[test]
+    fn ids_are_case_sensitive() {
+        let document = Document::load_from_bytes(
+            br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg">
+  <style>
+    #Foo { opacity: 0.5; }
+    #bar { opacity: 0.5; }
+  </style>
+  <rect id="foo"/>
+  <rect id="Bar"/>
+  <rect id="Foo"/>
+</svg>
+"#,
+        );
+
+        assert_eq!(opacity(&document, "foo"), 1.0);
+        assert_eq!(opacity(&document, "Bar"), 1.0);
+        assert_eq!(opacity(&document, "Foo"), 0.5);
+
+        assert!(document.lookup_internal_node("bar").is_none());
+        assert!(document.lookup_internal_node("FOO").is_none());
+    }
+
+    #[test]
+    fn matches_negation_pseudo_class() {
+        let document = Document::load_from_bytes(
+            br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg">
+  <style>
+    text:not(.title) { opacity: 0.5; }
+    rect:not(#b) { opacity: 0.5; }
+    rect { opacity: 0.1; }
+  </style>
+  <text id="title" class="title">Title</text>
+  <text id="body" class="body">Body</text>
+  <text id="plain">Plain</text>
+  <rect id="a"/>
+  <rect id="b"/>
+</svg>
+"#,
+        );
+
+        assert_eq!(opacity(&document, "title"), 1.0);
+        assert_eq!(opacity(&document, "body"), 0.5);
+        assert_eq!(opacity(&document, "plain"), 0.5);
+
+        // The argument of :not() counts towards the specificity, so an id beats the
+        // plain type selector that comes later
+        assert_eq!(opacity(&document, "a"), 0.5);
+        assert_eq!(opacity(&document, "b"), 0.1);
+    }
+
+    #[test]
+    fn matches_sibling_combinators() {
+        let document = Document::load_from_bytes(
+            br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg">
+  <style>
+    tspan ~ tspan { opacity: 0.5; }
+    tspan + tspan { opacity: 0.1; }
+  </style>
+  <text>
+    <tspan id="a">a</tspan> text between <tspan id="b">b</tspan>
+    <a><tspan id="c">c</tspan></a>
+    <tspan id="d">d</tspan>
+  </text>
+</svg>
+"#,
+        );
+
+        assert_eq!(opacity(&document, "a"), 1.0);
+
+        // Adjacent siblings; the characters in between are not elements
+        assert_eq!(opacity(&document, "b"), 0.1);
+
+        // Not a sibling of the other tspans
+        assert_eq!(opacity(&document, "c"), 1.0);
+
+        // Preceded by <a>, so only the general sibling combinator matches
+        assert_eq!(opacity(&document, "d"), 0.5);
+    }
+
+    #[test]
+    fn matches_media_query_lists() {
+        assert!(media_list_matches(""));
+        assert!(media_list_matches("all"));
+        assert!(media_list_matches("screen"));
+        assert!(media_list_matches("only screen"));
+        assert!(media_list_matches("print, Screen"));
+        assert!(media_list_matches("not print"));
+
+        assert!(!media_list_matches("none"));
+        assert!(!media_list_matches("print"));
+        assert!(!media_list_matches("not screen"));
+        assert!(!media_list_matches("screen and (min-width: 10cm)"));
+    }
+
+    fn fixtures_resolver() -> UrlResolver {
+        let base = std::env::current_dir()
+            .unwrap()
+            .join("tests/fixtures/loading/base.svg");
+
+        UrlResolver::new(Some(url::Url::from_file_path(base).unwrap()))
+    }
+
+    #[test]
+    fn circular_imports_get_broken() {
+        // import-cycle-a.css imports import-cycle-b.css, which imports the first one back
+        let stylesheet =
+            Stylesheet::from_href("import-cycle-a.css", &fixtures_resolver(), Origin::Author)
+                .unwrap();
+
+        // One rule from each stylesheet, with the imported one first
+        assert_eq!(stylesheet.qualified_rules.len(), 2);
+        assert_eq!(
+            stylesheet.qualified_rules[0].selectors.to_css_string(),
+            "circle"
+        );
+        assert_eq!(
+            stylesheet.qualified_rules[1].selectors.to_css_string(),
+            "rect"
+        );
+    }
+
+    #[test]
+    fn imports_for_other_media_are_ignored() {
+        let mut stylesheet = Stylesheet::new(Origin::Author);
+        stylesheet
+            .parse(
+                r#"@import "import-cycle-a.css" print;"#,
+                &fixtures_resolver(),
+            )
+            .unwrap();
+        assert!(stylesheet.qualified_rules.is_empty());
+
+        let mut stylesheet = Stylesheet::new(Origin::Author);
+        stylesheet
+            .parse(
+                r#"@import "import-cycle-a.css" screen;"#,
+                &fixtures_resolver(),
+            )
+            .unwrap();
+        assert_eq!(stylesheet.qualified_rules.len(), 2);
+    }
+
+    #[test]
+    fn style_elements_for_other_media_are_ignored() {
+        let document = Document::load_from_bytes(
+            br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg">
+  <style media="print">
+    rect { opacity: 0.5; }
+  </style>
+  <style media="none">
+    circle { opacity: 0.5; }
+  </style>
+  <style media="screen">
+    ellipse { opacity: 0.5; }
+  </style>
+  <rect id="a"/>
+  <circle id="b"/>
+  <ellipse id="c"/>
+</svg>
+"#,
+        );
+
+        assert_eq!(opacity(&document, "a"), 1.0);
+        assert_eq!(opacity(&document, "b"), 1.0);
+        assert_eq!(opacity(&document, "c"), 0.5);
+    }
+
+    #[test]
+    fn author_rules_override_user_agent_rules() {
+        let overflow = |document: &Document, id| {
+            let node = document.lookup_internal_node(id).unwrap();
+            let values = node.borrow_element().get_computed_values().clone();
+            values.overflow()
+        };
+
+        // The user agent stylesheet has `marker { overflow: hidden; }`
+        let document = Document::load_from_bytes(
+            br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg">
+  <marker id="a"/>
+</svg>
+"#,
+        );
+
+        assert_eq!(overflow(&document, "a"), Overflow::Hidden);
+
+        // Even the universal selector, with a lower specificity, wins over it
+        let document = Document::load_from_bytes(
+            br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg">
+  <style>
+    * { overflow: visible; }
+  </style>
+  <marker id="a"/>
+</svg>
+"#,
+        );
+
+        assert_eq!(overflow(&document, "a"), Overflow::Visible);
+    }
+
+    #[test]
+    fn attribute_selectors_honor_the_case_insensitive_flag() {
+        let document = Document::load_from_bytes(
+            br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg">
+  <style>
+    [fill="RED" i] { opacity: 0.5; }
+    [stroke="BLUE"] { opacity: 0.1; }
+  </style>
+  <rect id="a" fill="red"/>
+  <rect id="b" fill="Red"/>
+  <rect id="c" fill="green"/>
+  <rect id="d" stroke="blue"/>
+  <rect id="e" stroke="BLUE"/>
+</svg>
+"#,
+        );
+
+        assert_eq!(opacity(&document, "a"), 0.5);
+        assert_eq!(opacity(&document, "b"), 0.5);
+        assert_eq!(opacity(&document, "c"), 1.0);
+
+        // Without the flag, values are compared case-sensitively
+        assert_eq!(opacity(&document, "d"), 1.0);
+        assert_eq!(opacity(&document, "e"), 0.1);
+    }
+
+    #[test]
+    fn unknown_properties_do_not_discard_the_rest_of_the_block() {
+        let document = Document::load_from_bytes(
+            br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg">
+  <style>
+    #a { -webkit-text-stroke: 1px; opacity: 0.5; some-future-property: foo; }
+  </style>
+  <rect id="a"/>
+  <rect id="b" style="-moz-foo: bar; opacity: 0.5; another-unknown: 1"/>
+</svg>
+"#,
+        );
+
+        assert_eq!(opacity(&document, "a"), 0.5);
+        assert_eq!(opacity(&document, "b"), 0.5);
+    }
 }
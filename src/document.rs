@@ -12,8 +12,10 @@ use std::include_str;
 use std::rc::Rc;
 use std::str::FromStr;
 
+use crate::cond::Extensions;
 use crate::css::{self, Origin, Stylesheet};
 use crate::error::{AcquireError, AllowedUrlError, LoadingError, NodeIdError};
+use crate::font_face::FontFace;
 use crate::handle::LoadOptions;
 use crate::io::{self, BinaryData};
 use crate::limits;
@@ -22,6 +24,10 @@ use crate::surface_utils::shared_surface::SharedImageSurface;
 use crate::url_resolver::{AllowedUrl, UrlResolver};
 use crate::xml::{xml_load_from_possibly_compressed_stream, Attributes};
 
+/// Default styles for SVG elements, like `display: none` for `<defs>`.
+///
+/// These go into the cascade with the lowest origin, so any author rule overrides
+/// them.  Adjustments to the default styles of elements belong in `ua.css`.
 static UA_STYLESHEETS: Lazy<Vec<Stylesheet>> = Lazy::new(|| {
     vec![Stylesheet::from_data(
         include_str!("ua.css"),
@@ -73,6 +79,12 @@ pub struct Document {
     tree: Node,
 
     /// Mapping from `id` attributes to nodes.
+    ///
+    /// This is built once while loading, so that resolving references from
+    /// `<use>`, `<tref>`, paint servers, etc. does not need to walk the tree.
+    /// The tree is immutable after loading, so there is nothing to keep in sync.
+    /// If several elements have the same id, the first one in document order
+    /// wins.
     ids: HashMap<String, Node>,
 
     // The following two require interior mutability because we load the extern
@@ -127,6 +139,16 @@ impl Document {
         self.tree.clone()
     }
 
+    /// The extensions that the application registered for `requiredExtensions`.
+    pub fn extensions(&self) -> &Extensions {
+        &self.load_options.extensions
+    }
+
+    /// Whether `<switch>` picks the child whose `systemLanguage` matches best.
+    pub fn best_language_match(&self) -> bool {
+        self.load_options.best_language_match
+    }
+
     /// Looks up a node in this document or one of its resources by its `id` attribute.
     pub fn lookup_node(&self, node_id: &NodeId) -> Option<Node> {
         match node_id {
@@ -162,6 +184,11 @@ impl Document {
     pub fn cascade(&mut self, extra: &[Stylesheet]) {
         css::cascade(&mut self.tree, &UA_STYLESHEETS, &self.stylesheets, extra);
     }
+
+    /// The `@font-face` rules of the document's stylesheets.
+    pub fn font_faces(&self) -> impl Iterator<Item = &FontFace> {
+        self.stylesheets.iter().flat_map(|s| s.font_faces())
+    }
 }
 
 struct Resources {
@@ -367,6 +394,16 @@ impl<'i> AcquiredNodes<'i> {
         self.document.lookup_image(href)
     }
 
+    /// The extensions that the application registered for `requiredExtensions`.
+    pub fn extensions(&self) -> &Extensions {
+        self.document.extensions()
+    }
+
+    /// Whether `<switch>` picks the child whose `systemLanguage` matches best.
+    pub fn best_language_match(&self) -> bool {
+        self.document.best_language_match()
+    }
+
     /// Acquires a node.
     /// Nodes acquired by this function must be released in reverse acquiring order.
     pub fn acquire(&mut self, node_id: &NodeId) -> Result<AcquiredNode, AcquireError> {
@@ -591,6 +628,29 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn first_element_with_duplicate_id_wins() {
+        let document = Document::load_from_bytes(
+            br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg">
+  <rect id="foo" x="1"/>
+  <g>
+    <circle id="foo" cx="2"/>
+  </g>
+  <rect id="bar"/>
+</svg>
+"#,
+        );
+
+        let foo = document.lookup_internal_node("foo").unwrap();
+        assert!(is_element_of_type!(foo, Rect));
+
+        let bar = document.lookup_internal_node("bar").unwrap();
+        assert!(is_element_of_type!(bar, Rect));
+
+        assert!(document.lookup_internal_node("baz").is_none());
+    }
+
     #[test]
     fn unspecified_mime_type_yields_no_content_type() {
         // Issue #548
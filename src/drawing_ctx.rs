@@ -2,6 +2,7 @@
 
 use cssparser::RGBA;
 use float_cmp::approx_eq;
+use glib::translate::ToGlibPtrMut;
 use once_cell::sync::Lazy;
 use pango::prelude::FontMapExt;
 use regex::{Captures, Regex};
@@ -9,9 +10,12 @@ use std::borrow::Cow;
 use std::cell::RefCell;
 use std::convert::TryFrom;
 use std::f64::consts::*;
+use std::ops::Range;
 use std::rc::{Rc, Weak};
+use std::slice;
 
 use crate::accept_language::UserLanguage;
+use crate::angle::Angle;
 use crate::aspect_ratio::AspectRatio;
 use crate::bbox::BoundingBox;
 use crate::coord_units::CoordUnits;
@@ -23,7 +27,9 @@ use crate::filter::FilterValueList;
 use crate::filters::{self, FilterSpec};
 use crate::float_eq_cairo::ApproxEqCairo;
 use crate::gradient::{GradientVariant, SpreadMethod, UserSpaceGradient};
-use crate::layout::{Image, Shape, StackingContext, Stroke, TextSpan};
+use crate::layout::{
+    DecorationLine, Image, Shape, StackingContext, Stroke, TextSpan, TextSpanGeometry,
+};
 use crate::length::*;
 use crate::marker;
 use crate::node::{CascadedValues, Node, NodeBorrow, NodeDraw};
@@ -32,13 +38,15 @@ use crate::path_builder::*;
 use crate::pattern::UserSpacePattern;
 use crate::properties::{
     ClipRule, ComputedValues, FillRule, Filter, MixBlendMode, Opacity, Overflow, PaintTarget,
-    ShapeRendering, StrokeLinecap, StrokeLinejoin, TextRendering,
+    ShapeRendering, StrokeLinecap, StrokeLinejoin, TextRendering, VectorEffect,
 };
 use crate::rect::Rect;
 use crate::surface_utils::{
     shared_surface::ExclusiveImageSurface, shared_surface::SharedImageSurface,
     shared_surface::SurfaceType,
 };
+use crate::text::{FontMeasurementsCache, TextAttributesCache};
+use crate::text_options::{TextExtents, TextOptions};
 use crate::transform::Transform;
 use crate::unit_interval::UnitInterval;
 use crate::viewbox::ViewBox;
@@ -169,10 +177,33 @@ pub struct DrawingCtx {
 
     user_language: UserLanguage,
 
+    text_options: TextOptions,
+
     viewport_stack: Rc<RefCell<Vec<Viewport>>>,
 
     drawsub_stack: Vec<Node>,
 
+    /// Targets of the `<a>` elements being drawn, innermost last.
+    link_targets: Vec<String>,
+
+    /// Hit regions for text inside links, in device space, with their targets.
+    link_regions: Rc<RefCell<Vec<(Rect, String)>>>,
+
+    /// Text spans that have been laid out, if their geometry is being collected.
+    text_geometry: Option<Rc<RefCell<Vec<TextSpanGeometry>>>>,
+
+    /// Bounding box of the node at the bottom of the `drawsub_stack`, in the user
+    /// space of its nearest viewport, once it has been drawn.
+    target_bbox: Option<BoundingBox>,
+
+    /// Attribute lists for the Pango layouts of text spans, shared among the spans.
+    text_attributes_cache: Rc<TextAttributesCache>,
+
+    /// Glyph measurements of the fonts that lengths in text refer to.
+    font_measurements_cache: Rc<FontMeasurementsCache>,
+
+    /// Whether only the geometry of the elements is being computed.  In that case,
+    /// nothing gets painted or composited, and only the bounding boxes are computed.
     measuring: bool,
     testing: bool,
 }
@@ -186,12 +217,15 @@ pub enum DrawingMode {
 /// The toplevel drawing routine.
 ///
 /// This creates a DrawingCtx internally and starts drawing at the specified `node`.
+/// If `measuring` is true, nothing gets painted on the `cr`'s target; only the bounding
+/// box gets computed.
 pub fn draw_tree(
     mode: DrawingMode,
     cr: &cairo::Context,
     viewport: Rect,
     user_language: &UserLanguage,
     dpi: Dpi,
+    text_options: &TextOptions,
     measuring: bool,
     testing: bool,
     acquired_nodes: &mut AcquiredNodes<'_>,
@@ -235,6 +269,7 @@ pub fn draw_tree(
         viewport,
         user_language.clone(),
         dpi,
+        text_options.clone(),
         measuring,
         testing,
         drawsub_stack,
@@ -247,6 +282,172 @@ pub fn draw_tree(
     Ok(user_bbox)
 }
 
+/// Computes the hit regions of text inside `<a>` links.
+///
+/// The tree gets laid out just like for rendering, but nothing gets painted on the
+/// `cr`'s target.  Each line of text inside a link produces one rectangle, in the
+/// device space of `cr`, along with the link's target.
+pub fn link_regions_tree(
+    node: &Node,
+    cr: &cairo::Context,
+    viewport: Rect,
+    user_language: &UserLanguage,
+    dpi: Dpi,
+    text_options: &TextOptions,
+    testing: bool,
+    acquired_nodes: &mut AcquiredNodes<'_>,
+) -> Result<Vec<(Rect, String)>, RenderingError> {
+    let cascaded = CascadedValues::new_from_node(node);
+
+    let transform = Transform::from(cr.matrix()).pre_translate(viewport.x0, viewport.y0);
+    cr.set_matrix(transform.into());
+
+    let viewport = viewport.translate((-viewport.x0, -viewport.y0));
+
+    let mut draw_ctx = DrawingCtx::new(
+        cr,
+        transform,
+        viewport,
+        user_language.clone(),
+        dpi,
+        text_options.clone(),
+        true,
+        testing,
+        Vec::new(),
+    );
+
+    draw_ctx.draw_node_from_stack(node, acquired_nodes, &cascaded, false)?;
+
+    let regions = draw_ctx.link_regions.take();
+
+    Ok(regions)
+}
+
+/// Lays out the text in a node and returns the geometry of its text spans.
+///
+/// The spans are in the order of the text content, and their transformations lead to
+/// the user space of the text elements.  Nothing gets painted on the `cr`'s target.
+pub fn text_geometry_tree(
+    node: &Node,
+    cr: &cairo::Context,
+    viewport: Rect,
+    user_language: &UserLanguage,
+    dpi: Dpi,
+    text_options: &TextOptions,
+    testing: bool,
+    acquired_nodes: &mut AcquiredNodes<'_>,
+) -> Result<Vec<TextSpanGeometry>, RenderingError> {
+    let cascaded = CascadedValues::new_from_node(node);
+
+    let transform = Transform::from(cr.matrix()).pre_translate(viewport.x0, viewport.y0);
+    cr.set_matrix(transform.into());
+
+    let viewport = viewport.translate((-viewport.x0, -viewport.y0));
+
+    let mut draw_ctx = DrawingCtx::new(
+        cr,
+        transform,
+        viewport,
+        user_language.clone(),
+        dpi,
+        text_options.clone(),
+        true,
+        testing,
+        Vec::new(),
+    );
+
+    let text_geometry = Rc::new(RefCell::new(Vec::new()));
+    draw_ctx.text_geometry = Some(text_geometry.clone());
+
+    draw_ctx.draw_node_from_stack(node, acquired_nodes, &cascaded, false)?;
+
+    let spans = text_geometry.take();
+
+    Ok(spans)
+}
+
+/// Computes the bounding box of a node in the user space of its nearest viewport.
+///
+/// The tree gets drawn from the `root` down to the `node` like for
+/// `DrawingMode::LimitToStack`, so the `cr` should be for a scratch surface.  The nearest
+/// viewport is the one established by the closest `<svg>` or `<symbol>` around the
+/// `node`, so its bounding box includes its own transform and those of its ancestors up
+/// to that viewport.  Returns an empty bounding box if the node does not get drawn at
+/// all, for example because it is inside `<defs>`.
+pub fn element_bbox_tree(
+    node: Node,
+    root: Node,
+    cr: &cairo::Context,
+    viewport: Rect,
+    user_language: &UserLanguage,
+    dpi: Dpi,
+    text_options: &TextOptions,
+    testing: bool,
+    acquired_nodes: &mut AcquiredNodes<'_>,
+) -> Result<BoundingBox, RenderingError> {
+    let cascaded = CascadedValues::new_from_node(&root);
+
+    let transform = Transform::from(cr.matrix()).pre_translate(viewport.x0, viewport.y0);
+    cr.set_matrix(transform.into());
+
+    let viewport = viewport.translate((-viewport.x0, -viewport.y0));
+
+    let mut draw_ctx = DrawingCtx::new(
+        cr,
+        transform,
+        viewport,
+        user_language.clone(),
+        dpi,
+        text_options.clone(),
+        true,
+        testing,
+        node.ancestors().collect(),
+    );
+
+    draw_ctx.draw_node_from_stack(&root, acquired_nodes, &cascaded, false)?;
+
+    Ok(draw_ctx.target_bbox.take().unwrap_or_default())
+}
+
+/// Appends the outlines of a node's shapes and text to the current path of `cr`.
+///
+/// Text gets laid out and positioned just like for rendering, but instead of painting
+/// the glyphs, their outlines get added to the path as is done for clipping paths.
+/// Nothing gets painted on the `cr`'s target.
+pub fn outline_tree(
+    node: &Node,
+    cr: &cairo::Context,
+    viewport: Rect,
+    user_language: &UserLanguage,
+    dpi: Dpi,
+    text_options: &TextOptions,
+    testing: bool,
+    acquired_nodes: &mut AcquiredNodes<'_>,
+) -> Result<(), RenderingError> {
+    let cascaded = CascadedValues::new_from_node(node);
+
+    let transform = Transform::from(cr.matrix()).pre_translate(viewport.x0, viewport.y0);
+    cr.set_matrix(transform.into());
+
+    let viewport = viewport.translate((-viewport.x0, -viewport.y0));
+
+    let mut draw_ctx = DrawingCtx::new(
+        cr,
+        transform,
+        viewport,
+        user_language.clone(),
+        dpi,
+        text_options.clone(),
+        true,
+        testing,
+        Vec::new(),
+    );
+
+    draw_ctx.draw_node_from_stack(node, acquired_nodes, &cascaded, true)?;
+
+    Ok(())
+}
+
 pub fn with_saved_cr<O, F>(cr: &cairo::Context, f: F) -> Result<O, RenderingError>
 where
     F: FnOnce() -> Result<O, RenderingError>,
@@ -275,6 +476,7 @@ impl DrawingCtx {
         viewport: Rect,
         user_language: UserLanguage,
         dpi: Dpi,
+        text_options: TextOptions,
         measuring: bool,
         testing: bool,
         drawsub_stack: Vec<Node>,
@@ -290,8 +492,15 @@ impl DrawingCtx {
             cr_stack: Rc::new(RefCell::new(Vec::new())),
             cr: cr.clone(),
             user_language,
+            text_options,
             viewport_stack: Rc::new(RefCell::new(viewport_stack)),
             drawsub_stack,
+            link_targets: Vec::new(),
+            link_regions: Rc::new(RefCell::new(Vec::new())),
+            text_geometry: None,
+            target_bbox: None,
+            text_attributes_cache: Rc::new(TextAttributesCache::default()),
+            font_measurements_cache: Rc::new(FontMeasurementsCache::default()),
             measuring,
             testing,
         }
@@ -314,8 +523,15 @@ impl DrawingCtx {
             cr_stack,
             cr,
             user_language: self.user_language.clone(),
+            text_options: self.text_options.clone(),
             viewport_stack: self.viewport_stack.clone(),
             drawsub_stack: Vec::new(),
+            link_targets: self.link_targets.clone(),
+            link_regions: self.link_regions.clone(),
+            text_geometry: self.text_geometry.clone(),
+            target_bbox: None,
+            text_attributes_cache: self.text_attributes_cache.clone(),
+            font_measurements_cache: self.font_measurements_cache.clone(),
             measuring: self.measuring,
             testing: self.testing,
         }
@@ -325,6 +541,10 @@ impl DrawingCtx {
         &self.user_language
     }
 
+    pub fn text_options(&self) -> &TextOptions {
+        &self.text_options
+    }
+
     pub fn toplevel_viewport(&self) -> Rect {
         *self.initial_viewport.vbox
     }
@@ -500,7 +720,7 @@ impl DrawingCtx {
                 let top_viewport = self.get_top_viewport();
 
                 self.push_viewport(Viewport {
-                    transform: top_viewport.transform.post_transform(&t),
+                    transform: self.get_transform(),
                     vbox: vbox.unwrap_or(top_viewport.vbox),
                 })
             })
@@ -523,7 +743,9 @@ impl DrawingCtx {
             let cascaded = CascadedValues::new_from_node(node);
             let values = cascaded.get();
 
-            let node_transform = values.transform().post_transform(&transform);
+            let node_transform = values
+                .transform(&self.get_view_params(), || None)
+                .post_transform(&transform);
 
             let orig_transform = self.get_transform();
             self.cr.transform(node_transform.into());
@@ -587,7 +809,9 @@ impl DrawingCtx {
 
         let mask_element = mask_node.borrow_element();
 
-        let mask_transform = values.transform().post_transform(&transform);
+        let mask_transform = values
+            .transform(&self.get_view_params(), || None)
+            .post_transform(&transform);
 
         let mask_content_surface = self.create_surface_for_toplevel_viewport()?;
 
@@ -672,6 +896,9 @@ impl DrawingCtx {
 
         let res = if clipping {
             draw_fn(acquired_nodes, self)
+        } else if self.measuring {
+            // Opacity, masks, and filters don't change the bounding box
+            with_saved_cr(&self.cr.clone(), || draw_fn(acquired_nodes, self))
         } else {
             with_saved_cr(&self.cr.clone(), || {
                 let Opacity(UnitInterval(opacity)) = stacking_ctx.opacity;
@@ -908,13 +1135,49 @@ impl DrawingCtx {
         let cr = self.cr.clone();
         cr.tag_begin(CAIRO_TAG_LINK, &attributes);
 
+        self.link_targets.push(link_target.to_string());
         let res = draw_fn(self);
+        self.link_targets.pop();
 
         cr.tag_end(CAIRO_TAG_LINK);
 
         res
     }
 
+    /// Records a hit region for text inside the innermost link being drawn, if any.
+    ///
+    /// The `bbox` is usually that of a single line of text.
+    pub fn add_link_region(&self, bbox: &BoundingBox) {
+        if let Some(target) = self.link_targets.last() {
+            let mut device_bbox = BoundingBox::new();
+            device_bbox.insert(bbox);
+
+            if let Some(rect) = device_bbox.rect {
+                self.link_regions.borrow_mut().push((rect, target.clone()));
+            }
+        }
+    }
+
+    pub fn text_attributes_cache(&self) -> &TextAttributesCache {
+        &self.text_attributes_cache
+    }
+
+    pub fn font_measurements_cache(&self) -> &FontMeasurementsCache {
+        &self.font_measurements_cache
+    }
+
+    /// Whether the geometry of text spans is being collected with `add_text_span_geometry`.
+    pub fn collects_text_geometry(&self) -> bool {
+        self.text_geometry.is_some()
+    }
+
+    /// Records a laid out text span, if the geometry of text spans is being collected.
+    pub fn add_text_span_geometry(&self, geometry: TextSpanGeometry) {
+        if let Some(ref text_geometry) = self.text_geometry {
+            text_geometry.borrow_mut().push(geometry);
+        }
+    }
+
     fn run_filters(
         &mut self,
         surface_to_filter: SharedImageSurface,
@@ -1226,6 +1489,11 @@ impl DrawingCtx {
                 path_helper.set()?;
                 let bbox = compute_stroke_and_fill_box(&cr, &shape.stroke, &shape.stroke_paint)?;
 
+                if dc.measuring {
+                    path_helper.unset();
+                    return Ok(bbox);
+                }
+
                 let stroke_paint = shape.stroke_paint.to_user_space(&bbox, view_params, values);
                 let fill_paint = shape.fill_paint.to_user_space(&bbox, view_params, values);
 
@@ -1314,7 +1582,7 @@ impl DrawingCtx {
         // and not by the final computed image bounds.
         let bounds = self.empty_bbox().with_rect(image.rect);
 
-        if image.is_visible {
+        if image.is_visible && !self.measuring {
             self.with_discrete_layer(
                 stacking_ctx,
                 acquired_nodes,
@@ -1346,13 +1614,22 @@ impl DrawingCtx {
         values: &ComputedValues,
         clipping: bool,
     ) -> Result<BoundingBox, RenderingError> {
-        let transform = self.get_transform();
-
         let paint_order = values.paint_order();
 
         let gravity = span.layout.context().unwrap().gravity();
 
-        let bbox = compute_text_box(&span.layout, span.x, span.y, transform, gravity);
+        let span_transform = text_span_transform(span, gravity);
+        let transform = self.get_transform().pre_transform(&span_transform);
+
+        let bbox = compute_text_box(
+            &span.layout,
+            span.x,
+            span.y,
+            transform,
+            gravity,
+            self.text_options.text_extents,
+            span.visible_ranges.as_deref(),
+        );
         if bbox.is_none() {
             return Ok(self.empty_bbox());
         }
@@ -1360,6 +1637,12 @@ impl DrawingCtx {
         let mut bbox = bbox.unwrap();
 
         with_saved_cr(&self.cr.clone(), || {
+            let text_matrix = self.cr.matrix();
+
+            if span_transform != Transform::identity() {
+                self.cr.transform(span_transform.into());
+            }
+
             self.cr
                 .set_antialias(cairo::Antialias::from(span.text_rendering));
 
@@ -1380,8 +1663,13 @@ impl DrawingCtx {
                     self.cr.rotate(rot);
                 }
 
-                pangocairo::functions::update_layout(&self.cr, &span.layout);
-                pangocairo::functions::layout_path(&self.cr, &span.layout);
+                decoration_lines_path(&self.cr, &span.decoration_lines)?;
+
+                for decoration in &span.inherited_decorations {
+                    decoration_lines_path(&self.cr, &decoration.lines)?;
+                }
+
+                draw_span_glyphs(&self.cr, span, GlyphsMode::Path)?;
 
                 self.cr.set_matrix(matrix);
 
@@ -1391,6 +1679,8 @@ impl DrawingCtx {
             if span.is_visible {
                 for &target in &paint_order.targets {
                     match target {
+                        PaintTarget::Fill if self.measuring => {}
+
                         PaintTarget::Fill => {
                             self.cr.move_to(span.x, span.y);
 
@@ -1401,14 +1691,47 @@ impl DrawingCtx {
 
                             let fill_paint =
                                 span.fill_paint.to_user_space(&bbox, &view_params, values);
-                            let had_paint_server =
-                                self.set_paint_source(&fill_paint, acquired_nodes)?;
-                            if had_paint_server {
-                                pangocairo::functions::update_layout(&self.cr, &span.layout);
-                                pangocairo::functions::show_layout(&self.cr, &span.layout);
+
+                            if let UserSpacePaintSource::Pattern(..) = fill_paint {
+                                // The pattern's tiles are laid out in the text's user
+                                // space, not in the rotated or scaled space of the
+                                // glyphs, so build the glyph outlines first and fill
+                                // them with the pattern set up in the text's space.
+                                decoration_lines_path(&self.cr, &span.decoration_lines)?;
+
+                                draw_span_glyphs(&self.cr, span, GlyphsMode::Path)?;
+
+                                self.cr.set_matrix(text_matrix);
+                                self.cr.set_fill_rule(cairo::FillRule::Winding);
+
+                                if self.set_paint_source(&fill_paint, acquired_nodes)? {
+                                    self.cr.fill()?;
+                                } else {
+                                    self.cr.new_path();
+                                }
+                            } else if self.set_paint_source(&fill_paint, acquired_nodes)? {
+                                let (layout_x, layout_y) = self.cr.current_point()?;
+
+                                draw_span_glyphs(&self.cr, span, GlyphsMode::Show)?;
+
+                                if !span.decoration_lines.is_empty() {
+                                    self.cr.move_to(layout_x, layout_y);
+                                    decoration_lines_path(&self.cr, &span.decoration_lines)?;
+                                    self.cr.fill()?;
+                                }
                             }
 
                             self.cr.set_matrix(matrix);
+
+                            self.paint_inherited_decorations(
+                                span,
+                                target,
+                                rotation,
+                                &bbox,
+                                view_params,
+                                values,
+                                acquired_nodes,
+                            )?;
                         }
 
                         PaintTarget::Stroke => {
@@ -1421,22 +1744,56 @@ impl DrawingCtx {
 
                             let stroke_paint =
                                 span.stroke_paint.to_user_space(&bbox, &view_params, values);
-                            let had_paint_server =
-                                self.set_paint_source(&stroke_paint, acquired_nodes)?;
+                            let had_paint_server = if self.measuring {
+                                !matches!(stroke_paint, UserSpacePaintSource::None)
+                            } else {
+                                self.set_paint_source(&stroke_paint, acquired_nodes)?
+                            };
                             if had_paint_server {
-                                pangocairo::functions::update_layout(&self.cr, &span.layout);
-                                pangocairo::functions::layout_path(&self.cr, &span.layout);
+                                decoration_lines_path(&self.cr, &span.decoration_lines)?;
+
+                                draw_span_glyphs(&self.cr, span, GlyphsMode::Path)?;
+
+                                // The path is already in device space, so it keeps its
+                                // shape; only the stroke stops following the transform.
+                                match span.stroke.vector_effect {
+                                    VectorEffect::None => {}
+                                    VectorEffect::NonScalingStroke => self.cr.identity_matrix(),
+                                    other => {
+                                        rsvg_log!("vector-effect: {:?} is not supported", other)
+                                    }
+                                }
 
+                                // The stroke extents include half the stroke width, plus
+                                // whatever the joins and caps add.  They are in the space
+                                // of the glyphs, which may be rotated for vertical text.
                                 let (x0, y0, x1, y1) = self.cr.stroke_extents()?;
                                 let r = Rect::new(x0, y0, x1, y1);
                                 let ib = BoundingBox::new()
-                                    .with_transform(transform)
+                                    .with_transform(Transform::from(self.cr.matrix()))
                                     .with_ink_rect(r);
                                 bbox.insert(&ib);
-                                self.cr.stroke()?;
+
+                                if self.measuring {
+                                    self.cr.new_path();
+                                } else {
+                                    self.cr.stroke()?;
+                                }
                             }
 
                             self.cr.set_matrix(matrix);
+
+                            if !self.measuring {
+                                self.paint_inherited_decorations(
+                                    span,
+                                    target,
+                                    rotation,
+                                    &bbox,
+                                    view_params,
+                                    values,
+                                    acquired_nodes,
+                                )?;
+                            }
                         }
 
                         PaintTarget::Markers => {}
@@ -1448,6 +1805,60 @@ impl DrawingCtx {
         })
     }
 
+    /// Fills or strokes the decoration lines that a text span gets from its ancestors,
+    /// each one with the paint of the ancestor.
+    ///
+    /// This leaves the current transformation and stroke parameters of the span alone.
+    #[allow(clippy::too_many_arguments)]
+    fn paint_inherited_decorations(
+        &mut self,
+        span: &TextSpan,
+        target: PaintTarget,
+        rotation: Option<f64>,
+        bbox: &BoundingBox,
+        view_params: &ViewParams,
+        values: &ComputedValues,
+        acquired_nodes: &mut AcquiredNodes<'_>,
+    ) -> Result<(), RenderingError> {
+        for decoration in &span.inherited_decorations {
+            let paint = match target {
+                PaintTarget::Fill => &decoration.fill_paint,
+                PaintTarget::Stroke => &decoration.stroke_paint,
+                PaintTarget::Markers => continue,
+            };
+
+            let paint = paint.to_user_space(bbox, view_params, values);
+
+            with_saved_cr(&self.cr.clone(), || {
+                self.cr.move_to(span.x, span.y);
+
+                let matrix = self.cr.matrix();
+                if let Some(rot) = rotation {
+                    self.cr.rotate(rot);
+                }
+
+                decoration_lines_path(&self.cr, &decoration.lines)?;
+
+                // The paint is set up without the rotation for the glyphs' gravity
+                self.cr.set_matrix(matrix);
+
+                if !self.set_paint_source(&paint, acquired_nodes)? {
+                    self.cr.new_path();
+                } else if let PaintTarget::Fill = target {
+                    self.cr.set_fill_rule(cairo::FillRule::Winding);
+                    self.cr.fill()?;
+                } else {
+                    setup_cr_for_stroke(&self.cr, &decoration.stroke);
+                    self.cr.stroke()?;
+                }
+
+                Ok(())
+            })?;
+        }
+
+        Ok(())
+    }
+
     pub fn get_snapshot(
         &self,
         width: i32,
@@ -1548,6 +1959,15 @@ impl DrawingCtx {
         };
 
         if let Some(top) = stack_top {
+            if draw && self.drawsub_stack.is_empty() {
+                if let Ok(ref bbox) = res {
+                    let mut target_bbox =
+                        BoundingBox::new().with_transform(self.get_top_viewport().transform);
+                    target_bbox.insert(bbox);
+                    self.target_bbox = Some(target_bbox);
+                }
+            }
+
             self.drawsub_stack.push(top);
         }
 
@@ -1619,7 +2039,8 @@ impl DrawingCtx {
 
         let orig_transform = self.get_transform();
 
-        self.cr.transform(values.transform().into());
+        self.cr
+            .transform(values.transform(&self.get_view_params(), || None).into());
 
         let use_element = node.borrow_element();
 
@@ -1725,6 +2146,7 @@ fn element_can_be_used_inside_clip_path(element: &Element) -> bool {
             | Element::Polyline(_)
             | Element::Rect(_)
             | Element::Text(_)
+            | Element::TextArea(_)
             | Element::Use(_)
     )
 }
@@ -1741,6 +2163,7 @@ fn element_can_be_used_inside_use_inside_clip_path(element: &Element) -> bool {
             | Element::Polyline(_)
             | Element::Rect(_)
             | Element::Text(_)
+            | Element::TextArea(_)
     )
 }
 
@@ -1862,43 +2285,421 @@ fn compute_text_box(
     y: f64,
     transform: Transform,
     gravity: pango::Gravity,
+    text_extents: TextExtents,
+    visible_ranges: Option<&[(f64, f64)]>,
 ) -> Option<BoundingBox> {
-    #![allow(clippy::many_single_char_names)]
-
-    let (ink, _) = layout.extents();
+    let (ink, logical) = layout.extents();
     if ink.width == 0 || ink.height == 0 {
         return None;
     }
 
-    let ink_x = f64::from(ink.x);
-    let ink_y = f64::from(ink.y);
-    let ink_width = f64::from(ink.width);
-    let ink_height = f64::from(ink.height);
+    let mut ink_rect = layout_rect_to_user_space(&ink, x, y, gravity);
+
+    let mut rect = match text_extents {
+        TextExtents::Ink => ink_rect,
+        TextExtents::Logical => layout_rect_to_user_space(&logical, x, y, gravity),
+    };
+
+    // A span in a shared layout only covers part of the layout's width.  Only horizontal
+    // layouts get shared.
+    if let Some(ranges) = visible_ranges {
+        let x0 = ranges.iter().map(|r| r.0).fold(f64::INFINITY, f64::min);
+        let x1 = ranges.iter().map(|r| r.1).fold(f64::NEG_INFINITY, f64::max);
+
+        if x0 >= x1 {
+            return None;
+        }
+
+        let (x0, x1) = (x + x0, x + x1);
+        let clamp = |r: Rect| Rect::new(r.x0.max(x0), r.y0, r.x1.min(x1).max(x0), r.y1);
+
+        ink_rect = clamp(ink_rect);
+        rect = clamp(rect);
+    }
+
+    let bbox = BoundingBox::new()
+        .with_transform(transform)
+        .with_rect(rect)
+        .with_ink_rect(ink_rect);
+
+    Some(bbox)
+}
+
+/// How `draw_span_glyphs()` draws the glyphs of a text span.
+#[derive(Copy, Clone, PartialEq)]
+enum GlyphsMode {
+    /// Shows the glyphs with the current source.
+    Show,
+
+    /// Adds the outlines of the glyphs to the current path.
+    Path,
+}
+
+/// Draws the glyphs of a text span, with the top-left corner of its layout at the
+/// current point.
+///
+/// A span that shares its layout with the other spans of its chunk draws only the
+/// glyphs for its own characters, so each glyph gets drawn once no matter how many
+/// spans the chunk has.  A glyph for the characters of several spans, like a ligature,
+/// gets shown clipped to each span's share of its advance, so that each part gets the
+/// paint of its own span.  A path cannot be clipped, so the outline of such a glyph
+/// goes with the span of its first character.
+fn draw_span_glyphs(
+    cr: &cairo::Context,
+    span: &TextSpan,
+    mode: GlyphsMode,
+) -> Result<(), RenderingError> {
+    pangocairo::functions::update_layout(cr, &span.layout);
+
+    let range = match span.text_range {
+        Some(ref range) => range.clone(),
+
+        None => {
+            match mode {
+                GlyphsMode::Show => pangocairo::functions::show_layout(cr, &span.layout),
+                GlyphsMode::Path => pangocairo::functions::layout_path(cr, &span.layout),
+            }
+
+            return Ok(());
+        }
+    };
+
+    let (x, y) = cr.current_point()?;
+
+    let is_inside =
+        |cluster: &Range<usize>| cluster.start >= range.start && cluster.end <= range.end;
+
+    match mode {
+        GlyphsMode::Path => {
+            draw_layout_glyphs(cr, &span.layout, x, y, mode, |cluster| {
+                range.contains(&cluster.start)
+            })?;
+        }
+
+        GlyphsMode::Show => {
+            draw_layout_glyphs(cr, &span.layout, x, y, mode, is_inside)?;
+
+            if let Some(ref ranges) = span.visible_ranges {
+                with_saved_cr(cr, || {
+                    clip_to_visible_ranges(cr, span, ranges);
+
+                    draw_layout_glyphs(cr, &span.layout, x, y, mode, |cluster| {
+                        cluster.start < range.end
+                            && cluster.end > range.start
+                            && !is_inside(cluster)
+                    })
+                })?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Draws the glyphs of a layout whose clusters `keep` accepts, with the top-left
+/// corner of the layout at (`x`, `y`).
+///
+/// `keep` gets the byte range in the layout's text of each glyph's cluster.  The other
+/// glyphs become empty, so that the kept ones stay in their positions.
+fn draw_layout_glyphs<F>(
+    cr: &cairo::Context,
+    layout: &pango::Layout,
+    x: f64,
+    y: f64,
+    mode: GlyphsMode,
+    keep: F,
+) -> Result<(), RenderingError>
+where
+    F: Fn(&Range<usize>) -> bool,
+{
+    let pango_scale = f64::from(pango::SCALE);
+
+    let mut iter = match layout.iter() {
+        Some(iter) => iter,
+        None => return Ok(()),
+    };
+
+    loop {
+        if let Some(run) = iter.run_readonly() {
+            let item = run.item();
+            let mut glyphs = run.glyph_string();
+
+            let clusters = glyph_clusters(&glyphs, item.offset() as usize, item.length() as usize);
+            let hidden: Vec<bool> = clusters.iter().map(|cluster| !keep(cluster)).collect();
+
+            if hidden.iter().any(|hide| !hide) {
+                hide_glyphs(&mut glyphs, &hidden);
+
+                let (_, logical) = iter.run_extents();
+                cr.move_to(
+                    x + f64::from(logical.x) / pango_scale,
+                    y + f64::from(iter.baseline()) / pango_scale,
+                );
+
+                let font = item.analysis().font();
+
+                match mode {
+                    GlyphsMode::Show => {
+                        pangocairo::functions::show_glyph_string(cr, &font, &mut glyphs)
+                    }
+                    GlyphsMode::Path => {
+                        pangocairo::functions::glyph_string_path(cr, &font, &mut glyphs)
+                    }
+                }
+            }
+        }
+
+        if !iter.next_run() {
+            return Ok(());
+        }
+    }
+}
+
+/// The byte range in the layout's text of the cluster of each glyph of a run.
+///
+/// `offset` and `length` are the byte range of the run's item.  The glyphs of a
+/// right-to-left run are in visual order, so their clusters go backwards.
+fn glyph_clusters(glyphs: &pango::GlyphString, offset: usize, length: usize) -> Vec<Range<usize>> {
+    let mut starts: Vec<usize> = glyphs.log_clusters().iter().map(|&c| c as usize).collect();
+    starts.sort_unstable();
+    starts.dedup();
+
+    glyphs
+        .log_clusters()
+        .iter()
+        .map(|&c| {
+            let start = c as usize;
+            let end = starts
+                .iter()
+                .copied()
+                .find(|&s| s > start)
+                .unwrap_or(length);
+
+            offset + start..offset + end
+        })
+        .collect()
+}
+
+/// Pango's glyph that draws nothing and has no extents.
+const PANGO_GLYPH_EMPTY: u32 = 0x0FFF_FFFF;
+
+/// Replaces the glyphs for which `hidden` is true with empty ones, keeping their
+/// advances.
+fn hide_glyphs(glyphs: &mut pango::GlyphString, hidden: &[bool]) {
+    unsafe {
+        let ptr: *mut pango::ffi::PangoGlyphString = glyphs.to_glib_none_mut().0;
+        let infos = slice::from_raw_parts_mut((*ptr).glyphs, (*ptr).num_glyphs as usize);
+
+        for (info, &hide) in infos.iter_mut().zip(hidden) {
+            if hide {
+                info.glyph = PANGO_GLYPH_EMPTY;
+            }
+        }
+    }
+}
+
+/// Clips to the parts of a shared layout that belong to a span.
+///
+/// The current transformation must be the one for the span's glyphs.  The ranges at the
+/// ends of the layout get extended, so that the side bearings, overhangs, and strokes of
+/// the outermost glyphs do not get cut off.
+fn clip_to_visible_ranges(cr: &cairo::Context, span: &TextSpan, ranges: &[(f64, f64)]) {
+    let pango_scale = f64::from(pango::SCALE);
+
+    let (_, logical) = span.layout.extents();
+    let top = f64::from(logical.y) / pango_scale;
+    let width = f64::from(logical.width) / pango_scale;
+    let height = f64::from(logical.height) / pango_scale;
+
+    let margin = height + span.stroke.width;
+
+    for &(x0, x1) in ranges {
+        let x0 = if x0 <= 1.0 / pango_scale {
+            x0 - margin
+        } else {
+            x0
+        };
+        let x1 = if x1 >= width - 1.0 / pango_scale {
+            x1 + margin
+        } else {
+            x1
+        };
+
+        cr.rectangle(
+            span.x + x0,
+            span.y + top - margin,
+            x1 - x0,
+            height + 2.0 * margin,
+        );
+    }
+
+    cr.clip();
+}
+
+/// Converts a rectangle from a Pango layout's extents to user space, for a layout at `(x, y)`.
+fn layout_rect_to_user_space(
+    rect: &pango::Rectangle,
+    x: f64,
+    y: f64,
+    gravity: pango::Gravity,
+) -> Rect {
+    #![allow(clippy::many_single_char_names)]
+
+    let rect_x = f64::from(rect.x);
+    let rect_y = f64::from(rect.y);
+    let rect_width = f64::from(rect.width);
+    let rect_height = f64::from(rect.height);
     let pango_scale = f64::from(pango::SCALE);
 
     let (x, y, w, h) = if gravity_is_vertical(gravity) {
         (
-            x + (ink_x - ink_height) / pango_scale,
-            y + ink_y / pango_scale,
-            ink_height / pango_scale,
-            ink_width / pango_scale,
+            x + (rect_x - rect_height) / pango_scale,
+            y + rect_y / pango_scale,
+            rect_height / pango_scale,
+            rect_width / pango_scale,
         )
     } else {
         (
-            x + ink_x / pango_scale,
-            y + ink_y / pango_scale,
-            ink_width / pango_scale,
-            ink_height / pango_scale,
+            x + rect_x / pango_scale,
+            y + rect_y / pango_scale,
+            rect_width / pango_scale,
+            rect_height / pango_scale,
         )
     };
 
-    let r = Rect::new(x, y, x + w, y + h);
-    let bbox = BoundingBox::new()
-        .with_transform(transform)
-        .with_rect(r)
-        .with_ink_rect(r);
+    Rect::new(x, y, x + w, y + h)
+}
 
-    Some(bbox)
+/// Transformation from the coordinates of a text span's Pango layout to the text's
+/// user space.
+///
+/// This is the transformation with which `draw_text_span` paints the layout.
+pub fn text_span_layout_transform(span: &TextSpan) -> Transform {
+    text_layout_transform(
+        &span.layout,
+        span.x,
+        span.y,
+        span.rotation,
+        span.length_adjust_scale,
+        span.slant,
+    )
+}
+
+/// Transformation from the coordinates of a Pango layout to the text's user space, for
+/// a span at (`x`, `y`) with the `rotation`, `length_adjust_scale`, and `slant` of the
+/// fields of `TextSpan`.
+pub fn text_layout_transform(
+    layout: &pango::Layout,
+    x: f64,
+    y: f64,
+    rotation: f64,
+    length_adjust_scale: f64,
+    slant: f64,
+) -> Transform {
+    let gravity = layout.context().unwrap().gravity();
+
+    glyphs_transform(layout, x, y, rotation, length_adjust_scale, slant, gravity)
+        .pre_translate(x, y)
+        .pre_rotate(Angle::new(-gravity.to_rotation()))
+}
+
+/// Transformation for the glyphs of a text span, relative to the text's user space.
+fn text_span_transform(span: &TextSpan, gravity: pango::Gravity) -> Transform {
+    glyphs_transform(
+        &span.layout,
+        span.x,
+        span.y,
+        span.rotation,
+        span.length_adjust_scale,
+        span.slant,
+        gravity,
+    )
+}
+
+/// Transformation for the glyphs of a span at (`x`, `y`), relative to the text's user
+/// space.
+///
+/// `lengthAdjust="spacingAndGlyphs"` scales the layout along the inline direction, with the
+/// start of the span as the origin.  A rotation from the `rotate` attribute turns the
+/// glyph around its origin on the baseline.
+fn glyphs_transform(
+    layout: &pango::Layout,
+    x: f64,
+    y: f64,
+    rotation: f64,
+    length_adjust_scale: f64,
+    slant: f64,
+    gravity: pango::Gravity,
+) -> Transform {
+    let is_vertical = gravity_is_vertical(gravity);
+
+    let mut transform = Transform::identity();
+
+    if !rotation.approx_eq_cairo(0.0) {
+        let baseline = f64::from(layout.baseline()) / f64::from(pango::SCALE);
+
+        let (ox, oy) = if is_vertical {
+            (x - baseline, y)
+        } else {
+            (x, y + baseline)
+        };
+
+        transform = transform
+            .pre_translate(ox, oy)
+            .pre_rotate(Angle::from_degrees(rotation))
+            .pre_translate(-ox, -oy);
+    }
+
+    if !length_adjust_scale.approx_eq_cairo(1.0) {
+        let (sx, sy) = if is_vertical {
+            (1.0, length_adjust_scale)
+        } else {
+            (length_adjust_scale, 1.0)
+        };
+
+        transform = transform
+            .pre_translate(x, y)
+            .pre_scale(sx, sy)
+            .pre_translate(-x, -y);
+    }
+
+    // The synthetic slant leans the glyphs around the baseline.  Vertical text
+    // keeps its glyphs upright, since there is no clear direction for the slant.
+    if !slant.approx_eq_cairo(0.0) && !is_vertical {
+        let baseline = f64::from(layout.baseline()) / f64::from(pango::SCALE);
+        let (ox, oy) = (x, y + baseline);
+        let shear = Transform::new_unchecked(1.0, 0.0, -slant.to_radians().tan(), 1.0, 0.0, 0.0);
+
+        transform = transform
+            .pre_translate(ox, oy)
+            .pre_transform(&shear)
+            .pre_translate(-ox, -oy);
+    }
+
+    transform
+}
+
+/// Appends the rectangles for a text span's decoration lines to the current path.
+///
+/// The current point must be at the top-left corner of the span's layout; it is
+/// left there after adding the rectangles.
+fn decoration_lines_path(
+    cr: &cairo::Context,
+    lines: &[DecorationLine],
+) -> Result<(), cairo::Error> {
+    if lines.is_empty() {
+        return Ok(());
+    }
+
+    let (x, y) = cr.current_point()?;
+
+    for line in lines {
+        cr.rectangle(x + line.x, y + line.y, line.length, line.thickness);
+    }
+
+    cr.move_to(x, y);
+
+    Ok(())
 }
 
 fn setup_cr_for_stroke(cr: &cairo::Context, stroke: &Stroke) {
@@ -2036,45 +2837,49 @@ impl From<&DrawingCtx> for pango::Context {
     fn from(draw_ctx: &DrawingCtx) -> pango::Context {
         let cr = draw_ctx.cr.clone();
 
-        let mut options = cairo::FontOptions::new().unwrap();
-        if draw_ctx.testing {
-            options.set_antialias(cairo::Antialias::Gray);
-        }
-
-        options.set_hint_style(cairo::HintStyle::None);
-        options.set_hint_metrics(cairo::HintMetrics::Off);
-
+        let options = draw_ctx.text_options.cairo_font_options(draw_ctx.testing);
         cr.set_font_options(&options);
 
-        let font_map = pangocairo::FontMap::default().unwrap();
-        let context = font_map.create_context().unwrap();
-
-        context.set_round_glyph_positions(false);
+        let font_map = draw_ctx
+            .text_options
+            .font_map
+            .clone()
+            .unwrap_or_else(|| pangocairo::FontMap::default().unwrap());
+        let context = create_pango_context(&font_map);
 
         pangocairo::functions::update_context(&cr, &context);
 
-        // Pango says this about pango_cairo_context_set_resolution():
-        //
-        //     Sets the resolution for the context. This is a scale factor between
-        //     points specified in a #PangoFontDescription and Cairo units. The
-        //     default value is 96, meaning that a 10 point font will be 13
-        //     units high. (10 * 96. / 72. = 13.3).
-        //
-        // I.e. Pango font sizes in a PangoFontDescription are in *points*, not pixels.
-        // However, we are normalizing everything to userspace units, which amount to
-        // pixels.  So, we will use 72.0 here to make Pango not apply any further scaling
-        // to the size values we give it.
-        //
-        // An alternative would be to divide our font sizes by (dpi_y / 72) to effectively
-        // cancel out Pango's scaling, but it's probably better to deal with Pango-isms
-        // right here, instead of spreading them out through our Length normalization
-        // code.
-        pangocairo::functions::context_set_resolution(&context, 72.0);
-
         context
     }
 }
 
+/// Creates a Pango context that takes font sizes in user space units.
+pub fn create_pango_context(font_map: &pango::FontMap) -> pango::Context {
+    let context = font_map.create_context().unwrap();
+
+    context.set_round_glyph_positions(false);
+
+    // Pango says this about pango_cairo_context_set_resolution():
+    //
+    //     Sets the resolution for the context. This is a scale factor between
+    //     points specified in a #PangoFontDescription and Cairo units. The
+    //     default value is 96, meaning that a 10 point font will be 13
+    //     units high. (10 * 96. / 72. = 13.3).
+    //
+    // I.e. Pango font sizes in a PangoFontDescription are in *points*, not pixels.
+    // However, we are normalizing everything to userspace units, which amount to
+    // pixels.  So, we will use 72.0 here to make Pango not apply any further scaling
+    // to the size values we give it.
+    //
+    // An alternative would be to divide our font sizes by (dpi_y / 72) to effectively
+    // cancel out Pango's scaling, but it's probably better to deal with Pango-isms
+    // right here, instead of spreading them out through our Length normalization
+    // code.
+    pangocairo::functions::context_set_resolution(&context, 72.0);
+
+    context
+}
+
 impl From<cairo::Matrix> for Transform {
     #[inline]
     fn from(m: cairo::Matrix) -> Self {
@@ -2090,6 +2895,21 @@ impl From<Transform> for cairo::Matrix {
 }
 
 impl Path {
+    /// Computes the extents of the path's geometry, i.e. its object bounding box.
+    pub fn extents(&self) -> Option<Rect> {
+        if self.is_empty() {
+            return None;
+        }
+
+        let surface = cairo::ImageSurface::create(cairo::Format::A8, 1, 1).ok()?;
+        let cr = cairo::Context::new(&surface).ok()?;
+
+        self.to_cairo(&cr, false).ok()?;
+        let (x0, y0, x1, y1) = cr.path_extents().ok()?;
+
+        Some(Rect::new(x0, y0, x1, y1))
+    }
+
     pub fn to_cairo(
         &self,
         cr: &cairo::Context,
@@ -2173,3 +2993,99 @@ impl CubicBezierCurve {
         cr.curve_to(pt1.0, pt1.1, pt2.0, pt2.1, to.0, to.1);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::accept_language::Language;
+    use crate::document::Document;
+
+    fn assert_rects_eq(a: Option<Rect>, b: Option<Rect>) {
+        match (a, b) {
+            (None, None) => (),
+            (Some(a), Some(b)) => {
+                for &(x, y) in &[(a.x0, b.x0), (a.y0, b.y0), (a.x1, b.x1), (a.y1, b.y1)] {
+                    assert!(approx_eq!(f64, x, y, epsilon = 1e-6), "{:?} != {:?}", a, b);
+                }
+            }
+            _ => panic!("{:?} != {:?}", a, b),
+        }
+    }
+
+    /// Renders the tree down to the `node`, and returns its bounding box as painted.
+    fn rendered_bbox(document: &Document, node: &Node) -> BoundingBox {
+        let surface = cairo::ImageSurface::create(cairo::Format::ARgb32, 100, 100).unwrap();
+        let cr = cairo::Context::new(&surface).unwrap();
+
+        draw_tree(
+            DrawingMode::LimitToStack {
+                node: node.clone(),
+                root: document.root(),
+            },
+            &cr,
+            Rect::from_size(100.0, 100.0),
+            &UserLanguage::new(&Language::FromEnvironment),
+            Dpi::new(96.0, 96.0),
+            &TextOptions::default(),
+            false,
+            true,
+            &mut AcquiredNodes::new(document),
+        )
+        .unwrap()
+    }
+
+    /// Measures the `node` without painting, like `Handle::get_bbox_in_viewport_for_element()`.
+    fn measured_bbox(document: &Document, node: &Node) -> BoundingBox {
+        let surface = cairo::ImageSurface::create(cairo::Format::ARgb32, 1, 1).unwrap();
+        let cr = cairo::Context::new(&surface).unwrap();
+
+        element_bbox_tree(
+            node.clone(),
+            document.root(),
+            &cr,
+            Rect::from_size(100.0, 100.0),
+            &UserLanguage::new(&Language::FromEnvironment),
+            Dpi::new(96.0, 96.0),
+            &TextOptions::default(),
+            true,
+            &mut AcquiredNodes::new(document),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn measured_bboxes_match_the_rendered_bboxes() {
+        let document = Document::load_from_bytes(
+            br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <defs>
+    <filter id="blur">
+      <feGaussianBlur stdDeviation="2"/>
+    </filter>
+    <mask id="mask">
+      <rect width="50" height="50" fill="white"/>
+    </mask>
+  </defs>
+  <rect id="rect" x="10" y="10" width="20" height="30" stroke="black" stroke-width="4"/>
+  <g id="group" transform="translate(5, 10)" opacity="0.5">
+    <circle id="circle" cx="50" cy="50" r="10" filter="url(#blur)"/>
+    <path id="path" d="M 10 60 L 30 80 L 10 90" fill="none" stroke="blue" mask="url(#mask)"/>
+  </g>
+  <text id="text" x="10" y="90" stroke="red" stroke-width="2">Hello</text>
+</svg>
+"#,
+        );
+
+        for id in &["rect", "group", "circle", "path", "text"] {
+            let node = document.lookup_internal_node(id).unwrap();
+
+            let measured = measured_bbox(&document, &node);
+            let rendered = rendered_bbox(&document, &node);
+
+            assert!(rendered.rect.is_some());
+            assert_rects_eq(measured.rect, rendered.rect);
+            assert_rects_eq(measured.ink_rect, rendered.ink_rect);
+        }
+    }
+}
@@ -5,11 +5,12 @@ use once_cell::sync::Lazy;
 use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::ops::Deref;
+use std::rc::Rc;
 
 use crate::accept_language::UserLanguage;
 use crate::bbox::BoundingBox;
-use crate::cond::{RequiredExtensions, RequiredFeatures, SystemLanguage};
-use crate::css::{Declaration, Origin};
+use crate::cond::{Extensions, RequiredExtensions, RequiredFeatures, SystemLanguage};
+use crate::css::{parse_declarations, Declaration, Origin};
 use crate::document::AcquiredNodes;
 use crate::drawing_ctx::DrawingCtx;
 use crate::error::*;
@@ -36,14 +37,16 @@ use crate::gradient::{LinearGradient, RadialGradient, Stop};
 use crate::image::Image;
 use crate::marker::Marker;
 use crate::node::*;
-use crate::parsers::Parse;
 use crate::pattern::Pattern;
-use crate::properties::{ComputedValues, SpecifiedValues};
+use crate::properties::{
+    ComputedValues, ParsedProperty, RootFontSize, SpecifiedValue, SpecifiedValues,
+};
 use crate::shapes::{Circle, Ellipse, Line, Path, Polygon, Polyline, Rect};
-use crate::structure::{ClipPath, Group, Link, Mask, NonRendering, Svg, Switch, Symbol, Use};
+use crate::structure::{
+    ClipPath, Desc, Group, Link, Mask, NonRendering, Svg, Switch, Symbol, Title, Use,
+};
 use crate::style::Style;
-use crate::text::{TRef, TSpan, Text};
-use crate::transform::Transform;
+use crate::text::{FlowPara, FlowRegion, FlowRoot, FlowSpan, TRef, TSpan, Text, TextArea};
 use crate::xml::Attributes;
 
 // After creating/parsing a Element, it will be in a success or an error state.
@@ -102,7 +105,7 @@ pub struct ElementInner<T: SetAttributes + Draw> {
     specified_values: SpecifiedValues,
     important_styles: HashSet<QualName>,
     result: ElementResult,
-    values: ComputedValues,
+    values: Rc<ComputedValues>,
     required_extensions: Option<RequiredExtensions>,
     required_features: Option<RequiredFeatures>,
     system_language: Option<SystemLanguage>,
@@ -134,7 +137,6 @@ impl<T: SetAttributes + Draw> ElementInner<T> {
         };
 
         let mut set_attributes = || -> Result<(), ElementError> {
-            e.set_transform_attribute()?;
             e.set_conditional_processing_attributes()?;
             e.set_presentation_attributes()?;
             Ok(())
@@ -171,14 +173,18 @@ impl<T: SetAttributes + Draw> ElementInner<T> {
         &self.values
     }
 
-    fn set_computed_values(&mut self, values: &ComputedValues) {
-        self.values = values.clone();
+    fn get_shared_computed_values(&self) -> Rc<ComputedValues> {
+        self.values.clone()
+    }
+
+    fn set_computed_values(&mut self, values: Rc<ComputedValues>) {
+        self.values = values;
     }
 
-    fn get_cond(&self, user_language: &UserLanguage) -> bool {
+    fn get_cond(&self, user_language: &UserLanguage, extensions: &Extensions) -> bool {
         self.required_extensions
             .as_ref()
-            .map(|v| v.eval())
+            .map(|v| v.eval(extensions))
             .unwrap_or(true)
             && self
                 .required_features
@@ -192,19 +198,8 @@ impl<T: SetAttributes + Draw> ElementInner<T> {
                 .unwrap_or(true)
     }
 
-    // TODO for madds: this whole function will go away, as the transform attribute will be
-    // handled automatically by set_presentation_attributes() below.
-    fn set_transform_attribute(&mut self) -> Result<(), ElementError> {
-        let transform = self
-            .attributes
-            .iter()
-            .find(|(attr, _)| attr.expanded() == expanded_name!("", "transform"))
-            .map(|(attr, value)| Transform::parse_str(value).attribute(attr))
-            .unwrap_or_else(|| Ok(Transform::default()))?;
-
-        self.specified_values.set_transform(transform);
-
-        Ok(())
+    fn get_system_language(&self) -> Option<&SystemLanguage> {
+        self.system_language.as_ref()
     }
 
     fn set_conditional_processing_attributes(&mut self) -> Result<(), ElementError> {
@@ -239,6 +234,17 @@ impl<T: SetAttributes + Draw> ElementInner<T> {
             .parse_presentation_attributes(&self.attributes)
     }
 
+    /// Forgets the declarations from style sheets and the `style` attribute, so that a
+    /// new cascade of the style sheets starts over from the presentation attributes.
+    fn reset_style_declarations(&mut self) {
+        self.specified_values = Default::default();
+        self.important_styles.clear();
+
+        // Errors in the presentation attributes already put the element in error when
+        // it was created.
+        let _ = self.set_presentation_attributes();
+    }
+
     // Applies a style declaration to the node's specified_values
     fn apply_style_declaration(&mut self, declaration: &Declaration, origin: Origin) {
         self.specified_values.set_property_from_declaration(
@@ -248,23 +254,20 @@ impl<T: SetAttributes + Draw> ElementInner<T> {
         );
     }
 
-    /// Applies CSS styles from the "style" attribute
-    fn set_style_attribute(&mut self) {
-        let style = self
-            .attributes
+    fn specify_root_font_size(&mut self) {
+        self.specified_values
+            .set_parsed_property(&ParsedProperty::RootFontSize(SpecifiedValue::Specified(
+                RootFontSize::OwnFontSize,
+            )));
+    }
+
+    /// Parses the CSS declarations from the "style" attribute
+    fn style_attribute_declarations(&self) -> Vec<Declaration> {
+        self.attributes
             .iter()
             .find(|(attr, _)| attr.expanded() == expanded_name!("", "style"))
-            .map(|(_, value)| value);
-
-        if let Some(style) = style {
-            if let Err(e) = self.specified_values.parse_style_declarations(
-                style,
-                Origin::Author,
-                &mut self.important_styles,
-            ) {
-                self.set_error(e);
-            }
-        }
+            .map(|(_, value)| parse_declarations(value))
+            .unwrap_or_default()
     }
 
     fn set_error(&mut self, error: ElementError) {
@@ -327,8 +330,13 @@ impl<T: SetAttributes + Draw> Deref for ElementInner<T> {
 pub enum Element {
     Circle(Box<ElementInner<Circle>>),
     ClipPath(Box<ElementInner<ClipPath>>),
+    Desc(Box<ElementInner<Desc>>),
     Ellipse(Box<ElementInner<Ellipse>>),
     Filter(Box<ElementInner<Filter>>),
+    FlowPara(Box<ElementInner<FlowPara>>),
+    FlowRegion(Box<ElementInner<FlowRegion>>),
+    FlowRoot(Box<ElementInner<FlowRoot>>),
+    FlowSpan(Box<ElementInner<FlowSpan>>),
     Group(Box<ElementInner<Group>>),
     Image(Box<ElementInner<Image>>),
     Line(Box<ElementInner<Line>>),
@@ -349,6 +357,8 @@ pub enum Element {
     Switch(Box<ElementInner<Switch>>),
     Symbol(Box<ElementInner<Symbol>>),
     Text(Box<ElementInner<Text>>),
+    TextArea(Box<ElementInner<TextArea>>),
+    Title(Box<ElementInner<Title>>),
     TRef(Box<ElementInner<TRef>>),
     TSpan(Box<ElementInner<TSpan>>),
     Use(Box<ElementInner<Use>>),
@@ -386,8 +396,13 @@ macro_rules! call_inner {
         match $element {
             Element::Circle(i) => i.$method($($args),*),
             Element::ClipPath(i) => i.$method($($args),*),
+            Element::Desc(i) => i.$method($($args),*),
             Element::Ellipse(i) => i.$method($($args),*),
             Element::Filter(i) => i.$method($($args),*),
+            Element::FlowPara(i) => i.$method($($args),*),
+            Element::FlowRegion(i) => i.$method($($args),*),
+            Element::FlowRoot(i) => i.$method($($args),*),
+            Element::FlowSpan(i) => i.$method($($args),*),
             Element::Group(i) => i.$method($($args),*),
             Element::Image(i) => i.$method($($args),*),
             Element::Line(i) => i.$method($($args),*),
@@ -408,6 +423,8 @@ macro_rules! call_inner {
             Element::Switch(i) => i.$method($($args),*),
             Element::Symbol(i) => i.$method($($args),*),
             Element::Text(i) => i.$method($($args),*),
+            Element::TextArea(i) => i.$method($($args),*),
+            Element::Title(i) => i.$method($($args),*),
             Element::TRef(i) => i.$method($($args),*),
             Element::TSpan(i) => i.$method($($args),*),
             Element::Use(i) => i.$method($($args),*),
@@ -523,20 +540,39 @@ impl Element {
         call_inner!(self, get_computed_values)
     }
 
-    pub fn set_computed_values(&mut self, values: &ComputedValues) {
+    pub fn get_shared_computed_values(&self) -> Rc<ComputedValues> {
+        call_inner!(self, get_shared_computed_values)
+    }
+
+    pub fn set_computed_values(&mut self, values: Rc<ComputedValues>) {
         call_inner!(self, set_computed_values, values);
     }
 
-    pub fn get_cond(&self, user_language: &UserLanguage) -> bool {
-        call_inner!(self, get_cond, user_language)
+    pub fn get_cond(&self, user_language: &UserLanguage, extensions: &Extensions) -> bool {
+        call_inner!(self, get_cond, user_language, extensions)
+    }
+
+    pub fn get_system_language(&self) -> Option<&SystemLanguage> {
+        call_inner!(self, get_system_language)
+    }
+
+    pub fn reset_style_declarations(&mut self) {
+        call_inner!(self, reset_style_declarations)
     }
 
     pub fn apply_style_declaration(&mut self, declaration: &Declaration, origin: Origin) {
         call_inner!(self, apply_style_declaration, declaration, origin)
     }
 
-    pub fn set_style_attribute(&mut self) {
-        call_inner!(self, set_style_attribute);
+    /// Makes the `rem` units in this element and its descendants refer to its font size.
+    ///
+    /// The cascade calls this for the root element.
+    pub fn specify_root_font_size(&mut self) {
+        call_inner!(self, specify_root_font_size)
+    }
+
+    pub fn style_attribute_declarations(&self) -> Vec<Declaration> {
+        call_inner!(self, style_attribute_declarations)
     }
 
     pub fn is_in_error(&self) -> bool {
@@ -642,6 +678,7 @@ mod creators {
     e!(create_circle,                   Circle);
     e!(create_clip_path,                ClipPath);
     e!(create_defs,                     NonRendering);
+    e!(create_desc,                     Desc);
     e!(create_ellipse,                  Ellipse);
     e!(create_fe_blend,                 FeBlend);
     e!(create_fe_color_matrix,          FeColorMatrix);
@@ -668,6 +705,10 @@ mod creators {
     e!(create_fe_tile,                  FeTile);
     e!(create_fe_turbulence,            FeTurbulence);
     e!(create_filter,                   Filter);
+    e!(create_flow_para,                FlowPara);
+    e!(create_flow_region,              FlowRegion);
+    e!(create_flow_root,                FlowRoot);
+    e!(create_flow_span,                FlowSpan);
     e!(create_group,                    Group);
     e!(create_image,                    Image);
     e!(create_line,                     Line);
@@ -688,6 +729,8 @@ mod creators {
     e!(create_switch,                   Switch);
     e!(create_symbol,                   Symbol);
     e!(create_text,                     Text);
+    e!(create_text_area,                TextArea);
+    e!(create_title,                    Title);
     e!(create_tref,                     TRef);
     e!(create_tspan,                    TSpan);
     e!(create_use,                      Use);
@@ -741,7 +784,7 @@ static ELEMENT_CREATORS: Lazy<HashMap<&'static str, (ElementCreateFn, ElementCre
         /* ("color-profile",    ), */
         /* ("cursor",           ), */
         ("defs",                create_defs,                  Default),
-        /* ("desc",             ), */
+        ("desc",                create_desc,                  Default),
         ("ellipse",             create_ellipse,               Default),
         ("feBlend",             create_fe_blend,              Default),
         ("feColorMatrix",       create_fe_color_matrix,       Default),
@@ -768,6 +811,10 @@ static ELEMENT_CREATORS: Lazy<HashMap<&'static str, (ElementCreateFn, ElementCre
         ("feTile",              create_fe_tile,               Default),
         ("feTurbulence",        create_fe_turbulence,         Default),
         ("filter",              create_filter,                Default),
+        ("flowPara",            create_flow_para,             Default),
+        ("flowRegion",          create_flow_region,           Default),
+        ("flowRoot",            create_flow_root,             Default),
+        ("flowSpan",            create_flow_span,             Default),
         /* ("font",             ), */
         /* ("font-face",        ), */
         /* ("font-face-format", ), */
@@ -804,8 +851,9 @@ static ELEMENT_CREATORS: Lazy<HashMap<&'static str, (ElementCreateFn, ElementCre
         ("switch",              create_switch,                Default),
         ("symbol",              create_symbol,                Default),
         ("text",                create_text,                  Default),
+        ("textArea",            create_text_area,             Default),
         /* ("textPath",         ), */
-        /* ("title",            ), */
+        ("title",               create_title,                 Default),
         ("tref",                create_tref,                  Default),
         ("tspan",               create_tspan,                 Default),
         ("use",                 create_use,                   Default),
@@ -841,6 +889,7 @@ mod sizes {
         print_size!(Circle);
         print_size!(ClipPath);
         print_size!(NonRendering);
+        print_size!(Desc);
         print_size!(Ellipse);
         print_size!(FeBlend);
         print_size!(FeColorMatrix);
@@ -867,6 +916,10 @@ mod sizes {
         print_size!(FeTile);
         print_size!(FeTurbulence);
         print_size!(Filter);
+        print_size!(FlowPara);
+        print_size!(FlowRegion);
+        print_size!(FlowRoot);
+        print_size!(FlowSpan);
         print_size!(Group);
         print_size!(Image);
         print_size!(Line);
@@ -887,6 +940,8 @@ mod sizes {
         print_size!(Switch);
         print_size!(Symbol);
         print_size!(Text);
+        print_size!(TextArea);
+        print_size!(Title);
         print_size!(TRef);
         print_size!(TSpan);
         print_size!(Use);
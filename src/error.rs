@@ -361,6 +361,9 @@ pub enum LoadingError {
     /// An invalid stylesheet was used.
     BadCss,
 
+    /// Font data that is not a font that can be loaded.
+    BadFont,
+
     /// There is no `<svg>` root element in the XML.
     NoSvgRoot,
 
@@ -427,6 +430,7 @@ impl fmt::Display for LoadingError {
             LoadingError::OutOfMemory(ref s) => write!(f, "out of memory: {}", s),
             LoadingError::BadUrl => write!(f, "invalid URL"),
             LoadingError::BadCss => write!(f, "invalid CSS"),
+            LoadingError::BadFont => write!(f, "invalid font data"),
             LoadingError::NoSvgRoot => write!(f, "XML does not have <svg> root"),
             LoadingError::Io(ref s) => write!(f, "I/O error: {}", s),
             LoadingError::LimitExceeded(ref l) => write!(f, "{}", l),
@@ -0,0 +1,261 @@
+//! The `@font-face` at-rule, for fonts that a document brings along.
+//!
+//! A `@font-face` rule gives a family name to a font from its `src` descriptor:
+//!
+//! ```css
+//! @font-face {
+//!   font-family: "Headline";
+//!   src: url("headline.woff2") format("woff2"), url("headline.woff") format("woff");
+//! }
+//! ```
+//!
+//! The sources get tried in order, and the first one that loads gets added to the
+//! handle's fonts under the rule's family name; see `AppFonts::add_font_face()`.  A
+//! `local()` source uses an installed font with that full name or PostScript name, so
+//! a `url()` after it does not need to be loaded if the font is already there.  Sources
+//! whose `format()` hint is not supported get skipped without loading them.  The other
+//! descriptors, like `font-weight` or `unicode-range`, are not supported, so the loaded
+//! font keeps its own weight and style.
+//!
+//! The fonts of a document only get loaded if the `Loader` was asked to, with
+//! `Loader::with_document_fonts()`; the rules of a user stylesheet always get loaded.
+//!
+//! https://www.w3.org/TR/css-fonts-3/#font-face-rule
+
+use cssparser::{
+    _cssparser_internal_to_lowercase, match_ignore_ascii_case, AtRuleParser, CowRcStr,
+    DeclarationListParser, DeclarationParser, Parser,
+};
+
+use crate::error::*;
+use crate::url_resolver::UrlResolver;
+
+/// A `@font-face` rule from a stylesheet.
+#[derive(Clone)]
+pub struct FontFace {
+    /// The family name that the font gets in the document.
+    pub family: String,
+
+    /// The entries of the `src` descriptor, in order of preference.
+    pub sources: Vec<FontFaceSource>,
+
+    /// Resolves the URLs of the `sources`, which are relative to the stylesheet.
+    pub url_resolver: UrlResolver,
+}
+
+/// An entry of the `src` descriptor of a `@font-face` rule.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FontFaceSource {
+    /// The full name or PostScript name of an installed font.
+    Local(String),
+
+    /// The URL of a font file, and the format from its `format()` hint, if any.
+    Url(String, Option<String>),
+}
+
+impl FontFaceSource {
+    /// Whether the source can have a font that librsvg supports, judging by its
+    /// `format()` hint.
+    ///
+    /// Sources without a hint have to be loaded to find out.
+    pub fn has_supported_format(&self) -> bool {
+        match *self {
+            FontFaceSource::Local(_) | FontFaceSource::Url(_, None) => true,
+
+            FontFaceSource::Url(_, Some(ref format)) => {
+                ["truetype", "opentype", "woff", "woff2", "collection"]
+                    .iter()
+                    .any(|f| format.eq_ignore_ascii_case(f))
+            }
+        }
+    }
+}
+
+/// Parses the block of a `@font-face` rule into its family name and its sources.
+///
+/// Unknown descriptors and sources get ignored, but the rule needs both a `font-family`
+/// and a `src` descriptor.
+pub fn parse_font_face_block<'i>(
+    parser: &mut Parser<'i, '_>,
+) -> Result<(String, Vec<FontFaceSource>), ParseError<'i>> {
+    let loc = parser.current_source_location();
+
+    let mut family = None;
+    let mut sources = None;
+
+    for descriptor in DeclarationListParser::new(parser, DescriptorParser) {
+        match descriptor {
+            Ok(Descriptor::Family(f)) => family = Some(f),
+            Ok(Descriptor::Src(s)) => sources = Some(s),
+            Err(e) => rsvg_log!("Invalid @font-face descriptor; ignoring: {:?}", e),
+        }
+    }
+
+    match (family, sources) {
+        (Some(family), Some(sources)) => Ok((family, sources)),
+        _ => Err(loc.new_custom_error(ValueErrorKind::value_error(
+            "@font-face needs font-family and src descriptors",
+        ))),
+    }
+}
+
+enum Descriptor {
+    Family(String),
+    Src(Vec<FontFaceSource>),
+}
+
+struct DescriptorParser;
+
+impl<'i> DeclarationParser<'i> for DescriptorParser {
+    type Declaration = Descriptor;
+    type Error = ValueErrorKind;
+
+    fn parse_value<'t>(
+        &mut self,
+        name: CowRcStr<'i>,
+        input: &mut Parser<'i, 't>,
+    ) -> Result<Descriptor, ParseError<'i>> {
+        match_ignore_ascii_case! { &name,
+            "font-family" => Ok(Descriptor::Family(parse_family_name(input)?)),
+
+            "src" => {
+                let sources = input.parse_comma_separated(parse_source)?;
+                Ok(Descriptor::Src(sources.into_iter().flatten().collect()))
+            },
+
+            _ => Err(input.new_custom_error(ValueErrorKind::UnknownProperty)),
+        }
+    }
+}
+
+// Required by `DeclarationListParser`; descriptor blocks don't have nested at-rules.
+impl<'i> AtRuleParser<'i> for DescriptorParser {
+    type PreludeBlock = ();
+    type PreludeNoBlock = ();
+    type AtRule = Descriptor;
+    type Error = ValueErrorKind;
+}
+
+/// Parses a single font family name, either quoted or as a sequence of identifiers.
+fn parse_family_name<'i>(parser: &mut Parser<'i, '_>) -> Result<String, ParseError<'i>> {
+    if let Ok(name) = parser.try_parse(|p| p.expect_string_cloned()) {
+        return Ok(name.as_ref().to_owned());
+    }
+
+    let mut name = parser.expect_ident()?.as_ref().to_owned();
+
+    while let Ok(ident) = parser.try_parse(|p| p.expect_ident_cloned()) {
+        name.push(' ');
+        name.push_str(&ident);
+    }
+
+    Ok(name)
+}
+
+/// Parses an entry of the `src` descriptor, or returns `None` for the kinds of entries
+/// that are not supported.
+fn parse_source<'i>(parser: &mut Parser<'i, '_>) -> Result<Option<FontFaceSource>, ParseError<'i>> {
+    if let Ok(name) = parser.try_parse(|p| -> Result<String, ParseError<'_>> {
+        p.expect_function_matching("local")?;
+        p.parse_nested_block(parse_family_name)
+    }) {
+        return Ok(Some(FontFaceSource::Local(name)));
+    }
+
+    if let Ok(url) = parser.try_parse(|p| p.expect_url()) {
+        let url = url.as_ref().to_owned();
+
+        let format = parser
+            .try_parse(|p| -> Result<String, ParseError<'_>> {
+                p.expect_function_matching("format")?;
+                p.parse_nested_block(|p| Ok(p.expect_ident_or_string()?.as_ref().to_owned()))
+            })
+            .ok();
+
+        return Ok(Some(FontFaceSource::Url(url, format)));
+    }
+
+    let loc = parser.current_source_location();
+    while parser.next().is_ok() {}
+    rsvg_log!("unsupported @font-face source at {:?}; ignoring", loc);
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cssparser::ParserInput;
+
+    fn parse_block(s: &str) -> Result<(String, Vec<FontFaceSource>), ()> {
+        let mut input = ParserInput::new(s);
+        let mut parser = Parser::new(&mut input);
+
+        parse_font_face_block(&mut parser).map_err(|_| ())
+    }
+
+    #[test]
+    fn parses_font_face_blocks() {
+        assert_eq!(
+            parse_block(
+                r#"font-family: "My Font";
+                   src: url(a.woff2) format("woff2"), url("a.woff") format(woff), url(a.ttf)"#
+            ),
+            Ok((
+                String::from("My Font"),
+                vec![
+                    FontFaceSource::Url(String::from("a.woff2"), Some(String::from("woff2"))),
+                    FontFaceSource::Url(String::from("a.woff"), Some(String::from("woff"))),
+                    FontFaceSource::Url(String::from("a.ttf"), None),
+                ]
+            ))
+        );
+
+        assert_eq!(
+            parse_block("font-family: My Font; font-weight: bold; src: foo(bar), url(a.ttf)"),
+            Ok((
+                String::from("My Font"),
+                vec![FontFaceSource::Url(String::from("a.ttf"), None)]
+            ))
+        );
+
+        assert_eq!(
+            parse_block(
+                r#"font-family: Foo; src: local("Foo Bold"), local(Foo-Bold), url(foo.ttf)"#
+            ),
+            Ok((
+                String::from("Foo"),
+                vec![
+                    FontFaceSource::Local(String::from("Foo Bold")),
+                    FontFaceSource::Local(String::from("Foo-Bold")),
+                    FontFaceSource::Url(String::from("foo.ttf"), None),
+                ]
+            ))
+        );
+    }
+
+    #[test]
+    fn detects_invalid_font_face_blocks() {
+        assert!(parse_block("src: url(a.ttf)").is_err());
+        assert!(parse_block("font-family: Foo").is_err());
+        assert!(parse_block("font-family: Foo, Bar; src: url(a.ttf)").is_err());
+    }
+
+    #[test]
+    fn skips_unsupported_formats() {
+        assert!(FontFaceSource::Url(String::from("a"), None).has_supported_format());
+        assert!(
+            FontFaceSource::Url(String::from("a"), Some(String::from("WOFF")))
+                .has_supported_format()
+        );
+        assert!(
+            FontFaceSource::Url(String::from("a"), Some(String::from("woff2")))
+                .has_supported_format()
+        );
+        assert!(
+            !FontFaceSource::Url(String::from("a"), Some(String::from("svg")))
+                .has_supported_format()
+        );
+    }
+}
@@ -5,12 +5,13 @@
 
 use cast::{f64, u16};
 use cssparser::{Parser, Token};
+use std::hash::{Hash, Hasher};
 
 use crate::error::*;
 use crate::length::*;
-use crate::parsers::{finite_f32, Parse};
+use crate::parsers::{finite_f32, NonNegative, Parse};
 use crate::properties::ComputedValues;
-use crate::property_defs::{FontStretch, FontStyle, FontVariant};
+use crate::property_defs::FontVariant;
 
 /// `font` shorthand property.
 ///
@@ -90,7 +91,8 @@ impl Parse for Font {
                 }
             }
             if stretch.is_none() {
-                if let Ok(value) = parser.try_parse(FontStretch::parse) {
+                // Percentages are not allowed here; they would be ambiguous with the size
+                if let Ok(value) = parser.try_parse(parse_font_stretch_keyword) {
                     stretch = Some(value);
                     continue;
                 }
@@ -200,6 +202,10 @@ impl FontSize {
         }
     }
 
+    /// Resolves the specified font size against the parent's computed values.
+    ///
+    /// Percentages, `em` and `ex` are relative to the inherited font size, not to
+    /// the viewport, so nested relative sizes compound.
     pub fn compute(&self, v: &ComputedValues) -> Self {
         let compute_points = |p| 12.0 * 1.2f64.powf(p) / POINTS_PER_INCH;
 
@@ -210,6 +216,8 @@ impl FontSize {
             parent.unit != LengthUnit::Percent
                 && parent.unit != LengthUnit::Em
                 && parent.unit != LengthUnit::Ex
+                && parent.unit != LengthUnit::Ch
+                && parent.unit != LengthUnit::Rem
         );
 
         use FontSize::*;
@@ -240,6 +248,16 @@ impl FontSize {
                 Length::<Both>::new(parent.length * s.length / 2.0, parent.unit)
             }
 
+            Value(s) if s.unit == LengthUnit::Ch => {
+                // Likewise for the advance of the "0" glyph.
+                Length::<Both>::new(parent.length * s.length / 2.0, parent.unit)
+            }
+
+            Value(s) if s.unit == LengthUnit::Rem => {
+                let root = v.root_font_size().value();
+                Length::<Both>::new(root.length * s.length, root.unit)
+            }
+
             Value(s) => *s,
         };
 
@@ -274,12 +292,288 @@ impl Parse for FontSize {
     }
 }
 
+/// `font-size-adjust` property.
+///
+/// https://www.w3.org/TR/css-fonts-3/#font-size-adjust-prop
+///
+/// The number is the desired aspect value, i.e. the ratio of the x-height to the font
+/// size.  When a fallback font gets used, its size is scaled so that its x-height
+/// matches what the aspect value asks for.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum FontSizeAdjust {
+    None,
+    Number(f64),
+}
+
+impl Parse for FontSizeAdjust {
+    fn parse<'i>(parser: &mut Parser<'i, '_>) -> Result<FontSizeAdjust, ParseError<'i>> {
+        if parser
+            .try_parse(|p| p.expect_ident_matching("none"))
+            .is_ok()
+        {
+            return Ok(FontSizeAdjust::None);
+        }
+
+        let NonNegative(n) = NonNegative::parse(parser)?;
+        Ok(FontSizeAdjust::Number(n))
+    }
+}
+
+impl FontSizeAdjust {
+    /// Adjusts a `font_size` in user-space units given the font's measured `x_height`.
+    ///
+    /// Returns the `font_size` unchanged if the property is `none` or if the
+    /// x-height is not known.
+    pub fn adjust(&self, font_size: f64, x_height: Option<f64>) -> f64 {
+        match (*self, x_height) {
+            (FontSizeAdjust::Number(aspect), Some(x_height)) if x_height > 0.0 => {
+                let font_aspect = x_height / font_size;
+                font_size * aspect / font_aspect
+            }
+
+            _ => font_size,
+        }
+    }
+}
+
+/// `font-style` property.
+///
+/// https://www.w3.org/TR/SVG/text.html#FontStyleProperty
+///
+/// https://drafts.csswg.org/css-fonts-4/#font-style-prop
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum FontStyle {
+    Normal,
+    Italic,
+
+    /// Oblique text, with an optional slant angle in degrees.  Positive angles lean
+    /// the glyphs towards the end of the line.
+    Oblique(Option<f64>),
+}
+
+impl Parse for FontStyle {
+    fn parse<'i>(parser: &mut Parser<'i, '_>) -> Result<FontStyle, ParseError<'i>> {
+        let style = parse_identifiers!(
+            parser,
+            "normal" => FontStyle::Normal,
+            "italic" => FontStyle::Italic,
+            "oblique" => FontStyle::Oblique(None),
+        )?;
+
+        if style == FontStyle::Oblique(None) {
+            if let Ok(degrees) = parser.try_parse(parse_oblique_angle) {
+                return Ok(FontStyle::Oblique(Some(degrees)));
+            }
+        }
+
+        Ok(style)
+    }
+}
+
+/// Parses the angle after `oblique`, in degrees.
+///
+/// Unlike SVG angles, this requires a unit and is not normalized, since the sign
+/// indicates the direction of the slant.
+fn parse_oblique_angle<'i>(parser: &mut Parser<'i, '_>) -> Result<f64, ParseError<'i>> {
+    let loc = parser.current_source_location();
+
+    let token = parser.next()?;
+
+    let degrees = match *token {
+        Token::Dimension {
+            value, ref unit, ..
+        } => {
+            let value = f64::from(finite_f32(value).map_err(|e| loc.new_custom_error(e))?);
+
+            match unit.as_ref() {
+                "deg" => value,
+                "grad" => value * 360.0 / 400.0,
+                "rad" => value.to_degrees(),
+                "turn" => value * 360.0,
+                _ => return Err(loc.new_unexpected_token_error(token.clone())),
+            }
+        }
+
+        _ => return Err(loc.new_unexpected_token_error(token.clone())),
+    };
+
+    if (-90.0..=90.0).contains(&degrees) {
+        Ok(degrees)
+    } else {
+        Err(loc.new_custom_error(ValueErrorKind::value_error(
+            "oblique angle must be between -90deg and 90deg inclusive",
+        )))
+    }
+}
+
+impl FontStyle {
+    /// Returns the angle in degrees of the synthetic slant for this style.
+    ///
+    /// Fonts rarely provide an oblique face for an arbitrary angle, so an explicit
+    /// angle is rendered by shearing an upright face instead.  Plain `oblique`
+    /// leaves the choice of face to Pango, and returns 0.
+    pub fn synthetic_slant(self) -> f64 {
+        match self {
+            FontStyle::Oblique(Some(degrees)) => degrees,
+            _ => 0.0,
+        }
+    }
+}
+
+// The angle is always finite, so we can implement Eq.
+impl Eq for FontStyle {}
+
+impl Hash for FontStyle {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+
+        if let FontStyle::Oblique(Some(degrees)) = *self {
+            // Equal angles hash equally; -0.0 and 0.0 both become 0.
+            ((degrees * 1000.0).round() as i64).hash(state);
+        }
+    }
+}
+
+/// `font-stretch` property.
+///
+/// https://www.w3.org/TR/SVG/text.html#FontStretchProperty
+///
+/// https://drafts.csswg.org/css-fonts-4/#font-stretch-prop
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum FontStretch {
+    Normal,
+    Wider,
+    Narrower,
+    UltraCondensed,
+    ExtraCondensed,
+    Condensed,
+    SemiCondensed,
+    SemiExpanded,
+    Expanded,
+    ExtraExpanded,
+    UltraExpanded,
+
+    /// A width relative to the normal width of the font, like 0.75 for `75%`.
+    Percentage(f32),
+}
+
+impl Parse for FontStretch {
+    fn parse<'i>(parser: &mut Parser<'i, '_>) -> Result<FontStretch, ParseError<'i>> {
+        if let Ok(stretch) = parser.try_parse(parse_font_stretch_keyword) {
+            return Ok(stretch);
+        }
+
+        let loc = parser.current_source_location();
+
+        match *parser.next()? {
+            Token::Percentage { unit_value, .. } if unit_value >= 0.0 => {
+                Ok(FontStretch::Percentage(
+                    finite_f32(unit_value).map_err(|e| loc.new_custom_error(e))?,
+                ))
+            }
+
+            ref token => Err(loc.new_unexpected_token_error(token.clone())),
+        }
+    }
+}
+
+/// Parses the keywords for `font-stretch`, which are the only values allowed in the
+/// `font` shorthand.
+fn parse_font_stretch_keyword<'i>(
+    parser: &mut Parser<'i, '_>,
+) -> Result<FontStretch, ParseError<'i>> {
+    Ok(parse_identifiers!(
+        parser,
+        "normal" => FontStretch::Normal,
+        "wider" => FontStretch::Wider,
+        "narrower" => FontStretch::Narrower,
+        "ultra-condensed" => FontStretch::UltraCondensed,
+        "extra-condensed" => FontStretch::ExtraCondensed,
+        "condensed" => FontStretch::Condensed,
+        "semi-condensed" => FontStretch::SemiCondensed,
+        "semi-expanded" => FontStretch::SemiExpanded,
+        "expanded" => FontStretch::Expanded,
+        "extra-expanded" => FontStretch::ExtraExpanded,
+        "ultra-expanded" => FontStretch::UltraExpanded,
+    )?)
+}
+
+impl FontStretch {
+    /// Returns the percentage for a value, like 0.75 for `condensed`.
+    ///
+    /// The keywords have the percentages from the CSS Fonts spec.  The relative `wider`
+    /// and `narrower` have none.
+    pub fn percentage(self) -> Option<f32> {
+        use FontStretch::*;
+
+        match self {
+            Normal => Some(1.0),
+            Wider | Narrower => None,
+            UltraCondensed => Some(0.5),
+            ExtraCondensed => Some(0.625),
+            Condensed => Some(0.75),
+            SemiCondensed => Some(0.875),
+            SemiExpanded => Some(1.125),
+            Expanded => Some(1.25),
+            ExtraExpanded => Some(1.5),
+            UltraExpanded => Some(2.0),
+            Percentage(p) => Some(p),
+        }
+    }
+
+    /// Returns the keyword that is nearest to a percentage.
+    ///
+    /// Fonts that are not variable only come in the widths of the keywords.
+    pub fn nearest_keyword(self) -> FontStretch {
+        use FontStretch::*;
+
+        const KEYWORDS: [FontStretch; 9] = [
+            UltraCondensed,
+            ExtraCondensed,
+            Condensed,
+            SemiCondensed,
+            Normal,
+            SemiExpanded,
+            Expanded,
+            ExtraExpanded,
+            UltraExpanded,
+        ];
+
+        match self {
+            Percentage(p) => KEYWORDS
+                .iter()
+                .copied()
+                .min_by(|a, b| {
+                    let da = (a.percentage().unwrap() - p).abs();
+                    let db = (b.percentage().unwrap() - p).abs();
+                    da.partial_cmp(&db).unwrap()
+                })
+                .unwrap(),
+
+            _ => self,
+        }
+    }
+}
+
+// The percentage is always finite, so we can implement Eq.
+impl Eq for FontStretch {}
+
+impl Hash for FontStretch {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+
+        if let FontStretch::Percentage(p) = *self {
+            ((p * 10000.0).round() as i64).hash(state);
+        }
+    }
+}
+
 /// `font-weight` property.
 ///
 /// https://drafts.csswg.org/css-fonts-4/#font-weight-prop
 ///
 /// https://www.w3.org/TR/css-fonts-3/#propdef-font-weight
-#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum FontWeight {
     Normal,
     Bold,
@@ -378,24 +672,28 @@ pub enum LetterSpacing {
 }
 
 impl LetterSpacing {
-    pub fn value(&self) -> Length<Horizontal> {
+    /// Returns the explicit spacing, or `None` for `normal`.
+    ///
+    /// `normal` is kept distinct from `0` so that the font's own spacing can be used;
+    /// only an explicit value forces an exact amount of extra space.
+    pub fn value(&self) -> Option<Length<Horizontal>> {
         match self {
-            LetterSpacing::Value(s) => *s,
-            _ => unreachable!(),
+            LetterSpacing::Normal => None,
+            LetterSpacing::Value(s) => Some(*s),
         }
     }
 
-    pub fn compute(&self) -> Self {
-        let spacing = match self {
-            LetterSpacing::Normal => Length::<Horizontal>::new(0.0, LengthUnit::Px),
-            LetterSpacing::Value(s) => *s,
-        };
-
-        LetterSpacing::Value(spacing)
+    /// Resolves `em` and `rem` units; see `compute_spacing()`.
+    pub fn compute(&self, v: &ComputedValues) -> Self {
+        match *self {
+            LetterSpacing::Normal => LetterSpacing::Normal,
+            LetterSpacing::Value(s) => LetterSpacing::Value(compute_spacing(s, v)),
+        }
     }
 
-    pub fn to_user(&self, params: &NormalizeParams) -> f64 {
-        self.value().to_user(params)
+    /// Returns the explicit spacing in user units, or `None` for `normal`.
+    pub fn to_user(&self, params: &NormalizeParams) -> Option<f64> {
+        self.value().map(|s| s.to_user(params))
     }
 }
 
@@ -413,6 +711,83 @@ impl Parse for LetterSpacing {
     }
 }
 
+/// `word-spacing` property.
+///
+/// https://www.w3.org/TR/SVG/text.html#WordSpacingProperty
+///
+/// https://www.w3.org/TR/css-text-3/#word-spacing-property
+#[derive(Debug, Clone, PartialEq)]
+pub enum WordSpacing {
+    Normal,
+    Length(Length<Horizontal>),
+
+    /// Fraction of the advance of a space in the current font; `1.0` means 100%.
+    Percentage(f32),
+}
+
+impl WordSpacing {
+    /// Resolves `em` and `rem` units; see `compute_spacing()`.
+    pub fn compute(&self, v: &ComputedValues) -> Self {
+        match *self {
+            WordSpacing::Length(s) => WordSpacing::Length(compute_spacing(s, v)),
+            _ => self.clone(),
+        }
+    }
+
+    /// Returns the extra space between words in user units.
+    ///
+    /// Percentages refer to the advance of a space; see `NormalizeParams::space_advance()`.
+    pub fn to_user(&self, params: &NormalizeParams) -> f64 {
+        match *self {
+            WordSpacing::Normal => 0.0,
+            WordSpacing::Length(s) => s.to_user(params),
+            WordSpacing::Percentage(p) => f64::from(p) * params.space_advance(),
+        }
+    }
+}
+
+impl Parse for WordSpacing {
+    fn parse<'i>(parser: &mut Parser<'i, '_>) -> Result<WordSpacing, ParseError<'i>> {
+        let state = parser.state();
+        let loc = parser.current_source_location();
+
+        let token = parser.next()?.clone();
+
+        if let Token::Percentage { unit_value, .. } = token {
+            let p = finite_f32(unit_value).map_err(|e| loc.new_custom_error(e))?;
+            return Ok(WordSpacing::Percentage(p));
+        }
+
+        parser.reset(&state);
+
+        parser
+            .try_parse(|p| Length::<Horizontal>::parse(p))
+            .map(WordSpacing::Length)
+            .or_else(|_| {
+                Ok(parse_identifiers!(
+                    parser,
+                    "normal" => WordSpacing::Normal,
+                )?)
+            })
+    }
+}
+
+/// Resolves the `em` and `rem` units of a `letter-spacing` or `word-spacing` length
+/// against the computed font sizes.
+///
+/// The computed value is an absolute length, so descendants with a different font
+/// size inherit the same spacing.  `ex` and `ch` units are left as they are, since
+/// they get resolved against the metrics of the actual font when laying out text.
+fn compute_spacing(s: Length<Horizontal>, v: &ComputedValues) -> Length<Horizontal> {
+    let font_size = match s.unit {
+        LengthUnit::Em => v.font_size().value(),
+        LengthUnit::Rem => v.root_font_size().value(),
+        _ => return s,
+    };
+
+    Length::new(s.length * font_size.length, font_size.unit)
+}
+
 /// `line-height` property.
 ///
 /// https://drafts.csswg.org/css2/visudet.html#propdef-line-height
@@ -434,6 +809,10 @@ impl LineHeight {
         }
     }
 
+    /// Resolves the line height to a length, with the font size of the `values`.
+    ///
+    /// The computed value of the property keeps `normal` and numbers, so that they get
+    /// inherited as such; this gives the line height of the element with the `values`.
     pub fn compute(&self, values: &ComputedValues) -> Self {
         let font_size = values.font_size().value();
 
@@ -494,7 +873,7 @@ impl Parse for LineHeight {
 /// https://www.w3.org/TR/2008/REC-CSS2-20080411/fonts.html#propdef-font-family
 ///
 /// https://www.w3.org/TR/css-fonts-3/#font-family-prop
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct FontFamily(pub String);
 
 impl Parse for FontFamily {
@@ -536,6 +915,7 @@ impl FontFamily {
 mod tests {
     use super::*;
 
+    use crate::dpi::Dpi;
     use crate::properties::{ParsedProperty, SpecifiedValue, SpecifiedValues};
 
     #[test]
@@ -619,6 +999,222 @@ mod tests {
         }
     }
 
+    #[test]
+    fn nested_percentage_font_sizes_compound() {
+        let mut values = ComputedValues::default();
+
+        for (size, expected) in &[("20px", 20.0), ("50%", 10.0), ("50%", 5.0), ("200%", 10.0)] {
+            let mut specified = SpecifiedValues::default();
+            specified.set_parsed_property(&ParsedProperty::FontSize(SpecifiedValue::Specified(
+                FontSize::parse_str(size).unwrap(),
+            )));
+
+            specified.to_computed_values(&mut values);
+
+            assert_eq!(
+                values.font_size(),
+                FontSize::Value(Length::new(*expected, LengthUnit::Px))
+            );
+        }
+    }
+
+    #[test]
+    fn computes_absolute_font_size_keywords() {
+        let values = ComputedValues::default();
+
+        let compute = |s| match FontSize::parse_str(s).unwrap().compute(&values) {
+            FontSize::Value(v) => {
+                assert_eq!(v.unit, LengthUnit::In);
+                v.length
+            }
+            _ => unreachable!(),
+        };
+
+        let ladder = [
+            "xx-small", "x-small", "small", "medium", "large", "x-large", "xx-large",
+        ];
+
+        let sizes: Vec<f64> = ladder.iter().map(|k| compute(k)).collect();
+
+        // medium is 12pt, and the size does not depend on the parent
+        assert!((sizes[3] - 12.0 / POINTS_PER_INCH).abs() < 1e-10);
+
+        // each step up the ladder scales by 1.2
+        for pair in sizes.windows(2) {
+            assert!((pair[1] / pair[0] - 1.2).abs() < 1e-10);
+        }
+    }
+
+    #[test]
+    fn computes_relative_font_size_keywords_from_parent() {
+        let mut specified = SpecifiedValues::default();
+        specified.set_parsed_property(&ParsedProperty::FontSize(SpecifiedValue::Specified(
+            FontSize::parse_str("12px").unwrap(),
+        )));
+
+        let mut values = ComputedValues::default();
+        specified.to_computed_values(&mut values);
+
+        assert_eq!(
+            FontSize::parse_str("larger").unwrap().compute(&values),
+            FontSize::Value(Length::new(12.0 * 1.2, LengthUnit::Px))
+        );
+
+        assert_eq!(
+            FontSize::parse_str("smaller").unwrap().compute(&values),
+            FontSize::Value(Length::new(12.0 / 1.2, LengthUnit::Px))
+        );
+    }
+
+    fn compute_font_weights(values: &mut ComputedValues, weights: &[&str]) -> Vec<FontWeight> {
+        weights
+            .iter()
+            .map(|w| {
+                let mut specified = SpecifiedValues::default();
+                specified.set_parsed_property(&ParsedProperty::FontWeight(
+                    SpecifiedValue::Specified(FontWeight::parse_str(w).unwrap()),
+                ));
+
+                // Each step is a child of the previous one
+                specified.to_computed_values(values);
+                values.font_weight()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn resolves_relative_font_weights_against_parent() {
+        let mut values = ComputedValues::default();
+        assert_eq!(
+            compute_font_weights(&mut values, &["bolder", "bolder", "bolder"]),
+            vec![
+                FontWeight::Weight(700),
+                FontWeight::Weight(900),
+                FontWeight::Weight(900)
+            ]
+        );
+
+        assert_eq!(
+            compute_font_weights(&mut values, &["lighter", "lighter", "lighter", "lighter"]),
+            vec![
+                FontWeight::Weight(700),
+                FontWeight::Weight(400),
+                FontWeight::Weight(100),
+                FontWeight::Weight(100)
+            ]
+        );
+
+        let mut values = ComputedValues::default();
+        assert_eq!(
+            compute_font_weights(&mut values, &["50", "bolder", "300", "lighter"]),
+            vec![
+                FontWeight::Weight(50),
+                FontWeight::Weight(400),
+                FontWeight::Weight(300),
+                FontWeight::Weight(100)
+            ]
+        );
+    }
+
+    #[test]
+    fn physical_font_sizes_scale_with_dpi() {
+        let values = ComputedValues::default();
+        let font_size = FontSize::parse_str("12pt").unwrap().compute(&values);
+
+        let at_72 = NormalizeParams::from_dpi(Dpi::new(72.0, 72.0));
+        let at_144 = NormalizeParams::from_dpi(Dpi::new(144.0, 144.0));
+
+        assert!((font_size.to_user(&at_72) - 12.0).abs() < 1e-10);
+        assert!((font_size.to_user(&at_144) - 24.0).abs() < 1e-10);
+    }
+
+    #[test]
+    fn parses_font_size_adjust() {
+        assert_eq!(
+            FontSizeAdjust::parse_str("none").unwrap(),
+            FontSizeAdjust::None
+        );
+        assert_eq!(
+            FontSizeAdjust::parse_str("0.5").unwrap(),
+            FontSizeAdjust::Number(0.5)
+        );
+
+        assert!(FontSizeAdjust::parse_str("-0.5").is_err());
+        assert!(FontSizeAdjust::parse_str("auto").is_err());
+    }
+
+    #[test]
+    fn adjusts_font_size_by_aspect_value() {
+        // a font whose x-height is 0.4 of its size gets scaled up to reach 0.5
+        assert_eq!(FontSizeAdjust::Number(0.5).adjust(10.0, Some(4.0)), 12.5);
+
+        // no metrics, no adjustment
+        assert_eq!(FontSizeAdjust::Number(0.5).adjust(10.0, None), 10.0);
+
+        assert_eq!(FontSizeAdjust::None.adjust(10.0, Some(4.0)), 10.0);
+    }
+
+    #[test]
+    fn parses_font_style() {
+        assert_eq!(
+            <FontStyle as Parse>::parse_str("italic").unwrap(),
+            FontStyle::Italic
+        );
+        assert_eq!(
+            <FontStyle as Parse>::parse_str("oblique").unwrap(),
+            FontStyle::Oblique(None)
+        );
+        assert_eq!(
+            <FontStyle as Parse>::parse_str("oblique 20deg").unwrap(),
+            FontStyle::Oblique(Some(20.0))
+        );
+        assert_eq!(
+            <FontStyle as Parse>::parse_str("oblique -90deg").unwrap(),
+            FontStyle::Oblique(Some(-90.0))
+        );
+
+        assert!(<FontStyle as Parse>::parse_str("oblique 20").is_err());
+        assert!(<FontStyle as Parse>::parse_str("oblique -0.5turn").is_err());
+        assert!(<FontStyle as Parse>::parse_str("oblique 91deg").is_err());
+        assert!(<FontStyle as Parse>::parse_str("italic 20deg").is_err());
+    }
+
+    #[test]
+    fn parses_font_stretch() {
+        assert_eq!(
+            <FontStretch as Parse>::parse_str("condensed").unwrap(),
+            FontStretch::Condensed
+        );
+        assert_eq!(
+            <FontStretch as Parse>::parse_str("50%").unwrap(),
+            FontStretch::Percentage(0.5)
+        );
+
+        assert!(<FontStretch as Parse>::parse_str("-10%").is_err());
+        assert!(<FontStretch as Parse>::parse_str("0.5").is_err());
+    }
+
+    #[test]
+    fn snaps_font_stretch_percentages_to_keywords() {
+        assert_eq!(
+            FontStretch::Percentage(0.5).nearest_keyword(),
+            FontStretch::UltraCondensed
+        );
+        assert_eq!(
+            FontStretch::Percentage(0.8).nearest_keyword(),
+            FontStretch::Condensed
+        );
+        assert_eq!(
+            FontStretch::Percentage(1.0).nearest_keyword(),
+            FontStretch::Normal
+        );
+        assert_eq!(
+            FontStretch::Percentage(3.0).nearest_keyword(),
+            FontStretch::UltraExpanded
+        );
+        assert_eq!(FontStretch::Wider.nearest_keyword(), FontStretch::Wider);
+    }
+
     #[test]
     fn parses_font_weight() {
         assert_eq!(
@@ -659,20 +1255,96 @@ mod tests {
 
     #[test]
     fn computes_letter_spacing() {
+        let values = ComputedValues::default();
+
         assert_eq!(
             <LetterSpacing as Parse>::parse_str("normal")
-                .map(|s| s.compute())
+                .map(|s| s.compute(&values))
+                .unwrap(),
+            LetterSpacing::Normal
+        );
+        assert_eq!(
+            <LetterSpacing as Parse>::parse_str("0")
+                .map(|s| s.compute(&values))
                 .unwrap(),
             LetterSpacing::Value(Length::<Horizontal>::new(0.0, LengthUnit::Px,))
         );
         assert_eq!(
             <LetterSpacing as Parse>::parse_str("10em")
-                .map(|s| s.compute())
+                .map(|s| s.compute(&values))
                 .unwrap(),
-            LetterSpacing::Value(Length::<Horizontal>::new(10.0, LengthUnit::Em,))
+            LetterSpacing::Value(Length::<Horizontal>::new(120.0, LengthUnit::Px,))
         );
     }
 
+    #[test]
+    fn em_letter_spacing_scales_with_font_size() {
+        let compute = |font_size: &str, letter_spacing: &str| {
+            let mut specified = SpecifiedValues::default();
+            specified.set_parsed_property(&ParsedProperty::FontSize(SpecifiedValue::Specified(
+                FontSize::parse_str(font_size).unwrap(),
+            )));
+            specified.set_parsed_property(&ParsedProperty::LetterSpacing(
+                SpecifiedValue::Specified(LetterSpacing::parse_str(letter_spacing).unwrap()),
+            ));
+
+            let mut values = ComputedValues::default();
+            specified.to_computed_values(&mut values);
+            values.letter_spacing()
+        };
+
+        let px = |length| LetterSpacing::Value(Length::<Horizontal>::new(length, LengthUnit::Px));
+
+        assert_eq!(compute("10px", "0.25em"), px(2.5));
+        assert_eq!(compute("20px", "0.25em"), px(5.0));
+
+        assert_eq!(compute("10px", "2px"), px(2.0));
+        assert_eq!(compute("20px", "2px"), px(2.0));
+    }
+
+    #[test]
+    fn em_letter_spacing_inherits_as_an_absolute_length() {
+        let mut parent = SpecifiedValues::default();
+        parent.set_parsed_property(&ParsedProperty::FontSize(SpecifiedValue::Specified(
+            FontSize::parse_str("10px").unwrap(),
+        )));
+        parent.set_parsed_property(&ParsedProperty::LetterSpacing(SpecifiedValue::Specified(
+            LetterSpacing::parse_str("0.5em").unwrap(),
+        )));
+
+        let mut child = SpecifiedValues::default();
+        child.set_parsed_property(&ParsedProperty::FontSize(SpecifiedValue::Specified(
+            FontSize::parse_str("40px").unwrap(),
+        )));
+
+        let mut values = ComputedValues::default();
+        parent.to_computed_values(&mut values);
+        child.to_computed_values(&mut values);
+
+        assert_eq!(
+            values.letter_spacing(),
+            LetterSpacing::Value(Length::<Horizontal>::new(5.0, LengthUnit::Px))
+        );
+    }
+
+    #[test]
+    fn parses_word_spacing() {
+        assert_eq!(
+            <WordSpacing as Parse>::parse_str("normal").unwrap(),
+            WordSpacing::Normal
+        );
+        assert_eq!(
+            <WordSpacing as Parse>::parse_str("2px").unwrap(),
+            WordSpacing::Length(Length::<Horizontal>::new(2.0, LengthUnit::Px))
+        );
+        assert_eq!(
+            <WordSpacing as Parse>::parse_str("50%").unwrap(),
+            WordSpacing::Percentage(0.5)
+        );
+
+        assert!(WordSpacing::parse_str("furlong").is_err());
+    }
+
     #[test]
     fn detects_invalid_invalid_letter_spacing() {
         assert!(LetterSpacing::parse_str("furlong").is_err());
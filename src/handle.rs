@@ -3,16 +3,25 @@
 //! This module provides the primitives on which the public APIs are implemented.
 
 use crate::accept_language::UserLanguage;
+use crate::app_fonts::AppFonts;
 use crate::bbox::BoundingBox;
+use crate::cond::{explain_condition, ConditionResult, ConditionalElement, Extensions};
 use crate::css::{Origin, Stylesheet};
 use crate::document::{AcquiredNodes, Document, NodeId};
 use crate::dpi::Dpi;
-use crate::drawing_ctx::{draw_tree, with_saved_cr, DrawingMode, ViewParams};
+use crate::drawing_ctx::{
+    draw_tree, element_bbox_tree, link_regions_tree, outline_tree, text_geometry_tree,
+    with_saved_cr, DrawingMode, ViewParams,
+};
+use crate::element::Element;
 use crate::error::{DefsLookupErrorKind, LoadingError, RenderingError};
+use crate::font_face::FontFace;
 use crate::length::*;
-use crate::node::{CascadedValues, Node, NodeBorrow};
+use crate::node::{CascadedValues, Node, NodeBorrow, NodeTextContent};
 use crate::rect::Rect;
 use crate::structure::IntrinsicDimensions;
+use crate::text::{used_fonts, TextCharacters};
+use crate::text_options::{TextOptions, UsedFont};
 use crate::url_resolver::{AllowedUrl, UrlResolver};
 
 /// Loading options for SVG documents.
@@ -26,6 +35,15 @@ pub struct LoadOptions {
 
     /// Whether to keep original (undecoded) image data to embed in Cairo PDF surfaces.
     pub keep_image_data: bool,
+
+    /// Extensions for `requiredExtensions`, registered by the application.
+    pub extensions: Extensions,
+
+    /// Whether `<switch>` picks the child whose `systemLanguage` matches best.
+    pub best_language_match: bool,
+
+    /// Whether to load the fonts of the document's `@font-face` rules.
+    pub document_fonts: bool,
 }
 
 impl LoadOptions {
@@ -35,6 +53,9 @@ impl LoadOptions {
             url_resolver,
             unlimited_size: false,
             keep_image_data: false,
+            extensions: Extensions::default(),
+            best_language_match: false,
+            document_fonts: false,
         }
     }
 
@@ -56,6 +77,26 @@ impl LoadOptions {
         self
     }
 
+    /// Sets the extensions that `requiredExtensions` can ask for.
+    pub fn with_extensions(mut self, extensions: Extensions) -> Self {
+        self.extensions = extensions;
+        self
+    }
+
+    /// Sets whether `<switch>` elements pick the child with the best language match.
+    pub fn with_best_language_match(mut self, best: bool) -> Self {
+        self.best_language_match = best;
+        self
+    }
+
+    /// Sets whether to load the fonts of the document's `@font-face` rules.
+    ///
+    /// This should only be done for trusted data.
+    pub fn with_document_fonts(mut self, load: bool) -> Self {
+        self.document_fonts = load;
+        self
+    }
+
     /// Creates a new `LoadOptions` with a different `url resolver`.
     ///
     /// This is used when loading a referenced file that may in turn cause other files
@@ -68,6 +109,9 @@ impl LoadOptions {
             url_resolver,
             unlimited_size: self.unlimited_size,
             keep_image_data: self.keep_image_data,
+            extensions: self.extensions.clone(),
+            best_language_match: self.best_language_match,
+            document_fonts: self.document_fonts,
         }
     }
 }
@@ -80,6 +124,9 @@ impl LoadOptions {
 /// [`from_stream`]: #method.from_stream
 pub struct Handle {
     document: Document,
+
+    /// Fonts that the application added from memory; see `add_font_data()`.
+    app_fonts: Option<AppFonts>,
 }
 
 impl Handle {
@@ -89,9 +136,17 @@ impl Handle {
         stream: &gio::InputStream,
         cancellable: Option<&gio::Cancellable>,
     ) -> Result<Handle, LoadingError> {
-        Ok(Handle {
+        let mut handle = Handle {
             document: Document::load_from_stream(load_options, stream, cancellable)?,
-        })
+            app_fonts: None,
+        };
+
+        if load_options.document_fonts {
+            let faces: Vec<FontFace> = handle.document.font_faces().cloned().collect();
+            handle.add_font_faces(&faces);
+        }
+
+        Ok(handle)
     }
 
     /// Queries whether a document has a certain element `#foo`.
@@ -108,6 +163,22 @@ impl Handle {
         }
     }
 
+    /// Returns the text of the first `<title>` child of an element, or of the
+    /// toplevel `<svg>` element if `id` is `None`.
+    pub fn get_title(&self, id: Option<&str>) -> Result<Option<String>, RenderingError> {
+        let node = self.get_node_or_root(id)?;
+
+        Ok(first_child_text(&node, |e| matches!(*e, Element::Title(_))))
+    }
+
+    /// Returns the text of the first `<desc>` child of an element, or of the
+    /// toplevel `<svg>` element if `id` is `None`.
+    pub fn get_desc(&self, id: Option<&str>) -> Result<Option<String>, RenderingError> {
+        let node = self.get_node_or_root(id)?;
+
+        Ok(first_child_text(&node, |e| matches!(*e, Element::Desc(_))))
+    }
+
     /// If the intrinsic dimensions are in physical units, computes their pixel size, or
     /// returns `None`.
     ///
@@ -155,6 +226,7 @@ impl Handle {
         viewport: Rect,
         user_language: &UserLanguage,
         dpi: Dpi,
+        text_options: &TextOptions,
         is_testing: bool,
     ) -> Result<(Rect, Rect), RenderingError> {
         let root = self.document.root();
@@ -168,6 +240,7 @@ impl Handle {
             viewport,
             user_language,
             dpi,
+            text_options,
             true,
             is_testing,
             &mut AcquiredNodes::new(&self.document),
@@ -185,13 +258,14 @@ impl Handle {
         viewport: &cairo::Rectangle,
         user_language: &UserLanguage,
         dpi: Dpi,
+        text_options: &TextOptions,
         is_testing: bool,
     ) -> Result<(cairo::Rectangle, cairo::Rectangle), RenderingError> {
         let viewport = Rect::from(*viewport);
         let node = self.get_node_or_root(id)?;
 
         let (ink_rect, logical_rect) =
-            self.geometry_for_layer(node, viewport, user_language, dpi, is_testing)?;
+            self.geometry_for_layer(node, viewport, user_language, dpi, text_options, is_testing)?;
 
         Ok((
             cairo::Rectangle::from(ink_rect),
@@ -232,9 +306,18 @@ impl Handle {
         viewport: &cairo::Rectangle,
         user_language: &UserLanguage,
         dpi: Dpi,
+        text_options: &TextOptions,
         is_testing: bool,
     ) -> Result<(), RenderingError> {
-        self.render_layer(cr, None, viewport, user_language, dpi, is_testing)
+        self.render_layer(
+            cr,
+            None,
+            viewport,
+            user_language,
+            dpi,
+            text_options,
+            is_testing,
+        )
     }
 
     pub fn render_layer(
@@ -244,6 +327,7 @@ impl Handle {
         viewport: &cairo::Rectangle,
         user_language: &UserLanguage,
         dpi: Dpi,
+        text_options: &TextOptions,
         is_testing: bool,
     ) -> Result<(), RenderingError> {
         cr.status()?;
@@ -260,6 +344,7 @@ impl Handle {
                 viewport,
                 user_language,
                 dpi,
+                text_options,
                 false,
                 is_testing,
                 &mut AcquiredNodes::new(&self.document),
@@ -273,6 +358,7 @@ impl Handle {
         node: &Node,
         user_language: &UserLanguage,
         dpi: Dpi,
+        text_options: &TextOptions,
         is_testing: bool,
     ) -> Result<BoundingBox, RenderingError> {
         let target = cairo::ImageSurface::create(cairo::Format::Rgb24, 1, 1)?;
@@ -286,23 +372,207 @@ impl Handle {
             unit_rectangle(),
             user_language,
             dpi,
+            text_options,
             true,
             is_testing,
             &mut AcquiredNodes::new(&self.document),
         )
     }
 
+    /// Returns the outlines of an element's shapes and text glyphs.
+    ///
+    /// The path is in the same coordinate space as the rectangles from
+    /// `get_geometry_for_element`, before they get translated to the origin.
+    pub fn get_outline_for_element(
+        &self,
+        id: Option<&str>,
+        user_language: &UserLanguage,
+        dpi: Dpi,
+        text_options: &TextOptions,
+        is_testing: bool,
+    ) -> Result<cairo::Path, RenderingError> {
+        let node = self.get_node_or_root(id)?;
+
+        let target = cairo::ImageSurface::create(cairo::Format::Rgb24, 1, 1)?;
+        let cr = cairo::Context::new(&target)?;
+
+        outline_tree(
+            &node,
+            &cr,
+            unit_rectangle(),
+            user_language,
+            dpi,
+            text_options,
+            is_testing,
+            &mut AcquiredNodes::new(&self.document),
+        )?;
+
+        // The path is stored in device space, so get it back in the initial user space.
+        cr.identity_matrix();
+
+        Ok(cr.copy_path()?)
+    }
+
+    /// Returns the outlines of an element's shapes and text glyphs as SVG path data.
+    pub fn get_outline_path_data_for_element(
+        &self,
+        id: Option<&str>,
+        user_language: &UserLanguage,
+        dpi: Dpi,
+        text_options: &TextOptions,
+        is_testing: bool,
+    ) -> Result<String, RenderingError> {
+        let path =
+            self.get_outline_for_element(id, user_language, dpi, text_options, is_testing)?;
+
+        Ok(svg_path_data(&path))
+    }
+
+    /// Returns the hit regions of text inside `<a>` links, with the links' targets.
+    pub fn get_link_regions(
+        &self,
+        viewport: &cairo::Rectangle,
+        user_language: &UserLanguage,
+        dpi: Dpi,
+        text_options: &TextOptions,
+        is_testing: bool,
+    ) -> Result<Vec<(cairo::Rectangle, String)>, RenderingError> {
+        let root = self.document.root();
+
+        let target = cairo::ImageSurface::create(cairo::Format::Rgb24, 1, 1)?;
+        let cr = cairo::Context::new(&target)?;
+
+        let regions = link_regions_tree(
+            &root,
+            &cr,
+            Rect::from(*viewport),
+            user_language,
+            dpi,
+            text_options,
+            is_testing,
+            &mut AcquiredNodes::new(&self.document),
+        )?;
+
+        Ok(regions
+            .into_iter()
+            .map(|(rect, target)| (cairo::Rectangle::from(rect), target))
+            .collect())
+    }
+
+    /// Lays out the text in an element to query the positions of its characters.
+    pub fn get_text_characters(
+        &self,
+        id: &str,
+        user_language: &UserLanguage,
+        dpi: Dpi,
+        text_options: &TextOptions,
+        is_testing: bool,
+    ) -> Result<TextCharacters, RenderingError> {
+        let node = self.lookup_node(id)?;
+
+        let target = cairo::ImageSurface::create(cairo::Format::Rgb24, 1, 1)?;
+        let cr = cairo::Context::new(&target)?;
+
+        let spans = text_geometry_tree(
+            &node,
+            &cr,
+            unit_rectangle(),
+            user_language,
+            dpi,
+            text_options,
+            is_testing,
+            &mut AcquiredNodes::new(&self.document),
+        )?;
+
+        Ok(TextCharacters::new(spans))
+    }
+
+    /// Lays out the whole document to find the fonts that its text gets rendered with.
+    pub fn get_used_fonts(
+        &self,
+        viewport: &cairo::Rectangle,
+        user_language: &UserLanguage,
+        dpi: Dpi,
+        text_options: &TextOptions,
+        is_testing: bool,
+    ) -> Result<Vec<UsedFont>, RenderingError> {
+        let root = self.document.root();
+
+        let target = cairo::ImageSurface::create(cairo::Format::Rgb24, 1, 1)?;
+        let cr = cairo::Context::new(&target)?;
+
+        let spans = text_geometry_tree(
+            &root,
+            &cr,
+            Rect::from(*viewport),
+            user_language,
+            dpi,
+            text_options,
+            is_testing,
+            &mut AcquiredNodes::new(&self.document),
+        )?;
+
+        let mut fonts = Vec::new();
+
+        for span in &spans {
+            for font in used_fonts(&span.layout) {
+                if !fonts.contains(&font) {
+                    fonts.push(font);
+                }
+            }
+        }
+
+        Ok(fonts)
+    }
+
+    /// Evaluates the conditional processing attributes of all the elements.
+    pub fn get_conditional_elements(
+        &self,
+        user_language: &UserLanguage,
+    ) -> Vec<ConditionalElement> {
+        let extensions = self.document.extensions();
+
+        self.document
+            .root()
+            .descendants()
+            .filter(|n| n.is_element())
+            .filter_map(|node| {
+                let elt = node.borrow_element();
+
+                let conditions: Vec<ConditionResult> = elt
+                    .get_attributes()
+                    .iter()
+                    .filter_map(|(attr, value)| {
+                        explain_condition(&attr, value, user_language, extensions)
+                    })
+                    .collect();
+
+                if conditions.is_empty() {
+                    None
+                } else {
+                    Some(ConditionalElement {
+                        element_name: elt.element_name().local.to_string(),
+                        id: elt.get_id().map(String::from),
+                        conditions,
+                    })
+                }
+            })
+            .collect()
+    }
+
     /// Returns (ink_rect, logical_rect)
     pub fn get_geometry_for_element(
         &self,
         id: Option<&str>,
         user_language: &UserLanguage,
         dpi: Dpi,
+        text_options: &TextOptions,
         is_testing: bool,
     ) -> Result<(cairo::Rectangle, cairo::Rectangle), RenderingError> {
         let node = self.get_node_or_root(id)?;
 
-        let bbox = self.get_bbox_for_element(&node, user_language, dpi, is_testing)?;
+        let bbox =
+            self.get_bbox_for_element(&node, user_language, dpi, text_options, is_testing)?;
 
         let ink_rect = bbox.ink_rect.unwrap_or_default();
         let logical_rect = bbox.rect.unwrap_or_default();
@@ -316,6 +586,42 @@ impl Handle {
         ))
     }
 
+    /// Returns (ink_rect, logical_rect) in the user space of the element's nearest viewport
+    pub fn get_bbox_in_viewport_for_element(
+        &self,
+        id: &str,
+        user_language: &UserLanguage,
+        dpi: Dpi,
+        text_options: &TextOptions,
+        is_testing: bool,
+    ) -> Result<(cairo::Rectangle, cairo::Rectangle), RenderingError> {
+        let node = self.lookup_node(id)?;
+        let root = self.document.root();
+
+        let target = cairo::ImageSurface::create(cairo::Format::Rgb24, 1, 1)?;
+        let cr = cairo::Context::new(&target)?;
+
+        let bbox = element_bbox_tree(
+            node,
+            root,
+            &cr,
+            unit_rectangle(),
+            user_language,
+            dpi,
+            text_options,
+            is_testing,
+            &mut AcquiredNodes::new(&self.document),
+        )?;
+
+        let ink_rect = bbox.ink_rect.unwrap_or_default();
+        let logical_rect = bbox.rect.unwrap_or_default();
+
+        Ok((
+            cairo::Rectangle::from(ink_rect),
+            cairo::Rectangle::from(logical_rect),
+        ))
+    }
+
     pub fn render_element(
         &self,
         cr: &cairo::Context,
@@ -323,13 +629,15 @@ impl Handle {
         element_viewport: &cairo::Rectangle,
         user_language: &UserLanguage,
         dpi: Dpi,
+        text_options: &TextOptions,
         is_testing: bool,
     ) -> Result<(), RenderingError> {
         cr.status()?;
 
         let node = self.get_node_or_root(id)?;
 
-        let bbox = self.get_bbox_for_element(&node, user_language, dpi, is_testing)?;
+        let bbox =
+            self.get_bbox_for_element(&node, user_language, dpi, text_options, is_testing)?;
 
         if bbox.ink_rect.is_none() || bbox.rect.is_none() {
             // Nothing to draw
@@ -358,6 +666,7 @@ impl Handle {
                 unit_rectangle(),
                 user_language,
                 dpi,
+                text_options,
                 false,
                 is_testing,
                 &mut AcquiredNodes::new(&self.document),
@@ -373,9 +682,72 @@ impl Handle {
     pub fn set_stylesheet(&mut self, css: &str) -> Result<(), LoadingError> {
         let mut stylesheet = Stylesheet::new(Origin::User);
         stylesheet.parse(css, &UrlResolver::new(None))?;
+        self.add_font_faces(stylesheet.font_faces());
         self.document.cascade(&[stylesheet]);
         Ok(())
     }
+
+    pub fn add_font_data(&mut self, data: &[u8]) -> Result<(), LoadingError> {
+        self.app_fonts()?.add_font_data(data)
+    }
+
+    /// Adds the fonts of `@font-face` rules.
+    ///
+    /// Rules whose fonts can't be loaded get ignored, like in browsers, so the text just
+    /// uses a fallback font.
+    fn add_font_faces(&mut self, faces: &[FontFace]) {
+        for face in faces {
+            let res = self
+                .app_fonts()
+                .and_then(|app_fonts| app_fonts.add_font_face(face));
+
+            if let Err(e) = res {
+                rsvg_log!("ignoring @font-face for \"{}\": {}", face.family, e);
+            }
+        }
+    }
+
+    fn app_fonts(&mut self) -> Result<&mut AppFonts, LoadingError> {
+        if self.app_fonts.is_none() {
+            self.app_fonts = Some(AppFonts::new().ok_or_else(|| {
+                LoadingError::Other(String::from("fonts from memory are not supported"))
+            })?);
+        }
+
+        Ok(self.app_fonts.as_mut().unwrap())
+    }
+
+    /// The font map with the system's fonts and the ones added with `add_font_data()`,
+    /// or `None` if no fonts were added.
+    pub fn font_map(&self) -> Option<pango::FontMap> {
+        self.app_fonts.as_ref().map(|f| f.font_map().clone())
+    }
+}
+
+/// Converts a cairo path to SVG path data, as for the `d` attribute of a `<path>`.
+fn svg_path_data(path: &cairo::Path) -> String {
+    path.iter()
+        .map(|segment| match segment {
+            cairo::PathSegment::MoveTo((x, y)) => format!("M {} {}", x, y),
+            cairo::PathSegment::LineTo((x, y)) => format!("L {} {}", x, y),
+            cairo::PathSegment::CurveTo((x1, y1), (x2, y2), (x3, y3)) => {
+                format!("C {} {} {} {} {} {}", x1, y1, x2, y2, x3, y3)
+            }
+            cairo::PathSegment::ClosePath => String::from("Z"),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Finds the first element child of `node` for which `pred` is true, and returns its
+/// whitespace-normalized text content.
+fn first_child_text<F>(node: &Node, pred: F) -> Option<String>
+where
+    F: Fn(&Element) -> bool,
+{
+    node.children()
+        .find(|c| c.is_element() && pred(&c.borrow_element()))
+        .map(|c| c.get_text_content(true))
 }
 
 fn unit_rectangle() -> Rect {
@@ -0,0 +1,171 @@
+//! Hand-written binding to the very minimal part of HarfBuzz that we need.
+//!
+//! Pango does not tell which OpenType features a font supports, or where the baselines
+//! of its `BASE` table are, so we ask HarfBuzz through the `hb_font_t` that Pango uses
+//! for shaping.
+
+#![allow(non_camel_case_types)]
+
+use glib::translate::*;
+use std::ptr;
+
+type hb_tag_t = u32;
+type hb_bool_t = libc::c_int;
+type hb_codepoint_t = u32;
+type hb_position_t = i32;
+type hb_script_t = u32;
+type hb_direction_t = libc::c_int;
+
+#[repr(C)]
+struct hb_font_t {
+    _private: [u8; 0],
+}
+
+#[repr(C)]
+struct hb_face_t {
+    _private: [u8; 0],
+}
+
+#[repr(C)]
+struct hb_unicode_funcs_t {
+    _private: [u8; 0],
+}
+
+#[repr(C)]
+struct hb_language_impl_t {
+    _private: [u8; 0],
+}
+
+extern "C" {
+    fn pango_font_get_hb_font(font: *mut pango::ffi::PangoFont) -> *mut hb_font_t;
+
+    fn hb_font_get_face(font: *mut hb_font_t) -> *mut hb_face_t;
+
+    fn hb_ot_layout_table_get_feature_tags(
+        face: *mut hb_face_t,
+        table_tag: hb_tag_t,
+        start_offset: libc::c_uint,
+        feature_count: *mut libc::c_uint,
+        feature_tags: *mut hb_tag_t,
+    ) -> libc::c_uint;
+
+    fn hb_unicode_funcs_get_default() -> *mut hb_unicode_funcs_t;
+
+    fn hb_unicode_script(ufuncs: *mut hb_unicode_funcs_t, unicode: hb_codepoint_t) -> hb_script_t;
+
+    fn hb_ot_tags_from_script_and_language(
+        script: hb_script_t,
+        language: *const hb_language_impl_t,
+        script_count: *mut libc::c_uint,
+        script_tags: *mut hb_tag_t,
+        language_count: *mut libc::c_uint,
+        language_tags: *mut hb_tag_t,
+    );
+
+    fn hb_ot_layout_get_baseline(
+        font: *mut hb_font_t,
+        baseline_tag: hb_tag_t,
+        direction: hb_direction_t,
+        script_tag: hb_tag_t,
+        language_tag: hb_tag_t,
+        coord: *mut hb_position_t,
+    ) -> hb_bool_t;
+}
+
+const HB_OT_TAG_GSUB: hb_tag_t = tag(b"GSUB");
+
+/// The `HB_OT_TAG_DEFAULT_SCRIPT` for fonts that have nothing specific to a script.
+const HB_OT_TAG_DEFAULT_SCRIPT: hb_tag_t = tag(b"DFLT");
+
+/// The `HB_OT_TAG_DEFAULT_LANGUAGE`.
+const HB_OT_TAG_DEFAULT_LANGUAGE: hb_tag_t = tag(b"dflt");
+
+/// The `HB_DIRECTION_LTR` value of the `hb_direction_t` enum.
+const HB_DIRECTION_LTR: hb_direction_t = 4;
+
+const fn tag(name: &[u8; 4]) -> hb_tag_t {
+    u32::from_be_bytes(*name)
+}
+
+/// Whether a font has a glyph substitution feature, like `b"smcp"` for small caps.
+///
+/// Returns `false` if Pango does not have a HarfBuzz font for `font`.
+pub fn font_has_substitution(font: &pango::Font, feature: &[u8; 4]) -> bool {
+    unsafe {
+        let hb_font = pango_font_get_hb_font(font.to_glib_none().0);
+        if hb_font.is_null() {
+            return false;
+        }
+
+        let face = hb_font_get_face(hb_font);
+
+        let mut count = 0;
+        let total = hb_ot_layout_table_get_feature_tags(
+            face,
+            HB_OT_TAG_GSUB,
+            0,
+            &mut count,
+            ptr::null_mut(),
+        );
+
+        let mut tags = vec![0; total as usize];
+        count = total;
+        hb_ot_layout_table_get_feature_tags(face, HB_OT_TAG_GSUB, 0, &mut count, tags.as_mut_ptr());
+        tags.truncate(count as usize);
+
+        tags.contains(&tag(feature))
+    }
+}
+
+/// Where the font's `BASE` table puts a horizontal baseline, like `b"hang"` for the
+/// hanging baseline, for the script of the character `c`.
+///
+/// The result is in Pango units above the font's origin, which is usually on the
+/// alphabetic baseline.  Returns `None` if the font has no such baseline, or if Pango
+/// does not have a HarfBuzz font for `font`.
+pub fn font_baseline(font: &pango::Font, baseline: &[u8; 4], c: char) -> Option<i32> {
+    unsafe {
+        let hb_font = pango_font_get_hb_font(font.to_glib_none().0);
+        if hb_font.is_null() {
+            return None;
+        }
+
+        let script = hb_unicode_script(hb_unicode_funcs_get_default(), u32::from(c));
+
+        // Scripts like Devanagari have several tags, newest first; the `BASE` tables
+        // that are around use the original one.
+        let mut script_tags = [HB_OT_TAG_DEFAULT_SCRIPT; 3];
+        let mut script_count = script_tags.len() as libc::c_uint;
+        let mut language_count = 0;
+        hb_ot_tags_from_script_and_language(
+            script,
+            ptr::null(),
+            &mut script_count,
+            script_tags.as_mut_ptr(),
+            &mut language_count,
+            ptr::null_mut(),
+        );
+
+        let script_tag = match script_count {
+            0 => HB_OT_TAG_DEFAULT_SCRIPT,
+            n => script_tags[n as usize - 1],
+        };
+
+        // HarfBuzz uses the default script of the table if it does not have this one.
+        let mut coord = 0;
+        let found = hb_ot_layout_get_baseline(
+            hb_font,
+            tag(baseline),
+            HB_DIRECTION_LTR,
+            script_tag,
+            HB_OT_TAG_DEFAULT_LANGUAGE,
+            &mut coord,
+        );
+
+        if found != 0 {
+            Some(coord)
+        } else {
+            None
+        }
+    }
+}
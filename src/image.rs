@@ -101,7 +101,8 @@ impl Draw for Image {
         };
 
         let elt = node.borrow_element();
-        let stacking_ctx = StackingContext::new(acquired_nodes, &elt, values.transform(), values);
+        let transform = values.transform(&view_params, || Some(rect));
+        let stacking_ctx = StackingContext::new(acquired_nodes, &elt, transform, values);
 
         draw_ctx.draw_image(&image, &stacking_ctx, acquired_nodes, values, clipping)
     }
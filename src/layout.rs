@@ -2,6 +2,8 @@
 //!
 //! The idea is to take the DOM tree and produce a layout tree with SVG concepts.
 
+use std::hash::{Hash, Hasher};
+use std::ops::Range;
 use std::rc::Rc;
 
 use crate::aspect_ratio::AspectRatio;
@@ -14,10 +16,11 @@ use crate::node::*;
 use crate::paint_server::PaintSource;
 use crate::path_builder::Path;
 use crate::properties::{
-    ClipRule, ComputedValues, Direction, FillRule, Filter, FontFamily, FontStretch, FontStyle,
-    FontVariant, FontWeight, MixBlendMode, Opacity, Overflow, PaintOrder, ShapeRendering,
-    StrokeDasharray, StrokeLinecap, StrokeLinejoin, StrokeMiterlimit, TextDecoration,
-    TextRendering, UnicodeBidi, WritingMode, XmlLang,
+    ClipRule, ComputedValues, Direction, FillRule, Filter, FontFamily, FontSizeAdjust, FontStretch,
+    FontStyle, FontVariant, FontVariantCaps, FontVariantEastAsian, FontVariantNumeric, FontWeight,
+    MixBlendMode, Opacity, Overflow, PaintOrder, ShapeRendering, StrokeDasharray, StrokeLinecap,
+    StrokeLinejoin, StrokeMiterlimit, TabSize, TextDecoration, TextRendering, UnicodeBidi,
+    VectorEffect, WritingMode, XmlLang,
 };
 use crate::rect::Rect;
 use crate::surface_utils::shared_surface::SharedImageSurface;
@@ -56,6 +59,7 @@ pub struct Stroke {
     pub line_join: StrokeLinejoin,
     pub dash_offset: f64,
     pub dashes: Box<[f64]>,
+    pub vector_effect: VectorEffect,
 }
 
 /// Paths and basic shapes resolved to a path.
@@ -103,9 +107,84 @@ pub struct TextSpan {
     pub stroke_paint: PaintSource,
     pub fill_paint: PaintSource,
     pub text_rendering: TextRendering,
+    pub decoration_lines: Vec<DecorationLine>,
+
+    /// Lines from the ancestors that specify a `text-decoration`, outermost first.
+    pub inherited_decorations: Vec<InheritedDecoration>,
+
+    /// Scale factor along the inline direction, for `lengthAdjust="spacingAndGlyphs"`.
+    pub length_adjust_scale: f64,
+
+    /// Rotation in degrees around the span's origin on the baseline, from the
+    /// `rotate` attribute.
+    pub rotation: f64,
+
+    /// Angle in degrees of a synthetic oblique slant, from `font-style: oblique <angle>`.
+    pub slant: f64,
+
+    /// Byte range of the span's text in the layout's text.  When the spans of a text
+    /// chunk share a layout, each one draws only the glyphs for its own characters.
+    pub text_range: Option<Range<usize>>,
+
+    /// Horizontal ranges of the layout that belong to this span, relative to `x`.
+    pub visible_ranges: Option<Vec<(f64, f64)>>,
+}
+
+/// A text span as laid out for rendering, for querying the positions of its characters.
+pub struct TextSpanGeometry {
+    pub layout: pango::Layout,
+
+    /// Transformation from the coordinates of the layout to the text's user space.
+    pub transform: Transform,
+
+    /// Byte ranges of the span's addressable characters in the layout's text.
+    pub chars: Vec<Range<usize>>,
+
+    /// Rotation in degrees of the span's glyphs, from the `rotate` attribute.
+    pub rotation: f64,
+
+    /// Spacing after the span's last character that its layout does not have, from
+    /// `lengthAdjust="spacing"`.
+    pub spacing_after: f64,
+}
+
+/// A text decoration line that librsvg draws by hand instead of letting Pango do it.
+///
+/// The coordinates are relative to the top-left corner of the span's layout, along
+/// the text's direction.
+pub struct DecorationLine {
+    pub x: f64,
+    pub y: f64,
+    pub thickness: f64,
+    pub length: f64,
+}
+
+/// Decoration lines that a text span gets from an ancestor element.
+///
+/// They get painted with the fill and stroke of the ancestor that specified the
+/// `text-decoration`, not with the ones of the span.
+pub struct InheritedDecoration {
+    pub lines: Vec<DecorationLine>,
+    pub stroke: Stroke,
+    pub stroke_paint: PaintSource,
+    pub fill_paint: PaintSource,
+}
+
+/// Distance between tab stops, from the `tab-size` property.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum TabStops {
+    /// A multiple of the width of a space in the current font.
+    Spaces(f64),
+
+    /// A distance in user units.
+    Width(f64),
 }
 
 /// Font-related properties extracted from `ComputedValues`.
+///
+/// Two spans with equal `FontProperties` and the same text produce the same Pango
+/// layout, so this can be used as a cache key.  The lengths in user units are compared
+/// after rounding them to Pango units, since Pango cannot tell apart smaller differences.
 pub struct FontProperties {
     pub xml_lang: XmlLang,
     pub writing_mode: WritingMode,
@@ -113,12 +192,25 @@ pub struct FontProperties {
     pub direction: Direction,
     pub font_family: FontFamily,
     pub font_style: FontStyle,
-    pub font_variant: FontVariant,
+
+    /// Combines the `font-variant` and `font-variant-caps` properties; the latter takes
+    /// precedence if it is not `normal`.
+    pub font_variant_caps: FontVariantCaps,
+
+    pub font_variant_numeric: FontVariantNumeric,
+    pub font_variant_east_asian: FontVariantEastAsian,
     pub font_weight: FontWeight,
     pub font_stretch: FontStretch,
     pub font_size: f64,
-    pub letter_spacing: f64,
+    pub font_size_adjust: FontSizeAdjust,
+    /// Explicit letter spacing in user units, or `None` for `letter-spacing: normal`.
+    pub letter_spacing: Option<f64>,
+    /// Extra space after each word-separator character, in user units.
+    pub word_spacing: f64,
     pub text_decoration: TextDecoration,
+    pub text_decoration_thickness: Option<f64>,
+    pub text_underline_offset: Option<f64>,
+    pub tab_stops: TabStops,
 }
 
 impl StackingContext {
@@ -226,6 +318,8 @@ impl Stroke {
                 .collect::<Box<[f64]>>(),
         };
 
+        let vector_effect = values.vector_effect();
+
         Stroke {
             width,
             miter_limit,
@@ -233,6 +327,7 @@ impl Stroke {
             line_join,
             dash_offset,
             dashes,
+            vector_effect,
         }
     }
 }
@@ -246,12 +341,185 @@ impl FontProperties {
             direction: values.direction(),
             font_family: values.font_family(),
             font_style: values.font_style(),
-            font_variant: values.font_variant(),
+            font_variant_caps: match (values.font_variant(), values.font_variant_caps()) {
+                (FontVariant::SmallCaps, FontVariantCaps::Normal) => FontVariantCaps::SmallCaps,
+                (_, caps) => caps,
+            },
+            font_variant_numeric: values.font_variant_numeric(),
+            font_variant_east_asian: values.font_variant_east_asian(),
             font_weight: values.font_weight(),
             font_stretch: values.font_stretch(),
             font_size: values.font_size().to_user(params),
+            font_size_adjust: values.font_size_adjust(),
             letter_spacing: values.letter_spacing().to_user(params),
+            word_spacing: values.word_spacing().to_user(params),
             text_decoration: values.text_decoration(),
+            text_decoration_thickness: values.text_decoration_thickness().to_user(params),
+            text_underline_offset: values.text_underline_offset().to_user(params),
+            tab_stops: match values.tab_size() {
+                TabSize::Number(n) => TabStops::Spaces(n),
+                TabSize::Length(l) => TabStops::Width(l.to_user(params)),
+            },
+        }
+    }
+
+    /// The floating-point fields, quantized to Pango units.
+    fn quantized_lengths(&self) -> [Option<i64>; 8] {
+        let font_size_adjust = match self.font_size_adjust {
+            FontSizeAdjust::None => None,
+            FontSizeAdjust::Number(n) => Some(n),
+        };
+
+        let (tab_spaces, tab_width) = match self.tab_stops {
+            TabStops::Spaces(n) => (Some(n), None),
+            TabStops::Width(w) => (None, Some(w)),
+        };
+
+        [
+            Some(quantize(self.font_size)),
+            font_size_adjust.map(quantize),
+            self.letter_spacing.map(quantize),
+            Some(quantize(self.word_spacing)),
+            self.text_decoration_thickness.map(quantize),
+            self.text_underline_offset.map(quantize),
+            tab_spaces.map(quantize),
+            tab_width.map(quantize),
+        ]
+    }
+}
+
+fn quantize(v: f64) -> i64 {
+    (v * f64::from(pango::SCALE)).round() as i64
+}
+
+impl PartialEq for FontProperties {
+    fn eq(&self, other: &FontProperties) -> bool {
+        self.xml_lang == other.xml_lang
+            && self.writing_mode == other.writing_mode
+            && self.unicode_bidi == other.unicode_bidi
+            && self.direction == other.direction
+            && self.font_family == other.font_family
+            && self.font_style == other.font_style
+            && self.font_variant_caps == other.font_variant_caps
+            && self.font_variant_numeric == other.font_variant_numeric
+            && self.font_variant_east_asian == other.font_variant_east_asian
+            && self.font_weight == other.font_weight
+            && self.font_stretch == other.font_stretch
+            && self.text_decoration == other.text_decoration
+            && self.quantized_lengths() == other.quantized_lengths()
+    }
+}
+
+impl Eq for FontProperties {}
+
+impl Hash for FontProperties {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.xml_lang.hash(state);
+        self.writing_mode.hash(state);
+        self.unicode_bidi.hash(state);
+        self.direction.hash(state);
+        self.font_family.hash(state);
+        self.font_style.hash(state);
+        self.font_variant_caps.hash(state);
+        self.font_variant_numeric.hash(state);
+        self.font_variant_east_asian.hash(state);
+        self.font_weight.hash(state);
+        self.font_stretch.hash(state);
+        self.text_decoration.hash(state);
+        self.quantized_lengths().hash(state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::hash_map::DefaultHasher;
+
+    use crate::dpi::Dpi;
+    use crate::parsers::Parse;
+    use crate::properties::{ParsedProperty, SpecifiedValue, SpecifiedValues};
+
+    fn hash(props: &FontProperties) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        props.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn font_properties() -> FontProperties {
+        let values = ComputedValues::default();
+        let params = NormalizeParams::from_dpi(Dpi::new(96.0, 96.0));
+        FontProperties::new(&values, &params)
+    }
+
+    #[test]
+    fn stroke_gets_vector_effect() {
+        let params = NormalizeParams::from_dpi(Dpi::new(96.0, 96.0));
+
+        let mut values = ComputedValues::default();
+        assert_eq!(
+            Stroke::new(&values, &params).vector_effect,
+            VectorEffect::None
+        );
+
+        let mut specified = SpecifiedValues::default();
+        specified.set_parsed_property(&ParsedProperty::VectorEffect(SpecifiedValue::Specified(
+            VectorEffect::parse_str("non-scaling-stroke").unwrap(),
+        )));
+        specified.to_computed_values(&mut values);
+
+        assert_eq!(
+            Stroke::new(&values, &params).vector_effect,
+            VectorEffect::NonScalingStroke
+        );
+    }
+
+    #[test]
+    fn font_properties_hash_equal_when_identical() {
+        let a = font_properties();
+        let mut b = font_properties();
+
+        assert!(a == b);
+        assert_eq!(hash(&a), hash(&b));
+
+        // Differences that Pango cannot represent are ignored
+        b.font_size += 1e-6;
+        assert!(a == b);
+        assert_eq!(hash(&a), hash(&b));
+    }
+
+    #[test]
+    fn font_properties_differ_when_any_field_changes() {
+        let base = font_properties();
+
+        let changes: Vec<fn(&mut FontProperties)> = vec![
+            |p| p.xml_lang = XmlLang(Some("ja".to_string())),
+            |p| p.writing_mode = WritingMode::TbRl,
+            |p| p.unicode_bidi = UnicodeBidi::Override,
+            |p| p.direction = Direction::Rtl,
+            |p| p.font_family = FontFamily("Sans".to_string()),
+            |p| p.font_style = FontStyle::Italic,
+            |p| p.font_variant_caps = FontVariantCaps::SmallCaps,
+            |p| p.font_variant_numeric.tabular_nums = true,
+            |p| p.font_variant_east_asian.ruby = true,
+            |p| p.font_weight = FontWeight::Bold,
+            |p| p.font_stretch = FontStretch::Condensed,
+            |p| p.font_size += 1.0,
+            |p| p.font_size_adjust = FontSizeAdjust::Number(0.5),
+            |p| p.letter_spacing = Some(2.0),
+            |p| p.word_spacing = 2.0,
+            |p| p.text_decoration.underline = true,
+            |p| p.text_decoration_thickness = Some(1.0),
+            |p| p.text_underline_offset = Some(1.0),
+            |p| p.tab_stops = TabStops::Width(8.0),
+        ];
+
+        for change in changes {
+            let mut props = font_properties();
+            change(&mut props);
+
+            assert!(props != base);
+            assert_ne!(hash(&props), hash(&base));
         }
     }
 }
@@ -54,10 +54,44 @@ use crate::properties::ComputedValues;
 use crate::rect::Rect;
 use crate::viewbox::ViewBox;
 
-/// Units for length values.
+/// Units for the lengths of the public API.
 // This needs to be kept in sync with `rsvg.h:RsvgUnit`.
 #[repr(C)]
 #[derive(Debug, PartialEq, Copy, Clone)]
+pub enum RsvgUnit {
+    /// `1.0` means 100%
+    Percent,
+
+    /// Pixels, or the CSS default unit
+    Px,
+
+    /// Size of the current font
+    Em,
+
+    /// x-height of the current font
+    Ex,
+
+    /// Inches (25.4 mm)
+    In,
+
+    /// Centimeters
+    Cm,
+
+    /// Millimeters
+    Mm,
+
+    /// Points (1/72 inch)
+    Pt,
+
+    /// Picas (12 points)
+    Pc,
+}
+
+/// Units for length values.
+///
+/// These are the units of [`RsvgUnit`], plus the ones that the public API does not have;
+/// see the conversion to [`RsvgLength`].
+#[derive(Debug, PartialEq, Copy, Clone)]
 pub enum LengthUnit {
     /// `1.0` means 100%
     Percent,
@@ -85,6 +119,12 @@ pub enum LengthUnit {
 
     /// Picas (12 points)
     Pc,
+
+    /// Advance of the "0" glyph in the current font
+    Ch,
+
+    /// Size of the root element's font
+    Rem,
 }
 
 /// A CSS length value.
@@ -94,10 +134,10 @@ pub enum LengthUnit {
 /// [CSS lengths]: https://www.w3.org/TR/CSS22/syndata.html#length-units
 ///
 /// It is up to the calling application to convert lengths in non-pixel units (i.e. those
-/// where the [`unit`][RsvgLength::unit] field is not [`LengthUnit::Px`]) into something
+/// where the [`unit`][RsvgLength::unit] field is not [`RsvgUnit::Px`]) into something
 /// meaningful to the application.  For example, if your application knows the
 /// dots-per-inch (DPI) it is using, it can convert lengths with [`unit`] in
-/// [`LengthUnit::In`] or other physical units.
+/// [`RsvgUnit::In`] or other physical units.
 // Keep this in sync with rsvg.h:RsvgLength
 #[repr(C)]
 #[derive(Debug, PartialEq, Copy, Clone)]
@@ -106,11 +146,11 @@ pub struct RsvgLength {
     pub length: f64,
 
     /// Unit part of the length
-    pub unit: LengthUnit,
+    pub unit: RsvgUnit,
 }
 
 impl RsvgLength {
-    pub fn new(l: f64, unit: LengthUnit) -> RsvgLength {
+    pub fn new(l: f64, unit: RsvgUnit) -> RsvgLength {
         RsvgLength { length: l, unit }
     }
 }
@@ -233,12 +273,27 @@ pub struct CssLength<N: Normalize, V: Validate> {
     validation: PhantomData<V>,
 }
 
+/// Lengths in the units that `RsvgUnit` does not have get converted to `em`.
+///
+/// The public API only has the lengths of the root `<svg>` element, which is not text, so
+/// its `ch` is half of its font size, and its `rem` is its font size.
 impl<N: Normalize, V: Validate> From<CssLength<N, V>> for RsvgLength {
     fn from(l: CssLength<N, V>) -> RsvgLength {
-        RsvgLength {
-            length: l.length,
-            unit: l.unit,
-        }
+        let (length, unit) = match l.unit {
+            LengthUnit::Percent => (l.length, RsvgUnit::Percent),
+            LengthUnit::Px => (l.length, RsvgUnit::Px),
+            LengthUnit::Em => (l.length, RsvgUnit::Em),
+            LengthUnit::Ex => (l.length, RsvgUnit::Ex),
+            LengthUnit::In => (l.length, RsvgUnit::In),
+            LengthUnit::Cm => (l.length, RsvgUnit::Cm),
+            LengthUnit::Mm => (l.length, RsvgUnit::Mm),
+            LengthUnit::Pt => (l.length, RsvgUnit::Pt),
+            LengthUnit::Pc => (l.length, RsvgUnit::Pc),
+            LengthUnit::Ch => (l.length / 2.0, RsvgUnit::Em),
+            LengthUnit::Rem => (l.length, RsvgUnit::Em),
+        };
+
+        RsvgLength::new(length, unit)
     }
 }
 
@@ -285,6 +340,8 @@ impl<N: Normalize, V: Validate> Parse for CssLength<N, V> {
                     "mm" => LengthUnit::Mm,
                     "pt" => LengthUnit::Pt,
                     "pc" => LengthUnit::Pc,
+                    "ch" => LengthUnit::Ch,
+                    "rem" => LengthUnit::Rem,
 
                     _ => return Err(parser.new_unexpected_token_error(token)),
                 };
@@ -302,9 +359,14 @@ impl<N: Normalize, V: Validate> Parse for CssLength<N, V> {
 }
 
 /// Parameters to normalize [`Length`] values to user-space distances.
+#[derive(Clone)]
 pub struct NormalizeParams {
     vbox: ViewBox,
     font_size: f64,
+    root_font_size: f64,
+    x_height: f64,
+    zero_advance: f64,
+    space_advance: f64,
     dpi: Dpi,
 }
 
@@ -315,18 +377,67 @@ impl NormalizeParams {
     // the font size in the end.  Can we instead have a `ComputedFontSize(FontSize)`
     // newtype, extracted from the `ComputedValues`?
     pub fn new(values: &ComputedValues, params: &ViewParams) -> NormalizeParams {
+        let font_size = font_size_to_user(&values.font_size().value(), params.dpi);
+        let root_font_size = font_size_to_user(&values.root_font_size().value(), params.dpi);
+
         NormalizeParams {
             vbox: params.vbox,
-            font_size: font_size_from_values(values, params.dpi),
+            font_size,
+            root_font_size,
+            x_height: font_size / 2.0,
+            zero_advance: font_size / 2.0,
+            space_advance: font_size / 4.0,
             dpi: params.dpi,
         }
     }
 
+    /// Returns a copy of these parameters where percentages refer to the size of
+    /// `reference_box` instead of the viewport.
+    pub fn with_reference_box(&self, reference_box: &Rect) -> NormalizeParams {
+        NormalizeParams {
+            vbox: ViewBox::from(*reference_box),
+            ..*self
+        }
+    }
+
+    /// Returns a copy of these parameters where `ex` units refer to the x-height of the
+    /// actual font, instead of half the font size.
+    pub fn with_x_height(&self, x_height: f64) -> NormalizeParams {
+        NormalizeParams { x_height, ..*self }
+    }
+
+    /// Returns a copy of these parameters where `ch` units refer to the advance of the
+    /// "0" glyph in the actual font, instead of half the font size.
+    pub fn with_zero_advance(&self, zero_advance: f64) -> NormalizeParams {
+        NormalizeParams {
+            zero_advance,
+            ..*self
+        }
+    }
+
+    /// Returns a copy of these parameters where `space_advance()` is the advance of the
+    /// space glyph in the actual font, instead of a quarter of the font size.
+    pub fn with_space_advance(&self, space_advance: f64) -> NormalizeParams {
+        NormalizeParams {
+            space_advance,
+            ..*self
+        }
+    }
+
+    /// The advance of a space, which percentages in `word-spacing` refer to.
+    pub fn space_advance(&self) -> f64 {
+        self.space_advance
+    }
+
     /// Just used by rsvg-convert, where there is no font size nor viewport.
     pub fn from_dpi(dpi: Dpi) -> NormalizeParams {
         NormalizeParams {
             vbox: ViewBox::from(Rect::default()),
             font_size: 1.0,
+            root_font_size: 1.0,
+            x_height: 0.5,
+            zero_advance: 0.5,
+            space_advance: 0.25,
             dpi,
         }
     }
@@ -374,7 +485,11 @@ impl<N: Normalize, V: Validate> CssLength<N, V> {
 
             LengthUnit::Em => self.length * params.font_size,
 
-            LengthUnit::Ex => self.length * params.font_size / 2.0,
+            LengthUnit::Ex => self.length * params.x_height,
+
+            LengthUnit::Ch => self.length * params.zero_advance,
+
+            LengthUnit::Rem => self.length * params.root_font_size,
 
             LengthUnit::In => self.length * <N as Normalize>::normalize(params.dpi.x, params.dpi.y),
 
@@ -402,7 +517,8 @@ impl<N: Normalize, V: Validate> CssLength<N, V> {
     ///
     /// # Panics
     ///
-    /// Will panic if the length is in Percent, Em, or Ex units.
+    /// Will panic if the length is in Percent, Em, or Ex units.  Lengths in Ch or Rem units
+    /// get resolved with the `params` first, like in [`CssLength::to_user`].
     pub fn to_points(&self, params: &NormalizeParams) -> f64 {
         match self.unit {
             LengthUnit::Px => {
@@ -421,6 +537,11 @@ impl<N: Normalize, V: Validate> CssLength<N, V> {
                 panic!("Cannot convert an Ex length into points");
             }
 
+            LengthUnit::Ch | LengthUnit::Rem => {
+                self.to_user(params) / <N as Normalize>::normalize(params.dpi.x, params.dpi.y)
+                    * 72.0
+            }
+
             LengthUnit::In => self.length * POINTS_PER_INCH,
 
             LengthUnit::Cm => self.length / CM_PER_INCH * POINTS_PER_INCH,
@@ -434,9 +555,7 @@ impl<N: Normalize, V: Validate> CssLength<N, V> {
     }
 }
 
-fn font_size_from_values(values: &ComputedValues, dpi: Dpi) -> f64 {
-    let v = &values.font_size().value();
-
+fn font_size_to_user(v: &Length<Both>, dpi: Dpi) -> f64 {
     match v.unit {
         LengthUnit::Percent => unreachable!("ComputedValues can't have a relative font size"),
 
@@ -446,6 +565,8 @@ fn font_size_from_values(values: &ComputedValues, dpi: Dpi) -> f64 {
         // matches the default from the FontSize property.
         LengthUnit::Em => v.length * 12.0,
         LengthUnit::Ex => v.length * 12.0 / 2.0,
+        LengthUnit::Ch => v.length * 12.0 / 2.0,
+        LengthUnit::Rem => v.length * 12.0,
 
         // FontSize always is a Both, per properties.rs
         LengthUnit::In => v.length * Both::normalize(dpi.x, dpi.y),
@@ -538,6 +659,22 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parses_font_ch() {
+        assert_eq!(
+            Length::<Horizontal>::parse_str("2ch").unwrap(),
+            Length::<Horizontal>::new(2.0, LengthUnit::Ch)
+        );
+    }
+
+    #[test]
+    fn parses_font_rem() {
+        assert_eq!(
+            Length::<Horizontal>::parse_str("1.5rem").unwrap(),
+            Length::<Horizontal>::new(1.5, LengthUnit::Rem)
+        );
+    }
+
     #[test]
     fn parses_physical_units() {
         assert_eq!(
@@ -672,6 +809,23 @@ mod tests {
         );
     }
 
+    #[test]
+    fn normalize_ex_uses_x_height() {
+        let view_params = ViewParams::new(Dpi::new(40.0, 40.0), 100.0, 200.0);
+        let values = ComputedValues::default();
+        let params = NormalizeParams::new(&values, &view_params).with_x_height(5.0);
+
+        assert_approx_eq_cairo!(
+            Length::<Horizontal>::new(2.0, LengthUnit::Ex).to_user(&params),
+            10.0
+        );
+
+        assert_approx_eq_cairo!(
+            Length::<Horizontal>::new(1.0, LengthUnit::Em).to_user(&params),
+            12.0
+        );
+    }
+
     #[test]
     fn to_points_works() {
         let params = NormalizeParams::from_dpi(Dpi::new(40.0, 96.0));
@@ -684,5 +838,13 @@ mod tests {
             Length::<Vertical>::new(192.0, LengthUnit::Px).to_points(&params),
             2.0 * 72.0
         );
+        assert_approx_eq_cairo!(
+            Length::<Horizontal>::new(40.0, LengthUnit::Rem).to_points(&params),
+            72.0
+        );
+        assert_approx_eq_cairo!(
+            Length::<Horizontal>::new(80.0, LengthUnit::Ch).to_points(&params),
+            72.0
+        );
     }
 }
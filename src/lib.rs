@@ -178,6 +178,7 @@ mod util;
 mod accept_language;
 mod angle;
 mod api;
+mod app_fonts;
 mod aspect_ratio;
 mod bbox;
 pub mod c_api;
@@ -193,9 +194,11 @@ mod error;
 mod filter;
 mod filter_func;
 pub mod filters;
+mod font_face;
 mod font_props;
 mod gradient;
 mod handle;
+mod harfbuzz;
 mod href;
 mod image;
 mod io;
@@ -217,10 +220,15 @@ mod structure;
 mod style;
 pub mod surface_utils;
 mod text;
+mod text_options;
 mod transform;
 mod unit_interval;
 mod url_resolver;
 mod viewbox;
+#[cfg(all(system_deps_have_fontconfig, system_deps_have_pangoft2))]
+mod woff;
+#[cfg(all(system_deps_have_fontconfig, system_deps_have_pangoft2))]
+mod woff2;
 mod xml;
 
 #[doc(hidden)]
@@ -248,6 +256,7 @@ pub mod rsvg_convert_only {
     pub use crate::dpi::Dpi;
     pub use crate::error::ParseError;
     pub use crate::length::{
-        CssLength, Horizontal, Length, Normalize, NormalizeParams, ULength, Validate, Vertical,
+        CssLength, Horizontal, Length, LengthUnit, Normalize, NormalizeParams, ULength, Validate,
+        Vertical,
     };
 }
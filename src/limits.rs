@@ -32,3 +32,17 @@ pub const MAX_REFERENCED_ELEMENTS: usize = 500_000;
 /// in an attempt to exhaust memory.  We don't allow loading more than
 /// this number of elements during the initial streaming load process.
 pub const MAX_LOADED_ELEMENTS: usize = 1_000_000;
+
+/// Maximum depth of nested elements inside a text element.
+///
+/// Collecting the characters of a `<text>` recurses into its `<tspan>` children, and into
+/// the subtrees that `<tref>` elements reference.  Elements nested deeper than this
+/// get ignored, instead of risking a stack overflow with a pathological document.
+pub const MAX_TEXT_NESTING_DEPTH: usize = 100;
+
+/// Maximum size in bytes of the font inside a WOFF file.
+///
+/// WOFF tables are compressed with zlib, so a small file could decompress to a huge font.
+/// Fonts whose `totalSfntSize` header is larger than this get rejected before any table
+/// gets decompressed.
+pub const MAX_WOFF_SFNT_SIZE: usize = 64 * 1024 * 1024;
@@ -5,11 +5,19 @@
 //! Librsvg puts a [`NodeData`] as the type parameter of [`rctree::Node`].  For convenience,
 //! librsvg has a type alias [`Node`]` = rctree::Node<NodeData>`.
 //!
+//! Tree traversal comes from [`rctree::Node`] as well.  For example, `descendants()`
+//! walks a subtree in document order by following the sibling and parent links, so
+//! deeply nested documents don't need recursion to be visited, and `ancestors()` follows
+//! the parent links up to the root.  The [`NodeSiblings`] trait adds iterators over the
+//! element siblings of a node, which skip the character data in between.
+//!
 //! Nodes are not constructed directly by callers;
 
 use markup5ever::QualName;
 use std::cell::{Ref, RefMut};
 use std::fmt;
+use std::ptr;
+use std::rc::Rc;
 
 use crate::bbox::BoundingBox;
 use crate::document::AcquiredNodes;
@@ -107,7 +115,7 @@ pub struct CascadedValues<'a> {
 
 enum CascadedInner<'a> {
     FromNode(Ref<'a, Element>),
-    FromValues(Box<ComputedValues>),
+    FromValues(Rc<ComputedValues>),
 }
 
 impl<'a> CascadedValues<'a> {
@@ -151,16 +159,39 @@ impl<'a> CascadedValues<'a> {
     ///
     /// This is for the `<use>` element, which draws the element which it references with the
     /// `<use>`'s own cascade, not with the element's original cascade.
+    ///
+    /// The computed values only depend on the `values` that get inherited, so when those
+    /// are the same as in the node's original cascade, or when the recomputed values turn
+    /// out to be the same as the original ones, the node's own `ComputedValues` get shared
+    /// instead of cloned.  The node's children then find their parent's values unchanged, and
+    /// an instanced subtree only gets recomputed down to where the cascade stops differing.
     pub fn new_from_values(
         node: &'a Node,
         values: &ComputedValues,
         fill: Option<PaintSource>,
         stroke: Option<PaintSource>,
     ) -> CascadedValues<'a> {
-        let mut v = Box::new(values.clone());
-        node.borrow_element()
-            .get_specified_values()
-            .to_computed_values(&mut v);
+        let elt = node.borrow_element();
+        let own_values = elt.get_shared_computed_values();
+
+        let inherits_unchanged = node.parent().map_or(false, |parent| {
+            let parent = parent.borrow_element();
+            let parent_values = parent.get_computed_values();
+            ptr::eq(values, parent_values) || values == parent_values
+        });
+
+        let v = if inherits_unchanged {
+            own_values
+        } else {
+            let mut v = values.clone();
+            elt.get_specified_values().to_computed_values(&mut v);
+
+            if v == *own_values {
+                own_values
+            } else {
+                Rc::new(v)
+            }
+        };
 
         CascadedValues {
             inner: CascadedInner::FromValues(v),
@@ -243,6 +274,71 @@ impl NodeBorrow for Node {
     }
 }
 
+/// Helper trait to walk over the element siblings of a node
+pub trait NodeSiblings {
+    /// Returns an iterator over the element siblings before this node, nearest first.
+    ///
+    /// `Chars` nodes are skipped, and the node itself is not included.
+    fn preceding_element_siblings(&self) -> ElementSiblings;
+
+    /// Returns an iterator over the element siblings after this node, nearest first.
+    ///
+    /// `Chars` nodes are skipped, and the node itself is not included.
+    fn following_element_siblings(&self) -> ElementSiblings;
+
+    /// Returns the nearest element sibling before this node, skipping `Chars` nodes.
+    fn previous_element_sibling(&self) -> Option<Node> {
+        self.preceding_element_siblings().next()
+    }
+
+    /// Returns the nearest element sibling after this node, skipping `Chars` nodes.
+    fn next_element_sibling(&self) -> Option<Node> {
+        self.following_element_siblings().next()
+    }
+}
+
+impl NodeSiblings for Node {
+    fn preceding_element_siblings(&self) -> ElementSiblings {
+        ElementSiblings {
+            next: self.previous_sibling(),
+            following: false,
+        }
+    }
+
+    fn following_element_siblings(&self) -> ElementSiblings {
+        ElementSiblings {
+            next: self.next_sibling(),
+            following: true,
+        }
+    }
+}
+
+/// Iterator over the element siblings of a node; see [`NodeSiblings`].
+pub struct ElementSiblings {
+    next: Option<Node>,
+    following: bool,
+}
+
+impl Iterator for ElementSiblings {
+    type Item = Node;
+
+    fn next(&mut self) -> Option<Node> {
+        loop {
+            let node = self.next.take()?;
+
+            self.next = if self.following {
+                node.next_sibling()
+            } else {
+                node.previous_sibling()
+            };
+
+            if node.is_element() {
+                return Some(node);
+            }
+        }
+    }
+}
+
 #[macro_export]
 macro_rules! is_element_of_type {
     ($node:expr, $element_type:ident) => {
@@ -272,12 +368,15 @@ impl NodeCascade for Node {
     fn cascade(&mut self, values: &ComputedValues) {
         let mut values = values.clone();
 
-        {
+        let values = {
             let mut elt = self.borrow_element_mut();
 
             elt.get_specified_values().to_computed_values(&mut values);
-            elt.set_computed_values(&values);
-        }
+
+            let values = Rc::new(values);
+            elt.set_computed_values(values.clone());
+            values
+        };
 
         for mut child in self.children().filter(|c| c.is_element()) {
             child.cascade(&values);
@@ -285,6 +384,38 @@ impl NodeCascade for Node {
     }
 }
 
+/// Helper trait to extract the character data of a subtree
+pub trait NodeTextContent {
+    /// Concatenates the contents of all the `Chars` descendants of a node, in document order.
+    ///
+    /// If `normalize` is true, each piece of text gets its whitespace normalized
+    /// according to the `xml:space` of its parent element, just like when rendering.
+    /// Otherwise the text is returned as it came out of the XML parser.
+    fn get_text_content(&self, normalize: bool) -> String;
+}
+
+impl NodeTextContent for Node {
+    fn get_text_content(&self, normalize: bool) -> String {
+        let mut content = String::new();
+
+        for node in self.descendants().filter(|n| n.is_chars()) {
+            let chars = node.borrow_chars();
+
+            match node.parent() {
+                Some(ref parent) if normalize => {
+                    let elt = parent.borrow_element();
+                    content
+                        .push_str(&chars.get_normalized_string(&node, elt.get_computed_values()));
+                }
+
+                _ => content.push_str(&chars.get_string()),
+            }
+        }
+
+        content
+    }
+}
+
 /// Helper trait for drawing recursively
 pub trait NodeDraw {
     fn draw(
@@ -313,7 +444,14 @@ impl NodeDraw for Node {
         clipping: bool,
     ) -> Result<BoundingBox, RenderingError> {
         match *self.borrow() {
-            NodeData::Element(ref e) => e.draw(self, acquired_nodes, cascaded, draw_ctx, clipping),
+            // Conditional processing attributes apply to every element, not just
+            // to the children of a <switch>; a false condition means that the
+            // element and its children are not rendered.
+            NodeData::Element(ref e)
+                if e.get_cond(draw_ctx.user_language(), acquired_nodes.extensions()) =>
+            {
+                e.draw(self, acquired_nodes, cascaded, draw_ctx, clipping)
+            }
             _ => Ok(draw_ctx.empty_bbox()),
         }
     }
@@ -340,3 +478,275 @@ impl NodeDraw for Node {
         Ok(bbox)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::document::Document;
+
+    fn element_ids(nodes: impl Iterator<Item = Node>) -> Vec<String> {
+        nodes
+            .filter(|n| n.is_element())
+            .map(|n| n.borrow_element().get_id().unwrap_or("").to_string())
+            .collect()
+    }
+
+    #[test]
+    fn descendants_are_in_document_order() {
+        let document = Document::load_from_bytes(
+            br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" id="root">
+  <g id="a">
+    <rect id="a1"/>
+    <g id="a2">
+      <rect id="a2a"/>
+    </g>
+    <rect id="a3"/>
+  </g>
+  <g id="b">
+    <text id="b1">Hello <tspan id="b1a">world</tspan></text>
+  </g>
+  <rect id="c"/>
+</svg>
+"#,
+        );
+
+        assert_eq!(
+            element_ids(document.root().descendants()),
+            vec!["root", "a", "a1", "a2", "a2a", "a3", "b", "b1", "b1a", "c"]
+        );
+
+        let a = document.lookup_internal_node("a").unwrap();
+        assert_eq!(
+            element_ids(a.descendants()),
+            vec!["a", "a1", "a2", "a2a", "a3"]
+        );
+
+        let leaf = document.lookup_internal_node("a2a").unwrap();
+        assert_eq!(element_ids(leaf.descendants()), vec!["a2a"]);
+
+        let text = document.lookup_internal_node("b1").unwrap();
+        assert_eq!(text.descendants().filter(|n| n.is_chars()).count(), 2);
+    }
+
+    #[test]
+    fn walks_ancestors_and_element_siblings() {
+        let document = Document::load_from_bytes(
+            br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" id="root">
+  <text id="text">one <tspan id="a">two</tspan> three <tspan id="b">four</tspan><tspan id="c">five <tspan id="c1">six</tspan></tspan> seven</text>
+</svg>
+"#,
+        );
+
+        let a = document.lookup_internal_node("a").unwrap();
+        let b = document.lookup_internal_node("b").unwrap();
+        let c = document.lookup_internal_node("c").unwrap();
+        let c1 = document.lookup_internal_node("c1").unwrap();
+
+        assert_eq!(element_ids(c1.ancestors()), vec!["c1", "c", "text", "root"]);
+
+        assert_eq!(
+            element_ids(a.preceding_element_siblings()),
+            Vec::<String>::new()
+        );
+        assert_eq!(element_ids(a.following_element_siblings()), vec!["b", "c"]);
+
+        assert_eq!(element_ids(b.preceding_element_siblings()), vec!["a"]);
+        assert_eq!(element_ids(b.following_element_siblings()), vec!["c"]);
+
+        assert_eq!(
+            element_ids(b.previous_element_sibling().into_iter()),
+            vec!["a"]
+        );
+        assert_eq!(element_ids(b.next_element_sibling().into_iter()), vec!["c"]);
+        assert!(a.previous_element_sibling().is_none());
+        assert!(c.next_element_sibling().is_none());
+
+        assert_eq!(element_ids(c.preceding_element_siblings()), vec!["b", "a"]);
+        assert_eq!(
+            element_ids(c.following_element_siblings()),
+            Vec::<String>::new()
+        );
+
+        // The iterators only return elements, even when starting from character data
+        let first_chars = document
+            .lookup_internal_node("text")
+            .unwrap()
+            .first_child()
+            .unwrap();
+        assert!(first_chars.is_chars());
+        assert_eq!(
+            element_ids(first_chars.following_element_siblings()),
+            vec!["a", "b", "c"]
+        );
+
+        assert_eq!(c1.preceding_element_siblings().count(), 0);
+    }
+
+    /// Checks the cascade of an instanced subtree against cloning and recomputing
+    /// the values of each node from its parent's, like a plain cascade does.
+    fn assert_same_as_naive_cascade(
+        node: &Node,
+        cascaded: &CascadedValues,
+        inherited: &ComputedValues,
+    ) {
+        let mut naive = inherited.clone();
+        node.borrow_element()
+            .get_specified_values()
+            .to_computed_values(&mut naive);
+
+        assert_eq!(*cascaded.get(), naive);
+
+        for child in node.children().filter(|c| c.is_element()) {
+            assert_same_as_naive_cascade(&child, &cascaded.new(&child), &naive);
+        }
+    }
+
+    fn instance(document: &Document, id: &str, context_id: &str) -> (Node, Rc<ComputedValues>) {
+        let node = document.lookup_internal_node(id).unwrap();
+        let context = document.lookup_internal_node(context_id).unwrap();
+        let values = context.borrow_element().get_shared_computed_values();
+
+        (node, values)
+    }
+
+    #[test]
+    fn instanced_cascade_matches_naive_cascade() {
+        let document = Document::load_from_bytes(
+            br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" id="root">
+  <defs id="defs">
+    <g id="sym">
+      <g stroke="blue">
+        <rect stroke-width="0.5em"/>
+        <circle style="font-size: 2em"/>
+      </g>
+      <text>Hello <tspan fill="inherit">world</tspan></text>
+    </g>
+  </defs>
+  <g id="translucent" opacity="0.5"/>
+  <g id="lime" fill="lime" font-size="20"/>
+</svg>
+"#,
+        );
+
+        // Same context as in the document: the values are shared, not recomputed
+        let (sym, values) = instance(&document, "sym", "defs");
+        let cascaded = CascadedValues::new_from_values(&sym, &values, None, None);
+        assert!(ptr::eq(
+            cascaded.get(),
+            sym.borrow_element().get_computed_values()
+        ));
+        assert_same_as_naive_cascade(&sym, &cascaded, &values);
+
+        // Only a property that does not inherit differs: the recomputed values are the
+        // same as the original ones, so those get shared
+        let (sym, values) = instance(&document, "sym", "translucent");
+        let cascaded = CascadedValues::new_from_values(&sym, &values, None, None);
+        assert!(ptr::eq(
+            cascaded.get(),
+            sym.borrow_element().get_computed_values()
+        ));
+        assert_same_as_naive_cascade(&sym, &cascaded, &values);
+
+        // Inherited properties differ: the whole subtree gets recomputed
+        let (sym, values) = instance(&document, "sym", "lime");
+        let cascaded = CascadedValues::new_from_values(&sym, &values, None, None);
+        assert!(!ptr::eq(
+            cascaded.get(),
+            sym.borrow_element().get_computed_values()
+        ));
+        assert_same_as_naive_cascade(&sym, &cascaded, &values);
+    }
+
+    fn text_element(document: &Document) -> Node {
+        document.lookup_internal_node("text").unwrap()
+    }
+
+    #[test]
+    fn gathers_text_content_in_document_order() {
+        let document = Document::load_from_bytes(
+            br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg">
+  <text id="text">Hello <tspan>beautiful <tspan>wide</tspan></tspan> <!-- comment -->world</text>
+</svg>
+"#,
+        );
+
+        assert_eq!(
+            text_element(&document).get_text_content(false),
+            "Hello beautiful wide world"
+        );
+    }
+
+    #[test]
+    fn normalizes_text_content_per_xml_space() {
+        let document = Document::load_from_bytes(
+            br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg">
+  <text id="text">
+    foo  <tspan xml:space="preserve">  bar  </tspan>
+  </text>
+</svg>
+"#,
+        );
+
+        let text = text_element(&document);
+
+        assert_eq!(text.get_text_content(false), "\n    foo    bar  \n  ");
+        assert_eq!(text.get_text_content(true), "foo   bar  ");
+    }
+
+    fn normalized_content(document: &Document, id: &str) -> String {
+        document
+            .lookup_internal_node(id)
+            .unwrap()
+            .get_text_content(true)
+    }
+
+    #[test]
+    fn normalizes_text_content_per_white_space() {
+        let document = Document::load_from_bytes(
+            br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg">
+  <text>
+    <tspan id="normal" style="white-space: normal">  a &#9; b  &#10; c </tspan>
+    <tspan id="nowrap" style="white-space: nowrap">  a &#9; b  &#10; c </tspan>
+    <tspan id="pre-line" style="white-space: pre-line">  a &#9; b  &#10; c </tspan>
+    <tspan id="pre" style="white-space: pre">  a &#9; b  &#10; c </tspan>
+    <tspan id="pre-wrap" white-space="pre-wrap">  a &#9; b  &#10; c </tspan>
+  </text>
+</svg>
+"#,
+        );
+
+        assert_eq!(normalized_content(&document, "normal"), "a b c");
+        assert_eq!(normalized_content(&document, "nowrap"), "a b c");
+        assert_eq!(normalized_content(&document, "pre-line"), "a b c");
+        assert_eq!(normalized_content(&document, "pre"), "  a   b    c ");
+        assert_eq!(normalized_content(&document, "pre-wrap"), "  a   b    c ");
+    }
+
+    #[test]
+    fn white_space_overrides_xml_space() {
+        let document = Document::load_from_bytes(
+            br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg">
+  <text>
+    <tspan id="attribute" white-space="normal" xml:space="preserve">  a  b  </tspan>
+    <tspan id="style" xml:space="preserve" style="white-space: normal">  a  b  </tspan>
+    <tspan id="preserve" style="white-space: pre" xml:space="default">  a  b  </tspan>
+    <tspan id="xml-space" xml:space="preserve">  a  b  </tspan>
+  </text>
+</svg>
+"#,
+        );
+
+        assert_eq!(normalized_content(&document, "attribute"), "a b");
+        assert_eq!(normalized_content(&document, "style"), "a b");
+        assert_eq!(normalized_content(&document, "preserve"), "  a  b  ");
+        assert_eq!(normalized_content(&document, "xml-space"), "  a  b  ");
+    }
+}
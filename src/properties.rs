@@ -14,19 +14,20 @@
 //! * Modify the rest of librsvg wherever the computed value of the property needs to be used.
 //! This is available in methods that take an argument of type [`ComputedValues`].
 
-use cssparser::{
-    self, BasicParseErrorKind, DeclarationListParser, ParseErrorKind, Parser, ParserInput, ToCss,
-};
+use cssparser::{self, BasicParseErrorKind, ParseErrorKind, Parser, ParserInput, ToCss};
 use markup5ever::{
     expanded_name, local_name, namespace_url, ns, ExpandedName, LocalName, QualName,
 };
 use std::collections::HashSet;
 
-use crate::css::{DeclParser, Declaration, Origin};
+use crate::css::{parse_declarations, Declaration, Origin};
+use crate::drawing_ctx::ViewParams;
 use crate::error::*;
+use crate::length::NormalizeParams;
 use crate::parsers::{Parse, ParseValue};
 use crate::property_macros::Property;
-use crate::transform::Transform;
+use crate::rect::Rect;
+use crate::transform::{Transform, TransformList};
 use crate::xml::Attributes;
 
 // Re-export the actual properties so they are easy to find from a single place `properties::*`.
@@ -91,10 +92,6 @@ impl PropertyId {
 pub struct SpecifiedValues {
     indices: [u8; PropertyId::UnsetProperty as usize],
     props: Vec<ParsedProperty>,
-
-    // TODO for madds: the following field will go away once the machinery for properties
-    // actually knows about the transform property.
-    transform: Transform,
 }
 
 impl Default for SpecifiedValues {
@@ -103,16 +100,72 @@ impl Default for SpecifiedValues {
             // this many elements, with the same value
             indices: [PropertyId::UnsetProperty.as_u8(); PropertyId::UnsetProperty as usize],
             props: Vec::new(),
-            transform: Default::default(),
         }
     }
 }
 
 impl ComputedValues {
-    // TODO for madds: this function will go away, to be replaced by the one generated
-    // automatically by the macros.
-    pub fn transform(&self) -> Transform {
-        self.transform
+    /// The element's transform, resolved for drawing.
+    ///
+    /// Percentages in the `transform` and `transform-origin` properties refer to the
+    /// reference box from `transform-box`.  For `fill-box` and `stroke-box` this is
+    /// based on the element's `object_box`, which only gets computed if needed.  If the
+    /// element doesn't have one, the viewport is used as for `view-box`, and the
+    /// fallback gets logged.
+    ///
+    /// FIXME: containers only know their bounding box after drawing their children, so
+    /// they don't provide an `object_box` yet.
+    pub fn transform<F>(&self, view_params: &ViewParams, object_box: F) -> Transform
+    where
+        F: FnOnce() -> Option<Rect>,
+    {
+        let TransformProperty(transform) = self.transform_property();
+
+        if transform.is_empty() {
+            return Transform::identity();
+        }
+
+        let params = NormalizeParams::new(self, view_params);
+
+        let view_box = *view_params.vbox;
+
+        let object_box_or_view_box = |object_box: Option<Rect>| {
+            object_box.unwrap_or_else(|| {
+                rsvg_log!(
+                    "element has no bounding box for transform-box: {:?}; using the viewport",
+                    self.transform_box()
+                );
+                view_box
+            })
+        };
+
+        let reference_box = match self.transform_box() {
+            TransformBox::ViewBox => view_box,
+
+            TransformBox::ContentBox | TransformBox::FillBox => {
+                object_box_or_view_box(object_box())
+            }
+
+            TransformBox::BorderBox | TransformBox::StrokeBox => {
+                // FIXME: this does not account for miters or line caps.
+                let half_width = self.stroke_width().0.to_user(&params) / 2.0;
+
+                object_box_or_view_box(object_box().map(|r| {
+                    Rect::new(
+                        r.x0 - half_width,
+                        r.y0 - half_width,
+                        r.x1 + half_width,
+                        r.y1 + half_width,
+                    )
+                }))
+            }
+        };
+
+        let (origin_x, origin_y) = self.transform_origin().to_user(&params, &reference_box);
+
+        Transform::new_translate(origin_x, origin_y)
+            .pre_transform(&transform.to_transform(&params, &reference_box))
+            .pre_translate(-origin_x, -origin_y)
     }
 
     pub fn is_overflow(&self) -> bool {
@@ -233,7 +286,7 @@ macro_rules! make_properties {
         }
 
         /// Holds the computed values for the CSS properties of an element.
-        #[derive(Debug, Default, Clone)]
+        #[derive(Debug, Default, Clone, PartialEq)]
         pub struct ComputedValues {
             $(
                 $long_field: $long_name,
@@ -246,9 +299,6 @@ macro_rules! make_properties {
             $(
                 $nonprop_field: $nonprop_name,
             )+
-
-            // TODO for madds: this will go away
-            transform: Transform,
         }
 
         impl ParsedProperty {
@@ -389,6 +439,7 @@ make_properties! {
         "color-interpolation-filters" => color_interpolation_filters : ColorInterpolationFilters,
         "direction"                   => direction                   : Direction,
         "display"                     => display                     : Display,
+        "dominant-baseline"           => dominant_baseline           : DominantBaseline,
         "enable-background"           => enable_background           : EnableBackground,
         "fill"                        => fill                        : Fill,
         "fill-opacity"                => fill_opacity                : FillOpacity,
@@ -398,10 +449,12 @@ make_properties! {
         "flood-opacity"               => flood_opacity               : FloodOpacity,
         "font-family"                 => font_family                 : FontFamily,
         "font-size"                   => font_size                   : FontSize,
+        "font-size-adjust"            => font_size_adjust            : FontSizeAdjust,
         "font-stretch"                => font_stretch                : FontStretch,
         "font-style"                  => font_style                  : FontStyle,
         "font-variant"                => font_variant                : FontVariant,
         "font-weight"                 => font_weight                 : FontWeight,
+        "glyph-orientation-horizontal" => glyph_orientation_horizontal : GlyphOrientationHorizontal,
         "letter-spacing"              => letter_spacing              : LetterSpacing,
         "lighting-color"              => lighting_color              : LightingColor,
         "marker-end"                  => marker_end                  : MarkerEnd,
@@ -424,15 +477,30 @@ make_properties! {
         "text-anchor"                 => text_anchor                 : TextAnchor,
         "text-decoration"             => text_decoration             : TextDecoration,
         "text-rendering"              => text_rendering              : TextRendering,
+        "transform"                   => transform_property          : TransformProperty,
         "unicode-bidi"                => unicode_bidi                : UnicodeBidi,
         "visibility"                  => visibility                  : Visibility,
+        "word-spacing"                => word_spacing                : WordSpacing,
         "writing-mode"                => writing_mode                : WritingMode,
     }
 
     longhands_not_supported_by_markup5ever: {
+        "font-variant-caps"           => font_variant_caps           : FontVariantCaps,
+        "font-variant-east-asian"     => font_variant_east_asian     : FontVariantEastAsian,
+        "font-variant-numeric"        => font_variant_numeric        : FontVariantNumeric,
         "line-height"                 => line_height                 : LineHeight,
         "mix-blend-mode"              => mix_blend_mode              : MixBlendMode,
+        "overflow-wrap"               => overflow_wrap               : OverflowWrap,
         "paint-order"                 => paint_order                 : PaintOrder,
+        "tab-size"                    => tab_size                    : TabSize,
+        "text-decoration-thickness"   => text_decoration_thickness   : TextDecorationThickness,
+        "text-transform"              => text_transform              : TextTransform,
+        "text-underline-offset"       => text_underline_offset       : TextUnderlineOffset,
+        "transform-box"               => transform_box               : TransformBox,
+        "transform-origin"            => transform_origin            : TransformOrigin,
+        "vector-effect"               => vector_effect               : VectorEffect,
+        "white-space"                 => white_space                 : WhiteSpace,
+        "word-break"                  => word_break                  : WordBreak,
     }
 
     // These are not properties, but presentation attributes.  However,
@@ -441,16 +509,11 @@ make_properties! {
     non_properties: {
         xml_lang: XmlLang,
         xml_space: XmlSpace,
+        root_font_size: RootFontSize,
     }
 }
 
 impl SpecifiedValues {
-    // TODO for madds: this function will go away; it's just a setter
-    // used by ElementInner::set_transform_attribute(), which in turn will go away.
-    pub fn set_transform(&mut self, transform: Transform) {
-        self.transform = transform;
-    }
-
     fn property_index(&self, id: PropertyId) -> Option<usize> {
         let v = self.indices[id.as_usize()];
 
@@ -591,6 +654,10 @@ impl SpecifiedValues {
 
         compute!(FontSize, font_size);
 
+        // Likewise, percentages in baseline-shift refer to the line-height.
+
+        compute!(LineHeight, line_height);
+
         // Then, do all the other properties.
 
         compute!(BaselineShift, baseline_shift);
@@ -600,6 +667,7 @@ impl SpecifiedValues {
         compute!(ColorInterpolationFilters, color_interpolation_filters);
         compute!(Direction, direction);
         compute!(Display, display);
+        compute!(DominantBaseline, dominant_baseline);
         compute!(EnableBackground, enable_background);
         compute!(Fill, fill);
         compute!(FillOpacity, fill_opacity);
@@ -608,10 +676,15 @@ impl SpecifiedValues {
         compute!(FloodColor, flood_color);
         compute!(FloodOpacity, flood_opacity);
         compute!(FontFamily, font_family);
+        compute!(FontSizeAdjust, font_size_adjust);
         compute!(FontStretch, font_stretch);
         compute!(FontStyle, font_style);
         compute!(FontVariant, font_variant);
+        compute!(FontVariantCaps, font_variant_caps);
+        compute!(FontVariantEastAsian, font_variant_east_asian);
+        compute!(FontVariantNumeric, font_variant_numeric);
         compute!(FontWeight, font_weight);
+        compute!(GlyphOrientationHorizontal, glyph_orientation_horizontal);
         compute!(LetterSpacing, letter_spacing);
         compute!(LightingColor, lighting_color);
         compute!(MarkerEnd, marker_end);
@@ -621,6 +694,7 @@ impl SpecifiedValues {
         compute!(MixBlendMode, mix_blend_mode);
         compute!(Opacity, opacity);
         compute!(Overflow, overflow);
+        compute!(OverflowWrap, overflow_wrap);
         compute!(PaintOrder, paint_order);
         compute!(ShapeRendering, shape_rendering);
         compute!(StopColor, stop_color);
@@ -633,17 +707,26 @@ impl SpecifiedValues {
         compute!(StrokeOpacity, stroke_opacity);
         compute!(StrokeMiterlimit, stroke_miterlimit);
         compute!(StrokeWidth, stroke_width);
+        compute!(TabSize, tab_size);
         compute!(TextAnchor, text_anchor);
         compute!(TextDecoration, text_decoration);
+        compute!(TextDecorationThickness, text_decoration_thickness);
         compute!(TextRendering, text_rendering);
+        compute!(TextTransform, text_transform);
+        compute!(TextUnderlineOffset, text_underline_offset);
+        compute!(TransformBox, transform_box);
+        compute!(TransformOrigin, transform_origin);
+        compute!(TransformProperty, transform_property);
         compute!(UnicodeBidi, unicode_bidi);
+        compute!(VectorEffect, vector_effect);
         compute!(Visibility, visibility);
+        compute!(WhiteSpace, white_space);
+        compute!(WordBreak, word_break);
+        compute!(WordSpacing, word_spacing);
         compute!(WritingMode, writing_mode);
         compute!(XmlLang, xml_lang);
         compute!(XmlSpace, xml_space);
-
-        // TODO for madds: this will go away
-        computed.transform = self.transform;
+        compute!(RootFontSize, root_font_size);
     }
 
     pub fn is_overflow(&self) -> bool {
@@ -757,13 +840,48 @@ impl SpecifiedValues {
                     )));
                 }
 
+                expanded_name!("", "lang") => {
+                    // SVG2 allows a plain lang attribute, but xml:lang takes
+                    // precedence, so don't replace it if it is already set.
+                    self.set_property(
+                        &ParsedProperty::XmlLang(SpecifiedValue::Specified(attr.parse(value)?)),
+                        false,
+                    );
+                }
+
                 expanded_name!(xml "space") => {
                     // xml:space is a non-presentation attribute and as such cannot have the
                     // "inherit" value.  So, we don't call parse_one_presentation_attribute()
                     // for it, but rather call its parser directly.
+                    let xml_space: XmlSpace = attr.parse(value)?;
+
                     self.set_parsed_property(&ParsedProperty::XmlSpace(SpecifiedValue::Specified(
-                        attr.parse(value)?,
+                        xml_space,
                     )));
+
+                    // SVG2 maps xml:space onto the white-space property, but a
+                    // white-space from an attribute or from CSS takes precedence, so
+                    // don't replace it if it is already set.
+                    self.set_property(
+                        &ParsedProperty::WhiteSpace(SpecifiedValue::Specified(WhiteSpace::from(
+                            xml_space,
+                        ))),
+                        false,
+                    );
+                }
+
+                expanded_name!("", "transform") => {
+                    // The transform attribute uses the SVG syntax for transforms, and
+                    // unlike other presentation attributes, an invalid value puts the
+                    // element in error.  A transform from CSS overrides it later during
+                    // the cascade.
+                    let transform: Transform = attr.parse(value)?;
+
+                    self.set_parsed_property(&ParsedProperty::TransformProperty(
+                        SpecifiedValue::Specified(TransformProperty(TransformList::from(
+                            transform,
+                        ))),
+                    ));
                 }
 
                 _ => self.parse_one_presentation_attribute(attr, value),
@@ -800,18 +918,9 @@ impl SpecifiedValues {
         origin: Origin,
         important_styles: &mut HashSet<QualName>,
     ) -> Result<(), ElementError> {
-        let mut input = ParserInput::new(declarations);
-        let mut parser = Parser::new(&mut input);
-
-        DeclarationListParser::new(&mut parser, DeclParser)
-            .filter_map(|r| match r {
-                Ok(decl) => Some(decl),
-                Err(e) => {
-                    rsvg_log!("Invalid declaration; ignoring: {:?}", e);
-                    None
-                }
-            })
-            .for_each(|decl| self.set_property_from_declaration(&decl, origin, important_styles));
+        for decl in parse_declarations(declarations) {
+            self.set_property_from_declaration(&decl, origin, important_styles);
+        }
 
         Ok(())
     }
@@ -36,19 +36,24 @@
 //! * An implementation of the [`Parse`] trait for the underlying type.
 use std::convert::TryInto;
 
-use cssparser::{Parser, Token};
+use cssparser::{match_ignore_ascii_case, Parser, Token};
 
+use crate::angle::Angle;
 use crate::dasharray::Dasharray;
 use crate::error::*;
 use crate::filter::FilterValueList;
-use crate::font_props::{Font, FontFamily, FontSize, FontWeight, LetterSpacing, LineHeight};
+use crate::font_props::{
+    Font, FontFamily, FontSize, FontSizeAdjust, FontStretch, FontStyle, FontWeight, LetterSpacing,
+    LineHeight, WordSpacing,
+};
 use crate::iri::Iri;
 use crate::length::*;
 use crate::paint_server::PaintServer;
-use crate::parsers::Parse;
+use crate::parsers::{NonNegative, Parse};
 use crate::properties::ComputedValues;
 use crate::property_macros::Property;
 use crate::rect::Rect;
+use crate::transform::TransformList;
 use crate::unit_interval::UnitInterval;
 
 make_property!(
@@ -68,10 +73,17 @@ make_property!(
 
             fn compute(&self, v: &ComputedValues) -> Self {
                 let font_size = v.font_size().value();
+                let line_height = v.line_height().compute(v).value();
                 let parent = v.baseline_shift();
 
                 match (self.0.unit, parent.0.unit) {
+                    // Percentages refer to the line-height, which gets computed before
+                    // this property.
                     (LengthUnit::Percent, _) => {
+                        BaselineShift(Length::<Both>::new(self.0.length * line_height.length + parent.0.length, line_height.unit))
+                    }
+
+                    (LengthUnit::Em, _) => {
                         BaselineShift(Length::<Both>::new(self.0.length * font_size.length + parent.0.length, font_size.unit))
                     }
 
@@ -104,9 +116,9 @@ make_property!(
                         Ok(parse_identifiers!(
                             parser,
                             "baseline" => BaselineShift(Length::<Both>::new(0.0, LengthUnit::Percent)),
-                            "sub" => BaselineShift(Length::<Both>::new(-0.2, LengthUnit::Percent)),
+                            "sub" => BaselineShift(Length::<Both>::new(-0.2, LengthUnit::Em)),
 
-                            "super" => BaselineShift(Length::<Both>::new(0.4, LengthUnit::Percent)),
+                            "super" => BaselineShift(Length::<Both>::new(0.4, LengthUnit::Em)),
                         )?)
                     })
             }
@@ -221,6 +233,28 @@ make_property!(
     "none" => None,
 );
 
+make_property!(
+    /// `dominant-baseline` property.
+    ///
+    /// https://www.w3.org/TR/SVG2/text.html#DominantBaselineProperty
+    ///
+    /// https://www.w3.org/TR/css-inline-3/#dominant-baseline-property
+    ///
+    /// The alphabetic, central, hanging, and ideographic baselines are supported, the
+    /// last two only for horizontal text.  With `auto`, horizontal text uses the
+    /// alphabetic baseline and vertical text the central one.
+    DominantBaseline,
+    default: Auto,
+    inherits_automatically: true,
+
+    identifiers:
+    "auto" => Auto,
+    "alphabetic" => Alphabetic,
+    "central" => Central,
+    "hanging" => Hanging,
+    "ideographic" => Ideographic,
+);
+
 /// `enable-background` property.
 ///
 /// https://www.w3.org/TR/SVG/filters.html#EnableBackgroundProperty
@@ -421,62 +455,308 @@ make_property!(
 );
 
 make_property!(
-    /// `font-stretch` property.
+    // docs are in font_props.rs
+    FontSizeAdjust,
+    default: FontSizeAdjust::None,
+    inherits_automatically: true,
+);
+
+make_property!(
+    // docs are in font_props.rs
+    FontStretch,
+    default: FontStretch::Normal,
+    inherits_automatically: true,
+);
+
+make_property!(
+    // docs are in font_props.rs
+    FontStyle,
+    default: FontStyle::Normal,
+    inherits_automatically: true,
+);
+
+make_property!(
+    /// `font-variant` property.
+    ///
+    /// https://www.w3.org/TR/SVG/text.html#FontVariantProperty
     ///
-    /// https://www.w3.org/TR/SVG/text.html#FontStretchProperty
+    /// https://www.w3.org/TR/css-fonts-3/#propdef-font-variant
     ///
-    /// https://www.w3.org/TR/css-fonts-3/#font-size-propstret
-    FontStretch,
+    /// Note that in CSS3, this is a lot more complex than CSS2.1 / SVG1.1.
+    FontVariant,
     default: Normal,
     inherits_automatically: true,
 
     identifiers:
     "normal" => Normal,
-    "wider" => Wider,
-    "narrower" => Narrower,
-    "ultra-condensed" => UltraCondensed,
-    "extra-condensed" => ExtraCondensed,
-    "condensed" => Condensed,
-    "semi-condensed" => SemiCondensed,
-    "semi-expanded" => SemiExpanded,
-    "expanded" => Expanded,
-    "extra-expanded" => ExtraExpanded,
-    "ultra-expanded" => UltraExpanded,
+    "small-caps" => SmallCaps,
 );
 
 make_property!(
-    /// `font-style` property.
+    /// `font-variant-caps` property.
     ///
-    /// https://www.w3.org/TR/SVG/text.html#FontStyleProperty
+    /// https://www.w3.org/TR/css-fonts-3/#font-variant-caps-prop
     ///
-    /// https://www.w3.org/TR/css-fonts-3/#font-size-propstret
-    FontStyle,
+    /// The `small-caps` value is the same as for the `font-variant` property; the other
+    /// values select more specific OpenType features.
+    FontVariantCaps,
     default: Normal,
     inherits_automatically: true,
 
     identifiers:
     "normal" => Normal,
-    "italic" => Italic,
-    "oblique" => Oblique,
+    "small-caps" => SmallCaps,
+    "all-small-caps" => AllSmallCaps,
+    "petite-caps" => PetiteCaps,
+    "all-petite-caps" => AllPetiteCaps,
+    "unicase" => Unicase,
+    "titling-caps" => TitlingCaps,
 );
 
+/// Glyph variants for `font-variant-east-asian`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum EastAsianVariant {
+    Jis78,
+    Jis83,
+    Jis90,
+    Jis04,
+    Simplified,
+    Traditional,
+}
+
+/// Glyph widths for `font-variant-east-asian`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum EastAsianWidth {
+    FullWidth,
+    ProportionalWidth,
+}
+
 make_property!(
-    /// `font-variant` property.
+    /// `font-variant-east-asian` property.
     ///
-    /// https://www.w3.org/TR/SVG/text.html#FontVariantProperty
+    /// https://www.w3.org/TR/css-fonts-3/#font-variant-east-asian-prop
+    #[derive(Eq, Hash)]
+    FontVariantEastAsian,
+    inherits_automatically: true,
+
+    fields: {
+        variant: Option<EastAsianVariant>, default: None,
+        width: Option<EastAsianWidth>, default: None,
+        ruby: bool, default: false,
+    }
+
+    parse_impl: {
+        impl Parse for FontVariantEastAsian {
+            fn parse<'i>(parser: &mut Parser<'i, '_>) -> Result<FontVariantEastAsian, ParseError<'i>> {
+                let mut v = FontVariantEastAsian::default();
+
+                if parser.try_parse(|p| p.expect_ident_matching("normal")).is_ok() {
+                    return Ok(v);
+                }
+
+                loop {
+                    let loc = parser.current_source_location();
+                    let token = parser.next()?;
+
+                    let ok = match token {
+                        Token::Ident(ref cow) => {
+                            let variant = match_ignore_ascii_case! { &**cow,
+                                "jis78" => Some(EastAsianVariant::Jis78),
+                                "jis83" => Some(EastAsianVariant::Jis83),
+                                "jis90" => Some(EastAsianVariant::Jis90),
+                                "jis04" => Some(EastAsianVariant::Jis04),
+                                "simplified" => Some(EastAsianVariant::Simplified),
+                                "traditional" => Some(EastAsianVariant::Traditional),
+                                _ => None,
+                            };
+
+                            let width = match_ignore_ascii_case! { &**cow,
+                                "full-width" => Some(EastAsianWidth::FullWidth),
+                                "proportional-width" => Some(EastAsianWidth::ProportionalWidth),
+                                _ => None,
+                            };
+
+                            if variant.is_some() && v.variant.is_none() {
+                                v.variant = variant;
+                                true
+                            } else if width.is_some() && v.width.is_none() {
+                                v.width = width;
+                                true
+                            } else if cow.eq_ignore_ascii_case("ruby") && !v.ruby {
+                                v.ruby = true;
+                                true
+                            } else {
+                                false
+                            }
+                        }
+
+                        _ => false,
+                    };
+
+                    if !ok {
+                        return Err(loc.new_basic_unexpected_token_error(token.clone()).into());
+                    }
+
+                    if parser.is_exhausted() {
+                        break;
+                    }
+                }
+
+                Ok(v)
+            }
+        }
+    }
+);
+
+#[cfg(test)]
+#[test]
+fn parses_font_variant_east_asian() {
+    assert_eq!(
+        FontVariantEastAsian::parse_str("normal").unwrap(),
+        FontVariantEastAsian::default()
+    );
+
+    assert_eq!(
+        FontVariantEastAsian::parse_str("jis04").unwrap(),
+        FontVariantEastAsian {
+            variant: Some(EastAsianVariant::Jis04),
+            ..Default::default()
+        }
+    );
+
+    assert_eq!(
+        FontVariantEastAsian::parse_str("ruby full-width traditional").unwrap(),
+        FontVariantEastAsian {
+            variant: Some(EastAsianVariant::Traditional),
+            width: Some(EastAsianWidth::FullWidth),
+            ruby: true,
+        }
+    );
+
+    assert!(FontVariantEastAsian::parse_str("").is_err());
+    assert!(FontVariantEastAsian::parse_str("normal ruby").is_err());
+    assert!(FontVariantEastAsian::parse_str("jis78 jis83").is_err());
+    assert!(FontVariantEastAsian::parse_str("full-width proportional-width").is_err());
+    assert!(FontVariantEastAsian::parse_str("ruby ruby").is_err());
+    assert!(FontVariantEastAsian::parse_str("foo").is_err());
+}
+
+make_property!(
+    /// `font-variant-numeric` property.
     ///
-    /// https://www.w3.org/TR/css-fonts-3/#propdef-font-variant
+    /// https://www.w3.org/TR/css-fonts-3/#font-variant-numeric-prop
     ///
-    /// Note that in CSS3, this is a lot more complex than CSS2.1 / SVG1.1.
-    FontVariant,
-    default: Normal,
+    /// Each field corresponds to one OpenType feature.
+    #[derive(Eq, Hash)]
+    FontVariantNumeric,
     inherits_automatically: true,
 
-    identifiers:
-    "normal" => Normal,
-    "small-caps" => SmallCaps,
+    fields: {
+        lining_nums: bool, default: false,
+        oldstyle_nums: bool, default: false,
+        proportional_nums: bool, default: false,
+        tabular_nums: bool, default: false,
+        diagonal_fractions: bool, default: false,
+        stacked_fractions: bool, default: false,
+        ordinal: bool, default: false,
+        slashed_zero: bool, default: false,
+    }
+
+    parse_impl: {
+        impl Parse for FontVariantNumeric {
+            fn parse<'i>(parser: &mut Parser<'i, '_>) -> Result<FontVariantNumeric, ParseError<'i>> {
+                let mut v = FontVariantNumeric::default();
+
+                if parser.try_parse(|p| p.expect_ident_matching("normal")).is_ok() {
+                    return Ok(v);
+                }
+
+                loop {
+                    let loc = parser.current_source_location();
+                    let token = parser.next()?;
+
+                    // Each value can only appear once, and values from the same group
+                    // are mutually exclusive.
+                    let (field, other) = match token {
+                        Token::Ident(ref cow) if cow.eq_ignore_ascii_case("lining-nums") => {
+                            (&mut v.lining_nums, v.oldstyle_nums)
+                        }
+                        Token::Ident(ref cow) if cow.eq_ignore_ascii_case("oldstyle-nums") => {
+                            (&mut v.oldstyle_nums, v.lining_nums)
+                        }
+                        Token::Ident(ref cow) if cow.eq_ignore_ascii_case("proportional-nums") => {
+                            (&mut v.proportional_nums, v.tabular_nums)
+                        }
+                        Token::Ident(ref cow) if cow.eq_ignore_ascii_case("tabular-nums") => {
+                            (&mut v.tabular_nums, v.proportional_nums)
+                        }
+                        Token::Ident(ref cow) if cow.eq_ignore_ascii_case("diagonal-fractions") => {
+                            (&mut v.diagonal_fractions, v.stacked_fractions)
+                        }
+                        Token::Ident(ref cow) if cow.eq_ignore_ascii_case("stacked-fractions") => {
+                            (&mut v.stacked_fractions, v.diagonal_fractions)
+                        }
+                        Token::Ident(ref cow) if cow.eq_ignore_ascii_case("ordinal") => {
+                            (&mut v.ordinal, false)
+                        }
+                        Token::Ident(ref cow) if cow.eq_ignore_ascii_case("slashed-zero") => {
+                            (&mut v.slashed_zero, false)
+                        }
+                        _ => return Err(loc.new_basic_unexpected_token_error(token.clone()).into()),
+                    };
+
+                    if *field || other {
+                        return Err(loc.new_basic_unexpected_token_error(token.clone()).into());
+                    }
+
+                    *field = true;
+
+                    if parser.is_exhausted() {
+                        break;
+                    }
+                }
+
+                Ok(v)
+            }
+        }
+    }
 );
 
+#[cfg(test)]
+#[test]
+fn parses_font_variant_numeric() {
+    assert_eq!(
+        FontVariantNumeric::parse_str("normal").unwrap(),
+        FontVariantNumeric::default()
+    );
+
+    assert_eq!(
+        FontVariantNumeric::parse_str("tabular-nums").unwrap(),
+        FontVariantNumeric {
+            tabular_nums: true,
+            ..Default::default()
+        }
+    );
+
+    assert_eq!(
+        FontVariantNumeric::parse_str("oldstyle-nums slashed-zero diagonal-fractions").unwrap(),
+        FontVariantNumeric {
+            oldstyle_nums: true,
+            slashed_zero: true,
+            diagonal_fractions: true,
+            ..Default::default()
+        }
+    );
+
+    assert!(FontVariantNumeric::parse_str("").is_err());
+    assert!(FontVariantNumeric::parse_str("normal tabular-nums").is_err());
+    assert!(FontVariantNumeric::parse_str("tabular-nums tabular-nums").is_err());
+    assert!(FontVariantNumeric::parse_str("lining-nums oldstyle-nums").is_err());
+    assert!(FontVariantNumeric::parse_str("proportional-nums tabular-nums").is_err());
+    assert!(FontVariantNumeric::parse_str("diagonal-fractions stacked-fractions").is_err());
+    assert!(FontVariantNumeric::parse_str("foo").is_err());
+}
+
 make_property!(
     // docs are in font_props.rs
     FontWeight,
@@ -494,6 +774,59 @@ make_property!(
     }
 );
 
+make_property!(
+    /// `glyph-orientation-horizontal` property.
+    ///
+    /// https://www.w3.org/TR/SVG11/text.html#GlyphOrientationHorizontalProperty
+    ///
+    /// This rotates each glyph of horizontal text by a multiple of 90 degrees.  SVG2
+    /// deprecates it in favor of `text-orientation`; librsvg supports it for compatibility
+    /// with older documents.
+    GlyphOrientationHorizontal,
+    default: Angle::new(0.0),
+    inherits_automatically: true,
+    newtype: Angle,
+    parse_impl: {
+        impl Parse for GlyphOrientationHorizontal {
+            fn parse<'i>(parser: &mut Parser<'i, '_>) -> Result<GlyphOrientationHorizontal, ParseError<'i>> {
+                let loc = parser.current_source_location();
+                let degrees = Angle::parse(parser)?.radians().to_degrees();
+                let quadrants = (degrees / 90.0).round();
+
+                if (degrees - quadrants * 90.0).abs() < 1e-6 {
+                    Ok(GlyphOrientationHorizontal(Angle::from_degrees(quadrants * 90.0)))
+                } else {
+                    Err(loc.new_custom_error(ValueErrorKind::value_error(
+                        "glyph-orientation-horizontal must be a multiple of 90 degrees",
+                    )))
+                }
+            }
+        }
+    },
+);
+
+impl GlyphOrientationHorizontal {
+    /// Returns the rotation in degrees, one of 0, 90, 180, or 270.
+    pub fn degrees(&self) -> f64 {
+        self.0.radians().to_degrees().round()
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn parses_glyph_orientation_horizontal() {
+    let degrees = |s| GlyphOrientationHorizontal::parse_str(s).unwrap().degrees();
+
+    assert_eq!(degrees("0"), 0.0);
+    assert_eq!(degrees("90"), 90.0);
+    assert_eq!(degrees("180deg"), 180.0);
+    assert_eq!(degrees("-90"), 270.0);
+    assert_eq!(degrees("200grad"), 180.0);
+
+    assert!(GlyphOrientationHorizontal::parse_str("45").is_err());
+    assert!(GlyphOrientationHorizontal::parse_str("auto").is_err());
+}
+
 make_property!(
     // docs are in font_props.rs
     LetterSpacing,
@@ -504,8 +837,8 @@ make_property!(
                 true
             }
 
-            fn compute(&self, _v: &ComputedValues) -> Self {
-                self.compute()
+            fn compute(&self, v: &ComputedValues) -> Self {
+                self.compute(v)
             }
         }
     }
@@ -515,7 +848,23 @@ make_property!(
     // docs are in font_props.rs
     LineHeight,
     default: LineHeight::Normal,
-    inherits_automatically: true,
+    property_impl: {
+        impl Property for LineHeight {
+            fn inherits_automatically() -> bool {
+                true
+            }
+
+            // A number is inherited as such, so that it scales with the font size of
+            // each descendant; only percentages become lengths.  Use `compute()` from
+            // font_props.rs to get the line height of a particular element.
+            fn compute(&self, v: &ComputedValues) -> Self {
+                match *self {
+                    LineHeight::Percentage(_) => self.compute(v),
+                    _ => self.clone(),
+                }
+            }
+        }
+    }
 );
 
 make_property!(
@@ -643,6 +992,23 @@ make_property!(
     "auto" => Auto,
 );
 
+make_property!(
+    /// `overflow-wrap` property.
+    ///
+    /// https://www.w3.org/TR/css-text-3/#overflow-wrap-property
+    ///
+    /// This only has an effect on text that librsvg wraps into lines, like in a
+    /// `<textArea>`.  Librsvg treats `anywhere` like `break-word`.
+    OverflowWrap,
+    default: Normal,
+    inherits_automatically: true,
+
+    identifiers:
+    "normal" => Normal,
+    "break-word" => BreakWord,
+    "anywhere" => Anywhere,
+);
+
 /// One of the three operations for the `paint-order` property; see [`PaintOrder`].
 #[repr(u8)]
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
@@ -855,6 +1221,52 @@ make_property!(
     newtype_parse: Length::<Both>,
 );
 
+/// `tab-size` property.
+///
+/// https://www.w3.org/TR/css-text-3/#tab-size-property
+///
+/// A number is a multiple of the width of a space in the current font.  Tabs are only
+/// kept in the text with `white-space: pre` or `pre-wrap`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TabSize {
+    Number(f64),
+    Length(ULength<Horizontal>),
+}
+
+make_property!(
+    TabSize,
+    default: TabSize::Number(8.0),
+    inherits_automatically: true,
+
+    parse_impl: {
+        impl Parse for TabSize {
+            fn parse<'i>(parser: &mut Parser<'i, '_>) -> Result<Self, crate::error::ParseError<'i>> {
+                parser
+                    .try_parse(|p| NonNegative::parse(p))
+                    .map(|NonNegative(n)| TabSize::Number(n))
+                    .or_else(|_: ParseError<'_>| {
+                        Ok(TabSize::Length(ULength::<Horizontal>::parse(parser)?))
+                    })
+            }
+        }
+    }
+);
+
+#[cfg(test)]
+#[test]
+fn parses_tab_size() {
+    assert_eq!(TabSize::parse_str("4").unwrap(), TabSize::Number(4.0));
+
+    assert_eq!(
+        TabSize::parse_str("2em").unwrap(),
+        TabSize::Length(ULength::<Horizontal>::new(2.0, LengthUnit::Em))
+    );
+
+    assert!(TabSize::parse_str("-4").is_err());
+    assert!(TabSize::parse_str("-4px").is_err());
+    assert!(TabSize::parse_str("wide").is_err());
+}
+
 make_property!(
     /// `text-anchor` property.
     ///
@@ -877,6 +1289,7 @@ make_property!(
     /// https://www.w3.org/TR/css-text-decor-3/#text-decoration-property
     ///
     /// Note that librsvg implements SVG1.1 semantics, where this is not a shorthand.
+    #[derive(Eq, Hash)]
     TextDecoration,
     inherits_automatically: false,
 
@@ -970,6 +1383,73 @@ fn parses_text_decoration() {
     assert!(TextDecoration::parse_str("airline").is_err())
 }
 
+/// `text-decoration-thickness` property.
+///
+/// https://www.w3.org/TR/css-text-decor-4/#text-decoration-width-property
+///
+/// Both `auto` and `from-font` use the thickness from the font's metrics.  Percentages
+/// refer to 1em in the current font.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextDecorationThickness {
+    Auto,
+    FromFont,
+    Length(Length<Both>),
+}
+
+make_property!(
+    TextDecorationThickness,
+    default: TextDecorationThickness::Auto,
+    inherits_automatically: true,
+
+    parse_impl: {
+        impl Parse for TextDecorationThickness {
+            fn parse<'i>(parser: &mut Parser<'i, '_>) -> Result<Self, crate::error::ParseError<'i>> {
+                parser
+                    .try_parse(|p| Length::<Both>::parse(p))
+                    .map(TextDecorationThickness::Length)
+                    .or_else(|_: ParseError<'_>| {
+                        Ok(parse_identifiers!(
+                            parser,
+                            "auto" => TextDecorationThickness::Auto,
+                            "from-font" => TextDecorationThickness::FromFont,
+                        )?)
+                    })
+            }
+        }
+    }
+);
+
+impl TextDecorationThickness {
+    /// Resolves the thickness to user-space units, or `None` to use the font's metrics.
+    pub fn to_user(&self, params: &NormalizeParams) -> Option<f64> {
+        match *self {
+            TextDecorationThickness::Auto | TextDecorationThickness::FromFont => None,
+            TextDecorationThickness::Length(l) => Some(em_relative(l).to_user(params)),
+        }
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn parses_text_decoration_thickness() {
+    assert_eq!(
+        TextDecorationThickness::parse_str("auto").unwrap(),
+        TextDecorationThickness::Auto
+    );
+
+    assert_eq!(
+        TextDecorationThickness::parse_str("from-font").unwrap(),
+        TextDecorationThickness::FromFont
+    );
+
+    assert_eq!(
+        TextDecorationThickness::parse_str("3px").unwrap(),
+        TextDecorationThickness::Length(Length::<Both>::new(3.0, LengthUnit::Px))
+    );
+
+    assert!(TextDecorationThickness::parse_str("thick").is_err());
+}
+
 make_property!(
     /// `text-rendering` property.
     ///
@@ -986,26 +1466,519 @@ make_property!(
 );
 
 make_property!(
-    /// `unicode-bidi` property.
+    /// `text-transform` property.
     ///
-    /// https://www.w3.org/TR/css-writing-modes-3/#unicode-bidi
-    UnicodeBidi,
-    default: Normal,
-    inherits_automatically: false,
+    /// https://www.w3.org/TR/css-text-3/#text-transform-property
+    TextTransform,
+    default: None,
+    inherits_automatically: true,
 
     identifiers:
-    "normal" => Normal,
-    "embed" => Embed,
-    "bidi-override" => Override,
+    "none" => None,
+    "capitalize" => Capitalize,
+    "uppercase" => Uppercase,
+    "lowercase" => Lowercase,
 );
 
-make_property!(
-    /// `visibility` property.
+impl TextTransform {
+    /// Changes the case of a string, which must already have its whitespace normalized.
     ///
-    /// https://www.w3.org/TR/CSS2/visufx.html#visibility
+    /// For `capitalize`, a word starts after whitespace, and its first letter gets
+    /// uppercased; punctuation before the letter does not count.
     ///
-    /// https://drafts.csswg.org/css2/#propdef-visibility
-    Visibility,
+    /// The casing follows the Unicode default rules, plus the language-specific rules
+    /// for the dotted and dotless i in Turkish and Azerbaijani, as given by `lang`.
+    pub fn apply(&self, s: &str, lang: &XmlLang) -> String {
+        self.apply_with_word_start(s, lang, true)
+    }
+
+    /// Like `apply()`, for a string that may continue a word from the text before it.
+    ///
+    /// The `at_word_start` is false if the preceding text ended in the middle of a
+    /// word, like the `bar` in `Foo<tspan>bar</tspan>`; `capitalize` does not uppercase
+    /// the first letter of `s` then.
+    pub fn apply_with_word_start(&self, s: &str, lang: &XmlLang, at_word_start: bool) -> String {
+        let turkic = uses_turkic_casing(lang);
+
+        match *self {
+            TextTransform::None => s.to_string(),
+            TextTransform::Uppercase => to_uppercase(s, turkic),
+
+            TextTransform::Lowercase => {
+                if turkic {
+                    s.replace('I', "\u{131}")
+                        .replace('\u{130}', "i")
+                        .to_lowercase()
+                } else {
+                    s.to_lowercase()
+                }
+            }
+
+            TextTransform::Capitalize => {
+                let mut result = String::with_capacity(s.len());
+                let mut at_word_start = at_word_start;
+
+                for c in s.chars() {
+                    if c.is_whitespace() {
+                        at_word_start = true;
+                        result.push(c);
+                    } else if at_word_start && c.is_alphanumeric() {
+                        at_word_start = false;
+                        result.push_str(&to_uppercase(c.encode_utf8(&mut [0; 4]), turkic));
+                    } else {
+                        result.push(c);
+                    }
+                }
+
+                result
+            }
+        }
+    }
+}
+
+/// Whether the language uses a dotted capital I for `i`, and a dotless `ı` for `I`.
+fn uses_turkic_casing(lang: &XmlLang) -> bool {
+    lang.0.as_ref().map_or(false, |tag| {
+        let primary = tag.split(|c| c == '-' || c == '_').next().unwrap_or("");
+        primary.eq_ignore_ascii_case("tr") || primary.eq_ignore_ascii_case("az")
+    })
+}
+
+fn to_uppercase(s: &str, turkic: bool) -> String {
+    if turkic {
+        s.replace('i', "\u{130}").to_uppercase()
+    } else {
+        s.to_uppercase()
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn text_transform_changes_case() {
+    let lang = XmlLang(None);
+
+    assert_eq!(
+        TextTransform::None.apply("hello World", &lang),
+        "hello World"
+    );
+    assert_eq!(
+        TextTransform::Uppercase.apply("hello World", &lang),
+        "HELLO WORLD"
+    );
+    assert_eq!(
+        TextTransform::Lowercase.apply("hello World", &lang),
+        "hello world"
+    );
+    assert_eq!(
+        TextTransform::Capitalize.apply("hello (world) don't 42nd", &lang),
+        "Hello (World) Don't 42nd"
+    );
+    assert_eq!(
+        TextTransform::Uppercase.apply("stra\u{df}e", &lang),
+        "STRASSE"
+    );
+}
+
+#[cfg(test)]
+#[test]
+fn capitalize_continues_the_word_of_the_preceding_text() {
+    let lang = XmlLang(None);
+
+    assert_eq!(
+        TextTransform::Capitalize.apply_with_word_start("bar baz", &lang, false),
+        "bar Baz"
+    );
+    assert_eq!(
+        TextTransform::Capitalize.apply_with_word_start("bar baz", &lang, true),
+        "Bar Baz"
+    );
+    assert_eq!(
+        TextTransform::Capitalize.apply_with_word_start(" bar", &lang, false),
+        " Bar"
+    );
+}
+
+#[cfg(test)]
+#[test]
+fn text_transform_follows_the_language() {
+    let default = XmlLang(None);
+    let turkish = XmlLang(Some("tr".to_string()));
+    let azerbaijani = XmlLang(Some("az-Latn-AZ".to_string()));
+
+    assert_eq!(
+        TextTransform::Uppercase.apply("istanbul", &default),
+        "ISTANBUL"
+    );
+    assert_eq!(
+        TextTransform::Uppercase.apply("istanbul", &turkish),
+        "\u{130}STANBUL"
+    );
+    assert_eq!(
+        TextTransform::Uppercase.apply("bir", &azerbaijani),
+        "B\u{130}R"
+    );
+
+    assert_eq!(
+        TextTransform::Lowercase.apply("ISPARTA", &default),
+        "isparta"
+    );
+    assert_eq!(
+        TextTransform::Lowercase.apply("ISPARTA", &turkish),
+        "\u{131}sparta"
+    );
+    assert_eq!(
+        TextTransform::Lowercase.apply("\u{130}ZM\u{130}R", &turkish),
+        "izmir"
+    );
+
+    assert_eq!(
+        TextTransform::Capitalize.apply("iyi gece", &default),
+        "Iyi Gece"
+    );
+    assert_eq!(
+        TextTransform::Capitalize.apply("iyi gece", &turkish),
+        "\u{130}yi Gece"
+    );
+
+    // German casing is the same as the default
+    let german = XmlLang(Some("de".to_string()));
+    assert_eq!(
+        TextTransform::Uppercase.apply("stra\u{df}e", &german),
+        "STRASSE"
+    );
+}
+
+/// `text-underline-offset` property.
+///
+/// https://www.w3.org/TR/css-text-decor-4/#underline-offset
+///
+/// A length is the distance from the alphabetic baseline to the top of the underline,
+/// positive downwards.  Percentages refer to 1em in the current font.  With `auto`, the
+/// position comes from the font's metrics.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextUnderlineOffset {
+    Auto,
+    Length(Length<Both>),
+}
+
+make_property!(
+    TextUnderlineOffset,
+    default: TextUnderlineOffset::Auto,
+    inherits_automatically: true,
+
+    parse_impl: {
+        impl Parse for TextUnderlineOffset {
+            fn parse<'i>(parser: &mut Parser<'i, '_>) -> Result<Self, crate::error::ParseError<'i>> {
+                parser
+                    .try_parse(|p| Length::<Both>::parse(p))
+                    .map(TextUnderlineOffset::Length)
+                    .or_else(|_: ParseError<'_>| {
+                        Ok(parse_identifiers!(
+                            parser,
+                            "auto" => TextUnderlineOffset::Auto,
+                        )?)
+                    })
+            }
+        }
+    }
+);
+
+impl TextUnderlineOffset {
+    /// Resolves the offset to user-space units, or `None` to use the font's metrics.
+    pub fn to_user(&self, params: &NormalizeParams) -> Option<f64> {
+        match *self {
+            TextUnderlineOffset::Auto => None,
+            TextUnderlineOffset::Length(l) => Some(em_relative(l).to_user(params)),
+        }
+    }
+}
+
+/// Turns a percentage into the equivalent `em` length, for properties whose percentages
+/// refer to the font size.
+fn em_relative(l: Length<Both>) -> Length<Both> {
+    if l.unit == LengthUnit::Percent {
+        Length::<Both>::new(l.length, LengthUnit::Em)
+    } else {
+        l
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn parses_text_underline_offset() {
+    assert_eq!(
+        TextUnderlineOffset::parse_str("auto").unwrap(),
+        TextUnderlineOffset::Auto
+    );
+
+    assert_eq!(
+        TextUnderlineOffset::parse_str("-2px").unwrap(),
+        TextUnderlineOffset::Length(Length::<Both>::new(-2.0, LengthUnit::Px))
+    );
+
+    assert!(TextUnderlineOffset::parse_str("under").is_err());
+}
+
+make_property!(
+    /// `transform-box` property.
+    ///
+    /// https://www.w3.org/TR/css-transforms-1/#transform-box
+    ///
+    /// This is the reference box for percentages in `transform` and `transform-origin`.
+    /// SVG elements don't have CSS layout boxes, so `content-box` works like `fill-box`,
+    /// and `border-box` like `stroke-box`.
+    TransformBox,
+    default: ViewBox,
+    inherits_automatically: false,
+
+    identifiers:
+    "content-box" => ContentBox,
+    "border-box" => BorderBox,
+    "fill-box" => FillBox,
+    "stroke-box" => StrokeBox,
+    "view-box" => ViewBox,
+);
+
+make_property!(
+    /// `transform-origin` property.
+    ///
+    /// https://www.w3.org/TR/css-transforms-1/#transform-origin-property
+    ///
+    /// The initial value in CSS is `50% 50%`, but the SVG user agent stylesheet sets it
+    /// to `0 0` for all SVG elements, so that is our default.  The origin is relative to
+    /// the reference box from `transform-box`.
+    TransformOrigin,
+    inherits_automatically: false,
+
+    fields: {
+        x: Length<Horizontal>, default: Length::<Horizontal>::new(0.0, LengthUnit::Px),
+        y: Length<Vertical>, default: Length::<Vertical>::new(0.0, LengthUnit::Px),
+    }
+
+    parse_impl: {
+        impl Parse for TransformOrigin {
+            fn parse<'i>(parser: &mut Parser<'i, '_>) -> Result<TransformOrigin, ParseError<'i>> {
+                let loc = parser.current_source_location();
+
+                let first = OriginComponent::parse(parser)?;
+                let second = parser.try_parse(OriginComponent::parse).ok();
+
+                let (x, y) = match (first, second) {
+                    (y @ OriginComponent::Top, None) | (y @ OriginComponent::Bottom, None) => {
+                        (OriginComponent::Center, y)
+                    }
+
+                    (x, None) => (x, OriginComponent::Center),
+
+                    (y @ OriginComponent::Top, Some(x))
+                    | (y @ OriginComponent::Bottom, Some(x))
+                    | (y, Some(x @ OriginComponent::Left))
+                    | (y, Some(x @ OriginComponent::Right)) => (x, y),
+
+                    (x, Some(y)) => (x, y),
+                };
+
+                match (x.horizontal(), y.vertical()) {
+                    (Some(x), Some(y)) => Ok(TransformOrigin { x, y }),
+                    _ => Err(loc.new_custom_error(ValueErrorKind::parse_error(
+                        "invalid transform-origin",
+                    ))),
+                }
+            }
+        }
+    },
+);
+
+impl TransformOrigin {
+    /// Computes the origin in user space.
+    ///
+    /// The origin's coordinates are relative to the top-left corner of the
+    /// `reference_box`, and percentages refer to its size.
+    pub fn to_user(&self, params: &NormalizeParams, reference_box: &Rect) -> (f64, f64) {
+        let params = params.with_reference_box(reference_box);
+
+        (
+            reference_box.x0 + self.x.to_user(&params),
+            reference_box.y0 + self.y.to_user(&params),
+        )
+    }
+}
+
+/// One of the values in `transform-origin`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum OriginComponent {
+    Left,
+    Center,
+    Right,
+    Top,
+    Bottom,
+    Length(Length<Both>),
+}
+
+impl Parse for OriginComponent {
+    fn parse<'i>(parser: &mut Parser<'i, '_>) -> Result<OriginComponent, ParseError<'i>> {
+        if let Ok(l) = parser.try_parse(Length::<Both>::parse) {
+            return Ok(OriginComponent::Length(l));
+        }
+
+        Ok(parse_identifiers!(
+            parser,
+            "left" => OriginComponent::Left,
+            "center" => OriginComponent::Center,
+            "right" => OriginComponent::Right,
+            "top" => OriginComponent::Top,
+            "bottom" => OriginComponent::Bottom,
+        )?)
+    }
+}
+
+impl OriginComponent {
+    fn horizontal(self) -> Option<Length<Horizontal>> {
+        match self {
+            OriginComponent::Left => Some(Length::new(0.0, LengthUnit::Percent)),
+            OriginComponent::Center => Some(Length::new(0.5, LengthUnit::Percent)),
+            OriginComponent::Right => Some(Length::new(1.0, LengthUnit::Percent)),
+            OriginComponent::Length(l) => Some(Length::new(l.length, l.unit)),
+            OriginComponent::Top | OriginComponent::Bottom => None,
+        }
+    }
+
+    fn vertical(self) -> Option<Length<Vertical>> {
+        match self {
+            OriginComponent::Top => Some(Length::new(0.0, LengthUnit::Percent)),
+            OriginComponent::Center => Some(Length::new(0.5, LengthUnit::Percent)),
+            OriginComponent::Bottom => Some(Length::new(1.0, LengthUnit::Percent)),
+            OriginComponent::Length(l) => Some(Length::new(l.length, l.unit)),
+            OriginComponent::Left | OriginComponent::Right => None,
+        }
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn parses_transform_origin() {
+    let origin = |x, y| TransformOrigin {
+        x: Length::<Horizontal>::new(x, LengthUnit::Percent),
+        y: Length::<Vertical>::new(y, LengthUnit::Percent),
+    };
+
+    assert_eq!(
+        TransformOrigin::parse_str("center").unwrap(),
+        origin(0.5, 0.5)
+    );
+    assert_eq!(TransformOrigin::parse_str("top").unwrap(), origin(0.5, 0.0));
+    assert_eq!(
+        TransformOrigin::parse_str("right").unwrap(),
+        origin(1.0, 0.5)
+    );
+    assert_eq!(
+        TransformOrigin::parse_str("bottom left").unwrap(),
+        origin(0.0, 1.0)
+    );
+    assert_eq!(
+        TransformOrigin::parse_str("left bottom").unwrap(),
+        origin(0.0, 1.0)
+    );
+    assert_eq!(
+        TransformOrigin::parse_str("25% 75%").unwrap(),
+        origin(0.25, 0.75)
+    );
+
+    assert_eq!(
+        TransformOrigin::parse_str("10px center").unwrap(),
+        TransformOrigin {
+            x: Length::<Horizontal>::new(10.0, LengthUnit::Px),
+            y: Length::<Vertical>::new(0.5, LengthUnit::Percent),
+        }
+    );
+
+    assert!(TransformOrigin::parse_str("left right").is_err());
+    assert!(TransformOrigin::parse_str("top bottom").is_err());
+    assert!(TransformOrigin::parse_str("10px top 5px").is_err());
+}
+
+make_property!(
+    /// `transform` property.
+    ///
+    /// https://www.w3.org/TR/css-transforms-1/#transform-property
+    ///
+    /// In SVG2 the `transform` attribute is a presentation attribute for this property,
+    /// so a `transform` from CSS overrides the value of the attribute.
+    ///
+    /// Percentages in `translate()` refer to the size of the element's reference box.
+    TransformProperty,
+    default: TransformList::default(),
+    inherits_automatically: false,
+    newtype: TransformList,
+    parse_impl: {
+        impl Parse for TransformProperty {
+            fn parse<'i>(
+                parser: &mut Parser<'i, '_>,
+            ) -> Result<TransformProperty, ParseError<'i>> {
+                if parser.try_parse(|p| p.expect_ident_matching("none")).is_ok() {
+                    Ok(TransformProperty(TransformList::default()))
+                } else {
+                    Ok(TransformProperty(TransformList::parse(parser)?))
+                }
+            }
+        }
+    },
+);
+
+make_property!(
+    /// `unicode-bidi` property.
+    ///
+    /// https://www.w3.org/TR/css-writing-modes-3/#unicode-bidi
+    UnicodeBidi,
+    default: Normal,
+    inherits_automatically: false,
+
+    identifiers:
+    "normal" => Normal,
+    "embed" => Embed,
+    "bidi-override" => Override,
+);
+
+make_property!(
+    /// `vector-effect` property.
+    ///
+    /// https://www.w3.org/TR/SVG2/coords.html#VectorEffects
+    VectorEffect,
+    default: None,
+    inherits_automatically: false,
+
+    identifiers:
+    "none" => None,
+    "non-scaling-stroke" => NonScalingStroke,
+    "non-scaling-size" => NonScalingSize,
+    "non-rotation" => NonRotation,
+    "fixed-position" => FixedPosition,
+);
+
+#[cfg(test)]
+#[test]
+fn parses_vector_effect() {
+    assert_eq!(VectorEffect::parse_str("none").unwrap(), VectorEffect::None);
+    assert_eq!(
+        VectorEffect::parse_str("non-scaling-stroke").unwrap(),
+        VectorEffect::NonScalingStroke
+    );
+    assert_eq!(
+        VectorEffect::parse_str("fixed-position").unwrap(),
+        VectorEffect::FixedPosition
+    );
+
+    assert!(VectorEffect::parse_str("non-scaling-stroke none").is_err());
+    assert!(VectorEffect::parse_str("scaling-stroke").is_err());
+}
+
+make_property!(
+    /// `visibility` property.
+    ///
+    /// https://www.w3.org/TR/CSS2/visufx.html#visibility
+    ///
+    /// https://drafts.csswg.org/css2/#propdef-visibility
+    Visibility,
     default: Visible,
     inherits_automatically: true,
 
@@ -1015,6 +1988,89 @@ make_property!(
     "collapse" => Collapse,
 );
 
+make_property!(
+    /// `white-space` property.
+    ///
+    /// https://www.w3.org/TR/SVG2/text.html#TextWhiteSpace
+    ///
+    /// https://www.w3.org/TR/css-text-3/#white-space-property
+    ///
+    /// SVG2 replaces `xml:space` with this property.  The `xml:space` attribute still
+    /// works; it gets mapped to `normal` or `pre` when the element does not specify
+    /// `white-space` by itself.
+    WhiteSpace,
+    default: Normal,
+    inherits_automatically: true,
+
+    identifiers:
+    "normal" => Normal,
+    "pre" => Pre,
+    "nowrap" => NoWrap,
+    "pre-wrap" => PreWrap,
+    "pre-line" => PreLine,
+);
+
+impl WhiteSpace {
+    /// Whether spaces and tabs are kept as they are, like with `xml:space="preserve"`.
+    ///
+    /// Librsvg does not break lines yet, so `pre-line` collapses everything like
+    /// `normal`.
+    pub fn preserves_spaces(&self) -> bool {
+        matches!(*self, WhiteSpace::Pre | WhiteSpace::PreWrap)
+    }
+
+    /// Whether text may wrap at soft wrap opportunities, for text with an `inline-size`.
+    pub fn wraps(&self) -> bool {
+        matches!(
+            *self,
+            WhiteSpace::Normal | WhiteSpace::PreWrap | WhiteSpace::PreLine
+        )
+    }
+}
+
+impl From<XmlSpace> for WhiteSpace {
+    fn from(s: XmlSpace) -> WhiteSpace {
+        match s {
+            XmlSpace::Default => WhiteSpace::Normal,
+            XmlSpace::Preserve => WhiteSpace::Pre,
+        }
+    }
+}
+
+make_property!(
+    /// `word-break` property.
+    ///
+    /// https://www.w3.org/TR/css-text-3/#word-break-property
+    ///
+    /// This only has an effect on text that librsvg wraps into lines, like in a
+    /// `<textArea>`.  Librsvg treats `keep-all` like `normal`.
+    WordBreak,
+    default: Normal,
+    inherits_automatically: true,
+
+    identifiers:
+    "normal" => Normal,
+    "break-all" => BreakAll,
+    "keep-all" => KeepAll,
+);
+
+make_property!(
+    // docs are in font_props.rs
+    WordSpacing,
+    default: WordSpacing::Normal,
+    property_impl: {
+        impl Property for WordSpacing {
+            fn inherits_automatically() -> bool {
+                true
+            }
+
+            fn compute(&self, v: &ComputedValues) -> Self {
+                self.compute(v)
+            }
+        }
+    }
+);
+
 make_property!(
     /// `writing-mode` property.
     ///
@@ -1052,6 +2108,7 @@ make_property!(
     /// supposed to apply to an element and all its children.  This more or less matches
     /// CSS property inheritance, so librsvg reuses the machinery for property inheritance
     /// to propagate down the value of the `xml:lang` attribute to an element's children.
+    #[derive(Eq, Hash)]
     XmlLang,
     default: None,
     inherits_automatically: true,
@@ -1067,6 +2124,45 @@ make_property!(
     },
 );
 
+/// Font size of the root element, which `rem` units refer to.
+///
+/// This is not a property either.  The cascade specifies `OwnFontSize` on the root
+/// element, where it computes to the root's font size; all the other elements inherit
+/// that value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RootFontSize {
+    OwnFontSize,
+    Value(Length<Both>),
+}
+
+impl RootFontSize {
+    pub fn value(&self) -> Length<Both> {
+        match self {
+            RootFontSize::Value(s) => *s,
+            _ => unreachable!(),
+        }
+    }
+}
+
+make_property!(
+    RootFontSize,
+    default: RootFontSize::Value(Length::<Both>::new(12.0, LengthUnit::Px)),
+    property_impl: {
+        impl Property for RootFontSize {
+            fn inherits_automatically() -> bool {
+                true
+            }
+
+            fn compute(&self, v: &ComputedValues) -> Self {
+                match *self {
+                    RootFontSize::OwnFontSize => RootFontSize::Value(v.font_size().value()),
+                    RootFontSize::Value(s) => RootFontSize::Value(s),
+                }
+            }
+        }
+    }
+);
+
 #[cfg(test)]
 #[test]
 fn parses_xml_lang() {
@@ -115,7 +115,7 @@ macro_rules! make_property {
      $($str_prop: expr => $variant: ident,)+
     ) => {
         $(#[$attr])*
-        #[derive(Debug, Copy, Clone, PartialEq)]
+        #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
         #[repr(C)]
         pub enum $name {
             $($variant),+
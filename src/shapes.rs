@@ -128,9 +128,10 @@ macro_rules! impl_draw {
                     marker_end,
                 };
 
+                let transform = values.transform(&view_params, || shape.path.extents());
+
                 let elt = node.borrow_element();
-                let stacking_ctx =
-                    StackingContext::new(acquired_nodes, &elt, values.transform(), values);
+                let stacking_ctx = StackingContext::new(acquired_nodes, &elt, transform, values);
 
                 draw_ctx.draw_shape(
                     &view_params,
@@ -416,6 +417,28 @@ pub struct Rect {
 
 impl_draw!(Rect);
 
+impl Rect {
+    /// Returns the `(x, y, width, height)` of the rectangle in user space.
+    ///
+    /// This is used by elements like `<flowRegion>` that use a rectangle as an area
+    /// instead of drawing it.
+    pub fn area(&self, params: &NormalizeParams) -> (f64, f64, f64, f64) {
+        let x = self.x.to_user(params);
+        let y = self.y.to_user(params);
+
+        let w = match self.width {
+            LengthOrAuto::Length(l) => l.to_user(params),
+            LengthOrAuto::Auto => 0.0,
+        };
+        let h = match self.height {
+            LengthOrAuto::Length(l) => l.to_user(params),
+            LengthOrAuto::Auto => 0.0,
+        };
+
+        (x, y, w, h)
+    }
+}
+
 impl SetAttributes for Rect {
     fn set_attributes(&mut self, attrs: &Attributes) -> ElementResult {
         for (attr, value) in attrs.iter() {
@@ -437,17 +460,7 @@ impl SetAttributes for Rect {
 impl BasicShape for Rect {
     #[allow(clippy::many_single_char_names)]
     fn make_shape(&self, params: &NormalizeParams) -> ShapeDef {
-        let x = self.x.to_user(params);
-        let y = self.y.to_user(params);
-
-        let w = match self.width {
-            LengthOrAuto::Length(l) => l.to_user(&params),
-            LengthOrAuto::Auto => 0.0,
-        };
-        let h = match self.height {
-            LengthOrAuto::Length(l) => l.to_user(&params),
-            LengthOrAuto::Auto => 0.0,
-        };
+        let (x, y, w, h) = self.area(params);
 
         let specified_rx = self.rx.map(|l| l.to_user(params));
         let specified_ry = self.ry.map(|l| l.to_user(params));
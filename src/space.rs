@@ -2,14 +2,19 @@
 
 use itertools::Itertools;
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub struct NormalizeDefault {
     pub has_element_before: bool,
     pub has_element_after: bool,
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum XmlSpaceNormalize {
     Default(NormalizeDefault),
     Preserve,
+
+    /// Like `Preserve`, but keeps tab characters, for CSS `white-space: pre`.
+    PreserveTabs,
 }
 
 /// Implements `xml:space` handling per the SVG spec
@@ -20,7 +25,8 @@ pub enum XmlSpaceNormalize {
 pub fn xml_space_normalize(mode: XmlSpaceNormalize, s: &str) -> String {
     match mode {
         XmlSpaceNormalize::Default(d) => normalize_default(d, s),
-        XmlSpaceNormalize::Preserve => normalize_preserve(s),
+        XmlSpaceNormalize::Preserve => normalize_preserve(s, false),
+        XmlSpaceNormalize::PreserveTabs => normalize_preserve(s, true),
     }
 }
 
@@ -64,10 +70,14 @@ fn normalize_default(elements: NormalizeDefault, mut s: &str) -> String {
 // xml:space="preserve", the string "a   b" (three spaces between "a"
 // and "b") will produce a larger separation between "a" and "b" than
 // "a b" (one space between "a" and "b").
-fn normalize_preserve(s: &str) -> String {
+//
+// CSS keeps tabs with `white-space: pre` instead, so they can be rendered per the
+// `tab-size` property; `keep_tabs` selects this.
+fn normalize_preserve(s: &str, keep_tabs: bool) -> String {
     s.chars()
         .map(|ch| match ch {
-            '\n' | '\t' => ' ',
+            '\n' => ' ',
+            '\t' if !keep_tabs => ' ',
 
             c => c,
         })
@@ -181,4 +191,12 @@ mod tests {
             "       WS    example      duplicate letters       "
         );
     }
+
+    #[test]
+    fn preserve_tabs() {
+        assert_eq!(
+            xml_space_normalize(XmlSpaceNormalize::PreserveTabs, "\n  a\tb \t\n"),
+            "   a\tb \t "
+        );
+    }
 }
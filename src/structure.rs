@@ -1,7 +1,9 @@
 //! Structural elements in SVG: the `g`, `switch`, `svg`, `use`, `symbol`, `clip_path`, `mask`, `link` elements.
 
 use markup5ever::{expanded_name, local_name, namespace_url, ns};
+use std::cmp::Reverse;
 
+use crate::accept_language::UserLanguage;
 use crate::aspect_ratio::*;
 use crate::bbox::BoundingBox;
 use crate::coord_units::CoordUnits;
@@ -35,7 +37,8 @@ impl Draw for Group {
         let values = cascaded.get();
 
         let elt = node.borrow_element();
-        let stacking_ctx = StackingContext::new(acquired_nodes, &elt, values.transform(), values);
+        let transform = values.transform(&draw_ctx.get_view_params(), || None);
+        let stacking_ctx = StackingContext::new(acquired_nodes, &elt, transform, values);
 
         draw_ctx.with_discrete_layer(
             &stacking_ctx,
@@ -59,6 +62,34 @@ impl SetAttributes for NonRendering {}
 
 impl Draw for NonRendering {}
 
+/// The `<title>` element
+///
+/// It does not render anything; its text content is an accessible name for its
+/// parent element.
+#[derive(Default)]
+pub struct Title;
+
+impl SetAttributes for Title {}
+
+impl Draw for Title {}
+
+/// The `<desc>` element
+///
+/// It does not render anything; its text content is an accessible description for
+/// its parent element.
+#[derive(Default)]
+pub struct Desc;
+
+impl SetAttributes for Desc {}
+
+impl Draw for Desc {}
+
+/// The `switch` element.
+///
+/// Only its first direct child whose conditional processing attributes evaluate to
+/// true gets rendered, even if later children would match as well.  Optionally, the
+/// child whose `systemLanguage` matches best gets rendered instead; see
+/// `Loader::with_best_language_match`.
 #[derive(Default)]
 pub struct Switch();
 
@@ -76,7 +107,8 @@ impl Draw for Switch {
         let values = cascaded.get();
 
         let elt = node.borrow_element();
-        let stacking_ctx = StackingContext::new(acquired_nodes, &elt, values.transform(), values);
+        let transform = values.transform(&draw_ctx.get_view_params(), || None);
+        let stacking_ctx = StackingContext::new(acquired_nodes, &elt, transform, values);
 
         draw_ctx.with_discrete_layer(
             &stacking_ctx,
@@ -85,10 +117,20 @@ impl Draw for Switch {
             clipping,
             None,
             &mut |an, dc| {
-                if let Some(child) = node.children().filter(|c| c.is_element()).find(|c| {
-                    let elt = c.borrow_element();
-                    elt.get_cond(&dc.user_language()) && !elt.is_in_error()
-                }) {
+                let child = {
+                    let mut candidates = node.children().filter(|c| c.is_element()).filter(|c| {
+                        let elt = c.borrow_element();
+                        elt.get_cond(&dc.user_language(), an.extensions()) && !elt.is_in_error()
+                    });
+
+                    if an.best_language_match() {
+                        best_language_match(candidates, dc.user_language())
+                    } else {
+                        candidates.next()
+                    }
+                };
+
+                if let Some(child) = child {
                     child.draw(an, &CascadedValues::new(cascaded, &child), dc, clipping)
                 } else {
                     Ok(dc.empty_bbox())
@@ -98,6 +140,27 @@ impl Draw for Switch {
     }
 }
 
+/// Picks the first of the `candidates` whose `systemLanguage` matches best.
+fn best_language_match(
+    candidates: impl Iterator<Item = Node>,
+    user_language: &UserLanguage,
+) -> Option<Node> {
+    let mut best: Option<(Option<(f32, Reverse<usize>)>, Node)> = None;
+
+    for child in candidates {
+        let score = child
+            .borrow_element()
+            .get_system_language()
+            .and_then(|s| s.match_score(user_language));
+
+        if best.as_ref().map(|(s, _)| score > *s).unwrap_or(true) {
+            best = Some((score, child));
+        }
+    }
+
+    best.map(|(_, child)| child)
+}
+
 /// Intrinsic dimensions of an SVG document fragment: its `width`, `height`, `viewBox` attributes.
 ///
 /// Note that either of those attributes can be omitted, so they are all `Option<T>`.
@@ -264,7 +327,8 @@ impl Draw for Svg {
         let values = cascaded.get();
 
         let elt = node.borrow_element();
-        let stacking_ctx = StackingContext::new(acquired_nodes, &elt, values.transform(), values);
+        let transform = values.transform(&draw_ctx.get_view_params(), || None);
+        let stacking_ctx = StackingContext::new(acquired_nodes, &elt, transform, values);
 
         draw_ctx.with_discrete_layer(
             &stacking_ctx,
@@ -540,7 +604,8 @@ impl Draw for Link {
         let values = cascaded.get();
 
         let elt = node.borrow_element();
-        let stacking_ctx = StackingContext::new(acquired_nodes, &elt, values.transform(), values);
+        let transform = values.transform(&draw_ctx.get_view_params(), || None);
+        let stacking_ctx = StackingContext::new(acquired_nodes, &elt, transform, values);
 
         draw_ctx.with_discrete_layer(
             &stacking_ctx,
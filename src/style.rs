@@ -2,6 +2,7 @@
 
 use markup5ever::{expanded_name, local_name, namespace_url, ns};
 
+use crate::css::media_list_matches;
 use crate::element::{Draw, ElementResult, SetAttributes};
 use crate::error::*;
 use crate::xml::Attributes;
@@ -44,12 +45,23 @@ impl StyleType {
 #[derive(Default)]
 pub struct Style {
     type_: StyleType,
+    media: Option<String>,
 }
 
 impl Style {
     pub fn style_type(&self) -> StyleType {
         self.type_
     }
+
+    /// Whether the `media` attribute lets the stylesheet apply to the rendering.
+    ///
+    /// See `media_list_matches()` for the media queries that are supported.
+    pub fn applies_to_media(&self) -> bool {
+        self.media
+            .as_deref()
+            .map(media_list_matches)
+            .unwrap_or(true)
+    }
 }
 
 impl SetAttributes for Style {
@@ -57,6 +69,8 @@ impl SetAttributes for Style {
         for (attr, value) in attrs.iter() {
             if attr.expanded() == expanded_name!("", "type") {
                 self.type_ = StyleType::parse(value).attribute(attr)?;
+            } else if attr.expanded() == expanded_name!("", "media") {
+                self.media = Some(value.to_string());
             }
         }
 
@@ -1,23 +1,39 @@
-//! Text elements: `text`, `tspan`, `tref`.
+//! Text elements: `text`, `tspan`, `tref`, `textArea`, and SVG 1.2 flowed text.
 
+use cssparser::Parser;
 use markup5ever::{expanded_name, local_name, namespace_url, ns};
-use std::cell::RefCell;
+use std::cell::{Ref, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
 use std::rc::Rc;
 
 use crate::bbox::BoundingBox;
+use crate::css::Origin;
 use crate::document::{AcquiredNodes, NodeId};
-use crate::drawing_ctx::DrawingCtx;
+use crate::dpi::Dpi;
+use crate::drawing_ctx::{
+    create_pango_context, text_layout_transform, text_span_layout_transform, DrawingCtx, ViewParams,
+};
 use crate::element::{Draw, Element, ElementResult, SetAttributes};
 use crate::error::*;
-use crate::layout::{self, FontProperties, StackingContext, Stroke};
+use crate::float_eq_cairo::ApproxEqCairo;
+use crate::harfbuzz;
+use crate::layout::{self, FontProperties, StackingContext, Stroke, TabStops};
 use crate::length::*;
-use crate::node::{CascadedValues, Node, NodeBorrow};
-use crate::parsers::ParseValue;
+use crate::limits;
+use crate::node::{CascadedValues, Node, NodeBorrow, NodeSiblings, NodeTextContent};
+use crate::parsers::{optional_comma, Parse, ParseValue};
 use crate::properties::{
-    ComputedValues, Direction, FontStretch, FontStyle, FontVariant, FontWeight, TextAnchor,
-    UnicodeBidi, WritingMode, XmlLang, XmlSpace,
+    ComputedValues, Direction, DominantBaseline, EastAsianVariant, EastAsianWidth, Filter,
+    FontSizeAdjust, FontStretch, FontStyle, FontVariant, FontVariantCaps, FontVariantEastAsian,
+    FontVariantNumeric, FontWeight, MixBlendMode, Opacity, OverflowWrap, SpecifiedValues,
+    TextAnchor, UnicodeBidi, WordBreak, WritingMode, XmlLang, XmlSpace,
 };
+use crate::rect::Rect;
 use crate::space::{xml_space_normalize, NormalizeDefault, XmlSpaceNormalize};
+use crate::text_options::{unavailable_families, FontSubstitution, MissingGlyphs, UsedFont};
+use crate::transform::Transform;
+use crate::unit_interval::UnitInterval;
 use crate::xml::Attributes;
 
 /// An absolutely-positioned array of `Span`s
@@ -45,6 +61,10 @@ struct MeasuredChunk {
     x: Option<f64>,
     y: Option<f64>,
     advance: (f64, f64),
+
+    /// The part of `advance` that comes from white space at the end of the chunk.
+    trailing_space: (f64, f64),
+
     spans: Vec<MeasuredSpan>,
 }
 
@@ -60,8 +80,34 @@ struct Span {
     dx: f64,
     dy: f64,
     _depth: usize,
+    rotate: RotateLists,
+
+    /// The `<tspan>` ancestors that composite their spans in a layer, outermost first.
+    layers: Vec<SpanLayer>,
+
+    /// The bidi control characters that start the embeddings and overrides of the
+    /// span's ancestors, outermost first; see `bidi_control()`.
+    bidi_controls: Vec<char>,
+
+    /// The values of the span's ancestors that specify a `text-decoration`, outermost
+    /// first.  Their lines get drawn over the span with their own paint.
+    decorations: Vec<Rc<ComputedValues>>,
+}
+
+/// A `<tspan>` whose spans get composited together in a layer of their own, for
+/// example, to blend them with the content behind the text.
+#[derive(Clone)]
+struct SpanLayer {
+    node: Node,
+    values: Rc<ComputedValues>,
 }
 
+/// The `rotate` lists from a span's ancestors, innermost last.
+///
+/// Each list gets indexed by the addressable characters of the element that specified
+/// it, so the lists are shared among all the spans inside that element.
+type RotateLists = Vec<Rc<Vec<f64>>>;
+
 struct MeasuredSpan {
     values: Rc<ComputedValues>,
     layout: pango::Layout,
@@ -69,6 +115,50 @@ struct MeasuredSpan {
     advance: (f64, f64),
     dx: f64,
     dy: f64,
+    decorations: Vec<Rc<ComputedValues>>,
+
+    /// Parameters to normalize the span's lengths; see `text_normalize_params()`.
+    params: NormalizeParams,
+
+    /// The span's `baseline-shift` in user-space units.
+    baseline_shift: f64,
+
+    /// Byte ranges of the addressable characters in the span's text.
+    addressable_chars: Vec<Range<usize>>,
+
+    /// Scale factor along the inline direction, for `lengthAdjust="spacingAndGlyphs"`.
+    length_adjust_scale: f64,
+
+    /// Spacing after the span's last character that is in its `advance` but not in its
+    /// layout, for `lengthAdjust="spacing"`; see `adjust_text_length()`.
+    spacing_after: f64,
+
+    /// Supplemental rotation in degrees, from the `rotate` attribute.  Spans with a
+    /// rotation hold a single addressable character.
+    rotation: f64,
+
+    /// Set when the span's `layout` holds the text of the whole chunk.
+    shared_layout: Option<SharedLayout>,
+
+    layers: Vec<SpanLayer>,
+}
+
+/// Where a span's characters are within the layout that it shares with the other
+/// spans of its chunk.
+#[derive(Clone)]
+struct SharedLayout {
+    /// Byte offset of the span's text in the layout's text.
+    start: usize,
+
+    /// Byte offset of the end of the span's text in the layout's text.
+    end: usize,
+
+    /// Distance from the start of the layout to the start of the span, in logical order.
+    offset: f64,
+
+    /// Horizontal ranges covered by the span's characters, relative to the left edge
+    /// of the layout.  Bidi reordering can split a span into several ranges.
+    ranges: Vec<(f64, f64)>,
 }
 
 struct PositionedSpan {
@@ -78,12 +168,23 @@ struct PositionedSpan {
     rendered_position: (f64, f64),
     next_span_x: f64,
     next_span_y: f64,
+    decorations: Vec<Rc<ComputedValues>>,
+    params: NormalizeParams,
+    length_adjust_scale: f64,
+    spacing_after: f64,
+    rotation: f64,
+    shared_layout: Option<SharedLayout>,
+
+    /// Byte ranges of the addressable characters in the layout's text.
+    addressable_chars: Vec<Range<usize>>,
+
+    layers: Vec<SpanLayer>,
 }
 
 impl Chunk {
-    fn new(values: &ComputedValues, x: Option<f64>, y: Option<f64>) -> Chunk {
+    fn new(values: Rc<ComputedValues>, x: Option<f64>, y: Option<f64>) -> Chunk {
         Chunk {
-            values: Rc::new(values.clone()),
+            values,
             x,
             y,
             spans: Vec::new(),
@@ -92,58 +193,467 @@ impl Chunk {
 }
 
 impl MeasuredChunk {
-    fn from_chunk(chunk: &Chunk, draw_ctx: &DrawingCtx) -> MeasuredChunk {
-        let measured_spans: Vec<MeasuredSpan> = chunk
+    fn from_chunk(
+        chunk: &Chunk,
+        draw_ctx: &DrawingCtx,
+        view_params: &ViewParams,
+        rotate_counts: &mut RotateCounts,
+        share_layout: bool,
+    ) -> MeasuredChunk {
+        let chunk_layout = if share_layout && can_share_layout(chunk) {
+            ChunkLayout::new(chunk, draw_ctx, view_params)
+        } else {
+            None
+        };
+
+        let mut measured_spans: Vec<MeasuredSpan> = match chunk_layout {
+            Some(ref chunk_layout) => chunk_layout.measure_spans(chunk),
+            None => chunk
+                .spans
+                .iter()
+                .map(|span| MeasuredSpan::from_span(span, draw_ctx, view_params))
+                .collect(),
+        };
+
+        let rotations: Vec<Vec<f64>> = chunk
             .spans
             .iter()
-            .map(|span| MeasuredSpan::from_span(span, draw_ctx))
+            .zip(measured_spans.iter())
+            .map(|(span, measured)| {
+                let mut rotations =
+                    rotate_counts.next_rotations(&span.rotate, measured.addressable_chars.len());
+
+                // glyph-orientation-horizontal composes with the rotate attribute.
+                if !span.values.writing_mode().is_vertical() {
+                    let orientation = span.values.glyph_orientation_horizontal().degrees();
+                    for r in &mut rotations {
+                        *r += orientation;
+                    }
+                }
+
+                rotations
+            })
             .collect();
 
-        let advance = measured_spans.iter().fold((0.0, 0.0), |acc, measured| {
-            (acc.0 + measured.advance.0, acc.1 + measured.advance.1)
-        });
+        // Each rotated character gets a layout of its own, so the spans of a chunk with
+        // rotated characters cannot share a layout either.
+        if rotations.iter().flatten().any(|r| !r.approx_eq_cairo(0.0)) {
+            measured_spans = chunk
+                .spans
+                .iter()
+                .zip(measured_spans.into_iter())
+                .zip(rotations.iter())
+                .flat_map(|((span, measured), rotations)| {
+                    if rotations.iter().all(|r| r.approx_eq_cairo(0.0)) {
+                        if measured.shared_layout.is_some() {
+                            vec![MeasuredSpan::from_span(span, draw_ctx, view_params)]
+                        } else {
+                            vec![measured]
+                        }
+                    } else {
+                        MeasuredSpan::from_rotated_span(
+                            span,
+                            &measured.addressable_chars,
+                            rotations,
+                            draw_ctx,
+                            view_params,
+                        )
+                    }
+                })
+                .collect();
+        }
+
+        let advance = spans_advance(&measured_spans);
+        let trailing_space = spans_trailing_space(&measured_spans);
 
         MeasuredChunk {
             values: chunk.values.clone(),
             x: chunk.x,
             y: chunk.y,
             advance,
+            trailing_space,
             spans: measured_spans,
         }
     }
+
+    /// The chunk's advance along the inline direction of each of its spans.
+    ///
+    /// A `<tspan>` can change the `writing-mode`, so each span advances along its
+    /// own axis.
+    fn inline_advance(&self) -> f64 {
+        self.spans.iter().map(|span| span.inline_advance()).sum()
+    }
+
+    /// Recomputes the chunk's advance after its spans changed.
+    fn update_advance(&mut self) {
+        self.advance = spans_advance(&self.spans);
+        self.trailing_space = spans_trailing_space(&self.spans);
+    }
+}
+
+/// Whether the spans of a chunk can be laid out together in a single Pango layout.
+///
+/// The layout has a single gravity, so all the spans must be horizontal.  The bidi
+/// algorithm runs over the text of the whole chunk, so that a right-to-left word gets
+/// reordered as a whole even if it is split among several spans.  A chunk with a
+/// single span needs the shared layout too if the span is inside an embedding or an
+/// override, since only the shared layout has the bidi control characters for them.
+fn can_share_layout(chunk: &Chunk) -> bool {
+    (chunk.spans.len() > 1
+        || chunk
+            .spans
+            .iter()
+            .any(|span| !span.bidi_controls.is_empty()))
+        && !chunk.values.writing_mode().is_vertical()
+        && chunk
+            .spans
+            .iter()
+            .all(|span| !span.values.writing_mode().is_vertical())
+}
+
+/// Ends the innermost embedding or override that a bidi control character started.
+const POP_DIRECTIONAL_FORMATTING: char = '\u{202C}';
+
+/// The bidi control character that starts the embedding or override of an element.
+///
+/// Nested embeddings and overrides resolve correctly only if the bidi algorithm sees
+/// their nesting, so the text of a shared layout gets the control characters around
+/// the spans of each element with `unicode-bidi: embed` or `bidi-override`.
+///
+/// https://www.w3.org/TR/css-writing-modes-3/#bidi-control-codes
+fn bidi_control(values: &ComputedValues) -> Option<char> {
+    match (values.unicode_bidi(), values.direction()) {
+        (UnicodeBidi::Normal, _) => None,
+        (UnicodeBidi::Embed, Direction::Ltr) => Some('\u{202A}'),
+        (UnicodeBidi::Embed, Direction::Rtl) => Some('\u{202B}'),
+        (UnicodeBidi::Override, Direction::Ltr) => Some('\u{202D}'),
+        (UnicodeBidi::Override, Direction::Rtl) => Some('\u{202E}'),
+    }
+}
+
+/// Joins the text of a chunk's spans, with the bidi control characters between them.
+///
+/// Returns the text and the byte offset of each span's text in it.
+fn chunk_text(chunk: &Chunk) -> (String, Vec<usize>) {
+    let mut text = String::new();
+    let mut starts = Vec::with_capacity(chunk.spans.len());
+    let mut open: &[char] = &[];
+
+    for span in &chunk.spans {
+        let common = open
+            .iter()
+            .zip(span.bidi_controls.iter())
+            .take_while(|(a, b)| a == b)
+            .count();
+
+        for _ in common..open.len() {
+            text.push(POP_DIRECTIONAL_FORMATTING);
+        }
+
+        text.extend(&span.bidi_controls[common..]);
+        starts.push(text.len());
+        text.push_str(&span.text);
+        open = &span.bidi_controls;
+    }
+
+    for _ in 0..open.len() {
+        text.push(POP_DIRECTIONAL_FORMATTING);
+    }
+
+    (text, starts)
+}
+
+/// A single layout for the text of a whole chunk, which its spans share.
+///
+/// Pango shapes each layout on its own, so separate layouts lose kerning and
+/// ligatures at `<tspan>` boundaries, even if only the color changes between spans.
+/// In the shared layout, each span's font properties apply as attributes over its
+/// range of the text.  Each span then draws only the glyphs for its own characters;
+/// see `draw_span_glyphs()` in the drawing context.
+struct ChunkLayout {
+    layout: pango::Layout,
+    text: String,
+
+    /// Byte offset of each span's text in the layout's text.
+    starts: Vec<usize>,
+
+    /// The `NormalizeParams` of each span.
+    params: Vec<NormalizeParams>,
+}
+
+impl ChunkLayout {
+    /// Lays out the text of a chunk.
+    ///
+    /// Small caps get synthesized per layout, so this returns `None` if a span needs
+    /// them; the spans of that chunk keep their separate layouts.
+    fn new(chunk: &Chunk, draw_ctx: &DrawingCtx, view_params: &ViewParams) -> Option<ChunkLayout> {
+        let (text, starts) = chunk_text(chunk);
+
+        let pango_context = pango::Context::from(draw_ctx);
+        let cache = draw_ctx.font_measurements_cache();
+        let attr_list = pango::AttrList::new();
+        let mut params = Vec::with_capacity(chunk.spans.len());
+
+        for (span, &start) in chunk.spans.iter().zip(starts.iter()) {
+            let span_params =
+                text_normalize_params(&pango_context, cache, &span.values, view_params);
+            let props = FontProperties::new(&span.values, &span_params);
+            let end = start + span.text.len();
+
+            if needs_synthetic_small_caps(&pango_context, cache, &props) {
+                return None;
+            }
+
+            let font_desc = font_description(&pango_context, cache, &props);
+            insert_attribute(
+                &attr_list,
+                pango::Attribute::new_font_desc(&font_desc),
+                Some(start..end),
+            );
+
+            if let XmlLang(Some(ref lang)) = props.xml_lang {
+                insert_attribute(
+                    &attr_list,
+                    pango::Attribute::new_language(&pango::Language::from_string(lang)),
+                    Some(start..end),
+                );
+            }
+
+            // Pango draws its decoration lines with the whole layout, so the spans
+            // draw theirs by hand; see `decoration_lines()`.
+            let attributes = TextAttributes {
+                underline: false,
+                strikethrough: false,
+                ..TextAttributes::new(&props, false)
+            };
+
+            attributes.insert(&attr_list, Some(start..end));
+            insert_word_spacing(&attr_list, &props, &span.text, start);
+
+            params.push(span_params);
+        }
+
+        for (span, span_params) in chunk.spans.iter().zip(params.iter()) {
+            let props = FontProperties::new(&span.values, span_params);
+            let font_desc = font_description(&pango_context, cache, &props);
+            report_font_substitution(draw_ctx, &pango_context, &font_desc, &props);
+        }
+
+        let chunk_params = NormalizeParams::new(&chunk.values, view_params);
+        let chunk_props = FontProperties::new(&chunk.values, &chunk_params);
+        let layout = create_pango_layout_with_context(
+            &pango_context,
+            cache,
+            &chunk_props,
+            &attr_list,
+            &text,
+        );
+
+        Some(ChunkLayout {
+            layout,
+            text,
+            starts,
+            params,
+        })
+    }
+
+    /// Measures the spans of the chunk in the shared layout.
+    fn measure_spans(&self, chunk: &Chunk) -> Vec<MeasuredSpan> {
+        let log_attrs = self.layout.log_attrs();
+        let (w, h) = self.layout.size();
+        let layout_size = (
+            f64::from(w) / f64::from(pango::SCALE),
+            f64::from(h) / f64::from(pango::SCALE),
+        );
+
+        let mut offset = 0.0;
+
+        chunk
+            .spans
+            .iter()
+            .enumerate()
+            .map(|(i, span)| {
+                let start = self.starts[i];
+                let end = start + span.text.len();
+                let ranges = layout_ranges(&self.layout, &self.text, start..end);
+                let advance: f64 = ranges.iter().map(|(x0, x1)| x1 - x0).sum();
+
+                // The layout has a log attribute for each character of its text.
+                let first_char = self.text[..start].chars().count();
+                let addressable_chars =
+                    addressable_characters(&span.text, &log_attrs[first_char..]);
+
+                let params = self.params[i].clone();
+                let baseline_shift = span.values.baseline_shift().0.to_user(&params);
+
+                let measured = MeasuredSpan {
+                    values: span.values.clone(),
+                    layout: self.layout.clone(),
+                    _layout_size: layout_size,
+                    advance: (advance, 0.0),
+                    dx: span.dx,
+                    dy: span.dy,
+                    decorations: span.decorations.clone(),
+                    params,
+                    baseline_shift,
+                    addressable_chars,
+                    length_adjust_scale: 1.0,
+                    spacing_after: 0.0,
+                    rotation: 0.0,
+                    shared_layout: Some(SharedLayout {
+                        start,
+                        end,
+                        offset,
+                        ranges,
+                    }),
+                    layers: span.layers.clone(),
+                };
+
+                offset += advance;
+
+                measured
+            })
+            .collect()
+    }
+}
+
+/// Computes the horizontal ranges that the characters in `range` occupy in the layout.
+///
+/// The ranges are in user units relative to the left edge of the layout, sorted and
+/// with adjacent ones merged.
+///
+/// A ligature can join characters from different spans into a single glyph.  Pango
+/// divides the glyph's advance evenly among the characters in its cluster, so each
+/// span gets its share of the ligature and paints it with its own color.
+fn layout_ranges(layout: &pango::Layout, text: &str, range: Range<usize>) -> Vec<(f64, f64)> {
+    let pango_scale = f64::from(pango::SCALE);
+
+    let mut char_ranges: Vec<(f64, f64)> = text[range.clone()]
+        .char_indices()
+        .map(|(i, _)| {
+            // Pango uses a negative width for right-to-left characters.
+            let pos = layout.index_to_pos((range.start + i) as i32);
+            let x0 = f64::from(pos.x) / pango_scale;
+            let x1 = f64::from(pos.x + pos.width) / pango_scale;
+            (x0.min(x1), x0.max(x1))
+        })
+        .collect();
+
+    char_ranges.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+
+    let mut ranges: Vec<(f64, f64)> = Vec::new();
+
+    for (x0, x1) in char_ranges {
+        match ranges.last_mut() {
+            Some(last) if x0 <= last.1 + 1.0 / pango_scale => last.1 = last.1.max(x1),
+            _ => ranges.push((x0, x1)),
+        }
+    }
+
+    ranges
+}
+
+fn spans_advance(spans: &[MeasuredSpan]) -> (f64, f64) {
+    spans.iter().fold((0.0, 0.0), |acc, measured| {
+        (acc.0 + measured.advance.0, acc.1 + measured.advance.1)
+    })
+}
+
+/// The advance of the white space at the end of a chunk's spans.
+///
+/// Like white space at the end of a line in CSS, it hangs off the end of the text
+/// unless it is preserved, so it does not count for `text-anchor`.
+fn spans_trailing_space(spans: &[MeasuredSpan]) -> (f64, f64) {
+    let mut trailing = (0.0, 0.0);
+
+    for span in spans.iter().rev() {
+        if span.values.white_space().preserves_spaces() {
+            break;
+        }
+
+        let (advance, is_blank) = span.trailing_space_advance();
+
+        if span.values.writing_mode().is_vertical() {
+            trailing.1 += advance;
+        } else {
+            trailing.0 += advance;
+        }
+
+        if !is_blank {
+            break;
+        }
+    }
+
+    trailing
 }
 
 impl PositionedChunk {
-    fn from_measured(
-        measured: &MeasuredChunk,
-        draw_ctx: &DrawingCtx,
-        x: f64,
-        y: f64,
-    ) -> PositionedChunk {
+    fn from_measured(measured: &MeasuredChunk, x: f64, y: f64) -> PositionedChunk {
         let mut positioned = Vec::new();
 
-        // Adjust the specified coordinates with the text_anchor
+        // Right-to-left text advances toward -x.  Spans that share the chunk's layout
+        // get placed in visual order by the layout itself, but separate layouts go from
+        // the right edge of the chunk to its left edge.
+        let is_rtl = measured.values.direction() == Direction::Rtl
+            && !measured.values.writing_mode().is_vertical();
+        let is_shared = measured.spans.iter().any(|s| s.shared_layout.is_some());
+
+        // Adjust the specified coordinates with the text_anchor.  White space at the end
+        // of the chunk does not count, but the next chunk still continues after it.
+
+        let anchored_advance = (
+            measured.advance.0 - measured.trailing_space.0,
+            measured.advance.1 - measured.trailing_space.1,
+        );
 
         let adjusted_advance = text_anchor_advance(
             measured.values.text_anchor(),
             measured.values.writing_mode(),
-            measured.advance,
+            measured.values.direction(),
+            anchored_advance,
         );
 
         let mut x = x + adjusted_advance.0;
         let mut y = y + adjusted_advance.1;
 
-        // Position each span
+        // The trailing white space of right-to-left text is at its left edge.
+        if is_rtl {
+            x -= measured.trailing_space.0;
+        }
+
+        // Position each span.  A span's advance is along the axis of its own writing
+        // mode, which may differ from the chunk's if a <tspan> changed it.  The chunk
+        // establishes the dominant baseline that all the spans align to.
+
+        let dominant_baseline = measured.values.dominant_baseline();
+
+        if is_rtl && !is_shared {
+            x += measured.advance.0;
+        }
 
         for measured_span in &measured.spans {
-            let positioned_span = PositionedSpan::from_measured(measured_span, draw_ctx, x, y);
+            if is_rtl && !is_shared {
+                x -= measured_span.advance.0;
+            }
+
+            let positioned_span =
+                PositionedSpan::from_measured(measured_span, dominant_baseline, x, y);
 
             x = positioned_span.next_span_x;
             y = positioned_span.next_span_y;
 
+            if is_rtl && !is_shared {
+                x -= measured_span.advance.0;
+            }
+
             positioned.push(positioned_span);
         }
 
+        // The next chunk continues from the end of the text, which is its left edge.
+        if is_rtl && is_shared {
+            x -= measured.advance.0;
+        }
+
         PositionedChunk {
             next_chunk_x: x,
             next_chunk_y: y,
@@ -152,57 +662,131 @@ impl PositionedChunk {
     }
 }
 
+/// Computes the offset from a chunk's anchor point to the start of its first span.
+///
+/// The `start` and `end` anchors refer to the logical start and end of the text, so
+/// for right-to-left horizontal text `start` is the right edge of the chunk.
+///
+/// Each span advances along the axis of its own writing mode, so the `advance` of a
+/// chunk with both horizontal and vertical spans has a component along each axis.  The
+/// chunk gets anchored along both of them, so that its `end` is where the last span
+/// ends.
 fn text_anchor_advance(
     anchor: TextAnchor,
     writing_mode: WritingMode,
+    direction: Direction,
     advance: (f64, f64),
 ) -> (f64, f64) {
-    if writing_mode.is_vertical() {
-        match anchor {
-            TextAnchor::Start => (0.0, 0.0),
-            TextAnchor::Middle => (0.0, -advance.1 / 2.0),
-            TextAnchor::End => (0.0, -advance.1),
-        }
-    } else {
-        match anchor {
-            TextAnchor::Start => (0.0, 0.0),
-            TextAnchor::Middle => (-advance.0 / 2.0, 0.0),
-            TextAnchor::End => (-advance.0, 0.0),
-        }
+    let anchor = match (direction, anchor) {
+        (Direction::Rtl, TextAnchor::Start) if !writing_mode.is_vertical() => TextAnchor::End,
+        (Direction::Rtl, TextAnchor::End) if !writing_mode.is_vertical() => TextAnchor::Start,
+        (_, anchor) => anchor,
+    };
+
+    match anchor {
+        TextAnchor::Start => (0.0, 0.0),
+        TextAnchor::Middle => (-advance.0 / 2.0, -advance.1 / 2.0),
+        TextAnchor::End => (-advance.0, -advance.1),
     }
 }
 
 impl Span {
-    fn new(text: &str, values: Rc<ComputedValues>, dx: f64, dy: f64, depth: usize) -> Span {
+    fn new(
+        text: &str,
+        values: Rc<ComputedValues>,
+        dx: f64,
+        dy: f64,
+        depth: usize,
+        rotate: RotateLists,
+    ) -> Span {
         Span {
             values,
             text: text.to_string(),
             dx,
             dy,
             _depth: depth,
+            rotate,
+            layers: Vec::new(),
+            bidi_controls: Vec::new(),
+            decorations: Vec::new(),
+        }
+    }
+}
+
+/// The `dx`/`dy` offsets that have not been applied to a character yet.
+///
+/// A `dx` or `dy` attribute shifts the first character inside its element, so the
+/// offsets accumulate down the tree until a span with text consumes them.  An absolute
+/// `x` or `y` that starts a new chunk discards the pending offset along that axis.
+#[derive(Default)]
+struct PendingDelta {
+    dx: f64,
+    dy: f64,
+}
+
+impl PendingDelta {
+    fn take(&mut self) -> (f64, f64) {
+        let PendingDelta { dx, dy } = std::mem::take(self);
+        (dx, dy)
+    }
+}
+
+/// Keeps track of how many addressable characters each `rotate` list has covered.
+#[derive(Default)]
+struct RotateCounts(HashMap<*const Vec<f64>, usize>);
+
+impl RotateCounts {
+    /// Returns the rotations for the next `num_chars` addressable characters of a span.
+    ///
+    /// The innermost list wins, but all the lists advance so that the indexing
+    /// continues across span boundaries.  When a list runs out of values, its last
+    /// value applies to the rest of the characters.
+    fn next_rotations(&mut self, lists: &[Rc<Vec<f64>>], num_chars: usize) -> Vec<f64> {
+        let mut rotations = vec![0.0; num_chars];
+
+        for list in lists {
+            let count = self.0.entry(Rc::as_ptr(list)).or_insert(0);
+
+            for rotation in rotations.iter_mut() {
+                *rotation = list
+                    .get(*count)
+                    .or_else(|| list.last())
+                    .copied()
+                    .unwrap_or(0.0);
+                *count += 1;
+            }
         }
+
+        rotations
     }
 }
 
 impl MeasuredSpan {
-    fn from_span(span: &Span, draw_ctx: &DrawingCtx) -> MeasuredSpan {
+    fn from_span(span: &Span, draw_ctx: &DrawingCtx, view_params: &ViewParams) -> MeasuredSpan {
         let values = span.values.clone();
 
-        let view_params = draw_ctx.get_view_params();
-        let params = NormalizeParams::new(&values, &view_params);
+        let pango_context = pango::Context::from(draw_ctx);
+        let params = text_normalize_params(
+            &pango_context,
+            draw_ctx.font_measurements_cache(),
+            &values,
+            view_params,
+        );
 
         let properties = FontProperties::new(&values, &params);
         let layout = create_pango_layout(draw_ctx, &properties, &span.text);
         let (w, h) = layout.size();
 
+        let addressable_chars = addressable_characters(&span.text, &layout.log_attrs());
+
         let w = f64::from(w) / f64::from(pango::SCALE);
         let h = f64::from(h) / f64::from(pango::SCALE);
 
-        let advance = if values.writing_mode().is_vertical() {
-            (0.0, w)
-        } else {
-            (w, 0.0)
-        };
+        let advance = layout_advance(&layout, values.writing_mode());
+
+        // The computed baseline-shift already includes the shifts of the ancestor spans;
+        // see `BaselineShift::compute()`.
+        let baseline_shift = values.baseline_shift().0.to_user(&params);
 
         MeasuredSpan {
             values,
@@ -211,117 +795,676 @@ impl MeasuredSpan {
             advance,
             dx: span.dx,
             dy: span.dy,
+            decorations: span.decorations.clone(),
+            params,
+            baseline_shift,
+            addressable_chars,
+            length_adjust_scale: 1.0,
+            spacing_after: 0.0,
+            rotation: 0.0,
+            shared_layout: None,
+            layers: span.layers.clone(),
         }
     }
-}
 
-impl PositionedSpan {
-    fn from_measured(
-        measured: &MeasuredSpan,
+    /// Measures each addressable character of a rotated span separately, so that
+    /// each one can be drawn with its own rotation.
+    fn from_rotated_span(
+        span: &Span,
+        addressable_chars: &[Range<usize>],
+        rotations: &[f64],
         draw_ctx: &DrawingCtx,
-        x: f64,
-        y: f64,
-    ) -> PositionedSpan {
-        let layout = measured.layout.clone();
-        let values = measured.values.clone();
+        view_params: &ViewParams,
+    ) -> Vec<MeasuredSpan> {
+        addressable_chars
+            .iter()
+            .zip(rotations.iter())
+            .enumerate()
+            .map(|(i, (range, rotation))| {
+                // dx/dy only apply before the first character
+                let (dx, dy) = if i == 0 {
+                    (span.dx, span.dy)
+                } else {
+                    (0.0, 0.0)
+                };
+
+                let char_span = Span::new(
+                    &span.text[range.clone()],
+                    span.values.clone(),
+                    dx,
+                    dy,
+                    span._depth,
+                    Vec::new(),
+                );
+
+                let mut measured = MeasuredSpan::from_span(&char_span, draw_ctx, view_params);
+                measured.rotation = *rotation;
+                measured.layers = span.layers.clone();
+                measured.decorations = span.decorations.clone();
+                measured
+            })
+            .collect()
+    }
 
-        let view_params = draw_ctx.get_view_params();
-        let params = NormalizeParams::new(&values, &view_params);
+    /// The span's advance along its own inline direction.
+    fn inline_advance(&self) -> f64 {
+        if self.values.writing_mode().is_vertical() {
+            self.advance.1
+        } else {
+            self.advance.0
+        }
+    }
 
-        let baseline = f64::from(layout.baseline()) / f64::from(pango::SCALE);
-        let baseline_shift = values.baseline_shift().0.to_user(&params);
-        let offset = baseline + baseline_shift;
+    /// The advance of the spaces at the end of the span, along its inline direction.
+    ///
+    /// Also returns whether the span has nothing but spaces.
+    fn trailing_space_advance(&self) -> (f64, bool) {
+        let text = match self.layout.text() {
+            Some(text) => text,
+            None => return (0.0, false),
+        };
 
-        let dx = measured.dx;
-        let dy = measured.dy;
+        let start = self
+            .shared_layout
+            .as_ref()
+            .map(|shared| shared.start)
+            .unwrap_or(0);
+        let end = start + self.addressable_chars.last().map(|r| r.end).unwrap_or(0);
 
-        let (render_x, render_y) = if values.writing_mode().is_vertical() {
-            (x + offset + dx, y + dy)
-        } else {
-            (x + dx, y - offset + dy)
-        };
+        let trimmed_end = start + text[start..end].trim_end_matches(' ').len();
 
-        PositionedSpan {
-            layout: measured.layout.clone(),
-            values,
-            _position: (x, y),
-            rendered_position: (render_x, render_y),
-            next_span_x: x + measured.advance.0 + dx,
-            next_span_y: y + measured.advance.1 + dy,
-        }
-    }
+        let width: i32 = (trimmed_end..end)
+            .map(|i| self.layout.index_to_pos(i as i32).width.abs())
+            .sum();
 
-    fn draw(
-        &self,
-        acquired_nodes: &mut AcquiredNodes<'_>,
-        draw_ctx: &mut DrawingCtx,
-        clipping: bool,
-    ) -> Result<BoundingBox, RenderingError> {
-        let view_params = draw_ctx.get_view_params();
-        let params = NormalizeParams::new(&self.values, &view_params);
+        let advance = f64::from(width) / f64::from(pango::SCALE) * self.length_adjust_scale;
 
-        let layout = self.layout.clone();
-        let is_visible = self.values.is_visible();
-        let (x, y) = self.rendered_position;
+        (advance, trimmed_end == start)
+    }
 
-        let stroke = Stroke::new(&self.values, &params);
+    /// Adds `extra` user-space units of letter spacing to each addressable character.
+    fn add_letter_spacing(&mut self, extra: f64) {
+        let letter_spacing = self
+            .values
+            .letter_spacing()
+            .to_user(&self.params)
+            .unwrap_or(0.0)
+            + extra;
+
+        // Copy the list; Pango does not notice changes to the list it already has.
+        let attr_list = self
+            .layout
+            .attributes()
+            .and_then(|attrs| attrs.copy())
+            .unwrap_or_else(pango::AttrList::new);
+
+        attr_list.change(pango::Attribute::new_letter_spacing(to_pango_units(
+            letter_spacing,
+        )));
+
+        self.layout.set_attributes(Some(&attr_list));
+
+        let mut properties = FontProperties::new(&self.values, &self.params);
+        properties.letter_spacing = Some(letter_spacing);
+        apply_word_spacing(&self.layout, &properties);
+
+        self.advance = layout_advance(&self.layout, self.values.writing_mode());
+    }
+}
 
-        let stroke_paint = self.values.stroke().0.resolve(
-            acquired_nodes,
-            self.values.stroke_opacity().0,
-            self.values.color().0,
-            None,
-            None,
-        );
+/// The advance of a layout in the text's user space.
+///
+/// Pango lays out vertical text along the layout's x axis, and the layout gets rotated
+/// when it is drawn; see `text_span_layout_transform()`.  So the layout's width is the
+/// advance along the writing direction, including Pango's letter spacing, for both
+/// horizontal and vertical text.
+fn layout_advance(layout: &pango::Layout, writing_mode: WritingMode) -> (f64, f64) {
+    let (w, _) = layout.size();
+    let w = f64::from(w) / f64::from(pango::SCALE);
 
-        let fill_paint = self.values.fill().0.resolve(
-            acquired_nodes,
-            self.values.fill_opacity().0,
-            self.values.color().0,
-            None,
-            None,
-        );
+    if writing_mode.is_vertical() {
+        (0.0, w)
+    } else {
+        (w, 0.0)
+    }
+}
 
-        let text_rendering = self.values.text_rendering();
+/// `lengthAdjust` attribute.
+///
+/// https://www.w3.org/TR/SVG/text.html#TextElementLengthAdjustAttribute
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum LengthAdjust {
+    Spacing,
+    SpacingAndGlyphs,
+}
 
-        let span = layout::TextSpan {
-            layout,
-            is_visible,
-            x,
-            y,
-            stroke,
-            stroke_paint,
-            fill_paint,
-            text_rendering,
-        };
+enum_default!(LengthAdjust, LengthAdjust::Spacing);
 
-        draw_ctx.draw_text_span(&view_params, &span, acquired_nodes, &self.values, clipping)
+impl Parse for LengthAdjust {
+    fn parse<'i>(parser: &mut Parser<'i, '_>) -> Result<LengthAdjust, ParseError<'i>> {
+        Ok(parse_identifiers!(
+            parser,
+            "spacing" => LengthAdjust::Spacing,
+            "spacingAndGlyphs" => LengthAdjust::SpacingAndGlyphs,
+        )?)
     }
 }
 
-/// Walks the children of a `<text>`, `<tspan>`, or `<tref>` element
-/// and appends chunks/spans from them into the specified `chunks`
-/// array.
+/// `rotate` attribute of the `<text>` and `<tspan>` elements.
 ///
-/// `x` and `y` are the absolute position for the first chunk.  If the
+/// https://www.w3.org/TR/SVG/text.html#TextElementRotateAttribute
+///
+/// This is a list of angles in degrees, one for each addressable character.
+#[derive(Debug, Clone, PartialEq)]
+struct Rotate(Vec<f64>);
+
+impl Parse for Rotate {
+    fn parse<'i>(parser: &mut Parser<'i, '_>) -> Result<Rotate, ParseError<'i>> {
+        let mut angles = vec![f64::parse(parser)?];
+
+        while !parser.is_exhausted() {
+            optional_comma(parser);
+            angles.push(f64::parse(parser)?);
+        }
+
+        Ok(Rotate(angles))
+    }
+}
+
+/// Stretches or compresses the measured text so that its total advance is `text_length`.
+///
+/// With `lengthAdjust="spacing"`, this adds the same amount of space between each pair
+/// of adjacent addressable characters in a chunk.  With `lengthAdjust="spacingAndGlyphs"`,
+/// this scales the spans along the inline direction, glyphs included; the scale gets
+/// applied when drawing.
+fn adjust_text_length(chunks: &mut [MeasuredChunk], text_length: f64, length_adjust: LengthAdjust) {
+    let natural_length: f64 = chunks.iter().map(|c| c.inline_advance()).sum();
+
+    if natural_length.approx_eq_cairo(0.0) {
+        return;
+    }
+
+    match length_adjust {
+        LengthAdjust::Spacing => {
+            let num_gaps: usize = chunks
+                .iter()
+                .map(|c| {
+                    c.spans
+                        .iter()
+                        .map(|s| s.addressable_chars.len())
+                        .sum::<usize>()
+                        .saturating_sub(1)
+                })
+                .sum();
+
+            if num_gaps == 0 {
+                return;
+            }
+
+            let extra = (text_length - natural_length) / num_gaps as f64;
+
+            for chunk in chunks.iter_mut() {
+                // Pango leaves out the letter spacing at both ends of a layout, so each
+                // span only gets it between its own characters.  The gaps between the
+                // spans get it in the advance of the span before them.
+                let last = chunk
+                    .spans
+                    .iter()
+                    .rposition(|s| !s.addressable_chars.is_empty());
+
+                for (i, span) in chunk.spans.iter_mut().enumerate() {
+                    span.add_letter_spacing(extra);
+
+                    if Some(i) != last && !span.addressable_chars.is_empty() {
+                        if span.values.writing_mode().is_vertical() {
+                            span.advance.1 += extra;
+                        } else {
+                            span.advance.0 += extra;
+                        }
+
+                        span.spacing_after = extra;
+                    }
+                }
+
+                chunk.update_advance();
+            }
+        }
+
+        LengthAdjust::SpacingAndGlyphs => {
+            let scale = text_length / natural_length;
+
+            for chunk in chunks.iter_mut() {
+                for span in chunk.spans.iter_mut() {
+                    span.advance = (span.advance.0 * scale, span.advance.1 * scale);
+                    span.length_adjust_scale = scale;
+                }
+
+                chunk.update_advance();
+            }
+        }
+    }
+}
+
+/// Splits a string into SVG's addressable characters.
+///
+/// The SVG spec defines an [addressable character] as a grapheme cluster, not
+/// as a single Unicode code point; for example, a base character followed by a
+/// combining accent, or an emoji sequence joined with ZERO WIDTH JOINER, gets a
+/// single position from the `x`/`y`/`dx`/`dy`/`rotate` attributes.  We use Pango's
+/// cursor positions to find the cluster boundaries.
+///
+/// The `log_attrs` must come from a Pango layout for the same `text`; Pango returns
+/// one `LogAttr` per character, plus one for the end of the text.
+///
+/// Returns the byte ranges of each addressable character within `text`.
+///
+/// [addressable character]: https://www.w3.org/TR/SVG2/text.html#TermAddressableCharacter
+fn addressable_characters(text: &str, log_attrs: &[pango::LogAttr]) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut start = 0;
+
+    for ((byte_index, _), attr) in text.char_indices().zip(log_attrs.iter()).skip(1) {
+        if attr.is_cursor_position() {
+            ranges.push(start..byte_index);
+            start = byte_index;
+        }
+    }
+
+    if start < text.len() {
+        ranges.push(start..text.len());
+    }
+
+    ranges
+}
+
+impl PositionedSpan {
+    /// Positions a span so that its baseline of the `dominant_baseline` kind is at
+    /// (`x`, `y`), regardless of the span's own font size or `dominant-baseline`.
+    fn from_measured(
+        measured: &MeasuredSpan,
+        dominant_baseline: DominantBaseline,
+        x: f64,
+        y: f64,
+    ) -> PositionedSpan {
+        let layout = measured.layout.clone();
+        let values = measured.values.clone();
+
+        // By default, vertical text is centered on the glyph's inline axis instead of
+        // sitting on the alphabetic baseline.
+        let is_vertical = values.writing_mode().is_vertical();
+        let baseline = match dominant_baseline {
+            DominantBaseline::Central => central_baseline(&layout),
+            DominantBaseline::Auto if is_vertical => central_baseline(&layout),
+            DominantBaseline::Hanging if !is_vertical => hanging_baseline(&layout),
+            DominantBaseline::Ideographic if !is_vertical => ideographic_baseline(&layout),
+            _ => f64::from(layout.baseline()) / f64::from(pango::SCALE),
+        };
+
+        let offset = baseline + measured.baseline_shift;
+
+        let dx = measured.dx;
+        let dy = measured.dy;
+
+        // A shared layout starts where the chunk starts, not where the span starts.
+        let layout_offset = measured
+            .shared_layout
+            .as_ref()
+            .map(|shared| shared.offset)
+            .unwrap_or(0.0);
+
+        let (render_x, render_y) = if is_vertical {
+            (x + offset + dx, y + dy)
+        } else {
+            (x - layout_offset + dx, y - offset + dy)
+        };
+
+        // The characters of a shared layout are in the chunk's visual order, which may
+        // put the span somewhere else than after the preceding spans.
+        let position = match measured.shared_layout {
+            Some(ref shared) => {
+                let visual_start = shared.ranges.first().map(|r| r.0).unwrap_or(0.0);
+                (render_x - dx + visual_start, y)
+            }
+
+            None => (x, y),
+        };
+
+        let text_start = measured
+            .shared_layout
+            .as_ref()
+            .map(|shared| shared.start)
+            .unwrap_or(0);
+
+        let addressable_chars = measured
+            .addressable_chars
+            .iter()
+            .map(|r| r.start + text_start..r.end + text_start)
+            .collect();
+
+        PositionedSpan {
+            layout: measured.layout.clone(),
+            values,
+            _position: position,
+            rendered_position: (render_x, render_y),
+            next_span_x: x + measured.advance.0 + dx,
+            next_span_y: y + measured.advance.1 + dy,
+            decorations: measured.decorations.clone(),
+            params: measured.params.clone(),
+            length_adjust_scale: measured.length_adjust_scale,
+            spacing_after: measured.spacing_after,
+            rotation: measured.rotation,
+            shared_layout: measured.shared_layout.clone(),
+            addressable_chars,
+            layers: measured.layers.clone(),
+        }
+    }
+
+    /// Draws the span.
+    ///
+    /// The `cascaded` values of the text element provide the paints for
+    /// `context-fill` and `context-stroke`, for example, when the text is instanced
+    /// by a `<use>` element.
+    fn draw(
+        &self,
+        acquired_nodes: &mut AcquiredNodes<'_>,
+        draw_ctx: &mut DrawingCtx,
+        view_params: &ViewParams,
+        cascaded: &CascadedValues<'_>,
+        clipping: bool,
+    ) -> Result<BoundingBox, RenderingError> {
+        let layout = self.layout.clone();
+        let is_visible = self.values.is_visible();
+        let (x, y) = self.rendered_position;
+
+        let stroke = Stroke::new(&self.values, &self.params);
+
+        let stroke_paint = self.values.stroke().0.resolve(
+            acquired_nodes,
+            self.values.stroke_opacity().0,
+            self.values.color().0,
+            cascaded.context_fill.clone(),
+            cascaded.context_stroke.clone(),
+        );
+
+        let fill_paint = self.values.fill().0.resolve(
+            acquired_nodes,
+            self.values.fill_opacity().0,
+            self.values.color().0,
+            cascaded.context_fill.clone(),
+            cascaded.context_stroke.clone(),
+        );
+
+        let text_rendering = self.values.text_rendering();
+
+        let properties = FontProperties::new(&self.values, &self.params);
+        let ranges = self
+            .shared_layout
+            .as_ref()
+            .map(|shared| shared.ranges.clone());
+        let cache = draw_ctx.font_measurements_cache();
+        let decoration_lines = decoration_lines(&layout, cache, &properties, ranges.as_deref());
+
+        let inherited_decorations = self
+            .decorations
+            .iter()
+            .map(|values| {
+                self.inherited_decoration(
+                    acquired_nodes,
+                    cache,
+                    values,
+                    cascaded,
+                    ranges.as_deref(),
+                )
+            })
+            .collect();
+
+        let span = layout::TextSpan {
+            layout,
+            is_visible,
+            x,
+            y,
+            stroke,
+            stroke_paint,
+            fill_paint,
+            text_rendering,
+            decoration_lines,
+            inherited_decorations,
+            length_adjust_scale: self.length_adjust_scale,
+            rotation: self.rotation,
+            slant: properties.font_style.synthetic_slant(),
+            text_range: self
+                .shared_layout
+                .as_ref()
+                .map(|shared| shared.start..shared.end),
+            visible_ranges: ranges,
+        };
+
+        if !clipping {
+            report_missing_glyphs(draw_ctx, &span.layout, span.text_range.clone(), &properties);
+        }
+
+        if draw_ctx.collects_text_geometry() && !clipping {
+            draw_ctx.add_text_span_geometry(layout::TextSpanGeometry {
+                layout: span.layout.clone(),
+                transform: text_span_layout_transform(&span),
+                chars: self.addressable_chars.clone(),
+                rotation: self.rotation,
+                spacing_after: self.spacing_after,
+            });
+        }
+
+        draw_ctx.draw_text_span(view_params, &span, acquired_nodes, &self.values, clipping)
+    }
+
+    /// The glyph cells of the span in the text's user space, from the logical extents of
+    /// its layout, or `None` if the span is empty.
+    ///
+    /// This is the span's part of the text's bounding box for `transform-box: fill-box`.
+    fn object_box(&self) -> Option<Rect> {
+        let (_, logical) = self.layout.extents();
+        let pango_scale = f64::from(pango::SCALE);
+
+        let x0 = f64::from(logical.x) / pango_scale;
+        let y0 = f64::from(logical.y) / pango_scale;
+        let mut rect = Rect::new(
+            x0,
+            y0,
+            x0 + f64::from(logical.width) / pango_scale,
+            y0 + f64::from(logical.height) / pango_scale,
+        );
+
+        // A span in a shared layout only covers part of the layout's width.
+        if let Some(ref shared) = self.shared_layout {
+            let x0 = shared
+                .ranges
+                .iter()
+                .map(|r| r.0)
+                .fold(f64::INFINITY, f64::min);
+            let x1 = shared
+                .ranges
+                .iter()
+                .map(|r| r.1)
+                .fold(f64::NEG_INFINITY, f64::max);
+
+            let x0 = rect.x0.max(x0);
+            rect = Rect::new(x0, rect.y0, rect.x1.min(x1).max(x0), rect.y1);
+        }
+
+        if rect.is_empty() {
+            return None;
+        }
+
+        let (x, y) = self.rendered_position;
+        let properties = FontProperties::new(&self.values, &self.params);
+        let transform = text_layout_transform(
+            &self.layout,
+            x,
+            y,
+            self.rotation,
+            self.length_adjust_scale,
+            properties.font_style.synthetic_slant(),
+        );
+
+        Some(transform.transform_rect(&rect))
+    }
+
+    /// Computes the lines that an ancestor with the `values` draws over this span.
+    ///
+    /// The lines go where the span's font would put them, but they use the thickness,
+    /// offset, and paint of the ancestor that specified the `text-decoration`.
+    fn inherited_decoration(
+        &self,
+        acquired_nodes: &mut AcquiredNodes<'_>,
+        cache: &FontMeasurementsCache,
+        values: &ComputedValues,
+        cascaded: &CascadedValues<'_>,
+        ranges: Option<&[(f64, f64)]>,
+    ) -> layout::InheritedDecoration {
+        let mut properties = FontProperties::new(&self.values, &self.params);
+        properties.text_decoration = values.text_decoration();
+        properties.text_decoration_thickness =
+            values.text_decoration_thickness().to_user(&self.params);
+        properties.text_underline_offset = values.text_underline_offset().to_user(&self.params);
+
+        let lines = hand_drawn_decoration_lines(
+            &self.layout,
+            cache,
+            &properties,
+            ranges,
+            properties.text_decoration.underline,
+            properties.text_decoration.strike,
+        );
+
+        let stroke_paint = values.stroke().0.resolve(
+            acquired_nodes,
+            values.stroke_opacity().0,
+            values.color().0,
+            cascaded.context_fill.clone(),
+            cascaded.context_stroke.clone(),
+        );
+
+        let fill_paint = values.fill().0.resolve(
+            acquired_nodes,
+            values.fill_opacity().0,
+            values.color().0,
+            cascaded.context_fill.clone(),
+            cascaded.context_stroke.clone(),
+        );
+
+        layout::InheritedDecoration {
+            lines,
+            stroke: Stroke::new(values, &self.params),
+            stroke_paint,
+            fill_paint,
+        }
+    }
+}
+
+/// Draws `spans`, compositing the ones inside a [`SpanLayer`] together.
+///
+/// Each span comes with the index of its chunk, to collect the bounding box of each
+/// chunk in `chunk_bboxes`.  The layers before `depth` have already been started.
+fn draw_spans_in_layers(
+    spans: &[(usize, &PositionedSpan)],
+    depth: usize,
+    acquired_nodes: &mut AcquiredNodes<'_>,
+    draw_ctx: &mut DrawingCtx,
+    view_params: &ViewParams,
+    cascaded: &CascadedValues<'_>,
+    clipping: bool,
+    chunk_bboxes: &mut [BoundingBox],
+) -> Result<BoundingBox, RenderingError> {
+    let mut bbox = draw_ctx.empty_bbox();
+
+    let mut i = 0;
+    while i < spans.len() {
+        let (chunk, span) = spans[i];
+
+        let layer = match span.layers.get(depth) {
+            Some(layer) => layer,
+
+            None => {
+                let span_bbox =
+                    span.draw(acquired_nodes, draw_ctx, view_params, cascaded, clipping)?;
+                chunk_bboxes[chunk].insert(&span_bbox);
+                bbox.insert(&span_bbox);
+                i += 1;
+                continue;
+            }
+        };
+
+        let len = spans[i..]
+            .iter()
+            .take_while(|(_, s)| s.layers.get(depth).map(|l| l.node == layer.node) == Some(true))
+            .count();
+        let layer_spans = &spans[i..i + len];
+
+        let stacking_ctx = {
+            let elt = layer.node.borrow_element();
+            StackingContext::new(acquired_nodes, &elt, Transform::identity(), &layer.values)
+        };
+
+        let layer_bbox = draw_ctx.with_discrete_layer(
+            &stacking_ctx,
+            acquired_nodes,
+            &layer.values,
+            clipping,
+            None,
+            &mut |an, dc| {
+                draw_spans_in_layers(
+                    layer_spans,
+                    depth + 1,
+                    an,
+                    dc,
+                    view_params,
+                    cascaded,
+                    clipping,
+                    chunk_bboxes,
+                )
+            },
+        )?;
+        bbox.insert(&layer_bbox);
+
+        i += len;
+    }
+
+    Ok(bbox)
+}
+
+/// Walks the children of a `<text>`, `<tspan>`, or `<tref>` element
+/// and appends chunks/spans from them into the specified `chunks`
+/// array.
+///
+/// `x` and `y` are the absolute position for the first chunk.  If the
 /// first child is a `<tspan>` with a specified absolute position, it
 /// will be used instead of the given arguments.
+///
+/// The `values` are the ones from the `cascaded` for the `node`.  The spans for its
+/// character data share them, instead of each one getting a copy.
 fn children_to_chunks(
     chunks: &mut Vec<Chunk>,
     node: &Node,
     acquired_nodes: &mut AcquiredNodes<'_>,
     cascaded: &CascadedValues<'_>,
+    values: &Rc<ComputedValues>,
     draw_ctx: &mut DrawingCtx,
-    dx: f64,
-    dy: f64,
+    delta: &mut PendingDelta,
     depth: usize,
+    rotate: &[Rc<Vec<f64>>],
 ) {
+    if depth > limits::MAX_TEXT_NESTING_DEPTH {
+        rsvg_log!(
+            "ignoring children of {} since the text is nested too deeply",
+            node
+        );
+        return;
+    }
+
     for child in node.children() {
         if child.is_chars() {
-            let values = cascaded.get();
             child
                 .borrow_chars()
-                .to_chunks(&child, Rc::new(values.clone()), chunks, dx, dy, depth);
+                .to_chunks(&child, values.clone(), chunks, delta, depth, rotate);
         } else {
             assert!(child.is_element());
 
@@ -334,15 +1477,23 @@ fn children_to_chunks(
                         &cascaded,
                         draw_ctx,
                         chunks,
-                        dx,
-                        dy,
+                        delta,
                         depth + 1,
+                        rotate,
                     );
                 }
 
                 Element::TRef(ref tref) => {
                     let cascaded = CascadedValues::new(cascaded, &child);
-                    tref.to_chunks(&child, acquired_nodes, &cascaded, chunks, depth + 1);
+                    tref.to_chunks(
+                        &child,
+                        acquired_nodes,
+                        &cascaded,
+                        chunks,
+                        delta,
+                        depth + 1,
+                        rotate,
+                    );
                 }
 
                 _ => (),
@@ -377,7 +1528,14 @@ fn children_to_chunks(
 #[derive(Default)]
 pub struct Chars {
     string: RefCell<String>,
-    space_normalized: RefCell<Option<String>>,
+
+    /// The string normalized for the last mode that was asked for.
+    space_normalized: RefCell<Option<(XmlSpaceNormalize, String)>>,
+
+    /// The string normalized for other modes.  This is only used when the same
+    /// characters get rendered with different `white-space`, for example from
+    /// `<use>` elements.
+    other_space_normalized: RefCell<Vec<(XmlSpaceNormalize, String)>>,
 }
 
 impl Chars {
@@ -385,6 +1543,7 @@ impl Chars {
         Chars {
             string: RefCell::new(String::from(initial_text)),
             space_normalized: RefCell::new(None),
+            other_space_normalized: RefCell::new(Vec::new()),
         }
     }
 
@@ -395,45 +1554,78 @@ impl Chars {
     pub fn append(&self, s: &str) {
         self.string.borrow_mut().push_str(s);
         *self.space_normalized.borrow_mut() = None;
+        self.other_space_normalized.borrow_mut().clear();
     }
 
     fn ensure_normalized_string(&self, node: &Node, values: &ComputedValues) {
+        // The xml:space attribute gets mapped onto white-space during the cascade.
+        // SVG 1.1 turns tabs into spaces for xml:space="preserve", but CSS keeps
+        // them for white-space: pre.
+        let mode = if values.white_space().preserves_spaces() {
+            if values.xml_space() == XmlSpace::Preserve {
+                XmlSpaceNormalize::Preserve
+            } else {
+                XmlSpaceNormalize::PreserveTabs
+            }
+        } else {
+            XmlSpaceNormalize::Default(NormalizeDefault {
+                has_element_before: node.previous_element_sibling().is_some(),
+                has_element_after: node.next_element_sibling().is_some(),
+            })
+        };
+
         let mut normalized = self.space_normalized.borrow_mut();
 
-        if (*normalized).is_none() {
-            let mode = match values.xml_space() {
-                XmlSpace::Default => XmlSpaceNormalize::Default(NormalizeDefault {
-                    has_element_before: node.previous_sibling().is_some(),
-                    has_element_after: node.next_sibling().is_some(),
-                }),
+        if let Some((ref last_mode, _)) = *normalized {
+            if *last_mode == mode {
+                return;
+            }
+        }
+
+        let mut others = self.other_space_normalized.borrow_mut();
 
-                XmlSpace::Preserve => XmlSpaceNormalize::Preserve,
-            };
+        let string = match others.iter().position(|(m, _)| *m == mode) {
+            Some(i) => others.swap_remove(i).1,
+            None => xml_space_normalize(mode, &self.string.borrow()),
+        };
 
-            *normalized = Some(xml_space_normalize(mode, &self.string.borrow()));
+        if let Some(last) = normalized.replace((mode, string)) {
+            others.push(last);
         }
     }
 
+    /// Returns the string normalized by the last call to `ensure_normalized_string`.
+    fn normalized_string(&self) -> Ref<'_, str> {
+        Ref::map(self.space_normalized.borrow(), |n| {
+            n.as_ref().unwrap().1.as_str()
+        })
+    }
+
     fn make_span(
         &self,
         node: &Node,
         values: Rc<ComputedValues>,
-        dx: f64,
-        dy: f64,
+        delta: &mut PendingDelta,
         depth: usize,
+        rotate: &[Rc<Vec<f64>>],
+        at_word_start: bool,
     ) -> Option<Span> {
         self.ensure_normalized_string(node, &*values);
 
-        if self.space_normalized.borrow().as_ref().unwrap() == "" {
+        if self.normalized_string().is_empty() {
             None
         } else {
-            Some(Span::new(
-                self.space_normalized.borrow().as_ref().unwrap(),
-                values,
-                dx,
-                dy,
-                depth,
-            ))
+            let (dx, dy) = delta.take();
+
+            // The case gets changed after collapsing whitespace, so that `capitalize`
+            // sees the same word boundaries as the rendered text.
+            let text = values.text_transform().apply_with_word_start(
+                &self.normalized_string(),
+                &values.xml_lang(),
+                at_word_start,
+            );
+
+            Some(Span::new(&text, values, dx, dy, depth, rotate.to_vec()))
         }
     }
 
@@ -442,11 +1634,19 @@ impl Chars {
         node: &Node,
         values: Rc<ComputedValues>,
         chunks: &mut Vec<Chunk>,
-        dx: f64,
-        dy: f64,
+        delta: &mut PendingDelta,
         depth: usize,
+        rotate: &[Rc<Vec<f64>>],
     ) {
-        if let Some(span) = self.make_span(&node, values, dx, dy, depth) {
+        // A word can continue from the text of the preceding span, even if that is in
+        // another element or chunk.
+        let at_word_start = chunks
+            .iter()
+            .rev()
+            .find_map(|chunk| chunk.spans.last())
+            .map_or(true, |span| span.text.ends_with(char::is_whitespace));
+
+        if let Some(span) = self.make_span(&node, values, delta, depth, rotate, at_word_start) {
             let num_chunks = chunks.len();
             assert!(num_chunks > 0);
 
@@ -457,6 +1657,12 @@ impl Chars {
     pub fn get_string(&self) -> String {
         self.string.borrow().clone()
     }
+
+    /// Returns the string after applying the `white-space` rules from the `values`.
+    pub fn get_normalized_string(&self, node: &Node, values: &ComputedValues) -> String {
+        self.ensure_normalized_string(node, values);
+        self.normalized_string().to_string()
+    }
 }
 
 #[derive(Default)]
@@ -465,6 +1671,9 @@ pub struct Text {
     y: Length<Vertical>,
     dx: Length<Horizontal>,
     dy: Length<Vertical>,
+    text_length: Option<ULength<Horizontal>>,
+    length_adjust: LengthAdjust,
+    rotate: Option<Rotate>,
 }
 
 impl Text {
@@ -479,25 +1688,50 @@ impl Text {
     ) -> Vec<Chunk> {
         let mut chunks = Vec::new();
 
-        let values = cascaded.get();
+        let values = Rc::new(cascaded.get().clone());
         let view_params = draw_ctx.get_view_params();
-        let params = NormalizeParams::new(&values, &view_params);
+        let pango_context = pango::Context::from(&*draw_ctx);
+        let params = text_normalize_params(
+            &pango_context,
+            draw_ctx.font_measurements_cache(),
+            &values,
+            &view_params,
+        );
+
+        chunks.push(Chunk::new(values.clone(), Some(x), Some(y)));
 
-        chunks.push(Chunk::new(&values, Some(x), Some(y)));
+        let mut delta = PendingDelta {
+            dx: self.dx.to_user(&params),
+            dy: self.dy.to_user(&params),
+        };
 
-        let dx = self.dx.to_user(&params);
-        let dy = self.dy.to_user(&params);
+        let rotate: RotateLists = self.rotate.iter().map(|r| Rc::new(r.0.clone())).collect();
 
         children_to_chunks(
             &mut chunks,
             node,
             acquired_nodes,
             cascaded,
+            &values,
             draw_ctx,
-            dx,
-            dy,
+            &mut delta,
             0,
+            &rotate,
         );
+
+        let decorates = decorates_descendants(&values);
+        let bidi_control = bidi_control(&values);
+
+        for span in chunks.iter_mut().flat_map(|chunk| chunk.spans.iter_mut()) {
+            if decorates && !Rc::ptr_eq(&span.values, &values) {
+                span.decorations.insert(0, values.clone());
+            }
+
+            if let Some(c) = bidi_control {
+                span.bidi_controls.insert(0, c);
+            }
+        }
+
         chunks
     }
 }
@@ -510,6 +1744,9 @@ impl SetAttributes for Text {
                 expanded_name!("", "y") => self.y = attr.parse(value)?,
                 expanded_name!("", "dx") => self.dx = attr.parse(value)?,
                 expanded_name!("", "dy") => self.dy = attr.parse(value)?,
+                expanded_name!("", "textLength") => self.text_length = attr.parse(value)?,
+                expanded_name!("", "lengthAdjust") => self.length_adjust = attr.parse(value)?,
+                expanded_name!("", "rotate") => self.rotate = attr.parse(value)?,
                 _ => (),
             }
         }
@@ -518,6 +1755,60 @@ impl SetAttributes for Text {
     }
 }
 
+impl Text {
+    /// Lays out the text in chunks, and positions each of their spans.
+    fn position_chunks(
+        &self,
+        node: &Node,
+        acquired_nodes: &mut AcquiredNodes<'_>,
+        cascaded: &CascadedValues<'_>,
+        draw_ctx: &mut DrawingCtx,
+        view_params: &ViewParams,
+        params: &NormalizeParams,
+    ) -> Vec<PositionedChunk> {
+        // Text elements cannot establish a new viewport, so all the spans
+        // get normalized against the same one.
+        let mut x = self.x.to_user(params);
+        let mut y = self.y.to_user(params);
+
+        let chunks = self.make_chunks(node, acquired_nodes, cascaded, draw_ctx, x, y);
+
+        let mut rotate_counts = RotateCounts::default();
+
+        let mut measured_chunks = Vec::new();
+        for chunk in &chunks {
+            // textLength adjusts the letter spacing of each span's layout.
+            measured_chunks.push(MeasuredChunk::from_chunk(
+                chunk,
+                draw_ctx,
+                view_params,
+                &mut rotate_counts,
+                self.text_length.is_none(),
+            ));
+        }
+
+        if let Some(text_length) = self.text_length {
+            let text_length = text_length.to_user(params);
+            adjust_text_length(&mut measured_chunks, text_length, self.length_adjust);
+        }
+
+        let mut positioned_chunks = Vec::new();
+        for chunk in &measured_chunks {
+            let chunk_x = chunk.x.unwrap_or(x);
+            let chunk_y = chunk.y.unwrap_or(y);
+
+            let positioned = PositionedChunk::from_measured(chunk, chunk_x, chunk_y);
+
+            x = positioned.next_chunk_x;
+            y = positioned.next_chunk_y;
+
+            positioned_chunks.push(positioned);
+        }
+
+        positioned_chunks
+    }
+}
+
 impl Draw for Text {
     fn draw(
         &self,
@@ -533,7 +1824,26 @@ impl Draw for Text {
 
         let elt = node.borrow_element();
 
-        let stacking_ctx = StackingContext::new(acquired_nodes, &elt, values.transform(), values);
+        // The glyph cells only get known after laying out the text, so doing that for
+        // the reference box of `transform-box` takes a pass of its own.  The layouts
+        // for drawing get made inside the layer, with its transform.
+        let transform = values.transform(&view_params, || {
+            let chunks = self.position_chunks(
+                node,
+                acquired_nodes,
+                cascaded,
+                draw_ctx,
+                &view_params,
+                &params,
+            );
+
+            chunks
+                .iter()
+                .flat_map(|chunk| chunk.spans.iter())
+                .filter_map(PositionedSpan::object_box)
+                .reduce(|a, b| a.union(&b))
+        });
+        let stacking_ctx = StackingContext::new(acquired_nodes, &elt, transform, values);
 
         draw_ctx.with_discrete_layer(
             &stacking_ctx,
@@ -542,36 +1852,324 @@ impl Draw for Text {
             clipping,
             None,
             &mut |an, dc| {
-                let mut x = self.x.to_user(&params);
-                let mut y = self.y.to_user(&params);
+                let positioned_chunks =
+                    self.position_chunks(node, an, cascaded, dc, &view_params, &params);
+
+                // A layer can span several chunks, so draw all the spans in one go.
+                let spans: Vec<(usize, &PositionedSpan)> = positioned_chunks
+                    .iter()
+                    .enumerate()
+                    .flat_map(|(i, chunk)| chunk.spans.iter().map(move |span| (i, span)))
+                    .collect();
+
+                let mut chunk_bboxes = vec![dc.empty_bbox(); positioned_chunks.len()];
+
+                let bbox = draw_spans_in_layers(
+                    &spans,
+                    0,
+                    an,
+                    dc,
+                    &view_params,
+                    cascaded,
+                    clipping,
+                    &mut chunk_bboxes,
+                )?;
+
+                // Each chunk is a line of text, so it gets its own hit region.
+                for chunk_bbox in &chunk_bboxes {
+                    dc.add_link_region(chunk_bbox);
+                }
 
-                let chunks = self.make_chunks(node, an, cascaded, dc, x, y);
+                Ok(bbox)
+            },
+        )
+    }
+}
 
-                let mut measured_chunks = Vec::new();
-                for chunk in &chunks {
-                    measured_chunks.push(MeasuredChunk::from_chunk(chunk, dc));
-                }
+/// The SVG 1.2 Tiny `textArea` element.
+///
+/// Its text is flowed into the rectangle given by `x`, `y`, `width`, and `height`,
+/// breaking lines between words.  Lines that do not fit in the height are not
+/// rendered.  An `auto` width does not wrap the text, and an `auto` height does
+/// not clip it.
+///
+/// https://www.w3.org/TR/SVGTiny12/text.html#TextAreaElement
+#[derive(Default)]
+pub struct TextArea {
+    x: Length<Horizontal>,
+    y: Length<Vertical>,
+    width: LengthOrAuto<Horizontal>,
+    height: LengthOrAuto<Vertical>,
+}
+
+impl SetAttributes for TextArea {
+    fn set_attributes(&mut self, attrs: &Attributes) -> ElementResult {
+        for (attr, value) in attrs.iter() {
+            match attr.expanded() {
+                expanded_name!("", "x") => self.x = attr.parse(value)?,
+                expanded_name!("", "y") => self.y = attr.parse(value)?,
+                expanded_name!("", "width") => self.width = attr.parse(value)?,
+                expanded_name!("", "height") => self.height = attr.parse(value)?,
+                _ => (),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Draw for TextArea {
+    fn draw(
+        &self,
+        node: &Node,
+        acquired_nodes: &mut AcquiredNodes<'_>,
+        cascaded: &CascadedValues<'_>,
+        draw_ctx: &mut DrawingCtx,
+        clipping: bool,
+    ) -> Result<BoundingBox, RenderingError> {
+        let values = cascaded.get();
+        let view_params = draw_ctx.get_view_params();
+        let params = NormalizeParams::new(&values, &view_params);
+
+        let elt = node.borrow_element();
 
-                let mut positioned_chunks = Vec::new();
-                for chunk in &measured_chunks {
-                    let chunk_x = chunk.x.unwrap_or(x);
-                    let chunk_y = chunk.y.unwrap_or(y);
+        let transform = values.transform(&view_params, || None);
+        let stacking_ctx = StackingContext::new(acquired_nodes, &elt, transform, values);
 
-                    let positioned = PositionedChunk::from_measured(&chunk, dc, chunk_x, chunk_y);
+        let text = node.get_text_content(true);
+
+        draw_ctx.with_discrete_layer(
+            &stacking_ctx,
+            acquired_nodes,
+            values,
+            clipping,
+            None,
+            &mut |an, dc| {
+                let properties = FontProperties::new(values, &params);
+                let layout = create_pango_layout(dc, &properties, &text);
 
-                    x = positioned.next_chunk_x;
-                    y = positioned.next_chunk_y;
+                layout.set_wrap(wrap_mode(values));
+                layout.set_alignment(wrapped_text_alignment(values));
+                set_line_height(&layout, values, &params);
 
-                    positioned_chunks.push(positioned);
+                if let LengthOrAuto::Length(width) = self.width {
+                    layout.set_width(to_pango_units(width.to_user(&params)));
                 }
 
+                let text = match self.height {
+                    LengthOrAuto::Length(height) => {
+                        remove_lines_below(&layout, &text, height.to_user(&params))
+                    }
+                    LengthOrAuto::Auto => text,
+                };
+
+                let addressable_chars = addressable_characters(&text, &layout.log_attrs());
+
+                let x = self.x.to_user(&params);
+                let y = self.y.to_user(&params);
+
+                let span = PositionedSpan {
+                    layout,
+                    values: Rc::new(values.clone()),
+                    _position: (x, y),
+                    rendered_position: (x, y),
+                    next_span_x: x,
+                    next_span_y: y,
+                    params: params.clone(),
+                    length_adjust_scale: 1.0,
+                    spacing_after: 0.0,
+                    rotation: 0.0,
+                    shared_layout: None,
+                    addressable_chars,
+                    layers: Vec::new(),
+                    decorations: Vec::new(),
+                };
+
+                span.draw(an, dc, &view_params, cascaded, clipping)
+            },
+        )
+    }
+}
+
+/// Aligns the lines of wrapped text according to `text-anchor`.
+///
+/// A `<text>` gets moved as a whole by `text_anchor_advance()`, but the lines of wrapped
+/// text get aligned within its width by Pango instead.  Like for `<text>`, the `start`
+/// and `end` anchors refer to the logical start and end of the lines, so for
+/// right-to-left text `start` is the right edge.
+fn wrapped_text_alignment(values: &ComputedValues) -> pango::Alignment {
+    match (values.text_anchor(), values.direction()) {
+        (TextAnchor::Middle, _) => pango::Alignment::Center,
+        (TextAnchor::Start, Direction::Ltr) | (TextAnchor::End, Direction::Rtl) => {
+            pango::Alignment::Left
+        }
+        (TextAnchor::Start, Direction::Rtl) | (TextAnchor::End, Direction::Ltr) => {
+            pango::Alignment::Right
+        }
+    }
+}
+
+/// Picks how Pango breaks lines, from the `word-break` and `overflow-wrap` properties.
+///
+/// By default, words are kept intact even if they overflow the line.
+fn wrap_mode(values: &ComputedValues) -> pango::WrapMode {
+    match (values.word_break(), values.overflow_wrap()) {
+        (WordBreak::BreakAll, _) => pango::WrapMode::Char,
+        (_, OverflowWrap::BreakWord) | (_, OverflowWrap::Anywhere) => pango::WrapMode::WordChar,
+        (_, OverflowWrap::Normal) => pango::WrapMode::Word,
+    }
+}
+
+/// Spaces the lines of a wrapped layout so that their baselines are `line-height` apart.
+///
+/// Pango's spacing goes from the bottom of one line to the top of the next one, so it is
+/// the line height minus the height of the font's lines.  The spacing can be negative if
+/// the line height is smaller than the font.
+fn set_line_height(layout: &pango::Layout, values: &ComputedValues, params: &NormalizeParams) {
+    let line_height = values.line_height().compute(values).to_user(params);
+
+    let font_height = layout
+        .context()
+        .and_then(|context| context.metrics(layout.font_description().as_ref(), None))
+        .map(|metrics| f64::from(metrics.ascent() + metrics.descent()) / f64::from(pango::SCALE))
+        .unwrap_or(line_height);
+
+    // A factor of zero tells Pango to use the spacing as given.
+    layout.set_line_spacing(0.0);
+    layout.set_spacing(to_pango_units(line_height - font_height));
+}
+
+/// Invisible unless Pango breaks a line after it, in which case it shows a hyphen.
+const SOFT_HYPHEN: char = '\u{ad}';
+
+/// Truncates the text of a wrapped layout to the lines that fit in `height`.
+///
+/// Returns the text that remains in the layout.
+fn remove_lines_below(layout: &pango::Layout, text: &str, height: f64) -> String {
+    let height = to_pango_units(height);
+
+    let mut iter = match layout.iter() {
+        Some(iter) => iter,
+        None => return text.to_string(),
+    };
+
+    loop {
+        let (_, line_bottom) = iter.line_yrange();
+
+        if line_bottom > height {
+            let end = iter.index() as usize;
+            let mut remaining = text[..end].to_string();
+
+            // Pango shows a hyphen when it breaks a line at a soft hyphen, but there is
+            // no break at the end of the text, so spell the hyphen out.
+            if remaining.ends_with(SOFT_HYPHEN) {
+                remaining.pop();
+                remaining.push('-');
+            }
+
+            layout.set_text(&remaining);
+            return remaining;
+        }
+
+        if !iter.next_line() {
+            return text.to_string();
+        }
+    }
+}
+
+/// The `flowRoot` element from the SVG 1.2 drafts, as emitted by Inkscape.
+///
+/// Each `flowPara` child is laid out as a paragraph in the area of the first `rect`
+/// inside the `flowRegion` child, one paragraph below the other.  Lines that do not
+/// fit in the area are not rendered.  Only rectangular regions are supported.
+#[derive(Default)]
+pub struct FlowRoot;
+
+impl SetAttributes for FlowRoot {}
+
+impl Draw for FlowRoot {
+    fn draw(
+        &self,
+        node: &Node,
+        acquired_nodes: &mut AcquiredNodes<'_>,
+        cascaded: &CascadedValues<'_>,
+        draw_ctx: &mut DrawingCtx,
+        clipping: bool,
+    ) -> Result<BoundingBox, RenderingError> {
+        let values = cascaded.get();
+        let view_params = draw_ctx.get_view_params();
+
+        let area = match flow_region_area(node, &view_params) {
+            Some(area) => area,
+            None => return Ok(draw_ctx.empty_bbox()),
+        };
+
+        let elt = node.borrow_element();
+
+        let transform = values.transform(&view_params, || None);
+        let stacking_ctx = StackingContext::new(acquired_nodes, &elt, transform, values);
+
+        draw_ctx.with_discrete_layer(
+            &stacking_ctx,
+            acquired_nodes,
+            values,
+            clipping,
+            None,
+            &mut |an, dc| {
+                let (x, y, width, height) = area;
+
                 let mut bbox = dc.empty_bbox();
+                let mut para_y = y;
+
+                let paragraphs = node.children().filter(|child| {
+                    child.is_element() && matches!(*child.borrow_element(), Element::FlowPara(_))
+                });
 
-                for chunk in &positioned_chunks {
-                    for span in &chunk.spans {
-                        let span_bbox = span.draw(an, dc, clipping)?;
-                        bbox.insert(&span_bbox);
+                for para in paragraphs {
+                    let remaining = y + height - para_y;
+                    if remaining <= 0.0 {
+                        break;
                     }
+
+                    let para_elt = para.borrow_element();
+                    let para_values = para_elt.get_computed_values();
+                    let params = NormalizeParams::new(para_values, &view_params);
+
+                    let text = para.get_text_content(true);
+
+                    let properties = FontProperties::new(para_values, &params);
+                    let layout = create_pango_layout(dc, &properties, &text);
+
+                    layout.set_wrap(wrap_mode(para_values));
+                    layout.set_alignment(wrapped_text_alignment(para_values));
+                    set_line_height(&layout, para_values, &params);
+                    layout.set_width(to_pango_units(width));
+                    let text = remove_lines_below(&layout, &text, remaining);
+                    let addressable_chars = addressable_characters(&text, &layout.log_attrs());
+
+                    let (_, layout_height) = layout.size();
+
+                    let span = PositionedSpan {
+                        layout,
+                        values: Rc::new(para_values.clone()),
+                        _position: (x, para_y),
+                        rendered_position: (x, para_y),
+                        next_span_x: x,
+                        next_span_y: para_y,
+                        params,
+                        length_adjust_scale: 1.0,
+                        spacing_after: 0.0,
+                        rotation: 0.0,
+                        shared_layout: None,
+                        addressable_chars,
+                        layers: Vec::new(),
+                        decorations: Vec::new(),
+                    };
+
+                    let span_bbox = span.draw(an, dc, &view_params, cascaded, clipping)?;
+                    bbox.insert(&span_bbox);
+
+                    para_y += f64::from(layout_height) / f64::from(pango::SCALE);
                 }
 
                 Ok(bbox)
@@ -580,6 +2178,53 @@ impl Draw for Text {
     }
 }
 
+/// Returns the `(x, y, width, height)` of the first `rect` in a `flowRoot`'s `flowRegion`.
+fn flow_region_area(node: &Node, view_params: &ViewParams) -> Option<(f64, f64, f64, f64)> {
+    let region = node.children().find(|child| {
+        child.is_element() && matches!(*child.borrow_element(), Element::FlowRegion(_))
+    })?;
+
+    let rect = region
+        .children()
+        .find(|child| child.is_element() && matches!(*child.borrow_element(), Element::Rect(_)))?;
+
+    let elt = rect.borrow_element();
+    let params = NormalizeParams::new(elt.get_computed_values(), view_params);
+
+    match *elt {
+        Element::Rect(ref rect) => Some(rect.area(&params)),
+        _ => unreachable!(),
+    }
+}
+
+/// The `flowRegion` element; see [`FlowRoot`].
+///
+/// It does not render its children; they define the area for the text.
+#[derive(Default)]
+pub struct FlowRegion;
+
+impl SetAttributes for FlowRegion {}
+
+impl Draw for FlowRegion {}
+
+/// The `flowPara` element; see [`FlowRoot`].
+#[derive(Default)]
+pub struct FlowPara;
+
+impl SetAttributes for FlowPara {}
+
+impl Draw for FlowPara {}
+
+/// The `flowSpan` element; see [`FlowRoot`].
+///
+/// Its text is part of the enclosing paragraph, which provides the style for it.
+#[derive(Default)]
+pub struct FlowSpan;
+
+impl SetAttributes for FlowSpan {}
+
+impl Draw for FlowSpan {}
+
 #[derive(Default)]
 pub struct TRef {
     link: Option<NodeId>,
@@ -592,52 +2237,134 @@ impl TRef {
         acquired_nodes: &mut AcquiredNodes<'_>,
         cascaded: &CascadedValues<'_>,
         chunks: &mut Vec<Chunk>,
+        delta: &mut PendingDelta,
         depth: usize,
+        rotate: &[Rc<Vec<f64>>],
     ) {
-        if self.link.is_none() {
-            return;
-        }
-
-        let link = self.link.as_ref().unwrap();
-
         let values = cascaded.get();
         if !values.is_displayed() {
             return;
         }
 
-        if let Ok(acquired) = acquired_nodes.acquire(link) {
-            let c = acquired.get();
-            extract_chars_children_to_chunks_recursively(
-                chunks,
-                &c,
-                Rc::new(values.clone()),
-                depth,
-            );
-        } else {
-            rsvg_log!(
-                "element {} references a nonexistent text source \"{}\"",
-                node,
-                link,
-            );
+        self.extract_chars(
+            node,
+            acquired_nodes,
+            Rc::new(values.clone()),
+            chunks,
+            delta,
+            depth,
+            rotate,
+        );
+    }
+
+    /// Appends the character data of the referenced element to `chunks`.
+    ///
+    /// A `<tref>` inside the referenced element contributes the character data that it
+    /// references in turn.  Each `<tref>` stays acquired while its text gets extracted,
+    /// so a chain of references that leads back to one of them gets caught.
+    fn extract_chars(
+        &self,
+        node: &Node,
+        acquired_nodes: &mut AcquiredNodes<'_>,
+        values: Rc<ComputedValues>,
+        chunks: &mut Vec<Chunk>,
+        delta: &mut PendingDelta,
+        depth: usize,
+        rotate: &[Rc<Vec<f64>>],
+    ) {
+        let link = match self.link {
+            Some(ref link) => link,
+            None => return,
+        };
+
+        let _self_acquired = match acquired_nodes.acquire_ref(node) {
+            Ok(n) => n,
+
+            Err(AcquireError::CircularReference(_)) => {
+                rsvg_log!("circular reference in element {}", node);
+                return;
+            }
+
+            _ => unreachable!(),
+        };
+
+        match acquired_nodes.acquire(link) {
+            Ok(acquired) => {
+                extract_chars_children_to_chunks_recursively(
+                    chunks,
+                    acquired_nodes,
+                    acquired.get(),
+                    values,
+                    delta,
+                    depth,
+                    rotate,
+                );
+            }
+
+            Err(AcquireError::LinkNotFound(_)) => {
+                rsvg_log!(
+                    "element {} references a nonexistent text source \"{}\"",
+                    node,
+                    link,
+                );
+            }
+
+            Err(e) => {
+                rsvg_log!(
+                    "element {} cannot use text source \"{}\": {}",
+                    node,
+                    link,
+                    e
+                );
+            }
         }
     }
 }
 
 fn extract_chars_children_to_chunks_recursively(
     chunks: &mut Vec<Chunk>,
+    acquired_nodes: &mut AcquiredNodes<'_>,
     node: &Node,
     values: Rc<ComputedValues>,
+    delta: &mut PendingDelta,
     depth: usize,
+    rotate: &[Rc<Vec<f64>>],
 ) {
+    if depth > limits::MAX_TEXT_NESTING_DEPTH {
+        rsvg_log!(
+            "ignoring children of {} since the text is nested too deeply",
+            node
+        );
+        return;
+    }
+
     for child in node.children() {
         let values = values.clone();
 
         if child.is_chars() {
             child
                 .borrow_chars()
-                .to_chunks(&child, values, chunks, 0.0, 0.0, depth)
+                .to_chunks(&child, values, chunks, delta, depth, rotate)
+        } else if let Element::TRef(ref tref) = *child.borrow_element() {
+            tref.extract_chars(
+                &child,
+                acquired_nodes,
+                values,
+                chunks,
+                delta,
+                depth + 1,
+                rotate,
+            )
         } else {
-            extract_chars_children_to_chunks_recursively(chunks, &child, values, depth + 1)
+            extract_chars_children_to_chunks_recursively(
+                chunks,
+                acquired_nodes,
+                &child,
+                values,
+                delta,
+                depth + 1,
+                rotate,
+            )
         }
     }
 }
@@ -664,6 +2391,7 @@ pub struct TSpan {
     y: Option<Length<Vertical>>,
     dx: Length<Horizontal>,
     dy: Length<Vertical>,
+    rotate: Option<Rotate>,
 }
 
 impl TSpan {
@@ -674,26 +2402,52 @@ impl TSpan {
         cascaded: &CascadedValues<'_>,
         draw_ctx: &mut DrawingCtx,
         chunks: &mut Vec<Chunk>,
-        dx: f64,
-        dy: f64,
+        delta: &mut PendingDelta,
         depth: usize,
+        rotate: &[Rc<Vec<f64>>],
     ) {
-        let values = cascaded.get();
-        if !values.is_displayed() {
+        if !cascaded.get().is_displayed() {
             return;
         }
 
+        let values = Rc::new(cascaded.get().clone());
+
         let view_params = draw_ctx.get_view_params();
-        let params = NormalizeParams::new(values, &view_params);
+        let pango_context = pango::Context::from(&*draw_ctx);
+        let params = text_normalize_params(
+            &pango_context,
+            draw_ctx.font_measurements_cache(),
+            &values,
+            &view_params,
+        );
 
         let x = self.x.map(|l| l.to_user(&params));
         let y = self.y.map(|l| l.to_user(&params));
 
-        let span_dx = dx + self.dx.to_user(&params);
-        let span_dy = dy + self.dy.to_user(&params);
+        // Where our spans will start, to put them in our layer afterwards.
+        let first_chunk = chunks.len() - 1;
+        let first_span = chunks[first_chunk].spans.len();
 
         if x.is_some() || y.is_some() {
-            chunks.push(Chunk::new(values, x, y));
+            chunks.push(Chunk::new(values.clone(), x, y));
+        }
+
+        // An absolute position replaces the offsets from the ancestors that did not
+        // get applied to any character yet.
+        if x.is_some() {
+            delta.dx = 0.0;
+        }
+
+        if y.is_some() {
+            delta.dy = 0.0;
+        }
+
+        delta.dx += self.dx.to_user(&params);
+        delta.dy += self.dy.to_user(&params);
+
+        let mut rotate = rotate.to_vec();
+        if let Some(ref r) = self.rotate {
+            rotate.push(Rc::new(r.0.clone()));
         }
 
         children_to_chunks(
@@ -701,14 +2455,78 @@ impl TSpan {
             node,
             acquired_nodes,
             cascaded,
+            &values,
             draw_ctx,
-            span_dx,
-            span_dy,
+            delta,
             depth,
+            &rotate,
         );
+
+        let bidi_control = bidi_control(&values);
+        let decoration = if decorates_descendants(&values) {
+            Some(values.clone())
+        } else {
+            None
+        };
+
+        let layer = if needs_span_layer(&values) {
+            Some(SpanLayer {
+                node: node.clone(),
+                values,
+            })
+        } else {
+            None
+        };
+
+        // Our descendants have already added their layers, bidi controls, and
+        // decorations, which go inside ours.  Our own character data already draws our
+        // decorations.
+        for (i, chunk) in chunks.iter_mut().enumerate().skip(first_chunk) {
+            let start = if i == first_chunk { first_span } else { 0 };
+
+            for span in &mut chunk.spans[start..] {
+                if let Some(ref layer) = layer {
+                    span.layers.insert(0, layer.clone());
+                }
+
+                if let Some(c) = bidi_control {
+                    span.bidi_controls.insert(0, c);
+                }
+
+                if let Some(ref decoration) = decoration {
+                    if !Rc::ptr_eq(&span.values, decoration) {
+                        span.decorations.insert(0, decoration.clone());
+                    }
+                }
+            }
+        }
     }
 }
 
+/// Whether an element with these `values` draws decoration lines over the text of its
+/// descendants.
+///
+/// The lines of `overline` are not supported yet.
+fn decorates_descendants(values: &ComputedValues) -> bool {
+    let decoration = values.text_decoration();
+    decoration.underline || decoration.strike
+}
+
+/// Whether the spans of a `<tspan>` with these `values` need a [`SpanLayer`].
+///
+/// Like for any other element, the compositing properties apply to the `<tspan>` as
+/// a whole; for example, glyphs that overlap inside a translucent `<tspan>` must not
+/// make the overlap darker.
+fn needs_span_layer(values: &ComputedValues) -> bool {
+    let Opacity(UnitInterval(opacity)) = values.opacity();
+
+    !opacity.approx_eq_cairo(1.0)
+        || values.mix_blend_mode() != MixBlendMode::Normal
+        || values.filter() != Filter::None
+        || values.mask().0.get().is_some()
+        || values.clip_path().0.get().is_some()
+}
+
 impl SetAttributes for TSpan {
     fn set_attributes(&mut self, attrs: &Attributes) -> ElementResult {
         for (attr, value) in attrs.iter() {
@@ -717,6 +2535,7 @@ impl SetAttributes for TSpan {
                 expanded_name!("", "y") => self.y = attr.parse(value)?,
                 expanded_name!("", "dx") => self.dx = attr.parse(value)?,
                 expanded_name!("", "dy") => self.dy = attr.parse(value)?,
+                expanded_name!("", "rotate") => self.rotate = attr.parse(value)?,
                 _ => (),
             }
         }
@@ -736,7 +2555,11 @@ impl From<FontStyle> for pango::Style {
         match s {
             FontStyle::Normal => pango::Style::Normal,
             FontStyle::Italic => pango::Style::Italic,
-            FontStyle::Oblique => pango::Style::Oblique,
+            FontStyle::Oblique(None) => pango::Style::Oblique,
+
+            // An explicit angle is synthesized from the upright face; see
+            // `FontStyle::synthetic_slant()`.
+            FontStyle::Oblique(Some(_)) => pango::Style::Normal,
         }
     }
 }
@@ -764,6 +2587,7 @@ impl From<FontStretch> for pango::Stretch {
             FontStretch::Expanded => pango::Stretch::Expanded,
             FontStretch::ExtraExpanded => pango::Stretch::ExtraExpanded,
             FontStretch::UltraExpanded => pango::Stretch::UltraExpanded,
+            FontStretch::Percentage(_) => pango::Stretch::from(s.nearest_keyword()),
         }
     }
 }
@@ -783,15 +2607,6 @@ impl From<Direction> for pango::Direction {
     }
 }
 
-impl From<Direction> for pango::Alignment {
-    fn from(d: Direction) -> pango::Alignment {
-        match d {
-            Direction::Ltr => pango::Alignment::Left,
-            Direction::Rtl => pango::Alignment::Right,
-        }
-    }
-}
-
 impl From<WritingMode> for pango::Direction {
     fn from(m: WritingMode) -> pango::Direction {
         match m {
@@ -814,89 +2629,1402 @@ impl From<WritingMode> for pango::Gravity {
     }
 }
 
-fn create_pango_layout(draw_ctx: &DrawingCtx, props: &FontProperties, text: &str) -> pango::Layout {
-    let pango_context = pango::Context::from(draw_ctx);
+/// Logical size of a string, as computed by [`measure_string`](crate::measure_string).
+///
+/// All values are in pixels.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct StringMetrics {
+    /// Width of the laid-out text.
+    pub width: f64,
 
-    if let XmlLang(Some(ref lang)) = props.xml_lang {
-        pango_context.set_language(&pango::Language::from_string(lang));
-    }
+    /// Height of the line box.
+    pub height: f64,
 
-    pango_context.set_base_gravity(pango::Gravity::from(props.writing_mode));
+    /// Distance from the top of the line box to the baseline.
+    pub baseline: f64,
+}
 
-    match (props.unicode_bidi, props.direction) {
-        (UnicodeBidi::Override, _) | (UnicodeBidi::Embed, _) => {
-            pango_context.set_base_dir(pango::Direction::from(props.direction));
-        }
+/// The addressable characters of laid out text, for the text content queries of the
+/// SVG DOM's `SVGTextContentElement` interface.
+///
+/// The characters are numbered in the order of the text content, and collapsed white
+/// space does not count.  All positions are in the user space of the text element.
+pub struct TextCharacters {
+    chars: Vec<TextCharacter>,
+}
 
-        (_, direction) if direction != Direction::Ltr => {
-            pango_context.set_base_dir(pango::Direction::from(direction));
-        }
+struct TextCharacter {
+    layout: pango::Layout,
 
-        (_, _) => {
-            pango_context.set_base_dir(pango::Direction::from(props.writing_mode));
-        }
-    }
+    /// Transformation from the layout's coordinates to the text's user space.
+    transform: Transform,
 
-    let mut font_desc = pango_context.font_description().unwrap();
-    font_desc.set_family(props.font_family.as_str());
-    font_desc.set_style(pango::Style::from(props.font_style));
+    /// Byte range of the character in the layout's text.
+    range: Range<usize>,
 
-    // PANGO_VARIANT_SMALL_CAPS does nothing: https://gitlab.gnome.org/GNOME/pango/-/issues/566
-    // see below for using the "smcp" OpenType feature for fonts that support it.
-    // font_desc.set_variant(pango::Variant::from(props.font_variant));
+    rotation: f64,
 
-    font_desc.set_weight(pango::Weight::from(props.font_weight));
-    font_desc.set_stretch(pango::Stretch::from(props.font_stretch));
+    /// Spacing after the character that is not in the layout; see
+    /// `TextSpanGeometry::spacing_after`.
+    spacing_after: f64,
+}
 
-    font_desc.set_size(to_pango_units(props.font_size));
+impl TextCharacters {
+    pub fn new(spans: Vec<layout::TextSpanGeometry>) -> TextCharacters {
+        let chars = spans
+            .into_iter()
+            .flat_map(|span| {
+                let layout = span.layout;
+                let transform = span.transform;
+                let rotation = span.rotation;
+                let num_chars = span.chars.len();
+                let spacing_after = span.spacing_after;
+
+                span.chars
+                    .into_iter()
+                    .enumerate()
+                    .map(move |(i, range)| TextCharacter {
+                        layout: layout.clone(),
+                        transform,
+                        range,
+                        rotation,
+                        spacing_after: if i + 1 == num_chars {
+                            spacing_after
+                        } else {
+                            0.0
+                        },
+                    })
+            })
+            .collect();
 
-    let layout = pango::Layout::new(&pango_context);
-    layout.set_auto_dir(false);
-    layout.set_font_description(Some(&font_desc));
+        TextCharacters { chars }
+    }
 
-    // FIXME: For now we ignore the `line-height` property, even though we parse it.
-    // We would need to do something like this:
-    //
-    // layout.set_line_spacing(0.0); // "actually use the spacing I'll give you"
-    // layout.set_spacing(to_pango_units(???));
-    //
-    // However, Layout::set_spacing() takes an inter-line spacing (from the baseline of
-    // one line to the top of the next line), not the line height (from baseline to
-    // baseline).
-    //
-    // Maybe we need to implement layout of individual lines by hand.
+    /// The number of addressable characters, like the SVG DOM's `getNumberOfChars`.
+    ///
+    /// The valid indices for the other queries go from 0 up to this number.
+    pub fn number_of_chars(&self) -> usize {
+        self.chars.len()
+    }
 
-    let attr_list = pango::AttrList::new();
+    /// The point on the baseline where the glyph for a character starts, like the SVG
+    /// DOM's `getStartPositionOfChar`.
+    ///
+    /// Returns `None` if there is no character at `index`.
+    pub fn start_position_of_char(&self, index: usize) -> Option<(f64, f64)> {
+        self.chars.get(index).map(|c| c.start_position())
+    }
 
-    attr_list.insert(pango::Attribute::new_letter_spacing(to_pango_units(
-        props.letter_spacing,
-    )));
+    /// The total advance of all the characters, like the SVG DOM's
+    /// `getComputedTextLength`.
+    ///
+    /// This includes letter and word spacing, and the adjustments for `textLength`.  The
+    /// shifts from `dx` and `dy` move the characters but do not change their advances,
+    /// so they are not included.
+    pub fn computed_text_length(&self) -> f64 {
+        self.chars.iter().map(|c| c.advance()).sum()
+    }
 
-    if props.text_decoration.underline {
-        attr_list.insert(pango::Attribute::new_underline(pango::Underline::Single));
+    /// The bounding box of a character's cell, like the SVG DOM's `getExtentOfChar`.
+    ///
+    /// The cell spans the advance of the character along the inline direction, and the
+    /// ascent and descent of the line.  For a rotated glyph, the result is the bounding
+    /// box of the rotated cell.  Returns `None` if there is no character at `index`.
+    pub fn extent_of_char(&self, index: usize) -> Option<Rect> {
+        self.chars.get(index).map(|c| c.extent())
     }
 
-    if props.text_decoration.strike {
-        attr_list.insert(pango::Attribute::new_strikethrough(true));
+    /// The rotation in degrees of a character's glyph, like the SVG DOM's
+    /// `getRotationOfChar`.
+    ///
+    /// This is the value from the `rotate` attribute, combined with
+    /// `glyph-orientation-horizontal`; glyphs without a rotation return 0.  Returns
+    /// `None` if there is no character at `index`.
+    pub fn rotation_of_char(&self, index: usize) -> Option<f64> {
+        self.chars.get(index).map(|c| c.rotation)
     }
 
-    // FIXME: Using the "smcp" OpenType feature only works for fonts that support it.  We
-    // should query if the font supports small caps, and synthesize them if it doesn't.
-    if props.font_variant == FontVariant::SmallCaps {
-        // smcp - small capitals - https://docs.microsoft.com/en-ca/typography/opentype/spec/features_pt#smcp
-        attr_list.insert(pango::Attribute::new_font_features("'smcp' 1"));
+    /// The character whose cell contains a point, like the SVG DOM's
+    /// `getCharNumAtPosition`.
+    ///
+    /// If the cells of several characters contain the point, this returns the one that
+    /// gets drawn last.  Returns `None` if there is no such character.
+    pub fn char_num_at_position(&self, x: f64, y: f64) -> Option<usize> {
+        self.chars.iter().rposition(|c| c.contains_point(x, y))
+    }
+
+    /// The total advance of `nchars` characters starting at `charnum`, like the SVG
+    /// DOM's `getSubStringLength`.
+    ///
+    /// The range may cross the boundaries of spans and chunks; if it goes past the last
+    /// character, it stops there.  Returns `None` if there is no character at `charnum`.
+    pub fn sub_string_length(&self, charnum: usize, nchars: usize) -> Option<f64> {
+        self.chars.get(charnum)?;
+
+        Some(
+            self.chars[charnum..]
+                .iter()
+                .take(nchars)
+                .map(|c| c.advance())
+                .sum(),
+        )
+    }
+
+    /// The rectangles that cover the characters from `start` up to `end`, for drawing
+    /// a selection highlight.
+    ///
+    /// There is one rectangle for each run of selected characters that are next to
+    /// each other on a line, so a selection that crosses chunks, lines, or a change in
+    /// direction gets several of them.  Each rectangle covers the logical extents of its
+    /// line.  The range stops at the last character; if it is empty, there are no
+    /// rectangles.
+    pub fn selection_extents(&self, start: usize, end: usize) -> Vec<Rect> {
+        let end = end.min(self.chars.len());
+        if start >= end {
+            return Vec::new();
+        }
+
+        let mut rects = Vec::new();
+        let mut chars = self.chars[start..end].iter().peekable();
+
+        while let Some(first) = chars.next() {
+            let mut range = first.range.clone();
+
+            // Characters from spans that share a layout get selected together, so that
+            // Pango finds their runs in visual order.
+            while let Some(next) = chars.peek() {
+                if next.layout != first.layout || next.transform != first.transform {
+                    break;
+                }
+
+                range.start = range.start.min(next.range.start);
+                range.end = range.end.max(next.range.end);
+                chars.next();
+            }
+
+            rects.extend(
+                range_extents(&first.layout, &range)
+                    .iter()
+                    .map(|r| first.transform.transform_rect(r)),
+            );
+        }
+
+        rects
+    }
+}
+
+impl TextCharacter {
+    /// The character's cell in the layout, in Pango units.
+    ///
+    /// The cell goes from the leading to the trailing edge of the character's grapheme,
+    /// so its width is negative for right-to-left text.  Vertically, it covers the
+    /// logical extents of the line.
+    fn layout_cell(&self) -> pango::Rectangle {
+        self.layout.index_to_pos(self.range.start as i32)
+    }
+
+    fn start_position(&self) -> (f64, f64) {
+        let pango_scale = f64::from(pango::SCALE);
+
+        let cell = self.layout_cell();
+        let baseline = baseline_at_index(&self.layout, self.range.start);
+
+        self.transform.transform_point(
+            f64::from(cell.x) / pango_scale,
+            f64::from(baseline) / pango_scale,
+        )
+    }
+
+    fn extent(&self) -> Rect {
+        let pango_scale = f64::from(pango::SCALE);
+
+        let cell = self.layout_cell();
+        let x0 = f64::from(cell.x) / pango_scale;
+        let x1 = f64::from(cell.x + cell.width) / pango_scale;
+        let y0 = f64::from(cell.y) / pango_scale;
+        let y1 = f64::from(cell.y + cell.height) / pango_scale;
+
+        let rect = Rect::new(x0.min(x1), y0, x0.max(x1), y1);
+
+        self.transform.transform_rect(&rect)
+    }
+
+    /// Whether the character's cell contains a point in the text's user space.
+    ///
+    /// A span that shares its layout with others only owns some of the layout's
+    /// characters, so Pango's hit testing is checked against this character's range.
+    fn contains_point(&self, x: f64, y: f64) -> bool {
+        let inverse = match self.transform.invert() {
+            Some(inverse) => inverse,
+            None => return false,
+        };
+
+        let (lx, ly) = inverse.transform_point(x, y);
+
+        let (inside, index, _trailing) = self
+            .layout
+            .xy_to_index(to_pango_units(lx), to_pango_units(ly));
+
+        inside && self.range.contains(&(index as usize))
+    }
+
+    /// The advance of the character, in the text's user space.
+    fn advance(&self) -> f64 {
+        let pango_scale = f64::from(pango::SCALE);
+
+        let cell = self.layout_cell();
+        let (dx, dy) = self
+            .transform
+            .transform_distance(f64::from(cell.width) / pango_scale, 0.0);
+
+        dx.hypot(dy) + self.spacing_after
+    }
+}
+
+/// The rectangles that cover the bytes in `range` of a layout, in the layout's
+/// coordinates.
+///
+/// Pango splits the range of each line into the visual runs of its characters, so
+/// bidirectional text may get several rectangles on the same line.
+fn range_extents(layout: &pango::Layout, range: &Range<usize>) -> Vec<Rect> {
+    let pango_scale = f64::from(pango::SCALE);
+
+    let mut rects = Vec::new();
+
+    let mut iter = match layout.iter() {
+        Some(iter) => iter,
+        None => return rects,
+    };
+
+    loop {
+        let line_start = iter.index() as usize;
+        let (line_top, line_bottom) = iter.line_yrange();
+        let line = iter.line_readonly();
+
+        let has_next_line = iter.next_line();
+        let line_end = if has_next_line {
+            iter.index() as usize
+        } else {
+            range.end
+        };
+
+        let start = range.start.max(line_start);
+        let end = range.end.min(line_end);
+
+        if let (Some(line), true) = (line, start < end) {
+            let y0 = f64::from(line_top) / pango_scale;
+            let y1 = f64::from(line_bottom) / pango_scale;
+
+            for pair in line.x_ranges(start as i32, end as i32).chunks(2) {
+                if let [x0, x1] = *pair {
+                    let x0 = f64::from(x0) / pango_scale;
+                    let x1 = f64::from(x1) / pango_scale;
+                    rects.push(Rect::new(x0, y0, x1, y1));
+                }
+            }
+        }
+
+        if !has_next_line || line_end >= range.end {
+            break;
+        }
+    }
+
+    rects
+}
+
+/// The baseline of the layout's line that contains the byte `index`, in Pango units.
+fn baseline_at_index(layout: &pango::Layout, index: usize) -> i32 {
+    let mut baseline = layout.baseline();
+
+    if let Some(mut iter) = layout.iter() {
+        while iter.index() as usize <= index {
+            baseline = iter.baseline();
+
+            if !iter.next_line() {
+                break;
+            }
+        }
+    }
+
+    baseline
+}
+
+/// Measures a string with the CSS declarations in `style`, outside of any document.
+///
+/// The properties that the `style` does not mention have their initial values, and
+/// lengths get resolved with the `dpi`.  The string gets laid out with a Pango context
+/// of its own, since laying out text changes the language and direction of the context.
+pub fn measure_string_with_style(
+    font_map: &pango::FontMap,
+    style: &str,
+    text: &str,
+    dpi: Dpi,
+) -> StringMetrics {
+    let mut specified = SpecifiedValues::default();
+
+    // Invalid declarations get ignored, just like in a `style` attribute.
+    let _ = specified.parse_style_declarations(style, Origin::Author, &mut HashSet::new());
+
+    let mut values = ComputedValues::default();
+    specified.to_computed_values(&mut values);
+
+    let pango_context = create_pango_context(font_map);
+    let cache = FontMeasurementsCache::default();
+    let view_params = ViewParams::new(dpi, 0.0, 0.0);
+    let params = text_normalize_params(&pango_context, &cache, &values, &view_params);
+    let props = FontProperties::new(&values, &params);
+
+    measure_string(&pango_context, &cache, &props, text)
+}
+
+/// Measures a string outside of any text element.
+///
+/// The string goes through the same layout code as the spans in `<text>`, so the
+/// result matches what would get rendered with the same `props`.  The lengths in
+/// `FontProperties` are already resolved to user space by `to_user`, so the returned
+/// metrics are in user space units as well.
+///
+/// This changes the language, gravity, and base direction of the `pango_context`.
+fn measure_string(
+    pango_context: &pango::Context,
+    cache: &FontMeasurementsCache,
+    props: &FontProperties,
+    text: &str,
+) -> StringMetrics {
+    let synthetic_small_caps = needs_synthetic_small_caps(pango_context, cache, props);
+    let attr_list = TextAttributes::new(props, synthetic_small_caps).to_attr_list();
+    let layout = create_pango_layout_with_context(pango_context, cache, props, &attr_list, text);
+
+    if synthetic_small_caps {
+        synthesize_small_caps(&layout, props);
+    }
+
+    apply_word_spacing(&layout, props);
+
+    let (w, h) = layout.size();
+
+    StringMetrics {
+        width: f64::from(w) / f64::from(pango::SCALE),
+        height: f64::from(h) / f64::from(pango::SCALE),
+        baseline: f64::from(layout.baseline()) / f64::from(pango::SCALE),
+    }
+}
+
+fn create_pango_layout(draw_ctx: &DrawingCtx, props: &FontProperties, text: &str) -> pango::Layout {
+    let pango_context = pango::Context::from(draw_ctx);
+    let cache = draw_ctx.font_measurements_cache();
+    let synthetic_small_caps = needs_synthetic_small_caps(&pango_context, cache, props);
+    let attr_list = draw_ctx
+        .text_attributes_cache()
+        .attr_list(props, synthetic_small_caps);
+    let layout = create_pango_layout_with_context(&pango_context, cache, props, &attr_list, text);
+
+    if synthetic_small_caps {
+        synthesize_small_caps(&layout, props);
+    }
+
+    apply_word_spacing(&layout, props);
+
+    if let Some(font_desc) = layout.font_description() {
+        report_font_substitution(draw_ctx, &pango_context, &font_desc, props);
+    }
+
+    layout
+}
+
+/// Tells the font substitution callback if Pango will use a different font than the
+/// one in `font-family`.
+fn report_font_substitution(
+    draw_ctx: &DrawingCtx,
+    pango_context: &pango::Context,
+    font_desc: &pango::FontDescription,
+    props: &FontProperties,
+) {
+    if let Some(ref callback) = draw_ctx.text_options().font_substitution_callback {
+        if let Some(substitution) = font_substitution(pango_context, font_desc, props) {
+            callback(&substitution);
+        }
+    }
+}
+
+/// Finds out if Pango will use a different font than the one in `font-family`.
+fn font_substitution(
+    pango_context: &pango::Context,
+    font_desc: &pango::FontDescription,
+    props: &FontProperties,
+) -> Option<FontSubstitution> {
+    let font = pango_context.load_font(font_desc)?;
+    let actual = font.describe()?.family()?;
+
+    let requested = props.font_family.as_str();
+    let unavailable = unavailable_families(requested, &actual);
+
+    if unavailable.is_empty() {
+        None
+    } else {
+        rsvg_log!(
+            "font families {:?} from \"{}\" are not available; using \"{}\" instead",
+            unavailable,
+            requested,
+            actual
+        );
+
+        Some(FontSubstitution {
+            requested: requested.to_string(),
+            actual: actual.to_string(),
+            unavailable,
+        })
+    }
+}
+
+/// Creates a layout for `text` with the given attributes.
+///
+/// The `attr_list` usually comes from the `TextAttributes` for the `props`.  It does not
+/// get modified, so it can be shared among layouts.
+fn create_pango_layout_with_context(
+    pango_context: &pango::Context,
+    cache: &FontMeasurementsCache,
+    props: &FontProperties,
+    attr_list: &pango::AttrList,
+    text: &str,
+) -> pango::Layout {
+    if let XmlLang(Some(ref lang)) = props.xml_lang {
+        pango_context.set_language(&pango::Language::from_string(lang));
+    }
+
+    pango_context.set_base_gravity(pango::Gravity::from(props.writing_mode));
+
+    match (props.unicode_bidi, props.direction) {
+        (UnicodeBidi::Override, _) | (UnicodeBidi::Embed, _) => {
+            pango_context.set_base_dir(pango::Direction::from(props.direction));
+        }
+
+        (_, direction) if direction != Direction::Ltr => {
+            pango_context.set_base_dir(pango::Direction::from(direction));
+        }
+
+        (_, _) => {
+            pango_context.set_base_dir(pango::Direction::from(props.writing_mode));
+        }
+    }
+
+    let font_desc = font_description(pango_context, cache, props);
+
+    let layout = pango::Layout::new(pango_context);
+    layout.set_auto_dir(false);
+    layout.set_font_description(Some(&font_desc));
+
+    // The `line-height` property only matters for layouts with more than one line; the
+    // elements that wrap their text call set_line_height() on their layouts.
+
+    layout.set_attributes(Some(attr_list));
+    // The position of the text is determined by text-anchor in text_anchor_advance(),
+    // which already considers the direction.  Pango's alignment would shift RTL text a
+    // second time if the layout ever got a width, so always lay out from the left edge;
+    // only wrapped text changes this with wrapped_text_alignment().
+    layout.set_alignment(pango::Alignment::Left);
+
+    if text.contains('\t') {
+        layout.set_tabs(Some(&tab_array(pango_context, &font_desc, props)));
     }
 
-    layout.set_attributes(Some(&attr_list));
-    layout.set_alignment(pango::Alignment::from(props.direction));
     layout.set_text(text);
 
     layout
 }
 
+/// Makes the font description for a span's font properties.
+///
+/// For `font-size-adjust`, the x-height of the font comes from the `cache`.
+fn font_description(
+    pango_context: &pango::Context,
+    cache: &FontMeasurementsCache,
+    props: &FontProperties,
+) -> pango::FontDescription {
+    let mut font_desc = pango_context.font_description().unwrap();
+    font_desc.set_family(props.font_family.as_str());
+    font_desc.set_style(pango::Style::from(props.font_style));
+
+    // PANGO_VARIANT_SMALL_CAPS does nothing: https://gitlab.gnome.org/GNOME/pango/-/issues/566
+    // see below for using the "smcp" OpenType feature for fonts that support it, and
+    // synthesize_small_caps() for the ones that don't.
+    // font_desc.set_variant(pango::Variant::from(props.font_variant));
+
+    font_desc.set_weight(pango::Weight::from(props.font_weight));
+    font_desc.set_stretch(pango::Stretch::from(props.font_stretch));
+
+    // Variable fonts can have any width, not just the ones of the keywords.  Fonts
+    // without a "wdth" axis ignore this and use the face picked from the stretch.
+    if let FontStretch::Percentage(p) = props.font_stretch {
+        font_desc.set_variations(Some(&format!("wdth={}", p * 100.0)));
+    }
+
+    font_desc.set_size(to_pango_units(props.font_size));
+
+    if props.font_size_adjust != FontSizeAdjust::None {
+        // The x-height of the font at the unadjusted size determines the adjusted one.
+        let x_height = cache.get(pango_context, &font_desc).x_height;
+        let font_size = props.font_size_adjust.adjust(props.font_size, x_height);
+        font_desc.set_size(to_pango_units(font_size));
+    }
+
+    font_desc
+}
+
+/// The font properties that Pango does not take from the font description.
+///
+/// These go into a layout's attribute list.  Spans with equal `TextAttributes` can share
+/// the same list; see `TextAttributesCache`.
+#[derive(PartialEq, Eq, Hash)]
+struct TextAttributes {
+    /// Letter spacing in Pango units, or `None` to let the font decide the spacing
+    /// for `letter-spacing: normal`.
+    letter_spacing: Option<i32>,
+    underline: bool,
+    strikethrough: bool,
+    font_features: Option<String>,
+}
+
+impl TextAttributes {
+    /// If `synthetic_small_caps` is true, the font features don't include the ones for
+    /// `font-variant-caps`, since the small caps get synthesized instead.
+    fn new(props: &FontProperties, synthetic_small_caps: bool) -> TextAttributes {
+        TextAttributes {
+            letter_spacing: props.letter_spacing.map(to_pango_units),
+            underline: props.text_decoration.underline && !draws_underline_by_hand(props),
+            strikethrough: props.text_decoration.strike && !draws_strikethrough_by_hand(props),
+            font_features: font_features(props, !synthetic_small_caps),
+        }
+    }
+
+    fn to_attr_list(&self) -> pango::AttrList {
+        let attr_list = pango::AttrList::new();
+        self.insert(&attr_list, None);
+        attr_list
+    }
+
+    /// Adds the attributes to a list.
+    ///
+    /// `range` is the byte range of the text where the attributes apply, or `None` for
+    /// the whole text.
+    fn insert(&self, attr_list: &pango::AttrList, range: Option<Range<usize>>) {
+        if let Some(letter_spacing) = self.letter_spacing {
+            insert_attribute(
+                attr_list,
+                pango::Attribute::new_letter_spacing(letter_spacing),
+                range.clone(),
+            );
+        }
+
+        if self.underline {
+            insert_attribute(
+                attr_list,
+                pango::Attribute::new_underline(pango::Underline::Single),
+                range.clone(),
+            );
+        }
+
+        if self.strikethrough {
+            insert_attribute(
+                attr_list,
+                pango::Attribute::new_strikethrough(true),
+                range.clone(),
+            );
+        }
+
+        if let Some(ref features) = self.font_features {
+            insert_attribute(
+                attr_list,
+                pango::Attribute::new_font_features(features),
+                range,
+            );
+        }
+    }
+}
+
+/// Attribute lists for the spans' layouts, shared among the spans with the same
+/// `TextAttributes`.
+///
+/// Documents with many text elements usually have just a few different styles for them,
+/// so this saves creating the same list over and over.
+#[derive(Default)]
+pub struct TextAttributesCache {
+    attr_lists: RefCell<HashMap<TextAttributes, pango::AttrList>>,
+}
+
+impl TextAttributesCache {
+    fn attr_list(&self, props: &FontProperties, synthetic_small_caps: bool) -> pango::AttrList {
+        self.attr_lists
+            .borrow_mut()
+            .entry(TextAttributes::new(props, synthetic_small_caps))
+            .or_insert_with_key(|attributes| attributes.to_attr_list())
+            .clone()
+    }
+}
+
+fn insert_attribute(
+    attr_list: &pango::AttrList,
+    mut attr: pango::Attribute,
+    range: Option<Range<usize>>,
+) {
+    if let Some(range) = range {
+        attr.set_start_index(range.start as u32);
+        attr.set_end_index(range.end as u32);
+    }
+
+    attr_list.insert(attr);
+}
+
+/// Makes evenly spaced tab stops per the `tab-size` property.
+fn tab_array(
+    pango_context: &pango::Context,
+    font_desc: &pango::FontDescription,
+    props: &FontProperties,
+) -> pango::TabArray {
+    let width = match props.tab_stops {
+        TabStops::Width(w) => w,
+
+        TabStops::Spaces(n) => {
+            let layout = pango::Layout::new(pango_context);
+            layout.set_font_description(Some(font_desc));
+            layout.set_text(" ");
+
+            let (space_width, _) = layout.size();
+            let space_width = f64::from(space_width) / f64::from(pango::SCALE);
+
+            n * (space_width + props.letter_spacing.unwrap_or(0.0))
+        }
+    };
+
+    // Pango repeats the last tab stop at the same interval.
+    let mut tabs = pango::TabArray::new(1, false);
+    tabs.set_tab(0, pango::TabAlign::Left, to_pango_units(width));
+    tabs
+}
+
+/// Builds the list of OpenType features for Pango's font features attribute.
+///
+/// The features only work for fonts that support them.  If `with_caps` is false, the
+/// features for `font-variant-caps` are left out; see `needs_synthetic_small_caps()`.
+fn font_features(props: &FontProperties, with_caps: bool) -> Option<String> {
+    let mut features = if with_caps {
+        caps_features(props.font_variant_caps).to_vec()
+    } else {
+        Vec::new()
+    };
+    features.extend(numeric_features(&props.font_variant_numeric));
+    features.extend(east_asian_features(&props.font_variant_east_asian));
+
+    if features.is_empty() {
+        None
+    } else {
+        Some(
+            features
+                .iter()
+                .map(|f| format!("'{}' 1", f))
+                .collect::<Vec<_>>()
+                .join(", "),
+        )
+    }
+}
+
+/// OpenType features for each value of `font-variant-caps`.
+///
+/// https://www.w3.org/TR/css-fonts-3/#font-variant-caps-prop
+fn caps_features(caps: FontVariantCaps) -> &'static [&'static str] {
+    match caps {
+        FontVariantCaps::Normal => &[],
+        FontVariantCaps::SmallCaps => &["smcp"],
+        FontVariantCaps::AllSmallCaps => &["c2sc", "smcp"],
+        FontVariantCaps::PetiteCaps => &["pcap"],
+        FontVariantCaps::AllPetiteCaps => &["c2pc", "pcap"],
+        FontVariantCaps::Unicase => &["unic"],
+        FontVariantCaps::TitlingCaps => &["titl"],
+    }
+}
+
+/// Scale of the lowercase letters in synthesized small caps, relative to the font size.
+const SMALL_CAPS_SCALE: f64 = 0.7;
+
+/// Whether `font-variant-caps` asks for small caps that the font does not have.
+///
+/// Pango silently ignores the `smcp` feature for fonts that don't support it, so the
+/// text would look as if the property did nothing.  In that case we synthesize small
+/// caps by drawing uppercase letters at a smaller size.
+fn needs_synthetic_small_caps(
+    pango_context: &pango::Context,
+    cache: &FontMeasurementsCache,
+    props: &FontProperties,
+) -> bool {
+    match props.font_variant_caps {
+        FontVariantCaps::SmallCaps | FontVariantCaps::AllSmallCaps => {
+            let font_desc = font_description(pango_context, cache, props);
+
+            pango_context
+                .load_font(&font_desc)
+                .map(|font| !harfbuzz::font_has_substitution(&font, b"smcp"))
+                .unwrap_or(false)
+        }
+
+        _ => false,
+    }
+}
+
+/// Replaces the lowercase letters in a layout's text with smaller uppercase ones.
+///
+/// For `all-small-caps` the uppercase letters in the text get scaled down as well.
+fn synthesize_small_caps(layout: &pango::Layout, props: &FontProperties) {
+    let text = match layout.text() {
+        Some(text) => text,
+        None => return,
+    };
+
+    let all = props.font_variant_caps == FontVariantCaps::AllSmallCaps;
+    let (caps_text, ranges) = small_caps_text(&text, all);
+
+    if ranges.is_empty() {
+        return;
+    }
+
+    // The attribute list may be shared with other layouts, so add to a copy of it.
+    let attr_list = layout
+        .attributes()
+        .and_then(|attr_list| attr_list.copy())
+        .unwrap_or_else(pango::AttrList::new);
+
+    for range in ranges {
+        insert_attribute(
+            &attr_list,
+            pango::Attribute::new_scale(SMALL_CAPS_SCALE),
+            Some(range),
+        );
+    }
+
+    layout.set_attributes(Some(&attr_list));
+    layout.set_text(&caps_text);
+}
+
+/// Gives the word-separator characters in a layout's text the extra advance from
+/// `word-spacing`; see `insert_word_spacing()`.
+fn apply_word_spacing(layout: &pango::Layout, props: &FontProperties) {
+    let text = match layout.text() {
+        Some(text) => text,
+        None => return,
+    };
+
+    if props.word_spacing.approx_eq_cairo(0.0) || !text.contains(is_word_separator) {
+        return;
+    }
+
+    // The attribute list may be shared with other layouts, so add to a copy of it.
+    let attr_list = layout
+        .attributes()
+        .and_then(|attr_list| attr_list.copy())
+        .unwrap_or_else(pango::AttrList::new);
+
+    insert_word_spacing(&attr_list, props, &text, 0);
+
+    layout.set_attributes(Some(&attr_list));
+}
+
+/// Adds letter spacing attributes for `word-spacing` to the word-separator characters.
+///
+/// Pango has no attribute for word spacing, so the separators get more letter spacing
+/// than the rest of the characters instead.  `start` is the byte offset of `text` in the
+/// layout's text.
+fn insert_word_spacing(
+    attr_list: &pango::AttrList,
+    props: &FontProperties,
+    text: &str,
+    start: usize,
+) {
+    if props.word_spacing.approx_eq_cairo(0.0) {
+        return;
+    }
+
+    let spacing = to_pango_units(props.letter_spacing.unwrap_or(0.0) + props.word_spacing);
+
+    for (i, c) in text.char_indices().filter(|&(_, c)| is_word_separator(c)) {
+        insert_attribute(
+            attr_list,
+            pango::Attribute::new_letter_spacing(spacing),
+            Some(start + i..start + i + c.len_utf8()),
+        );
+    }
+}
+
+/// Whether `word-spacing` applies after a character.
+///
+/// https://www.w3.org/TR/css-text-3/#word-separator
+fn is_word_separator(c: char) -> bool {
+    matches!(
+        c,
+        ' ' | '\u{a0}' | '\u{1361}' | '\u{10100}' | '\u{10101}' | '\u{1039f}' | '\u{1091f}'
+    )
+}
+
+/// Uppercases the letters for synthesized small caps.
+///
+/// Returns the new text and the byte ranges of the letters to draw at a smaller size.
+/// The byte offsets of the characters don't change, so letters whose uppercase form
+/// has a different length in UTF-8, like "ß", are left alone.
+fn small_caps_text(text: &str, all: bool) -> (String, Vec<Range<usize>>) {
+    let mut caps_text = String::with_capacity(text.len());
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+
+    for (i, c) in text.char_indices() {
+        let mut upper = c.to_uppercase();
+
+        let small = match (upper.next(), upper.next()) {
+            (Some(u), None) if c.is_lowercase() && u.len_utf8() == c.len_utf8() => {
+                caps_text.push(u);
+                true
+            }
+
+            _ => {
+                caps_text.push(c);
+                all && c.is_uppercase()
+            }
+        };
+
+        if small {
+            let end = i + c.len_utf8();
+
+            match ranges.last_mut() {
+                Some(last) if last.end == i => last.end = end,
+                _ => ranges.push(i..end),
+            }
+        }
+    }
+
+    (caps_text, ranges)
+}
+
+/// OpenType features for `font-variant-numeric`.
+///
+/// https://www.w3.org/TR/css-fonts-3/#font-variant-numeric-prop
+fn numeric_features(numeric: &FontVariantNumeric) -> Vec<&'static str> {
+    [
+        (numeric.lining_nums, "lnum"),
+        (numeric.oldstyle_nums, "onum"),
+        (numeric.proportional_nums, "pnum"),
+        (numeric.tabular_nums, "tnum"),
+        (numeric.diagonal_fractions, "frac"),
+        (numeric.stacked_fractions, "afrc"),
+        (numeric.ordinal, "ordn"),
+        (numeric.slashed_zero, "zero"),
+    ]
+    .iter()
+    .filter(|(enabled, _)| *enabled)
+    .map(|(_, feature)| *feature)
+    .collect()
+}
+
+/// OpenType features for `font-variant-east-asian`.
+///
+/// https://www.w3.org/TR/css-fonts-3/#font-variant-east-asian-prop
+fn east_asian_features(east_asian: &FontVariantEastAsian) -> Vec<&'static str> {
+    let mut features = Vec::new();
+
+    if let Some(variant) = east_asian.variant {
+        features.push(match variant {
+            EastAsianVariant::Jis78 => "jp78",
+            EastAsianVariant::Jis83 => "jp83",
+            EastAsianVariant::Jis90 => "jp90",
+            EastAsianVariant::Jis04 => "jp04",
+            EastAsianVariant::Simplified => "smpl",
+            EastAsianVariant::Traditional => "trad",
+        });
+    }
+
+    if let Some(width) = east_asian.width {
+        features.push(match width {
+            EastAsianWidth::FullWidth => "fwid",
+            EastAsianWidth::ProportionalWidth => "pwid",
+        });
+    }
+
+    if east_asian.ruby {
+        features.push("ruby");
+    }
+
+    features
+}
+
+// Pango always uses the font's metrics for the thickness and position of decoration
+// lines, so we draw them ourselves when the properties ask for something else.
+//
+// In vertical text Pango puts the lines relative to the alphabetic baseline of the
+// sideways font, which goes through the middle of upright CJK glyphs, so we always
+// draw them ourselves along the sides of the line instead.
+fn draws_underline_by_hand(props: &FontProperties) -> bool {
+    props.text_decoration.underline
+        && (props.writing_mode.is_vertical()
+            || props.text_decoration_thickness.is_some()
+            || props.text_underline_offset.is_some())
+}
+
+fn draws_strikethrough_by_hand(props: &FontProperties) -> bool {
+    props.text_decoration.strike
+        && (props.writing_mode.is_vertical() || props.text_decoration_thickness.is_some())
+}
+
+/// Computes the decoration lines that `create_pango_layout` left out of the layout.
+///
+/// Whatever is not overriden by `text-decoration-thickness` or `text-underline-offset`
+/// comes from the font's metrics, just like Pango would do it.  The lines are in the
+/// coordinates of the layout, which gets rotated for vertical text; there, the bottom
+/// of the layout is the left side of the line.
+///
+/// `ranges` are the horizontal ranges of a shared layout that belong to the span, or
+/// `None` if the layout is the span's own.  A shared layout has no decoration lines
+/// from Pango, so all of the span's lines get computed here.
+fn decoration_lines(
+    layout: &pango::Layout,
+    cache: &FontMeasurementsCache,
+    props: &FontProperties,
+    ranges: Option<&[(f64, f64)]>,
+) -> Vec<layout::DecorationLine> {
+    let is_shared = ranges.is_some();
+
+    hand_drawn_decoration_lines(
+        layout,
+        cache,
+        props,
+        ranges,
+        props.text_decoration.underline && (is_shared || draws_underline_by_hand(props)),
+        props.text_decoration.strike && (is_shared || draws_strikethrough_by_hand(props)),
+    )
+}
+
+/// Computes the `underline` and `strikethrough` lines for a span's `layout`, regardless
+/// of whether Pango would draw them; see `decoration_lines()`.
+fn hand_drawn_decoration_lines(
+    layout: &pango::Layout,
+    cache: &FontMeasurementsCache,
+    props: &FontProperties,
+    ranges: Option<&[(f64, f64)]>,
+    underline: bool,
+    strikethrough: bool,
+) -> Vec<layout::DecorationLine> {
+    let mut lines = Vec::new();
+
+    if !underline && !strikethrough {
+        return lines;
+    }
+
+    // A shared layout's font description is the chunk's, so use the span's own font.
+    let metrics = layout.context().and_then(|context| {
+        let font_desc = font_description(&context, cache, props);
+        context.metrics(Some(&font_desc), None)
+    });
+
+    let metrics = match metrics {
+        Some(m) => m,
+        None => return lines,
+    };
+
+    let pango_scale = f64::from(pango::SCALE);
+    let baseline = f64::from(layout.baseline()) / pango_scale;
+    let (length, height) = layout.size();
+    let length = f64::from(length) / pango_scale;
+    let height = f64::from(height) / pango_scale;
+    let whole_layout = [(0.0, length)];
+    let ranges = ranges.unwrap_or(&whole_layout);
+    let is_vertical = props.writing_mode.is_vertical();
+
+    if underline {
+        let thickness = props
+            .text_decoration_thickness
+            .unwrap_or_else(|| f64::from(metrics.underline_thickness()) / pango_scale);
+
+        // In vertical text the underline runs along the left edge of the line, and
+        // `text-underline-offset` moves it away from the glyphs.
+        let y = if is_vertical {
+            height - thickness + props.text_underline_offset.unwrap_or(0.0)
+        } else {
+            // Pango's underline position is the distance above the baseline
+            let offset = props
+                .text_underline_offset
+                .unwrap_or_else(|| -f64::from(metrics.underline_position()) / pango_scale);
+
+            baseline + offset
+        };
+
+        lines.extend(ranges.iter().map(|&(x0, x1)| layout::DecorationLine {
+            x: x0,
+            y,
+            thickness,
+            length: x1 - x0,
+        }));
+    }
+
+    if strikethrough {
+        // Keep the line centered where the font would put it, or in the middle of the
+        // glyphs for vertical text
+        let font_thickness = f64::from(metrics.strikethrough_thickness()) / pango_scale;
+        let thickness = props.text_decoration_thickness.unwrap_or(font_thickness);
+        let center = if is_vertical {
+            central_baseline(layout)
+        } else {
+            baseline - f64::from(metrics.strikethrough_position()) / pango_scale
+                + font_thickness / 2.0
+        };
+
+        lines.extend(ranges.iter().map(|&(x0, x1)| layout::DecorationLine {
+            x: x0,
+            y: center - thickness / 2.0,
+            thickness,
+            length: x1 - x0,
+        }));
+    }
+
+    lines
+}
+
+/// Distance from the top of a layout to its central baseline, in user-space units.
+///
+/// The central baseline is halfway between the font's ascent and descent lines; this is
+/// where CJK glyphs are centered in vertical text.  Pango already centers the lines of
+/// a layout with a vertical gravity on their baseline, both for upright glyphs and for
+/// sideways ones.  If the font metrics are not available, this falls back to the
+/// alphabetic baseline.
+fn central_baseline(layout: &pango::Layout) -> f64 {
+    let baseline = f64::from(layout.baseline()) / f64::from(pango::SCALE);
+
+    let is_vertical = layout
+        .context()
+        .map(|context| {
+            matches!(
+                context.gravity(),
+                pango::Gravity::East | pango::Gravity::West
+            )
+        })
+        .unwrap_or(false);
+
+    if is_vertical {
+        return baseline;
+    }
+
+    layout_font_metrics(layout)
+        .map(|(ascent, descent)| baseline + (descent - ascent) / 2.0)
+        .unwrap_or(baseline)
+}
+
+/// Distance from the top of a layout to its hanging baseline, in user-space units.
+///
+/// This is where the font's `BASE` table puts it.  Fonts without one get a hanging
+/// baseline at 80% of the ascent, which is close to the top of Latin capitals.
+fn hanging_baseline(layout: &pango::Layout) -> f64 {
+    table_baseline(layout, b"hang").unwrap_or_else(|| {
+        let baseline = f64::from(layout.baseline()) / f64::from(pango::SCALE);
+        let (ascent, _) = layout_font_metrics(layout).unwrap_or((0.0, 0.0));
+        baseline - 0.8 * ascent
+    })
+}
+
+/// Distance from the top of a layout to its ideographic baseline, in user-space units.
+///
+/// This is the bottom of the ideographic em box, where the font's `BASE` table puts it.
+/// Fonts without one get it on the descent line.
+fn ideographic_baseline(layout: &pango::Layout) -> f64 {
+    table_baseline(layout, b"ideo").unwrap_or_else(|| {
+        let baseline = f64::from(layout.baseline()) / f64::from(pango::SCALE);
+        let (_, descent) = layout_font_metrics(layout).unwrap_or((0.0, 0.0));
+        baseline + descent
+    })
+}
+
+/// Distance from the top of a layout to a baseline from the OpenType `BASE` table, like
+/// `b"hang"`, in user-space units.
+///
+/// The baseline comes from the font and the script of the layout's first run.  Returns
+/// `None` if the font does not have that baseline.
+fn table_baseline(layout: &pango::Layout, tag: &[u8; 4]) -> Option<f64> {
+    let pango_scale = f64::from(pango::SCALE);
+
+    let run = layout.iter()?.run_readonly()?;
+    let item = run.item();
+    let font = item.analysis().font();
+
+    let text = layout.text()?;
+    let c = text[item.offset() as usize..].chars().next()?;
+
+    let coord = harfbuzz::font_baseline(&font, tag, c)?;
+
+    // The layout's baseline is the alphabetic one; the table has its own idea of where
+    // that is, which is usually the font's origin.
+    let alphabetic = harfbuzz::font_baseline(&font, b"romn", c).unwrap_or(0);
+
+    let baseline = f64::from(layout.baseline()) / pango_scale;
+    Some(baseline - f64::from(coord - alphabetic) / pango_scale)
+}
+
+/// The ascent and descent of the font of a layout, in user-space units.
+fn layout_font_metrics(layout: &pango::Layout) -> Option<(f64, f64)> {
+    let pango_scale = f64::from(pango::SCALE);
+
+    layout
+        .context()
+        .and_then(|context| context.metrics(layout.font_description().as_ref(), None))
+        .map(|metrics| {
+            (
+                f64::from(metrics.ascent()) / pango_scale,
+                f64::from(metrics.descent()) / pango_scale,
+            )
+        })
+}
+
+/// Pango sets this bit in the glyphs for characters that the font does not have.
+const PANGO_GLYPH_UNKNOWN_FLAG: u32 = 0x1000_0000;
+
+/// Tells the missing glyphs callback about the characters of a span that have no glyph.
+///
+/// `range` is the byte range of the span's text in a shared layout, or `None` if the
+/// layout is the span's own.
+fn report_missing_glyphs(
+    draw_ctx: &DrawingCtx,
+    layout: &pango::Layout,
+    range: Option<Range<usize>>,
+    props: &FontProperties,
+) {
+    if let Some(ref callback) = draw_ctx.text_options().missing_glyphs_callback {
+        let chars = missing_glyphs(layout, range);
+
+        if !chars.is_empty() {
+            rsvg_log!(
+                "font family \"{}\" has no glyphs for {:?}",
+                props.font_family.as_str(),
+                chars
+            );
+
+            callback(&MissingGlyphs {
+                font_family: props.font_family.as_str().to_string(),
+                chars,
+            });
+        }
+    }
+}
+
+/// Finds the characters in a byte `range` of a layout's text that Pango could not find a
+/// glyph for in any font, or in the whole text if `range` is `None`.
+fn missing_glyphs(layout: &pango::Layout, range: Option<Range<usize>>) -> Vec<char> {
+    let mut chars = Vec::new();
+
+    if layout.unknown_glyphs_count() == 0 {
+        return chars;
+    }
+
+    let text = match layout.text() {
+        Some(text) => text,
+        None => return chars,
+    };
+
+    let mut iter = match layout.iter() {
+        Some(iter) => iter,
+        None => return chars,
+    };
+
+    loop {
+        if let Some(run) = iter.run_readonly() {
+            let offset = run.item().offset() as usize;
+            let glyphs = run.glyph_string();
+
+            for (info, cluster) in glyphs.glyph_info().iter().zip(glyphs.log_clusters()) {
+                let index = offset + cluster as usize;
+
+                if info.glyph() & PANGO_GLYPH_UNKNOWN_FLAG == 0
+                    || !range.as_ref().map_or(true, |r| r.contains(&index))
+                {
+                    continue;
+                }
+
+                if let Some(c) = text[index..].chars().next() {
+                    if !chars.contains(&c) {
+                        chars.push(c);
+                    }
+                }
+            }
+        }
+
+        if !iter.next_run() {
+            return chars;
+        }
+    }
+}
+
+/// Finds the fonts that Pango picked for the runs of a layout.
+///
+/// The fonts are in the order in which they are first used, without repetitions.
+pub fn used_fonts(layout: &pango::Layout) -> Vec<UsedFont> {
+    let mut fonts = Vec::new();
+
+    let mut iter = match layout.iter() {
+        Some(iter) => iter,
+        None => return fonts,
+    };
+
+    loop {
+        if let Some(run) = iter.run_readonly() {
+            let font = run.item().analysis().font();
+
+            if let Some(mut font_desc) = font.describe() {
+                font_desc.unset_fields(pango::FontMask::SIZE | pango::FontMask::GRAVITY);
+
+                if let Some(family) = font_desc.family() {
+                    let used = UsedFont {
+                        family: family.to_string(),
+                        description: font_desc.to_str().to_string(),
+                    };
+
+                    if !fonts.contains(&used) {
+                        fonts.push(used);
+                    }
+                }
+            }
+        }
+
+        if !iter.next_run() {
+            return fonts;
+        }
+    }
+}
+
+/// Lays out `text` with the font that Pango picks for a font description.
+///
+/// Returns the ink and logical extents of the layout, or `None` if the font does not
+/// have glyphs for all of the text.
+fn measure_glyphs(
+    pango_context: &pango::Context,
+    font_desc: &pango::FontDescription,
+    text: &str,
+) -> Option<(pango::Rectangle, pango::Rectangle)> {
+    let layout = pango::Layout::new(pango_context);
+    layout.set_font_description(Some(font_desc));
+    layout.set_text(text);
+
+    if layout.unknown_glyphs_count() > 0 {
+        None
+    } else {
+        Some(layout.extents())
+    }
+}
+
+/// Measures the x-height of the font that Pango picks for a font description.
+///
+/// Pango's font metrics don't include the x-height, so we use the height of the ink
+/// extents of an "x" glyph instead.  Returns `None` if the font has no such glyph.
+fn measure_x_height(
+    pango_context: &pango::Context,
+    font_desc: &pango::FontDescription,
+) -> Option<f64> {
+    let (ink, _) = measure_glyphs(pango_context, font_desc, "x")?;
+
+    if ink.height == 0 {
+        None
+    } else {
+        Some(f64::from(ink.height) / f64::from(pango::SCALE))
+    }
+}
+
+/// Measures the advance of a glyph in the font that Pango picks for a font description,
+/// like the "0" for the `ch` unit.  Returns `None` if the font has no such glyph.
+fn measure_advance(
+    pango_context: &pango::Context,
+    font_desc: &pango::FontDescription,
+    glyph: &str,
+) -> Option<f64> {
+    let (_, logical) = measure_glyphs(pango_context, font_desc, glyph)?;
+
+    if logical.width == 0 {
+        None
+    } else {
+        Some(f64::from(logical.width) / f64::from(pango::SCALE))
+    }
+}
+
+/// The glyph measurements of a font that lengths in text can refer to.
+#[derive(Clone, Copy)]
+struct FontMeasurements {
+    x_height: Option<f64>,
+    zero_advance: Option<f64>,
+    space_advance: Option<f64>,
+}
+
+impl FontMeasurements {
+    fn new(pango_context: &pango::Context, font_desc: &pango::FontDescription) -> Self {
+        FontMeasurements {
+            x_height: measure_x_height(pango_context, font_desc),
+            zero_advance: measure_advance(pango_context, font_desc, "0"),
+            space_advance: measure_advance(pango_context, font_desc, " "),
+        }
+    }
+}
+
+/// Measurements of the fonts used in a document, by font description.
+///
+/// Each span needs them to normalize its lengths, so this saves laying out the same
+/// glyphs for every span with the same font.
+#[derive(Default)]
+pub struct FontMeasurementsCache {
+    measurements: RefCell<HashMap<String, FontMeasurements>>,
+}
+
+impl FontMeasurementsCache {
+    fn get(
+        &self,
+        pango_context: &pango::Context,
+        font_desc: &pango::FontDescription,
+    ) -> FontMeasurements {
+        *self
+            .measurements
+            .borrow_mut()
+            .entry(font_desc.to_string())
+            .or_insert_with(|| FontMeasurements::new(pango_context, font_desc))
+    }
+}
+
+/// Creates the parameters to normalize lengths in text.
+///
+/// Unlike `NormalizeParams::new()`, `ex` and `ch` units resolve against the x-height and
+/// the advance of the "0" glyph of the font that Pango picks for `values`, and
+/// percentages in `word-spacing` against the advance of its space.  If the font does
+/// not have those glyphs, the defaults from `NormalizeParams::new()` stay.
+///
+/// Each font gets measured only once and then comes from the `cache`.
+fn text_normalize_params(
+    pango_context: &pango::Context,
+    cache: &FontMeasurementsCache,
+    values: &ComputedValues,
+    view_params: &ViewParams,
+) -> NormalizeParams {
+    let params = NormalizeParams::new(values, view_params);
+    let props = FontProperties::new(values, &params);
+    let font_desc = font_description(pango_context, cache, &props);
+    let measurements = cache.get(pango_context, &font_desc);
+
+    let params = match measurements.x_height {
+        Some(x_height) => params.with_x_height(x_height),
+        None => params,
+    };
+
+    let params = match measurements.zero_advance {
+        Some(zero_advance) => params.with_zero_advance(zero_advance),
+        None => params,
+    };
+
+    match measurements.space_advance {
+        Some(space_advance) => params.with_space_advance(space_advance),
+        None => params,
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::Chars;
+    use super::*;
+
+    use crate::document::Document;
+    use crate::node::NodeData;
 
     #[test]
     fn chars_default() {
@@ -905,6 +4033,73 @@ mod tests {
         assert!(c.space_normalized.borrow().is_none());
     }
 
+    #[test]
+    fn keeps_normalized_strings_for_each_mode() {
+        let document = Document::load_from_bytes(
+            br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg">
+  <text id="default">  a   b  </text>
+  <text id="preserve" xml:space="preserve">  a   b  </text>
+</svg>
+"#,
+        );
+
+        let default = document.lookup_internal_node("default").unwrap();
+        let preserve = document.lookup_internal_node("preserve").unwrap();
+
+        let default_values = default.borrow_element().get_computed_values().clone();
+        let preserve_values = preserve.borrow_element().get_computed_values().clone();
+
+        // Use the characters of the first element with both sets of values, like a
+        // <use> element with a different xml:space would do.
+        let node = default.first_child().unwrap();
+        let chars = node.borrow_chars();
+
+        assert_eq!(chars.get_normalized_string(&node, &default_values), "a b");
+        assert_eq!(
+            chars.get_normalized_string(&node, &preserve_values),
+            "  a   b  "
+        );
+        assert_eq!(chars.other_space_normalized.borrow().len(), 1);
+
+        assert_eq!(chars.get_normalized_string(&node, &default_values), "a b");
+        assert_eq!(
+            chars.get_normalized_string(&node, &preserve_values),
+            "  a   b  "
+        );
+        assert_eq!(chars.other_space_normalized.borrow().len(), 1);
+
+        chars.append("c");
+        assert!(chars.space_normalized.borrow().is_none());
+        assert!(chars.other_space_normalized.borrow().is_empty());
+        assert_eq!(chars.get_normalized_string(&node, &default_values), "a b c");
+    }
+
+    #[test]
+    fn only_elements_keep_the_whitespace_around_character_data() {
+        // The XML loader coalesces character data, even around comments, so build
+        // adjacent text nodes by hand.
+        let document = Document::load_from_bytes(
+            br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg">
+  <text id="text"><tspan>a</tspan></text>
+</svg>
+"#,
+        );
+
+        let text = document.lookup_internal_node("text").unwrap();
+        let values = text.borrow_element().get_computed_values().clone();
+
+        let b = Node::new(NodeData::new_chars("  b  "));
+        let c = Node::new(NodeData::new_chars("  c  "));
+        text.append(b.clone());
+        text.append(c.clone());
+
+        // The space after "b" gets trimmed, since no element follows it
+        assert_eq!(b.borrow_chars().get_normalized_string(&b, &values), " b");
+        assert_eq!(c.borrow_chars().get_normalized_string(&c, &values), " c");
+    }
+
     #[test]
     fn chars_new() {
         let example = "Test 123";
@@ -912,4 +4107,280 @@ mod tests {
         assert_eq!(c.get_string(), example);
         assert!(c.space_normalized.borrow().is_none());
     }
+
+    #[test]
+    fn parses_rotate() {
+        assert_eq!(Rotate::parse_str("45").unwrap(), Rotate(vec![45.0]));
+        assert_eq!(
+            Rotate::parse_str("10, 20 -30").unwrap(),
+            Rotate(vec![10.0, 20.0, -30.0])
+        );
+
+        assert!(Rotate::parse_str("").is_err());
+        assert!(Rotate::parse_str("10,").is_err());
+        assert!(Rotate::parse_str("10 foo").is_err());
+    }
+
+    #[test]
+    fn rotate_list_continues_across_spans() {
+        // <text rotate="10 20 30"><tspan>ab</tspan><tspan>cde</tspan></text>
+        let text = Rc::new(vec![10.0, 20.0, 30.0]);
+        let lists = vec![text];
+
+        let mut counts = RotateCounts::default();
+        assert_eq!(counts.next_rotations(&lists, 2), vec![10.0, 20.0]);
+        assert_eq!(counts.next_rotations(&lists, 3), vec![30.0, 30.0, 30.0]);
+    }
+
+    #[test]
+    fn innermost_rotate_list_wins() {
+        // <text rotate="1 2 3 4 5">ab<tspan rotate="9">cd</tspan>ef</text>
+        let text = Rc::new(vec![1.0, 2.0, 3.0, 4.0, 5.0]);
+        let tspan = Rc::new(vec![9.0]);
+
+        let mut counts = RotateCounts::default();
+        assert_eq!(counts.next_rotations(&[text.clone()], 2), vec![1.0, 2.0]);
+        assert_eq!(
+            counts.next_rotations(&[text.clone(), tspan], 2),
+            vec![9.0, 9.0]
+        );
+        assert_eq!(counts.next_rotations(&[text], 2), vec![5.0, 5.0]);
+    }
+
+    fn pango_context() -> pango::Context {
+        let font_map = pangocairo::FontMap::default().unwrap();
+        font_map.create_context().unwrap()
+    }
+
+    fn layout_for_text(text: &str) -> pango::Layout {
+        let layout = pango::Layout::new(&pango_context());
+        layout.set_text(text);
+        layout
+    }
+
+    fn default_font_properties() -> FontProperties {
+        let values = ComputedValues::default();
+        let view_params = ViewParams::new(Dpi::new(96.0, 96.0), 100.0, 100.0);
+        let params = NormalizeParams::new(&values, &view_params);
+        FontProperties::new(&values, &params)
+    }
+
+    #[test]
+    fn shares_attr_lists_for_equal_text_attributes() {
+        let cache = TextAttributesCache::default();
+
+        let mut props = default_font_properties();
+        cache.attr_list(&props, false);
+        cache.attr_list(&props, false);
+        assert_eq!(cache.attr_lists.borrow().len(), 1);
+
+        props.font_variant_caps = FontVariantCaps::SmallCaps;
+        cache.attr_list(&props, false);
+        assert_eq!(cache.attr_lists.borrow().len(), 2);
+
+        props.letter_spacing = Some(2.0);
+        cache.attr_list(&props, false);
+        cache.attr_list(&props, false);
+        assert_eq!(cache.attr_lists.borrow().len(), 3);
+    }
+
+    #[test]
+    fn snaps_percentage_font_stretch_for_pango() {
+        assert_eq!(
+            pango::Stretch::from(FontStretch::Percentage(0.5)),
+            pango::Stretch::UltraCondensed
+        );
+        assert_eq!(
+            pango::Stretch::from(FontStretch::Percentage(1.1)),
+            pango::Stretch::SemiExpanded
+        );
+    }
+
+    #[test]
+    fn builds_font_features_for_caps() {
+        let mut props = default_font_properties();
+        assert_eq!(font_features(&props, true), None);
+
+        props.font_variant_caps = FontVariantCaps::SmallCaps;
+        assert_eq!(font_features(&props, true).unwrap(), "'smcp' 1");
+
+        props.font_variant_caps = FontVariantCaps::AllSmallCaps;
+        assert_eq!(font_features(&props, true).unwrap(), "'c2sc' 1, 'smcp' 1");
+
+        props.font_variant_caps = FontVariantCaps::AllPetiteCaps;
+        assert_eq!(font_features(&props, true).unwrap(), "'c2pc' 1, 'pcap' 1");
+
+        props.font_variant_caps = FontVariantCaps::TitlingCaps;
+        assert_eq!(font_features(&props, true).unwrap(), "'titl' 1");
+    }
+
+    #[test]
+    fn leaves_out_caps_features_for_fonts_without_small_caps() {
+        let mut props = default_font_properties();
+
+        props.font_variant_caps = FontVariantCaps::SmallCaps;
+        assert_eq!(font_features(&props, false), None);
+
+        props.font_variant_numeric = FontVariantNumeric {
+            oldstyle_nums: true,
+            ..Default::default()
+        };
+        assert_eq!(font_features(&props, false).unwrap(), "'onum' 1");
+    }
+
+    #[test]
+    fn synthesizes_small_caps_text() {
+        assert_eq!(
+            small_caps_text("Hello World", false),
+            (String::from("HELLO WORLD"), vec![1..5, 7..11])
+        );
+
+        assert_eq!(
+            small_caps_text("Hello World", true),
+            (String::from("HELLO WORLD"), vec![0..5, 6..11])
+        );
+
+        // "ß" uppercases to "SS", which would change the byte offsets
+        assert_eq!(
+            small_caps_text("Straße", false),
+            (String::from("STRAßE"), vec![1..4, 6..7])
+        );
+
+        assert_eq!(small_caps_text("123", true), (String::from("123"), vec![]));
+    }
+
+    #[test]
+    fn builds_font_features_for_numeric() {
+        let mut props = default_font_properties();
+
+        props.font_variant_numeric = FontVariantNumeric {
+            tabular_nums: true,
+            slashed_zero: true,
+            ..Default::default()
+        };
+        assert_eq!(font_features(&props, true).unwrap(), "'tnum' 1, 'zero' 1");
+
+        props.font_variant_caps = FontVariantCaps::SmallCaps;
+        props.font_variant_numeric = FontVariantNumeric {
+            oldstyle_nums: true,
+            ..Default::default()
+        };
+        assert_eq!(font_features(&props, true).unwrap(), "'smcp' 1, 'onum' 1");
+    }
+
+    #[test]
+    fn builds_font_features_for_east_asian() {
+        let mut props = default_font_properties();
+
+        props.font_variant_east_asian = FontVariantEastAsian {
+            variant: Some(EastAsianVariant::Jis78),
+            width: None,
+            ruby: false,
+        };
+        assert_eq!(font_features(&props, true).unwrap(), "'jp78' 1");
+
+        props.font_variant_east_asian = FontVariantEastAsian {
+            variant: Some(EastAsianVariant::Simplified),
+            width: Some(EastAsianWidth::ProportionalWidth),
+            ruby: true,
+        };
+        assert_eq!(
+            font_features(&props, true).unwrap(),
+            "'smpl' 1, 'pwid' 1, 'ruby' 1"
+        );
+
+        props.font_variant_numeric.tabular_nums = true;
+        props.font_variant_east_asian = FontVariantEastAsian {
+            variant: None,
+            width: Some(EastAsianWidth::FullWidth),
+            ruby: false,
+        };
+        assert_eq!(font_features(&props, true).unwrap(), "'tnum' 1, 'fwid' 1");
+    }
+
+    #[test]
+    fn font_size_adjust_measures_the_x_height_once() {
+        let context = pango_context();
+        let cache = FontMeasurementsCache::default();
+
+        let mut props = default_font_properties();
+        let unadjusted = font_description(&context, &cache, &props).to_string();
+        assert!(cache.measurements.borrow().is_empty());
+
+        props.font_size_adjust = FontSizeAdjust::Number(0.5);
+        let adjusted = font_description(&context, &cache, &props);
+        assert_eq!(
+            font_description(&context, &cache, &props).to_string(),
+            adjusted.to_string()
+        );
+
+        let measurements = cache.measurements.borrow();
+        assert_eq!(measurements.len(), 1);
+        assert!(measurements.contains_key(&unadjusted));
+    }
+
+    #[test]
+    fn measures_string_without_a_document() {
+        let props = default_font_properties();
+
+        let context = pango_context();
+        let cache = FontMeasurementsCache::default();
+        let one = measure_string(&context, &cache, &props, "abc");
+        let two = measure_string(&context, &cache, &props, "abcabc");
+
+        assert!(one.width > 0.0);
+        assert!(one.baseline > 0.0 && one.baseline < one.height);
+        assert!((two.width - 2.0 * one.width).abs() < 1.0);
+        assert_eq!(one.height, two.height);
+    }
+
+    #[test]
+    fn base_and_combining_mark_are_one_addressable_character() {
+        // "é" as U+0065 LATIN SMALL LETTER E + U+0301 COMBINING ACUTE ACCENT
+        let text = "e\u{0301}x";
+        let layout = layout_for_text(text);
+
+        assert_eq!(
+            addressable_characters(text, &layout.log_attrs()),
+            vec![0..3, 3..4]
+        );
+    }
+
+    #[test]
+    fn emoji_zwj_sequence_is_one_addressable_character() {
+        // Family: man, woman, girl, boy, joined with U+200D ZERO WIDTH JOINER
+        let family = "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}\u{200D}\u{1F466}";
+        let text = format!("{}x", family);
+        let layout = layout_for_text(&text);
+
+        assert_eq!(
+            addressable_characters(&text, &layout.log_attrs()),
+            vec![0..family.len(), family.len()..text.len()]
+        );
+    }
+
+    #[test]
+    fn astral_character_is_one_addressable_character() {
+        // U+1F600 GRINNING FACE takes four bytes in UTF-8
+        let text = "\u{1F600}ab";
+        let layout = layout_for_text(text);
+
+        assert_eq!(
+            addressable_characters(text, &layout.log_attrs()),
+            vec![0..4, 4..5, 5..6]
+        );
+    }
+
+    #[test]
+    fn zero_width_characters_follow_grapheme_boundaries() {
+        // U+200B ZERO WIDTH SPACE is a character of its own, but U+200D ZERO WIDTH JOINER
+        // belongs to the cluster of the character before it.
+        let text = "a\u{200B}b\u{200D}c";
+        let layout = layout_for_text(text);
+
+        assert_eq!(
+            addressable_characters(text, &layout.log_attrs()),
+            vec![0..1, 1..4, 4..8, 8..9]
+        );
+    }
 }
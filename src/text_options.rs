@@ -0,0 +1,210 @@
+//! Options that the caller can set for laying out and rendering text.
+
+use std::fmt;
+use std::rc::Rc;
+
+/// A font that got used instead of the one requested by an element's `font-family`.
+///
+/// Pango silently substitutes a similar font when it cannot find the requested one,
+/// so this is what an application gets told about when that happens.  This also
+/// happens when the first families in a list are not available, and Pango falls back
+/// to a later one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FontSubstitution {
+    /// Value of the `font-family` property, as a comma-separated list of families.
+    pub requested: String,
+
+    /// Family of the font that will actually be used.
+    pub actual: String,
+
+    /// The requested families that are not available, in the order of the list.
+    ///
+    /// These are the families before `actual` in the list, or all of them if `actual`
+    /// is not in the list.  Generic families like `sans-serif` are never included.
+    pub unavailable: Vec<String>,
+}
+
+/// Function that gets called when a font needs to be substituted.
+pub type FontSubstitutionCallback = Rc<dyn Fn(&FontSubstitution)>;
+
+/// Characters of a text span that none of the available fonts have a glyph for.
+///
+/// Pango renders a placeholder box for each of these characters, often called "tofu",
+/// so the rendered text does not show what the document says.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MissingGlyphs {
+    /// Value of the `font-family` property, as a comma-separated list of families.
+    pub font_family: String,
+
+    /// The characters without a glyph, in the order in which they first appear in the
+    /// text, without repetitions.
+    pub chars: Vec<char>,
+}
+
+/// Function that gets called when some characters could not be rendered.
+pub type MissingGlyphsCallback = Rc<dyn Fn(&MissingGlyphs)>;
+
+/// A font face that Pango picked for rendering some of a document's text.
+///
+/// This is the font that actually got used after any substitutions, including the
+/// fallback fonts for characters that the requested font does not cover.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct UsedFont {
+    /// Family of the font, like `"DejaVu Sans"`.
+    pub family: String,
+
+    /// Pango's description of the face without its size, like `"DejaVu Sans Bold"`.
+    pub description: String,
+}
+
+/// Which extents of the text's layout make up a text element's bounding box.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TextExtents {
+    /// The area actually covered by the glyphs' outlines.  This is the default.
+    Ink,
+
+    /// The layout's logical box, which spans the font's full ascent and descent and
+    /// the glyphs' advances.  This is useful for laying out text in a flow.
+    Logical,
+}
+
+impl Default for TextExtents {
+    fn default() -> TextExtents {
+        TextExtents::Ink
+    }
+}
+
+/// How glyph outlines get fitted to the pixel grid when rendering text.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FontHinting {
+    /// Use the hinting and antialiasing settings of the platform.  This is the default.
+    Default,
+
+    /// Turn off hinting and use grayscale antialiasing, so that text metrics and pixels
+    /// come out the same on every machine.
+    None,
+}
+
+impl Default for FontHinting {
+    fn default() -> FontHinting {
+        FontHinting::Default
+    }
+}
+
+/// Text options that are passed down from the public API to the drawing code.
+#[derive(Clone, Default)]
+pub struct TextOptions {
+    pub font_substitution_callback: Option<FontSubstitutionCallback>,
+    pub missing_glyphs_callback: Option<MissingGlyphsCallback>,
+    pub text_extents: TextExtents,
+    pub font_hinting: FontHinting,
+
+    /// Font map to lay out text with, instead of the default one for Cairo.
+    pub font_map: Option<pango::FontMap>,
+}
+
+impl fmt::Debug for TextOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TextOptions")
+            .field(
+                "font_substitution_callback",
+                &self.font_substitution_callback.is_some(),
+            )
+            .field(
+                "missing_glyphs_callback",
+                &self.missing_glyphs_callback.is_some(),
+            )
+            .field("text_extents", &self.text_extents)
+            .field("font_hinting", &self.font_hinting)
+            .field("font_map", &self.font_map.is_some())
+            .finish()
+    }
+}
+
+impl TextOptions {
+    /// Creates the Cairo font options to render text with.
+    ///
+    /// The test suite always needs reproducible output, so `testing` turns off hinting
+    /// just like `FontHinting::None`.
+    pub fn cairo_font_options(&self, testing: bool) -> cairo::FontOptions {
+        let mut options = cairo::FontOptions::new().unwrap();
+
+        if testing || self.font_hinting == FontHinting::None {
+            options.set_antialias(cairo::Antialias::Gray);
+            options.set_hint_style(cairo::HintStyle::None);
+            options.set_hint_metrics(cairo::HintMetrics::Off);
+        }
+
+        options
+    }
+}
+
+/// Finds the requested families that Pango skipped before picking the `actual` one.
+///
+/// Generic families like `sans-serif` match any font, so they are never unavailable.
+/// If the result is empty, there is no substitution to report.
+pub fn unavailable_families(requested: &str, actual: &str) -> Vec<String> {
+    requested
+        .split(',')
+        .map(str::trim)
+        .filter(|f| !f.is_empty() && !is_generic_family(f))
+        .take_while(|f| !f.eq_ignore_ascii_case(actual))
+        .map(String::from)
+        .collect()
+}
+
+fn is_generic_family(family: &str) -> bool {
+    [
+        "serif",
+        "sans-serif",
+        "sans",
+        "monospace",
+        "cursive",
+        "fantasy",
+        "system-ui",
+    ]
+    .iter()
+    .any(|g| g.eq_ignore_ascii_case(family))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_font_substitution() {
+        assert!(unavailable_families("DejaVu Sans", "DejaVu Sans").is_empty());
+        assert!(unavailable_families("dejavu sans", "DejaVu Sans").is_empty());
+        assert!(unavailable_families("sans-serif", "DejaVu Sans").is_empty());
+        assert!(unavailable_families("serif,monospace", "DejaVu Sans").is_empty());
+        assert!(unavailable_families("DejaVu Sans,Foo", "DejaVu Sans").is_empty());
+
+        assert_eq!(unavailable_families("Foo", "DejaVu Sans"), vec!["Foo"]);
+        assert_eq!(
+            unavailable_families("Foo,sans-serif", "DejaVu Sans"),
+            vec!["Foo"]
+        );
+        assert_eq!(
+            unavailable_families("Foo, Bar, DejaVu Sans", "DejaVu Sans"),
+            vec!["Foo", "Bar"]
+        );
+    }
+
+    #[test]
+    fn font_hinting_reaches_cairo_font_options() {
+        let options = TextOptions::default().cairo_font_options(false);
+        assert_eq!(options.antialias(), cairo::Antialias::Default);
+        assert_eq!(options.hint_style(), cairo::HintStyle::Default);
+        assert_eq!(options.hint_metrics(), cairo::HintMetrics::Default);
+
+        let text_options = TextOptions {
+            font_hinting: FontHinting::None,
+            ..TextOptions::default()
+        };
+
+        let options = text_options.cairo_font_options(false);
+        assert_eq!(options.antialias(), cairo::Antialias::Gray);
+        assert_eq!(options.hint_style(), cairo::HintStyle::None);
+        assert_eq!(options.hint_metrics(), cairo::HintMetrics::Off);
+    }
+}
@@ -8,6 +8,7 @@ use cssparser::{Parser, Token};
 
 use crate::angle::Angle;
 use crate::error::*;
+use crate::length::*;
 use crate::parsers::{optional_comma, Parse};
 use crate::rect::Rect;
 
@@ -220,6 +221,155 @@ impl Parse for Transform {
     }
 }
 
+/// Value of the CSS `transform` property.
+///
+/// Unlike the `transform` attribute, the CSS property can have percentages in its
+/// translations.  These refer to the size of a reference box, which is only known when
+/// the element gets drawn, so the list of functions is kept until then.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransformList(Vec<TransformFunction>);
+
+/// A single function from a [`TransformList`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum TransformFunction {
+    /// A transform that does not depend on the reference box.
+    Matrix(Transform),
+
+    /// A `translate()` whose lengths may be percentages of the reference box.
+    Translate(Length<Horizontal>, Length<Vertical>),
+}
+
+impl TransformList {
+    /// Whether any of the functions need the reference box to be resolved.
+    pub fn has_percentages(&self) -> bool {
+        self.0.iter().any(|f| match *f {
+            TransformFunction::Matrix(_) => false,
+            TransformFunction::Translate(tx, ty) => {
+                tx.unit == LengthUnit::Percent || ty.unit == LengthUnit::Percent
+            }
+        })
+    }
+
+    /// Whether the list has no functions, i.e. it is the identity transform.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Resolves the list into a single transform.
+    ///
+    /// Percentages get resolved against the size of the `reference_box`.
+    pub fn to_transform(&self, params: &NormalizeParams, reference_box: &Rect) -> Transform {
+        let params = params.with_reference_box(reference_box);
+
+        self.0.iter().fold(Transform::identity(), |t, f| {
+            f.to_transform(&params).post_transform(&t)
+        })
+    }
+}
+
+impl Default for TransformList {
+    fn default() -> TransformList {
+        TransformList(Vec::new())
+    }
+}
+
+impl From<Transform> for TransformList {
+    fn from(t: Transform) -> TransformList {
+        TransformList(vec![TransformFunction::Matrix(t)])
+    }
+}
+
+impl TransformFunction {
+    fn to_transform(&self, params: &NormalizeParams) -> Transform {
+        match *self {
+            TransformFunction::Matrix(t) => t,
+
+            TransformFunction::Translate(tx, ty) => {
+                Transform::new_translate(tx.to_user(params), ty.to_user(params))
+            }
+        }
+    }
+}
+
+impl Parse for TransformList {
+    fn parse<'i>(parser: &mut Parser<'i, '_>) -> Result<TransformList, ParseError<'i>> {
+        let mut functions = Vec::new();
+
+        loop {
+            if parser.is_exhausted() {
+                break;
+            }
+
+            functions.push(parse_css_transform_function(parser)?);
+            optional_comma(parser);
+        }
+
+        Ok(TransformList(functions))
+    }
+}
+
+fn parse_css_transform_function<'i>(
+    parser: &mut Parser<'i, '_>,
+) -> Result<TransformFunction, ParseError<'i>> {
+    if let Ok(f) = parser.try_parse(parse_css_translate) {
+        return Ok(f);
+    }
+
+    let loc = parser.current_source_location();
+
+    // Angles can have units in CSS; otherwise, the functions are the same as in the
+    // transform attribute.
+    let t = match parser.try_parse(parse_css_angle_function) {
+        Ok(t) => t,
+        Err(_) => parse_transform_command(parser)?,
+    };
+
+    if !t.is_invertible() {
+        return Err(loc.new_custom_error(ValueErrorKind::Value(
+            "invalid transformation matrix".to_string(),
+        )));
+    }
+
+    Ok(TransformFunction::Matrix(t))
+}
+
+fn parse_css_translate<'i>(
+    parser: &mut Parser<'i, '_>,
+) -> Result<TransformFunction, ParseError<'i>> {
+    parser.expect_function_matching("translate")?;
+
+    parser.parse_nested_block(|p| {
+        let tx = Length::<Horizontal>::parse(p)?;
+
+        let ty = p
+            .try_parse(|p| {
+                optional_comma(p);
+                Length::<Vertical>::parse(p)
+            })
+            .unwrap_or_else(|_| Length::<Vertical>::new(0.0, LengthUnit::Px));
+
+        Ok(TransformFunction::Translate(tx, ty))
+    })
+}
+
+/// Parses `rotate()`, `skewX()`, or `skewY()` with an angle that may have a unit.
+fn parse_css_angle_function<'i>(parser: &mut Parser<'i, '_>) -> Result<Transform, ParseError<'i>> {
+    let loc = parser.current_source_location();
+
+    let make_transform: fn(Angle) -> Transform = match parser.expect_function()?.as_ref() {
+        "rotate" => Transform::new_rotate,
+        "skewX" => |a| Transform::new_skew(a, Angle::new(0.0)),
+        "skewY" => |a| Transform::new_skew(Angle::new(0.0), a),
+        _ => {
+            return Err(
+                loc.new_custom_error(ValueErrorKind::parse_error("expected rotate|skewX|skewY"))
+            )
+        }
+    };
+
+    parser.parse_nested_block(|p| Ok(make_transform(Angle::parse(p)?)))
+}
+
 fn parse_transform_list<'i>(parser: &mut Parser<'i, '_>) -> Result<Transform, ParseError<'i>> {
     let mut t = Transform::identity();
 
@@ -364,6 +514,10 @@ mod tests {
     use float_cmp::ApproxEq;
     use std::f64;
 
+    use crate::dpi::Dpi;
+    use crate::drawing_ctx::ViewParams;
+    use crate::properties::ComputedValues;
+
     fn rotation_transform(deg: f64, tx: f64, ty: f64) -> Transform {
         Transform::new_translate(tx, ty)
             .pre_rotate(Angle::from_degrees(deg))
@@ -510,6 +664,72 @@ mod tests {
         );
     }
 
+    #[test]
+    fn resolves_percentage_translations_against_reference_box() {
+        let values = ComputedValues::default();
+        let view_params = ViewParams::new(Dpi::new(96.0, 96.0), 100.0, 100.0);
+        let params = NormalizeParams::new(&values, &view_params);
+        let reference_box = Rect::new(10.0, 10.0, 50.0, 30.0);
+
+        let list = TransformList::parse_str("translate(50%)").unwrap();
+        assert!(list.has_percentages());
+        assert_transform_eq(
+            &list.to_transform(&params, &reference_box),
+            &Transform::new_translate(20.0, 0.0),
+        );
+
+        let list = TransformList::parse_str("scale(2) translate(10px, 50%)").unwrap();
+        assert_transform_eq(
+            &list.to_transform(&params, &reference_box),
+            &parse_transform("scale(2) translate(10, 10)").unwrap(),
+        );
+
+        let list = TransformList::parse_str("translate(10, 20) rotate(30)").unwrap();
+        assert!(!list.has_percentages());
+        assert_transform_eq(
+            &list.to_transform(&params, &reference_box),
+            &parse_transform("translate(10, 20) rotate(30)").unwrap(),
+        );
+    }
+
+    #[test]
+    fn parses_angle_units_in_css_transforms() {
+        let values = ComputedValues::default();
+        let view_params = ViewParams::new(Dpi::new(96.0, 96.0), 100.0, 100.0);
+        let params = NormalizeParams::new(&values, &view_params);
+        let reference_box = Rect::new(0.0, 0.0, 100.0, 100.0);
+
+        let resolve = |s: &str| {
+            TransformList::parse_str(s)
+                .unwrap()
+                .to_transform(&params, &reference_box)
+        };
+
+        assert_transform_eq(&resolve("rotate(0.25turn)"), &resolve("rotate(90deg)"));
+        assert_transform_eq(
+            &resolve("rotate(90deg)"),
+            &parse_transform("rotate(90)").unwrap(),
+        );
+        assert_transform_eq(&resolve("rotate(100grad)"), &resolve("rotate(90)"));
+        assert_transform_eq(
+            &resolve("skewX(30deg) skewY(-30deg)"),
+            &parse_transform("skewX(30) skewY(-30)").unwrap(),
+        );
+        assert_transform_eq(
+            &resolve("translate(1in, 10px) rotate(45deg)"),
+            &parse_transform("translate(96, 10) rotate(45)").unwrap(),
+        );
+
+        // The center of rotation is only supported without units, like in the attribute
+        assert_transform_eq(
+            &resolve("rotate(30 10 10)"),
+            &parse_transform("rotate(30 10 10)").unwrap(),
+        );
+
+        // The attribute keeps its unitless grammar
+        assert!(parse_transform("rotate(90deg)").is_err());
+    }
+
     #[test]
     fn parses_scale() {
         assert_transform_eq(
@@ -544,6 +764,24 @@ mod tests {
         );
     }
 
+    #[test]
+    fn rotate_about_a_point_is_translate_rotate_translate() {
+        assert_transform_eq(
+            &parse_transform("rotate(45 10 20)").unwrap(),
+            &parse_transform("translate(10 20) rotate(45) translate(-10 -20)").unwrap(),
+        );
+
+        let t = parse_transform("rotate(90 10 20)").unwrap();
+        let (x, y) = t.transform_point(10.0, 20.0);
+        assert!(x.approx_eq(10.0, (1e-9, 1)) && y.approx_eq(20.0, (1e-9, 1)));
+
+        let (x, y) = t.transform_point(20.0, 20.0);
+        assert!(x.approx_eq(10.0, (1e-9, 1)) && y.approx_eq(30.0, (1e-9, 1)));
+
+        // The center needs both coordinates
+        assert!(parse_transform("rotate(45 10)").is_err());
+    }
+
     #[test]
     fn parses_skew_x() {
         assert_transform_eq(
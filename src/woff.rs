@@ -0,0 +1,288 @@
+//! Decoding of WOFF fonts into the SFNT data of TrueType and OpenType fonts.
+//!
+//! A WOFF file has the same tables as the font, each one compressed with zlib.  This
+//! rebuilds the font's table directory and decompresses the tables after it.
+//!
+//! WOFF 2.0 files are decoded in the `woff2` module, which shares the parts that
+//! rebuild the font.
+//!
+//! https://www.w3.org/TR/WOFF/
+
+use gio::prelude::*;
+
+use crate::limits;
+
+/// Size of the WOFF header, which comes before the table directory.
+const WOFF_HEADER_SIZE: usize = 44;
+
+/// Size of each entry in the WOFF table directory.
+const WOFF_ENTRY_SIZE: usize = 20;
+
+/// Size of the SFNT offset table, which comes before the table directory.
+pub const SFNT_HEADER_SIZE: usize = 12;
+
+/// Size of each entry in the SFNT table directory.
+pub const SFNT_ENTRY_SIZE: usize = 16;
+
+/// Maximum number of bytes to decompress at a time.
+const INFLATE_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Font data that starts like a WOFF file, but has invalid headers or tables.
+#[derive(Debug, PartialEq)]
+pub struct InvalidWoff;
+
+/// Whether the font `data` is in the WOFF 1.0 format.
+pub fn is_woff(data: &[u8]) -> bool {
+    data.starts_with(b"wOFF")
+}
+
+/// Whether the font `data` is in the WOFF 2.0 format.
+pub fn is_woff2(data: &[u8]) -> bool {
+    data.starts_with(b"wOF2")
+}
+
+/// A table of the font, with the checksum for its entry in the table directory.
+pub struct SfntTable {
+    pub tag: u32,
+    pub checksum: u32,
+    pub data: Vec<u8>,
+}
+
+/// Converts a WOFF 1.0 file to the TrueType or OpenType font inside it.
+///
+/// The extended metadata and private data blocks of the WOFF file are not part of the
+/// font, so they are ignored.
+///
+/// The font may not be larger than the `totalSfntSize` in the WOFF header, nor than
+/// [`limits::MAX_WOFF_SFNT_SIZE`].
+pub fn decode_woff(data: &[u8]) -> Result<Vec<u8>, InvalidWoff> {
+    if !is_woff(data) {
+        return Err(InvalidWoff);
+    }
+
+    let flavor = read_u32(data, 4)?;
+    let num_tables = usize::from(read_u16(data, 12)?);
+    let total_sfnt_size = read_u32(data, 16)? as usize;
+
+    if num_tables == 0 || total_sfnt_size > limits::MAX_WOFF_SFNT_SIZE {
+        return Err(InvalidWoff);
+    }
+
+    let mut tables = Vec::with_capacity(num_tables);
+    let mut sfnt_size = SFNT_HEADER_SIZE + SFNT_ENTRY_SIZE * num_tables;
+
+    for i in 0..num_tables {
+        let entry = WOFF_HEADER_SIZE + WOFF_ENTRY_SIZE * i;
+
+        let tag = read_u32(data, entry)?;
+        let offset = read_u32(data, entry + 4)? as usize;
+        let comp_length = read_u32(data, entry + 8)? as usize;
+        let orig_length = read_u32(data, entry + 12)? as usize;
+        let checksum = read_u32(data, entry + 16)?;
+
+        let end = offset.checked_add(comp_length).ok_or(InvalidWoff)?;
+        let compressed = data.get(offset..end).ok_or(InvalidWoff)?;
+
+        // Tables are padded to 4-byte boundaries in the font.
+        sfnt_size = orig_length
+            .checked_add(3)
+            .map(|len| len & !3)
+            .and_then(|len| sfnt_size.checked_add(len))
+            .ok_or(InvalidWoff)?;
+
+        if sfnt_size > total_sfnt_size {
+            return Err(InvalidWoff);
+        }
+
+        // Tables that would not get smaller are stored without compression.
+        let table = if comp_length < orig_length {
+            inflate(compressed, orig_length)?
+        } else if comp_length == orig_length {
+            compressed.to_vec()
+        } else {
+            return Err(InvalidWoff);
+        };
+
+        tables.push(SfntTable {
+            tag,
+            checksum,
+            data: table,
+        });
+    }
+
+    Ok(build_sfnt(flavor, &tables))
+}
+
+/// Builds the SFNT data of a font from its offset table and its `tables`, which must
+/// be sorted by tag.
+pub fn build_sfnt(flavor: u32, tables: &[SfntTable]) -> Vec<u8> {
+    let num_tables = tables.len();
+
+    let mut entry_selector = 0;
+    while 2 << entry_selector <= num_tables {
+        entry_selector += 1;
+    }
+    let search_range = (1 << entry_selector) * SFNT_ENTRY_SIZE;
+    let range_shift = num_tables * SFNT_ENTRY_SIZE - search_range;
+
+    let mut sfnt = Vec::new();
+    sfnt.extend_from_slice(&flavor.to_be_bytes());
+    sfnt.extend_from_slice(&(num_tables as u16).to_be_bytes());
+    sfnt.extend_from_slice(&(search_range as u16).to_be_bytes());
+    sfnt.extend_from_slice(&(entry_selector as u16).to_be_bytes());
+    sfnt.extend_from_slice(&(range_shift as u16).to_be_bytes());
+
+    let mut offset = SFNT_HEADER_SIZE + SFNT_ENTRY_SIZE * num_tables;
+
+    for table in tables {
+        sfnt.extend_from_slice(&table.tag.to_be_bytes());
+        sfnt.extend_from_slice(&table.checksum.to_be_bytes());
+        sfnt.extend_from_slice(&(offset as u32).to_be_bytes());
+        sfnt.extend_from_slice(&(table.data.len() as u32).to_be_bytes());
+
+        offset += padded_length(table.data.len());
+    }
+
+    for table in tables {
+        sfnt.extend_from_slice(&table.data);
+
+        // Tables start at 4-byte boundaries.
+        sfnt.resize(padded_length(sfnt.len()), 0);
+    }
+
+    sfnt
+}
+
+/// The length of a table padded to a 4-byte boundary.
+pub fn padded_length(length: usize) -> usize {
+    (length + 3) & !3
+}
+
+/// Decompresses a zlib stream that must produce exactly `length` bytes.
+fn inflate(compressed: &[u8], length: usize) -> Result<Vec<u8>, InvalidWoff> {
+    let bytes = glib::Bytes::from_owned(compressed.to_vec());
+    let stream = gio::MemoryInputStream::from_bytes(&bytes);
+    let decompressor = gio::ZlibDecompressor::new(gio::ZlibCompressorFormat::Zlib);
+    let converter = gio::ConverterInputStream::new(&stream, &decompressor);
+
+    let mut table = Vec::new();
+
+    while table.len() < length {
+        let chunk_size = (length - table.len()).min(INFLATE_CHUNK_SIZE);
+        let chunk = converter
+            .read_bytes(chunk_size, None::<&gio::Cancellable>)
+            .map_err(|_| InvalidWoff)?;
+
+        if chunk.is_empty() {
+            break;
+        }
+
+        table.extend_from_slice(&chunk);
+    }
+
+    if table.len() == length {
+        Ok(table)
+    } else {
+        Err(InvalidWoff)
+    }
+}
+
+pub fn read_u16(data: &[u8], offset: usize) -> Result<u16, InvalidWoff> {
+    data.get(offset..offset + 2)
+        .map(|b| u16::from_be_bytes([b[0], b[1]]))
+        .ok_or(InvalidWoff)
+}
+
+pub fn read_u32(data: &[u8], offset: usize) -> Result<u32, InvalidWoff> {
+    data.get(offset..offset + 4)
+        .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or(InvalidWoff)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A WOFF file with a single `test` table of 5 bytes, stored without compression.
+    fn stored_woff() -> Vec<u8> {
+        let mut woff = Vec::new();
+
+        // header
+        woff.extend_from_slice(b"wOFF");
+        woff.extend_from_slice(&0x0001_0000u32.to_be_bytes()); // flavor
+        woff.extend_from_slice(&72u32.to_be_bytes()); // length
+        woff.extend_from_slice(&1u16.to_be_bytes()); // numTables
+        woff.extend_from_slice(&0u16.to_be_bytes()); // reserved
+        woff.extend_from_slice(&36u32.to_be_bytes()); // totalSfntSize
+        woff.extend_from_slice(&[0; 24]); // version, metadata, private data
+
+        // table directory
+        woff.extend_from_slice(b"test");
+        woff.extend_from_slice(&64u32.to_be_bytes()); // offset
+        woff.extend_from_slice(&5u32.to_be_bytes()); // compLength
+        woff.extend_from_slice(&5u32.to_be_bytes()); // origLength
+        woff.extend_from_slice(&0x1234_5678u32.to_be_bytes()); // origChecksum
+
+        woff.extend_from_slice(b"hello\0\0\0");
+
+        woff
+    }
+
+    #[test]
+    fn detects_woff_formats() {
+        assert!(is_woff(b"wOFF\0\x01\0\0"));
+        assert!(is_woff2(b"wOF2\0\x01\0\0"));
+        assert!(!is_woff(b"\0\x01\0\0wOFF"));
+        assert!(!is_woff(b"wOF2\0\x01\0\0"));
+    }
+
+    #[test]
+    fn decodes_stored_tables() {
+        let sfnt = decode_woff(&stored_woff()).unwrap();
+
+        let mut expected = Vec::new();
+        expected.extend_from_slice(&[0, 1, 0, 0, 0, 1, 0, 16, 0, 0, 0, 0]);
+        expected.extend_from_slice(b"test");
+        expected.extend_from_slice(&0x1234_5678u32.to_be_bytes());
+        expected.extend_from_slice(&28u32.to_be_bytes());
+        expected.extend_from_slice(&5u32.to_be_bytes());
+        expected.extend_from_slice(b"hello\0\0\0");
+
+        assert_eq!(sfnt, expected);
+    }
+
+    #[test]
+    fn detects_invalid_woff() {
+        let woff = stored_woff();
+
+        assert_eq!(decode_woff(&woff[..50]), Err(InvalidWoff));
+        assert_eq!(decode_woff(&woff[..66]), Err(InvalidWoff));
+
+        // compLength larger than origLength
+        let mut bad_lengths = woff.clone();
+        bad_lengths[55] = 6;
+        assert_eq!(decode_woff(&bad_lengths), Err(InvalidWoff));
+
+        // compressed data that is not a zlib stream
+        let mut bad_zlib = woff.clone();
+        bad_zlib[59] = 10;
+        assert_eq!(decode_woff(&bad_zlib), Err(InvalidWoff));
+
+        // no tables
+        let mut no_tables = woff;
+        no_tables[13] = 0;
+        assert_eq!(decode_woff(&no_tables), Err(InvalidWoff));
+    }
+
+    #[test]
+    fn tables_must_fit_in_the_total_sfnt_size() {
+        // the padded table needs 36 bytes
+        let mut too_small = stored_woff();
+        too_small[19] = 35;
+        assert_eq!(decode_woff(&too_small), Err(InvalidWoff));
+
+        let mut too_large = stored_woff();
+        too_large[16..20].copy_from_slice(&(limits::MAX_WOFF_SFNT_SIZE as u32 + 1).to_be_bytes());
+        assert_eq!(decode_woff(&too_large), Err(InvalidWoff));
+    }
+}
@@ -0,0 +1,923 @@
+//! Decoding of WOFF 2.0 fonts into the SFNT data of TrueType and OpenType fonts.
+//!
+//! A WOFF 2.0 file compresses all of the font's tables together with Brotli.  Before
+//! that, the `glyf` and `loca` tables of TrueType fonts get transformed into separate
+//! streams of contours, points, and instructions that compress better, and the `hmtx`
+//! table may leave out the side bearings that are the same as the glyphs' `xMin`.  This
+//! decompresses the tables, undoes those transforms, and rebuilds the font's table
+//! directory with the `woff` module.
+//!
+//! The `glyf` table that comes out does not have the same bytes as the original font,
+//! since glyphs can be encoded in several ways, but it has the same glyphs.
+//!
+//! Font collections in WOFF 2.0 files are not supported.
+//!
+//! https://www.w3.org/TR/WOFF2/
+
+use std::convert::TryFrom;
+use std::io::Read;
+
+use crate::limits;
+use crate::woff::{
+    self, read_u16, read_u32, InvalidWoff, SfntTable, SFNT_ENTRY_SIZE, SFNT_HEADER_SIZE,
+};
+
+/// Size of the WOFF 2.0 header, which comes before the table directory.
+const WOFF2_HEADER_SIZE: usize = 48;
+
+/// The tags that entries in the table directory can refer to by their index.
+const KNOWN_TAGS: [&[u8; 4]; 63] = [
+    b"cmap", b"head", b"hhea", b"hmtx", b"maxp", b"name", b"OS/2", b"post", b"cvt ", b"fpgm",
+    b"glyf", b"loca", b"prep", b"CFF ", b"VORG", b"EBDT", b"EBLC", b"gasp", b"hdmx", b"kern",
+    b"LTSH", b"PCLT", b"VDMX", b"vhea", b"vmtx", b"BASE", b"GDEF", b"GPOS", b"GSUB", b"EBSC",
+    b"JSTF", b"MATH", b"CBDT", b"CBLC", b"COLR", b"CPAL", b"SVG ", b"sbix", b"acnt", b"avar",
+    b"bdat", b"bloc", b"bsln", b"cvar", b"fdsc", b"feat", b"fmtx", b"fvar", b"gvar", b"hsty",
+    b"just", b"lcar", b"mort", b"morx", b"opbd", b"prop", b"trak", b"Zapf", b"Silf", b"Glat",
+    b"Gloc", b"Feat", b"Sill",
+];
+
+/// Index in the table directory entries for a tag that follows the entry's flags.
+const ARBITRARY_TAG: u8 = 63;
+
+const TAG_GLYF: u32 = u32::from_be_bytes(*b"glyf");
+const TAG_HEAD: u32 = u32::from_be_bytes(*b"head");
+const TAG_HHEA: u32 = u32::from_be_bytes(*b"hhea");
+const TAG_HMTX: u32 = u32::from_be_bytes(*b"hmtx");
+const TAG_LOCA: u32 = u32::from_be_bytes(*b"loca");
+const TAG_TTCF: u32 = u32::from_be_bytes(*b"ttcf");
+
+/// Size of the header of the transformed `glyf` table, which comes before its streams.
+const GLYF_HEADER_SIZE: usize = 36;
+
+// Flags of the points of simple glyphs in the `glyf` table.
+const ON_CURVE_POINT: u8 = 0x01;
+const X_SHORT_VECTOR: u8 = 0x02;
+const Y_SHORT_VECTOR: u8 = 0x04;
+const X_IS_SAME_OR_POSITIVE: u8 = 0x10;
+const Y_IS_SAME_OR_POSITIVE: u8 = 0x20;
+const OVERLAP_SIMPLE: u8 = 0x40;
+
+// Flags of the components of composite glyphs in the `glyf` table.
+const ARG_1_AND_2_ARE_WORDS: u16 = 0x0001;
+const WE_HAVE_A_SCALE: u16 = 0x0008;
+const MORE_COMPONENTS: u16 = 0x0020;
+const WE_HAVE_AN_X_AND_Y_SCALE: u16 = 0x0040;
+const WE_HAVE_A_TWO_BY_TWO: u16 = 0x0080;
+const WE_HAVE_INSTRUCTIONS: u16 = 0x0100;
+
+/// Converts a WOFF 2.0 file to the TrueType or OpenType font inside it.
+///
+/// The extended metadata and private data blocks of the WOFF file are not part of the
+/// font, so they are ignored.
+///
+/// Neither the decompressed tables nor the font may be larger than
+/// [`limits::MAX_WOFF_SFNT_SIZE`].
+pub fn decode_woff2(data: &[u8]) -> Result<Vec<u8>, InvalidWoff> {
+    if !woff::is_woff2(data) {
+        return Err(InvalidWoff);
+    }
+
+    let flavor = read_u32(data, 4)?;
+    let num_tables = usize::from(read_u16(data, 12)?);
+    let total_compressed_size = read_u32(data, 20)? as usize;
+
+    if num_tables == 0 {
+        return Err(InvalidWoff);
+    }
+
+    if flavor == TAG_TTCF {
+        rsvg_log!("font collections in WOFF 2.0 files are not supported");
+        return Err(InvalidWoff);
+    }
+
+    let mut directory = Stream::new(data.get(WOFF2_HEADER_SIZE..).ok_or(InvalidWoff)?);
+    let mut entries = Vec::with_capacity(num_tables);
+    let mut decompressed_size = 0usize;
+
+    for _ in 0..num_tables {
+        let entry = TableEntry::read(&mut directory)?;
+
+        decompressed_size = decompressed_size
+            .checked_add(entry.length)
+            .ok_or(InvalidWoff)?;
+
+        entries.push(entry);
+    }
+
+    if decompressed_size > limits::MAX_WOFF_SFNT_SIZE {
+        return Err(InvalidWoff);
+    }
+
+    let compressed = directory.bytes(total_compressed_size)?;
+    let decompressed = decompress(compressed, decompressed_size)?;
+
+    // The tables are one after the other in the decompressed data.
+    let mut sources = Vec::with_capacity(num_tables);
+    let mut offset = 0;
+    for entry in &entries {
+        sources.push(&decompressed[offset..offset + entry.length]);
+        offset += entry.length;
+    }
+
+    let find = |tag| entries.iter().position(|e| e.tag == tag);
+    let is_transformed = |index: Option<usize>| index.map_or(false, |i| entries[i].transformed);
+
+    // The glyf and loca tables are transformed together, or not at all.
+    let (glyf_index, loca_index) = (find(TAG_GLYF), find(TAG_LOCA));
+
+    let glyf = match (glyf_index, loca_index) {
+        (Some(g), Some(l)) if entries[g].transformed && entries[l].transformed => {
+            Some(reconstruct_glyf(sources[g], entries[l].orig_length)?)
+        }
+
+        _ if is_transformed(glyf_index) || is_transformed(loca_index) => return Err(InvalidWoff),
+
+        _ => None,
+    };
+
+    let mut tables = Vec::with_capacity(num_tables);
+    let mut sfnt_size = SFNT_HEADER_SIZE + SFNT_ENTRY_SIZE * num_tables;
+
+    for (entry, &source) in entries.iter().zip(&sources) {
+        let mut table = match (entry.tag, &glyf) {
+            (TAG_GLYF, Some(glyf)) => glyf.glyf.clone(),
+            (TAG_LOCA, Some(glyf)) => glyf.loca.clone(),
+
+            (TAG_HMTX, Some(glyf)) if entry.transformed => {
+                let hhea = find(TAG_HHEA).ok_or(InvalidWoff)?;
+                let num_h_metrics = usize::from(read_u16(sources[hhea], 34)?);
+
+                reconstruct_hmtx(source, num_h_metrics, &glyf.x_mins, entry.orig_length)?
+            }
+
+            // Only the hmtx table of a transformed glyf table can be transformed, since
+            // it needs the glyphs' bounding boxes.
+            (TAG_HMTX, None) if entry.transformed => return Err(InvalidWoff),
+
+            _ => source.to_vec(),
+        };
+
+        // The checksum of the head table is computed without the checksum adjustment for
+        // the whole font, which gets computed at the end.
+        if entry.tag == TAG_HEAD {
+            table
+                .get_mut(8..12)
+                .ok_or(InvalidWoff)?
+                .copy_from_slice(&[0; 4]);
+        }
+
+        sfnt_size = sfnt_size
+            .checked_add(woff::padded_length(table.len()))
+            .ok_or(InvalidWoff)?;
+
+        if sfnt_size > limits::MAX_WOFF_SFNT_SIZE {
+            return Err(InvalidWoff);
+        }
+
+        tables.push(SfntTable {
+            tag: entry.tag,
+            checksum: checksum(&table),
+            data: table,
+        });
+    }
+
+    tables.sort_by_key(|t| t.tag);
+
+    let mut sfnt = woff::build_sfnt(flavor, &tables);
+    set_checksum_adjustment(&mut sfnt, num_tables);
+
+    Ok(sfnt)
+}
+
+/// An entry of the WOFF 2.0 table directory.
+struct TableEntry {
+    tag: u32,
+
+    /// Length of the table in the font.
+    orig_length: usize,
+
+    /// Whether the table is stored with a transform that has to be undone.
+    transformed: bool,
+
+    /// Length of the table in the decompressed data.
+    length: usize,
+}
+
+impl TableEntry {
+    fn read(directory: &mut Stream<'_>) -> Result<TableEntry, InvalidWoff> {
+        let flags = directory.u8()?;
+        let tag_index = flags & 0x3f;
+        let transform_version = flags >> 6;
+
+        let tag = if tag_index == ARBITRARY_TAG {
+            directory.u32()?
+        } else {
+            u32::from_be_bytes(*KNOWN_TAGS[usize::from(tag_index)])
+        };
+
+        let orig_length = directory.uint_base128()? as usize;
+
+        // For glyf and loca, version 0 is the transform and version 3 is none; for the
+        // other tables, version 0 is none.  Only hmtx has another transform, version 1.
+        let transformed = match (tag, transform_version) {
+            (TAG_GLYF, 0) | (TAG_LOCA, 0) | (TAG_HMTX, 1) => true,
+            (TAG_GLYF, 3) | (TAG_LOCA, 3) => false,
+            (TAG_GLYF, _) | (TAG_LOCA, _) => return Err(InvalidWoff),
+            (_, 0) => false,
+            _ => return Err(InvalidWoff),
+        };
+
+        let length = if transformed {
+            directory.uint_base128()? as usize
+        } else {
+            orig_length
+        };
+
+        // The transformed loca table is empty; it gets rebuilt from the glyf table.
+        if tag == TAG_LOCA && transformed && length != 0 {
+            return Err(InvalidWoff);
+        }
+
+        Ok(TableEntry {
+            tag,
+            orig_length,
+            transformed,
+            length,
+        })
+    }
+}
+
+/// Decompresses a Brotli stream that must produce exactly `length` bytes.
+fn decompress(compressed: &[u8], length: usize) -> Result<Vec<u8>, InvalidWoff> {
+    let mut decompressed = Vec::new();
+
+    brotli_decompressor::Decompressor::new(compressed, 4096)
+        .take(length as u64 + 1)
+        .read_to_end(&mut decompressed)
+        .map_err(|_| InvalidWoff)?;
+
+    if decompressed.len() == length {
+        Ok(decompressed)
+    } else {
+        Err(InvalidWoff)
+    }
+}
+
+/// The glyf and loca tables rebuilt from a transformed glyf table.
+struct Glyf {
+    glyf: Vec<u8>,
+    loca: Vec<u8>,
+
+    /// The `xMin` of each glyph's bounding box, for rebuilding a transformed hmtx table.
+    x_mins: Vec<i16>,
+}
+
+/// Undoes the transform of the glyf table, and rebuilds the loca table along with it.
+fn reconstruct_glyf(data: &[u8], loca_length: usize) -> Result<Glyf, InvalidWoff> {
+    let mut header = Stream::new(data);
+
+    let _reserved = header.u16()?;
+    let option_flags = header.u16()?;
+    let num_glyphs = usize::from(header.u16()?);
+    let index_format = header.u16()?;
+
+    let loca_entry_size = match index_format {
+        0 => 2,
+        1 => 4,
+        _ => return Err(InvalidWoff),
+    };
+
+    if loca_length != (num_glyphs + 1) * loca_entry_size {
+        return Err(InvalidWoff);
+    }
+
+    let mut streams = Stream::new(data.get(GLYF_HEADER_SIZE..).ok_or(InvalidWoff)?);
+    let mut substream = |header: &mut Stream<'_>| -> Result<Stream<'_>, InvalidWoff> {
+        let size = header.u32()? as usize;
+        Ok(Stream::new(streams.bytes(size)?))
+    };
+
+    let mut n_contours = substream(&mut header)?;
+    let mut n_points = substream(&mut header)?;
+    let mut flags = substream(&mut header)?;
+    let mut glyphs = substream(&mut header)?;
+    let mut composites = substream(&mut header)?;
+    let mut bboxes = substream(&mut header)?;
+    let mut instructions = substream(&mut header)?;
+
+    // Bit 0 of the option flags says that a bitmap with the OVERLAP_SIMPLE flag of each
+    // glyph comes after the streams.
+    let overlap_bitmap = if option_flags & 1 != 0 {
+        Some(streams.bytes((num_glyphs + 7) / 8)?)
+    } else {
+        None
+    };
+
+    let bbox_bitmap = bboxes.bytes(4 * ((num_glyphs + 31) / 32))?;
+
+    let mut glyf = Vec::new();
+    let mut offsets = Vec::with_capacity(num_glyphs + 1);
+    let mut x_mins = Vec::with_capacity(num_glyphs);
+
+    for i in 0..num_glyphs {
+        offsets.push(glyf.len());
+
+        let has_bbox = bit_is_set(bbox_bitmap, i);
+
+        match n_contours.i16()? {
+            0 => {
+                // Empty glyphs don't have a bounding box.
+                if has_bbox {
+                    return Err(InvalidWoff);
+                }
+
+                x_mins.push(0);
+            }
+
+            -1 => {
+                // Composite glyphs need an explicit bounding box.
+                if !has_bbox {
+                    return Err(InvalidWoff);
+                }
+
+                let bbox = bboxes.bytes(8)?;
+                x_mins.push(i16::from_be_bytes([bbox[0], bbox[1]]));
+
+                glyf.extend_from_slice(&(-1i16).to_be_bytes());
+                glyf.extend_from_slice(bbox);
+
+                let have_instructions = copy_components(&mut composites, &mut glyf)?;
+
+                if have_instructions {
+                    copy_instructions(&mut glyphs, &mut instructions, &mut glyf)?;
+                }
+            }
+
+            n if n > 0 => {
+                let overlap = overlap_bitmap.map_or(false, |b| bit_is_set(b, i));
+                let x_min = reconstruct_simple_glyph(
+                    n as usize,
+                    has_bbox,
+                    overlap,
+                    &mut n_points,
+                    &mut flags,
+                    &mut glyphs,
+                    &mut bboxes,
+                    &mut instructions,
+                    &mut glyf,
+                )?;
+
+                x_mins.push(x_min);
+            }
+
+            _ => return Err(InvalidWoff),
+        }
+
+        // Glyphs start at 4-byte boundaries.
+        glyf.resize(woff::padded_length(glyf.len()), 0);
+
+        if glyf.len() > limits::MAX_WOFF_SFNT_SIZE {
+            return Err(InvalidWoff);
+        }
+    }
+
+    offsets.push(glyf.len());
+
+    let mut loca = Vec::with_capacity(loca_length);
+
+    for offset in offsets {
+        if index_format == 0 {
+            // The short format has the offsets divided by 2.
+            let offset = u16::try_from(offset / 2).map_err(|_| InvalidWoff)?;
+            loca.extend_from_slice(&offset.to_be_bytes());
+        } else {
+            loca.extend_from_slice(&(offset as u32).to_be_bytes());
+        }
+    }
+
+    Ok(Glyf { glyf, loca, x_mins })
+}
+
+/// Rebuilds a simple glyph with `num_contours` contours, and returns its `xMin`.
+#[allow(clippy::too_many_arguments)]
+fn reconstruct_simple_glyph(
+    num_contours: usize,
+    has_bbox: bool,
+    overlap: bool,
+    n_points: &mut Stream<'_>,
+    flags: &mut Stream<'_>,
+    glyphs: &mut Stream<'_>,
+    bboxes: &mut Stream<'_>,
+    instructions: &mut Stream<'_>,
+    glyf: &mut Vec<u8>,
+) -> Result<i16, InvalidWoff> {
+    let mut end_points = Vec::with_capacity(num_contours);
+    let mut num_points = 0usize;
+
+    for _ in 0..num_contours {
+        num_points += usize::from(n_points.uint_255_16()?);
+
+        let end_point = num_points
+            .checked_sub(1)
+            .and_then(|p| u16::try_from(p).ok())
+            .ok_or(InvalidWoff)?;
+
+        end_points.push(end_point);
+    }
+
+    let point_flags = flags.bytes(num_points)?;
+    let points = decode_triplets(point_flags, glyphs)?;
+
+    let bbox = if has_bbox {
+        let b = bboxes.bytes(8)?;
+        [
+            i16::from_be_bytes([b[0], b[1]]),
+            i16::from_be_bytes([b[2], b[3]]),
+            i16::from_be_bytes([b[4], b[5]]),
+            i16::from_be_bytes([b[6], b[7]]),
+        ]
+    } else {
+        bounding_box(&points)?
+    };
+
+    glyf.extend_from_slice(&(num_contours as i16).to_be_bytes());
+    for v in &bbox {
+        glyf.extend_from_slice(&v.to_be_bytes());
+    }
+
+    for end_point in end_points {
+        glyf.extend_from_slice(&end_point.to_be_bytes());
+    }
+
+    copy_instructions(glyphs, instructions, glyf)?;
+
+    let mut xs = Vec::new();
+    let mut ys = Vec::new();
+    let mut previous = (0, 0);
+
+    for (i, point) in points.iter().enumerate() {
+        let mut flag = if point.on_curve { ON_CURVE_POINT } else { 0 };
+
+        if i == 0 && overlap {
+            flag |= OVERLAP_SIMPLE;
+        }
+
+        let dx = point.x.checked_sub(previous.0).ok_or(InvalidWoff)?;
+        let dy = point.y.checked_sub(previous.1).ok_or(InvalidWoff)?;
+        previous = (point.x, point.y);
+
+        flag |= encode_delta(dx, X_SHORT_VECTOR, X_IS_SAME_OR_POSITIVE, &mut xs)?;
+        flag |= encode_delta(dy, Y_SHORT_VECTOR, Y_IS_SAME_OR_POSITIVE, &mut ys)?;
+
+        glyf.push(flag);
+    }
+
+    glyf.extend_from_slice(&xs);
+    glyf.extend_from_slice(&ys);
+
+    Ok(bbox[0])
+}
+
+/// Copies the components of a composite glyph, and returns whether it has instructions.
+fn copy_components(composites: &mut Stream<'_>, glyf: &mut Vec<u8>) -> Result<bool, InvalidWoff> {
+    let mut have_instructions = false;
+
+    loop {
+        let flags = composites.u16()?;
+
+        let args_size = if flags & ARG_1_AND_2_ARE_WORDS != 0 {
+            4
+        } else {
+            2
+        };
+
+        let transform_size = if flags & WE_HAVE_A_SCALE != 0 {
+            2
+        } else if flags & WE_HAVE_AN_X_AND_Y_SCALE != 0 {
+            4
+        } else if flags & WE_HAVE_A_TWO_BY_TWO != 0 {
+            8
+        } else {
+            0
+        };
+
+        glyf.extend_from_slice(&flags.to_be_bytes());
+
+        // The glyph index, the arguments, and the transform
+        glyf.extend_from_slice(composites.bytes(2 + args_size + transform_size)?);
+
+        have_instructions |= flags & WE_HAVE_INSTRUCTIONS != 0;
+
+        if flags & MORE_COMPONENTS == 0 {
+            return Ok(have_instructions);
+        }
+    }
+}
+
+/// Copies the instructions of a glyph, whose length comes from the glyph stream.
+fn copy_instructions(
+    glyphs: &mut Stream<'_>,
+    instructions: &mut Stream<'_>,
+    glyf: &mut Vec<u8>,
+) -> Result<(), InvalidWoff> {
+    let length = glyphs.uint_255_16()?;
+
+    glyf.extend_from_slice(&length.to_be_bytes());
+    glyf.extend_from_slice(instructions.bytes(usize::from(length))?);
+
+    Ok(())
+}
+
+/// A point of a simple glyph, in font units.
+struct Point {
+    x: i32,
+    y: i32,
+    on_curve: bool,
+}
+
+/// Decodes the points of a simple glyph from their `flags` and the coordinates in the
+/// glyph stream.
+///
+/// Each point's flag says how many bytes of the glyph stream have its coordinates, as
+/// deltas from the previous point, and how those bytes are split between x and y.
+fn decode_triplets(flags: &[u8], glyphs: &mut Stream<'_>) -> Result<Vec<Point>, InvalidWoff> {
+    let mut points = Vec::with_capacity(flags.len());
+    let (mut x, mut y) = (0i32, 0i32);
+
+    for &flag in flags {
+        let on_curve = flag & 0x80 == 0;
+        let flag = flag & 0x7f;
+
+        let with_sign = |sign_flag: u8, value: i32| if sign_flag & 1 != 0 { value } else { -value };
+
+        let (dx, dy) = if flag < 10 {
+            let b = glyphs.bytes(1)?;
+            (
+                0,
+                with_sign(flag, (i32::from(flag & 14) << 7) + i32::from(b[0])),
+            )
+        } else if flag < 20 {
+            let b = glyphs.bytes(1)?;
+            (
+                with_sign(flag, (i32::from((flag - 10) & 14) << 7) + i32::from(b[0])),
+                0,
+            )
+        } else if flag < 84 {
+            let b = glyphs.bytes(1)?;
+            let f = i32::from(flag - 20);
+            let b = i32::from(b[0]);
+            (
+                with_sign(flag, 1 + (f & 0x30) + (b >> 4)),
+                with_sign(flag >> 1, 1 + ((f & 0x0c) << 2) + (b & 0x0f)),
+            )
+        } else if flag < 120 {
+            let b = glyphs.bytes(2)?;
+            let f = i32::from(flag - 84);
+            (
+                with_sign(flag, 1 + ((f / 12) << 8) + i32::from(b[0])),
+                with_sign(flag >> 1, 1 + (((f % 12) >> 2) << 8) + i32::from(b[1])),
+            )
+        } else if flag < 124 {
+            let b = glyphs.bytes(3)?;
+            let (b0, b1, b2) = (i32::from(b[0]), i32::from(b[1]), i32::from(b[2]));
+            (
+                with_sign(flag, (b0 << 4) + (b1 >> 4)),
+                with_sign(flag >> 1, ((b1 & 0x0f) << 8) + b2),
+            )
+        } else {
+            let b = glyphs.bytes(4)?;
+            (
+                with_sign(flag, i32::from(u16::from_be_bytes([b[0], b[1]]))),
+                with_sign(flag >> 1, i32::from(u16::from_be_bytes([b[2], b[3]]))),
+            )
+        };
+
+        x = x.checked_add(dx).ok_or(InvalidWoff)?;
+        y = y.checked_add(dy).ok_or(InvalidWoff)?;
+
+        points.push(Point { x, y, on_curve });
+    }
+
+    Ok(points)
+}
+
+/// Computes the `[xMin, yMin, xMax, yMax]` of a simple glyph from its points.
+fn bounding_box(points: &[Point]) -> Result<[i16; 4], InvalidWoff> {
+    let to_i16 = |v: i32| i16::try_from(v).map_err(|_| InvalidWoff);
+
+    match points.first() {
+        None => Ok([0; 4]),
+
+        Some(first) => {
+            let (mut x_min, mut y_min) = (first.x, first.y);
+            let (mut x_max, mut y_max) = (first.x, first.y);
+
+            for p in points {
+                x_min = x_min.min(p.x);
+                y_min = y_min.min(p.y);
+                x_max = x_max.max(p.x);
+                y_max = y_max.max(p.y);
+            }
+
+            Ok([
+                to_i16(x_min)?,
+                to_i16(y_min)?,
+                to_i16(x_max)?,
+                to_i16(y_max)?,
+            ])
+        }
+    }
+}
+
+/// Appends a coordinate delta of a simple glyph to its coordinates, and returns the
+/// bits for the point's flag.
+fn encode_delta(
+    delta: i32,
+    short_flag: u8,
+    same_or_positive_flag: u8,
+    coordinates: &mut Vec<u8>,
+) -> Result<u8, InvalidWoff> {
+    if delta == 0 {
+        Ok(same_or_positive_flag)
+    } else if let Ok(magnitude) = u8::try_from(delta.unsigned_abs()) {
+        coordinates.push(magnitude);
+
+        if delta > 0 {
+            Ok(short_flag | same_or_positive_flag)
+        } else {
+            Ok(short_flag)
+        }
+    } else {
+        let delta = i16::try_from(delta).map_err(|_| InvalidWoff)?;
+        coordinates.extend_from_slice(&delta.to_be_bytes());
+        Ok(0)
+    }
+}
+
+/// Undoes the transform of the hmtx table, which can leave out the left side bearings
+/// that are the same as the glyphs' `xMin`.
+fn reconstruct_hmtx(
+    data: &[u8],
+    num_h_metrics: usize,
+    x_mins: &[i16],
+    orig_length: usize,
+) -> Result<Vec<u8>, InvalidWoff> {
+    let num_glyphs = x_mins.len();
+
+    if num_h_metrics == 0 || num_h_metrics > num_glyphs {
+        return Err(InvalidWoff);
+    }
+
+    let mut hmtx = Stream::new(data);
+    let flags = hmtx.u8()?;
+
+    // Bit 0 says that the side bearings in the long metrics were left out, bit 1 that
+    // the ones for the rest of the glyphs were.  The other bits are reserved.
+    if flags & 0x03 == 0 || flags & 0xfc != 0 {
+        return Err(InvalidWoff);
+    }
+
+    let advances = hmtx.bytes(2 * num_h_metrics)?;
+
+    let mut bearings = Vec::with_capacity(2 * num_glyphs);
+
+    if flags & 0x01 == 0 {
+        bearings.extend_from_slice(hmtx.bytes(2 * num_h_metrics)?);
+    } else {
+        for x_min in &x_mins[..num_h_metrics] {
+            bearings.extend_from_slice(&x_min.to_be_bytes());
+        }
+    }
+
+    if flags & 0x02 == 0 {
+        bearings.extend_from_slice(hmtx.bytes(2 * (num_glyphs - num_h_metrics))?);
+    } else {
+        for x_min in &x_mins[num_h_metrics..] {
+            bearings.extend_from_slice(&x_min.to_be_bytes());
+        }
+    }
+
+    let mut table = Vec::with_capacity(orig_length);
+
+    for i in 0..num_h_metrics {
+        table.extend_from_slice(&advances[2 * i..2 * i + 2]);
+        table.extend_from_slice(&bearings[2 * i..2 * i + 2]);
+    }
+
+    table.extend_from_slice(&bearings[2 * num_h_metrics..]);
+
+    if table.len() == orig_length {
+        Ok(table)
+    } else {
+        Err(InvalidWoff)
+    }
+}
+
+/// Sets the `checkSumAdjustment` of the head table, so that the whole font adds up to
+/// the magic number of the OpenType spec.
+fn set_checksum_adjustment(sfnt: &mut [u8], num_tables: usize) {
+    let head = (0..num_tables)
+        .map(|i| SFNT_HEADER_SIZE + SFNT_ENTRY_SIZE * i)
+        .find(|&entry| sfnt[entry..entry + 4] == *b"head");
+
+    if let Some(entry) = head {
+        let offset = read_u32(sfnt, entry + 8).unwrap() as usize;
+        let adjustment = 0xb1b0_afbau32.wrapping_sub(checksum(sfnt));
+        sfnt[offset + 8..offset + 12].copy_from_slice(&adjustment.to_be_bytes());
+    }
+}
+
+/// Computes the checksum of a table: the sum of its 32-bit big-endian values, with the
+/// table padded with zeros.
+fn checksum(data: &[u8]) -> u32 {
+    data.chunks(4).fold(0u32, |sum, chunk| {
+        let mut value = [0; 4];
+        value[..chunk.len()].copy_from_slice(chunk);
+        sum.wrapping_add(u32::from_be_bytes(value))
+    })
+}
+
+/// Whether bit `i` is set in a bitmap whose bits go from the most significant one.
+fn bit_is_set(bitmap: &[u8], i: usize) -> bool {
+    bitmap[i / 8] & (0x80 >> (i % 8)) != 0
+}
+
+/// Reads the data types of WOFF 2.0 from a slice of bytes.
+struct Stream<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Stream<'a> {
+    fn new(data: &'a [u8]) -> Stream<'a> {
+        Stream { data, position: 0 }
+    }
+
+    fn bytes(&mut self, length: usize) -> Result<&'a [u8], InvalidWoff> {
+        let end = self.position.checked_add(length).ok_or(InvalidWoff)?;
+        let bytes = self.data.get(self.position..end).ok_or(InvalidWoff)?;
+        self.position = end;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, InvalidWoff> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, InvalidWoff> {
+        let b = self.bytes(2)?;
+        Ok(u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    fn i16(&mut self) -> Result<i16, InvalidWoff> {
+        Ok(self.u16()? as i16)
+    }
+
+    fn u32(&mut self) -> Result<u32, InvalidWoff> {
+        let b = self.bytes(4)?;
+        Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    /// Reads a `UIntBase128`, a number in up to 5 bytes of 7 bits each, most
+    /// significant first.
+    fn uint_base128(&mut self) -> Result<u32, InvalidWoff> {
+        let mut value = 0u32;
+
+        for i in 0..5 {
+            let byte = self.u8()?;
+
+            // No leading zeros
+            if i == 0 && byte == 0x80 {
+                return Err(InvalidWoff);
+            }
+
+            if value & 0xfe00_0000 != 0 {
+                return Err(InvalidWoff);
+            }
+
+            value = (value << 7) | u32::from(byte & 0x7f);
+
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+
+        Err(InvalidWoff)
+    }
+
+    /// Reads a `255UInt16`, a number in 1 byte if it is small, or in up to 3 bytes.
+    fn uint_255_16(&mut self) -> Result<u16, InvalidWoff> {
+        const WORD_CODE: u8 = 253;
+        const ONE_MORE_BYTE_CODE_2: u8 = 254;
+        const ONE_MORE_BYTE_CODE_1: u8 = 255;
+        const LOWEST_U_CODE: u16 = 253;
+
+        match self.u8()? {
+            WORD_CODE => self.u16(),
+            ONE_MORE_BYTE_CODE_1 => Ok(u16::from(self.u8()?) + LOWEST_U_CODE),
+            ONE_MORE_BYTE_CODE_2 => Ok(u16::from(self.u8()?) + LOWEST_U_CODE * 2),
+            code => Ok(u16::from(code)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_uint_base128() {
+        assert_eq!(Stream::new(&[0x3f]).uint_base128(), Ok(63));
+        assert_eq!(Stream::new(&[0x81, 0x00]).uint_base128(), Ok(128));
+        assert_eq!(
+            Stream::new(&[0x8f, 0xff, 0xff, 0xff, 0x7f]).uint_base128(),
+            Ok(u32::MAX)
+        );
+
+        // leading zeros
+        assert_eq!(Stream::new(&[0x80, 0x3f]).uint_base128(), Err(InvalidWoff));
+
+        // more than 32 bits
+        assert_eq!(
+            Stream::new(&[0x90, 0x80, 0x80, 0x80, 0x00]).uint_base128(),
+            Err(InvalidWoff)
+        );
+
+        // more than 5 bytes
+        assert_eq!(
+            Stream::new(&[0x81, 0x80, 0x80, 0x80, 0x80, 0x00]).uint_base128(),
+            Err(InvalidWoff)
+        );
+
+        assert_eq!(Stream::new(&[0x81]).uint_base128(), Err(InvalidWoff));
+    }
+
+    #[test]
+    fn reads_uint_255_16() {
+        assert_eq!(Stream::new(&[252]).uint_255_16(), Ok(252));
+        assert_eq!(Stream::new(&[255, 0]).uint_255_16(), Ok(253));
+        assert_eq!(Stream::new(&[254, 0]).uint_255_16(), Ok(506));
+        assert_eq!(Stream::new(&[253, 0x03, 0xe8]).uint_255_16(), Ok(1000));
+        assert_eq!(Stream::new(&[253, 0x03]).uint_255_16(), Err(InvalidWoff));
+    }
+
+    #[test]
+    fn decodes_triplets() {
+        let flags = [
+            1,         // dy = +(0 << 8) + 100, on the curve
+            0x80 | 12, // dx = -((2 << 7) + 44), off the curve
+            20 + 3,    // dx = +(1 + 0 + 2), dy = +(1 + 0 + 5)
+            124,       // dx = -1000, dy = -2000
+        ];
+        let glyphs = [100, 44, 0x25, 0x03, 0xe8, 0x07, 0xd0];
+
+        let points = decode_triplets(&flags, &mut Stream::new(&glyphs)).unwrap();
+        let coords: Vec<_> = points.iter().map(|p| (p.x, p.y, p.on_curve)).collect();
+
+        assert_eq!(
+            coords,
+            vec![
+                (0, 100, true),
+                (-300, 100, false),
+                (-297, 106, true),
+                (-1297, -1894, true)
+            ]
+        );
+
+        // missing coordinates
+        assert!(decode_triplets(&flags, &mut Stream::new(&glyphs[..6])).is_err());
+
+        // deltas of +65535 that add up beyond i32::MAX
+        let flags = vec![127; 32769];
+        let glyphs = vec![0xff; 4 * 32769];
+        assert_eq!(
+            decode_triplets(&flags, &mut Stream::new(&glyphs)).map(|_| ()),
+            Err(InvalidWoff)
+        );
+    }
+
+    #[test]
+    fn detects_invalid_woff2() {
+        let mut header = Vec::new();
+        header.extend_from_slice(b"wOF2");
+        header.extend_from_slice(&0x0001_0000u32.to_be_bytes()); // flavor
+        header.extend_from_slice(&48u32.to_be_bytes()); // length
+        header.extend_from_slice(&0u16.to_be_bytes()); // numTables
+        header.extend_from_slice(&[0; 34]);
+
+        // no tables
+        assert_eq!(decode_woff2(&header), Err(InvalidWoff));
+
+        // a table directory that ends too early
+        let mut truncated = header.clone();
+        truncated[13] = 1;
+        truncated.push(10); // glyf
+        assert_eq!(decode_woff2(&truncated), Err(InvalidWoff));
+
+        // a font collection
+        let mut collection = header;
+        collection[4..8].copy_from_slice(b"ttcf");
+        collection[13] = 1;
+        assert_eq!(decode_woff2(&collection), Err(InvalidWoff));
+    }
+}
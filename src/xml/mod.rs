@@ -370,9 +370,12 @@ impl XmlState {
         let mut inner = self.inner.borrow_mut();
         let current_node = inner.current_node.as_ref().unwrap();
 
-        let style_type = borrow_element_as!(current_node, Style).style_type();
+        let (style_type, applies_to_media) = {
+            let style = borrow_element_as!(current_node, Style);
+            (style.style_type(), style.applies_to_media())
+        };
 
-        if style_type == StyleType::TextCss {
+        if style_type == StyleType::TextCss && applies_to_media {
             let stylesheet_text = current_node
                 .children()
                 .map(|child| {
@@ -1,9 +1,16 @@
 use cairo;
+use glib::Cast;
+use std::cell::RefCell;
+use std::rc::Rc;
+
 use librsvg::surface_utils::shared_surface::{SharedImageSurface, SurfaceType};
-use librsvg::{CairoRenderer, RenderingError};
+use librsvg::{
+    AcceptLanguage, CairoRenderer, ConditionResult, ConditionalElement, FontSubstitution, Language,
+    Loader, LoadingError, MissingGlyphs, RenderingError, TextExtents, UsedFont,
+};
 
 use crate::reference_utils::{Compare, Evaluate, Reference};
-use crate::utils::load_svg;
+use crate::utils::{load_svg, render_document, setup_font_map, SurfaceSize};
 
 #[test]
 fn has_element_with_id_works() {
@@ -219,3 +226,2227 @@ fn set_stylesheet() {
         .compare(&output_surf)
         .evaluate(&output_surf, "set_stylesheet");
 }
+
+#[test]
+fn set_stylesheet_forgets_important_declarations_of_the_previous_one() {
+    let mut svg = load_svg(
+        br##"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" width="100" height="100">
+  <defs>
+    <rect id="rect" width="50" height="100"/>
+  </defs>
+  <use xlink:href="#rect" fill="lime"/>
+  <use xlink:href="#rect" x="50" fill="blue"/>
+</svg>
+"##,
+    )
+    .unwrap();
+
+    let viewport = cairo::Rectangle {
+        x: 0.0,
+        y: 0.0,
+        width: 100.0,
+        height: 100.0,
+    };
+
+    svg.set_stylesheet("rect { fill: red !important; }")
+        .unwrap();
+
+    let output = render_document(&svg, SurfaceSize(100, 100), |_| (), viewport).unwrap();
+    assert_eq!(output.get_pixel(25, 50).r, 255);
+    assert_eq!(output.get_pixel(75, 50).r, 255);
+
+    // The new stylesheet replaces the old one, so the rect inherits the fill of each <use>
+    svg.set_stylesheet("rect { stroke: none; }").unwrap();
+
+    let output = render_document(&svg, SurfaceSize(100, 100), |_| (), viewport).unwrap();
+
+    let lime = output.get_pixel(25, 50);
+    assert_eq!((lime.r, lime.g, lime.b), (0, 255, 0));
+
+    let blue = output.get_pixel(75, 50);
+    assert_eq!((blue.r, blue.g, blue.b), (0, 0, 255));
+}
+
+#[test]
+fn measures_a_string_outside_of_a_document() {
+    setup_font_map();
+
+    let font_map = pangocairo::FontMap::default()
+        .and_then(|font_map| font_map.downcast::<pangocairo::FontMap>().ok())
+        .unwrap();
+
+    let small = librsvg::measure_string(&font_map, "font-family: Roboto; font-size: 10px", "Hello");
+    let large = librsvg::measure_string(&font_map, "font-family: Roboto; font-size: 2em", "Hello");
+
+    assert!(small.width > 0.0);
+    assert!(small.baseline > 0.0 && small.baseline < small.height);
+
+    // 2em is relative to the initial font-size of 12px
+    assert!((large.width - 2.4 * small.width).abs() < 0.5);
+    assert!((large.height - 2.4 * small.height).abs() < 0.5);
+}
+
+#[test]
+fn reports_font_substitution() {
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <text x="10" y="50" font-family="This Font Does Not Exist">Hello</text>
+</svg>
+"#,
+    )
+    .unwrap();
+
+    let substitutions = Rc::new(RefCell::new(Vec::new()));
+
+    let renderer = {
+        let substitutions = substitutions.clone();
+
+        CairoRenderer::new(&svg).with_font_substitution_callback(move |s: &FontSubstitution| {
+            substitutions.borrow_mut().push(s.clone())
+        })
+    };
+
+    let output = cairo::ImageSurface::create(cairo::Format::ARgb32, 100, 100).unwrap();
+    let cr = cairo::Context::new(&output).expect("Failed to create cairo context");
+    let viewport = cairo::Rectangle {
+        x: 0.0,
+        y: 0.0,
+        width: 100.0,
+        height: 100.0,
+    };
+
+    renderer.render_document(&cr, &viewport).unwrap();
+
+    let substitutions = substitutions.borrow();
+    assert!(!substitutions.is_empty());
+    assert_eq!(substitutions[0].requested, "This Font Does Not Exist");
+    assert_ne!(substitutions[0].actual, "This Font Does Not Exist");
+    assert_eq!(
+        substitutions[0].unavailable,
+        vec!["This Font Does Not Exist"]
+    );
+}
+
+#[test]
+fn reports_fallback_in_a_font_family_list() {
+    setup_font_map();
+
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <text x="10" y="50" font-family="First Missing, 'Second Missing', Roboto">Hello</text>
+</svg>
+"#,
+    )
+    .unwrap();
+
+    let substitutions = Rc::new(RefCell::new(Vec::new()));
+
+    let renderer = {
+        let substitutions = substitutions.clone();
+
+        CairoRenderer::new(&svg)
+            .test_mode()
+            .with_font_substitution_callback(move |s: &FontSubstitution| {
+                substitutions.borrow_mut().push(s.clone())
+            })
+    };
+
+    let viewport = cairo::Rectangle {
+        x: 0.0,
+        y: 0.0,
+        width: 100.0,
+        height: 100.0,
+    };
+
+    renderer.used_fonts(&viewport).unwrap();
+
+    let substitutions = substitutions.borrow();
+    assert!(!substitutions.is_empty());
+    assert_eq!(substitutions[0].actual, "Roboto");
+    assert_eq!(
+        substitutions[0].unavailable,
+        vec!["First Missing", "Second Missing"]
+    );
+}
+
+#[test]
+fn reports_missing_glyphs() {
+    setup_font_map();
+
+    // The test fonts only cover the Latin script, so the Ethiopic letters have no glyphs
+    let svg = load_svg(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"200\" height=\"100\">
+  <text x=\"10\" y=\"50\" font-family=\"Roboto\">Hello \u{1230}\u{120B}\u{1230}</text>
+  <text x=\"10\" y=\"90\" font-family=\"Roboto\">Hello</text>
+</svg>
+"
+        .as_bytes(),
+    )
+    .unwrap();
+
+    let missing = Rc::new(RefCell::new(Vec::new()));
+
+    let renderer = {
+        let missing = missing.clone();
+
+        CairoRenderer::new(&svg)
+            .test_mode()
+            .with_missing_glyphs_callback(move |m: &MissingGlyphs| {
+                missing.borrow_mut().push(m.clone())
+            })
+    };
+
+    let output = cairo::ImageSurface::create(cairo::Format::ARgb32, 200, 100).unwrap();
+    let cr = cairo::Context::new(&output).expect("Failed to create cairo context");
+    let viewport = cairo::Rectangle {
+        x: 0.0,
+        y: 0.0,
+        width: 200.0,
+        height: 100.0,
+    };
+
+    renderer.render_document(&cr, &viewport).unwrap();
+
+    let missing = missing.borrow();
+    assert_eq!(
+        *missing,
+        vec![MissingGlyphs {
+            font_family: String::from("Roboto"),
+            chars: vec!['\u{1230}', '\u{120B}'],
+        }]
+    );
+}
+
+#[test]
+fn reports_missing_glyphs_for_each_span_of_a_text_chunk() {
+    setup_font_map();
+
+    // The spans of the chunk share a layout, but each one has its own font-family
+    let svg = load_svg(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"200\" height=\"100\">
+  <text x=\"10\" y=\"50\" font-family=\"Roboto\">Hello \u{1230}<tspan font-family=\"Rsvg Test Blocks\">X \u{120B}</tspan></text>
+</svg>
+"
+        .as_bytes(),
+    )
+    .unwrap();
+
+    let missing = Rc::new(RefCell::new(Vec::new()));
+
+    let renderer = {
+        let missing = missing.clone();
+
+        CairoRenderer::new(&svg)
+            .test_mode()
+            .with_missing_glyphs_callback(move |m: &MissingGlyphs| {
+                missing.borrow_mut().push(m.clone())
+            })
+    };
+
+    let output = cairo::ImageSurface::create(cairo::Format::ARgb32, 200, 100).unwrap();
+    let cr = cairo::Context::new(&output).expect("Failed to create cairo context");
+    let viewport = cairo::Rectangle {
+        x: 0.0,
+        y: 0.0,
+        width: 200.0,
+        height: 100.0,
+    };
+
+    renderer.render_document(&cr, &viewport).unwrap();
+
+    let missing = missing.borrow();
+    assert_eq!(
+        *missing,
+        vec![
+            MissingGlyphs {
+                font_family: String::from("Roboto"),
+                chars: vec!['\u{1230}'],
+            },
+            MissingGlyphs {
+                font_family: String::from("Rsvg Test Blocks"),
+                chars: vec!['\u{120B}'],
+            },
+        ]
+    );
+}
+
+#[test]
+fn renders_text_with_a_font_from_memory() {
+    let mut svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="200" height="100">
+  <text x="10" y="50" font-family="Roboto" font-weight="bold" font-style="italic">Hello</text>
+</svg>
+"#,
+    )
+    .unwrap();
+
+    svg.add_font_data(include_bytes!("../resources/Roboto-BoldItalic.ttf"))
+        .unwrap();
+
+    assert!(matches!(
+        svg.add_font_data(b"not a font"),
+        Err(LoadingError::BadFont)
+    ));
+
+    let viewport = cairo::Rectangle {
+        x: 0.0,
+        y: 0.0,
+        width: 200.0,
+        height: 100.0,
+    };
+
+    let fonts = CairoRenderer::new(&svg).used_fonts(&viewport).unwrap();
+
+    assert_eq!(
+        fonts,
+        vec![UsedFont {
+            family: String::from("Roboto"),
+            description: String::from("Roboto Bold Italic"),
+        }]
+    );
+}
+
+#[test]
+fn renders_text_with_a_woff_font_from_memory() {
+    let mut svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="200" height="100">
+  <text x="10" y="50" font-family="Rsvg Test Blocks">XX</text>
+</svg>
+"#,
+    )
+    .unwrap();
+
+    svg.add_font_data(include_bytes!("../fixtures/api/RsvgTestBlocks.woff"))
+        .unwrap();
+
+    assert!(matches!(
+        svg.add_font_data(b"wOF2\0\x01\0\0"),
+        Err(LoadingError::BadFont)
+    ));
+
+    let viewport = cairo::Rectangle {
+        x: 0.0,
+        y: 0.0,
+        width: 200.0,
+        height: 100.0,
+    };
+
+    let fonts = CairoRenderer::new(&svg).used_fonts(&viewport).unwrap();
+
+    assert_eq!(
+        fonts,
+        vec![UsedFont {
+            family: String::from("Rsvg Test Blocks"),
+            description: String::from("Rsvg Test Blocks"),
+        }]
+    );
+}
+
+#[test]
+fn renders_text_with_a_woff2_font_from_memory() {
+    let mut svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="200" height="100">
+  <text x="10" y="50" font-family="Rsvg Test Blocks">XX</text>
+</svg>
+"#,
+    )
+    .unwrap();
+
+    // The glyf, loca, and hmtx tables of this font are transformed.
+    svg.add_font_data(include_bytes!("../fixtures/api/RsvgTestBlocks.woff2"))
+        .unwrap();
+
+    let viewport = cairo::Rectangle {
+        x: 0.0,
+        y: 0.0,
+        width: 200.0,
+        height: 100.0,
+    };
+
+    let fonts = CairoRenderer::new(&svg).used_fonts(&viewport).unwrap();
+
+    assert_eq!(
+        fonts,
+        vec![UsedFont {
+            family: String::from("Rsvg Test Blocks"),
+            description: String::from("Rsvg Test Blocks"),
+        }]
+    );
+}
+
+#[test]
+fn loads_the_first_supported_font_face_source() {
+    let svg = Loader::new()
+        .with_document_fonts(true)
+        .read_path("tests/fixtures/api/font-face.svg")
+        .unwrap();
+
+    let viewport = cairo::Rectangle {
+        x: 0.0,
+        y: 0.0,
+        width: 200.0,
+        height: 100.0,
+    };
+
+    let fonts = CairoRenderer::new(&svg).used_fonts(&viewport).unwrap();
+
+    assert_eq!(
+        fonts,
+        vec![UsedFont {
+            family: String::from("Rsvg Test Blocks"),
+            description: String::from("Rsvg Test Blocks"),
+        }]
+    );
+}
+
+#[test]
+fn document_fonts_are_not_loaded_by_default() {
+    let svg = Loader::new()
+        .read_path("tests/fixtures/api/font-face.svg")
+        .unwrap();
+
+    let substitutions = Rc::new(RefCell::new(Vec::new()));
+
+    let renderer = {
+        let substitutions = substitutions.clone();
+
+        CairoRenderer::new(&svg).with_font_substitution_callback(move |s: &FontSubstitution| {
+            substitutions.borrow_mut().push(s.clone())
+        })
+    };
+
+    let viewport = cairo::Rectangle {
+        x: 0.0,
+        y: 0.0,
+        width: 200.0,
+        height: 100.0,
+    };
+
+    renderer.used_fonts(&viewport).unwrap();
+
+    let substitutions = substitutions.borrow();
+    assert!(!substitutions.is_empty());
+    assert_eq!(substitutions[0].requested, "Blocks Face");
+}
+
+#[test]
+fn local_font_face_sources_use_installed_fonts() {
+    let svg = Loader::new()
+        .with_document_fonts(true)
+        .read_path("tests/fixtures/api/font-face-local.svg")
+        .unwrap();
+
+    let viewport = cairo::Rectangle {
+        x: 0.0,
+        y: 0.0,
+        width: 200.0,
+        height: 100.0,
+    };
+
+    let fonts = CairoRenderer::new(&svg).used_fonts(&viewport).unwrap();
+
+    // Not "Rsvg Test Fallback", the font from the url() after each local()
+    assert_eq!(
+        fonts,
+        vec![UsedFont {
+            family: String::from("Rsvg Test Blocks"),
+            description: String::from("Rsvg Test Blocks"),
+        }]
+    );
+}
+
+#[test]
+fn local_font_face_sources_fall_back_to_urls() {
+    let svg = Loader::new()
+        .with_document_fonts(true)
+        .read_path("tests/fixtures/api/font-face-local-fallback.svg")
+        .unwrap();
+
+    let viewport = cairo::Rectangle {
+        x: 0.0,
+        y: 0.0,
+        width: 200.0,
+        height: 100.0,
+    };
+
+    let fonts = CairoRenderer::new(&svg).used_fonts(&viewport).unwrap();
+
+    assert_eq!(
+        fonts,
+        vec![UsedFont {
+            family: String::from("Rsvg Test Fallback"),
+            description: String::from("Rsvg Test Fallback"),
+        }]
+    );
+}
+
+#[test]
+fn reports_used_fonts() {
+    setup_font_map();
+
+    // The test font with blocks has no letters but "X", so the text of the unknown family
+    // gets resolved to Roboto as well
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="200" height="100">
+  <text x="10" y="20" font-family="Roboto">Hello</text>
+  <text x="10" y="40" font-family="Rsvg Test Blocks">XX</text>
+  <text x="10" y="60" font-family="Roboto">World</text>
+  <text x="10" y="90" font-family="Unknown Family" font-weight="bold" font-style="italic">Hi</text>
+</svg>
+"#,
+    )
+    .unwrap();
+
+    let viewport = cairo::Rectangle {
+        x: 0.0,
+        y: 0.0,
+        width: 200.0,
+        height: 100.0,
+    };
+
+    let fonts = CairoRenderer::new(&svg)
+        .test_mode()
+        .used_fonts(&viewport)
+        .unwrap();
+
+    assert_eq!(
+        fonts,
+        vec![
+            UsedFont {
+                family: String::from("Roboto"),
+                description: String::from("Roboto"),
+            },
+            UsedFont {
+                family: String::from("Rsvg Test Blocks"),
+                description: String::from("Rsvg Test Blocks"),
+            },
+            UsedFont {
+                family: String::from("Roboto"),
+                description: String::from("Roboto Bold Italic"),
+            },
+        ]
+    );
+}
+
+#[test]
+fn tabular_nums_have_equal_advances() {
+    setup_font_map();
+
+    // Roboto's digits are tabular by default, so check that proportional-nums makes
+    // them different first.
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="200" height="200">
+  <style>
+    text {
+      font-family: Roboto;
+      font-size: 40px;
+      font-variant-numeric: proportional-nums;
+    }
+
+    .tabular {
+      font-variant-numeric: tabular-nums;
+    }
+  </style>
+  <text id="proportional-ones" x="10" y="40">1111</text>
+  <text id="proportional-eights" x="10" y="90">8888</text>
+  <text id="tabular-ones" class="tabular" x="10" y="140">1111</text>
+  <text id="tabular-eights" class="tabular" x="10" y="190">8888</text>
+</svg>
+"#,
+    )
+    .unwrap();
+
+    let renderer = CairoRenderer::new(&svg).test_mode();
+
+    let width = |id: &str| renderer.geometry_for_element(Some(id)).unwrap().1.width;
+
+    assert!(width("#proportional-eights") - width("#proportional-ones") > 10.0);
+    assert!((width("#tabular-ones") - width("#tabular-eights")).abs() < 0.01);
+}
+
+#[test]
+fn text_extents_select_ink_or_logical_bounding_box() {
+    setup_font_map();
+
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="200" height="100">
+  <text id="text" x="10" y="50" font-family="Roboto" font-size="40">gjpqy</text>
+</svg>
+"#,
+    )
+    .unwrap();
+
+    let (_, ink) = CairoRenderer::new(&svg)
+        .test_mode()
+        .geometry_for_element(Some("#text"))
+        .unwrap();
+
+    let (_, logical) = CairoRenderer::new(&svg)
+        .test_mode()
+        .with_text_extents(TextExtents::Logical)
+        .geometry_for_element(Some("#text"))
+        .unwrap();
+
+    // The logical box spans the font's full ascent, which these glyphs don't reach,
+    // and its full descent, which is deeper than their descenders.
+    assert!(logical.y < ink.y);
+    assert!(logical.y + logical.height > ink.y + ink.height);
+}
+
+#[test]
+fn outline_for_element_returns_glyph_outlines() {
+    setup_font_map();
+
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="200" height="100">
+  <text id="text" x="10" y="50" font-family="Roboto" font-size="40">Hello</text>
+</svg>
+"#,
+    )
+    .unwrap();
+
+    let renderer = CairoRenderer::new(&svg).test_mode();
+    let path = renderer.outline_for_element(Some("#text")).unwrap();
+
+    let mut num_subpaths = 0;
+    let (mut min_x, mut min_y) = (f64::MAX, f64::MAX);
+    let (mut max_x, mut max_y) = (f64::MIN, f64::MIN);
+
+    for segment in path.iter() {
+        match segment {
+            cairo::PathSegment::MoveTo((x, y)) => {
+                num_subpaths += 1;
+                min_x = min_x.min(x);
+                min_y = min_y.min(y);
+                max_x = max_x.max(x);
+                max_y = max_y.max(y);
+            }
+            cairo::PathSegment::LineTo((x, y)) | cairo::PathSegment::CurveTo(_, _, (x, y)) => {
+                min_x = min_x.min(x);
+                min_y = min_y.min(y);
+                max_x = max_x.max(x);
+                max_y = max_y.max(y);
+            }
+            cairo::PathSegment::ClosePath => (),
+        }
+    }
+
+    // H, e, l, l, o, plus the inner contours of e and o
+    assert!(num_subpaths >= 7);
+
+    // The glyphs sit on the baseline at y=50, starting at x=10
+    assert!(min_x >= 10.0 && min_x < 20.0);
+    assert!(min_y > 10.0 && max_y <= 51.0);
+    assert!(max_x > 80.0);
+}
+
+#[test]
+fn outline_path_data_renders_like_the_text() {
+    setup_font_map();
+
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="200" height="100">
+  <text id="text" x="10" y="50" font-family="Roboto" font-size="40">Hello</text>
+</svg>
+"#,
+    )
+    .unwrap();
+
+    let d = CairoRenderer::new(&svg)
+        .test_mode()
+        .outline_path_data_for_element(Some("#text"))
+        .unwrap();
+
+    assert!(d.starts_with("M "));
+    assert!(d.ends_with('Z'));
+
+    let flattened = format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="200" height="100">
+  <path d="{}"/>
+</svg>
+"#,
+        d
+    );
+    let bytes = glib::Bytes::from_owned(flattened.into_bytes());
+    let stream = gio::MemoryInputStream::from_bytes(&bytes);
+    let flattened = Loader::new()
+        .read_stream(&stream, None::<&gio::File>, None::<&gio::Cancellable>)
+        .unwrap();
+
+    let viewport = cairo::Rectangle {
+        x: 0.0,
+        y: 0.0,
+        width: 200.0,
+        height: 100.0,
+    };
+
+    let output_surf = render_document(&flattened, SurfaceSize(200, 100), |_| (), viewport).unwrap();
+
+    // Clipping to the text uses its glyph outlines, just like the path data.
+    let reference = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="200" height="100">
+  <clipPath id="text">
+    <text x="10" y="50" font-family="Roboto" font-size="40">Hello</text>
+  </clipPath>
+  <rect width="200" height="100" clip-path="url(#text)"/>
+</svg>
+"#,
+    )
+    .unwrap();
+
+    let reference_surf =
+        render_document(&reference, SurfaceSize(200, 100), |_| (), viewport).unwrap();
+
+    Reference::from_surface(reference_surf.into_image_surface().unwrap())
+        .compare(&output_surf)
+        .evaluate(&output_surf, "outline_path_data_renders_like_the_text");
+}
+
+#[test]
+fn gets_title_and_description_of_elements() {
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="50" height="50">
+  <title>Document title</title>
+  <rect id="foo" x="10" y="10" width="30" height="30">
+    <title>A  square</title>
+    <desc>A square with
+      sides of 30 units</desc>
+  </rect>
+  <rect id="bar" x="10" y="10" width="30" height="30"/>
+</svg>
+"#,
+    )
+    .unwrap();
+
+    assert_eq!(
+        svg.title_for_element(Some("#foo")).unwrap().as_deref(),
+        Some("A square")
+    );
+    assert_eq!(
+        svg.description_for_element(Some("#foo"))
+            .unwrap()
+            .as_deref(),
+        Some("A square with sides of 30 units")
+    );
+    assert_eq!(
+        svg.title_for_element(None).unwrap().as_deref(),
+        Some("Document title")
+    );
+    assert_eq!(svg.title_for_element(Some("#bar")).unwrap(), None);
+}
+
+#[test]
+fn vertical_text_is_centered_on_its_position() {
+    setup_font_map();
+
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="200">
+  <line x1="50" y1="0" x2="50" y2="200" stroke="black"/>
+  <text id="text" x="50" y="20" writing-mode="tb" font-family="Roboto" font-size="40">&#x6F22;&#x5B57;</text>
+</svg>
+"#,
+    )
+    .unwrap();
+
+    let (_, logical) = CairoRenderer::new(&svg)
+        .test_mode()
+        .with_text_extents(TextExtents::Logical)
+        .geometry_for_element(Some("#text"))
+        .unwrap();
+
+    // The glyphs' em boxes straddle the rule at x=50
+    let center = logical.x + logical.width / 2.0;
+    assert!((center - 50.0).abs() < 0.5, "center is at {}", center);
+}
+
+#[test]
+fn central_baseline_centers_horizontal_text_on_its_position() {
+    setup_font_map();
+
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="200" height="100">
+  <text id="text" x="10" y="50" dominant-baseline="central" font-family="Roboto" font-size="40">Hello</text>
+</svg>
+"#,
+    )
+    .unwrap();
+
+    let (_, logical) = CairoRenderer::new(&svg)
+        .test_mode()
+        .with_text_extents(TextExtents::Logical)
+        .geometry_for_element(Some("#text"))
+        .unwrap();
+
+    // Halfway between the ascent and the descent
+    let center = logical.y + logical.height / 2.0;
+    assert!((center - 50.0).abs() < 0.5, "center is at {}", center);
+}
+
+#[test]
+fn hanging_baseline_comes_from_the_base_table() {
+    setup_font_map();
+
+    // The BASE table of the test font with blocks has the hanging baseline at 500 units
+    // above the alphabetic one for Devanagari, and at 700 units for other scripts, in an
+    // em of 1000 units.  Falling back to the ascent would give 0.8 * 800 units.
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="200" height="100" font-family="Rsvg Test Blocks" font-size="20">
+  <text id="deva-alphabetic" x="10" y="40">&#x915;</text>
+  <text id="deva-hanging" x="100" y="40" dominant-baseline="hanging">&#x915;</text>
+  <text id="latin-alphabetic" x="10" y="80">X</text>
+  <text id="latin-hanging" x="100" y="80" dominant-baseline="hanging">X</text>
+</svg>
+"#,
+    )
+    .unwrap();
+
+    let renderer = CairoRenderer::new(&svg)
+        .test_mode()
+        .with_text_extents(TextExtents::Logical);
+
+    let shift = |id: &str| {
+        let (_, alphabetic) = renderer
+            .geometry_for_element(Some(&format!("#{}-alphabetic", id)))
+            .unwrap();
+        let (_, hanging) = renderer
+            .geometry_for_element(Some(&format!("#{}-hanging", id)))
+            .unwrap();
+
+        hanging.y - alphabetic.y
+    };
+
+    let deva = shift("deva");
+    assert!((deva - 10.0).abs() < 0.05, "Devanagari shift is {}", deva);
+
+    let latin = shift("latin");
+    assert!((latin - 14.0).abs() < 0.05, "Latin shift is {}", latin);
+}
+
+#[test]
+fn hanging_baseline_falls_back_to_the_ascent() {
+    setup_font_map();
+
+    // Roboto has no BASE table
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="200" height="100" font-family="Roboto" font-size="20">
+  <text id="alphabetic" x="10" y="50">Hello</text>
+  <text id="hanging" x="100" y="50" dominant-baseline="hanging">Hello</text>
+</svg>
+"#,
+    )
+    .unwrap();
+
+    let renderer = CairoRenderer::new(&svg)
+        .test_mode()
+        .with_text_extents(TextExtents::Logical);
+
+    let (_, alphabetic) = renderer.geometry_for_element(Some("#alphabetic")).unwrap();
+    let (_, hanging) = renderer.geometry_for_element(Some("#hanging")).unwrap();
+
+    let ascent = 50.0 - alphabetic.y;
+    let shift = hanging.y - alphabetic.y;
+    assert!((shift - 0.8 * ascent).abs() < 0.1, "shift is {}", shift);
+}
+
+#[test]
+fn text_anchor_ignores_trailing_space_of_a_chunk() {
+    setup_font_map();
+
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="200" height="100" font-family="Roboto" font-size="20">
+  <text id="spaced" x="100" y="30" text-anchor="middle">Hello <tspan x="100" y="60">World</tspan></text>
+  <text id="plain" x="100" y="90" text-anchor="middle">Hello</text>
+</svg>
+"#,
+    )
+    .unwrap();
+
+    let renderer = CairoRenderer::new(&svg).test_mode();
+
+    // The space after "Hello" is part of the first chunk
+    assert_eq!(renderer.number_of_chars("#spaced").unwrap(), 11);
+
+    let spaced = renderer.extent_of_char("#spaced", 0).unwrap().unwrap();
+    let plain = renderer.extent_of_char("#plain", 0).unwrap().unwrap();
+
+    assert!(
+        (spaced.x - plain.x).abs() < 0.01,
+        "{} != {}",
+        spaced.x,
+        plain.x
+    );
+}
+
+#[test]
+fn rtl_text_is_positioned_only_by_text_anchor() {
+    setup_font_map();
+
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="200" height="200" font-family="Roboto" font-size="20">
+  <text id="start" x="100" y="20" direction="rtl" text-anchor="start">abc</text>
+  <text id="middle" x="100" y="50" direction="rtl" text-anchor="middle">abc</text>
+  <text id="end" x="100" y="80" direction="rtl" text-anchor="end">abc</text>
+  <g font-family="Rsvg Test Blocks" direction="rtl">
+    <textArea id="area-start" x="10" y="100" width="180" text-anchor="start">XX</textArea>
+    <textArea id="area-middle" x="10" y="130" width="180" text-anchor="middle">XX</textArea>
+    <textArea id="area-end" x="10" y="160" width="180" text-anchor="end">XX</textArea>
+  </g>
+</svg>
+"#,
+    )
+    .unwrap();
+
+    let renderer = CairoRenderer::new(&svg)
+        .test_mode()
+        .with_text_extents(TextExtents::Logical);
+
+    let (_, start) = renderer.geometry_for_element(Some("#start")).unwrap();
+    let (_, middle) = renderer.geometry_for_element(Some("#middle")).unwrap();
+    let (_, end) = renderer.geometry_for_element(Some("#end")).unwrap();
+
+    // The logical start of right-to-left text is its right edge
+    assert!((start.x + start.width - 100.0).abs() < 0.01);
+    assert!((middle.x + middle.width / 2.0 - 100.0).abs() < 0.01);
+    assert!((end.x - 100.0).abs() < 0.01);
+
+    // Wrapped text gets aligned within its width instead; "XX" is 24 wide.
+    let (_, area_start) = renderer.geometry_for_element(Some("#area-start")).unwrap();
+    let (_, area_middle) = renderer.geometry_for_element(Some("#area-middle")).unwrap();
+    let (_, area_end) = renderer.geometry_for_element(Some("#area-end")).unwrap();
+
+    assert!((area_start.x - 166.0).abs() < 0.01);
+    assert!((area_middle.x - 88.0).abs() < 0.01);
+    assert!((area_end.x - 10.0).abs() < 0.01);
+}
+
+#[test]
+fn font_size_in_points_scales_with_dpi() {
+    setup_font_map();
+
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="200" height="100">
+  <text id="text" x="10" y="50" font-family="Roboto" font-size="12pt">Hello</text>
+</svg>
+"#,
+    )
+    .unwrap();
+
+    let (_, at_96) = CairoRenderer::new(&svg)
+        .test_mode()
+        .with_text_extents(TextExtents::Logical)
+        .geometry_for_element(Some("#text"))
+        .unwrap();
+
+    let (_, at_192) = CairoRenderer::new(&svg)
+        .test_mode()
+        .with_dpi(192.0, 192.0)
+        .with_text_extents(TextExtents::Logical)
+        .geometry_for_element(Some("#text"))
+        .unwrap();
+
+    assert!((at_192.width / at_96.width - 2.0).abs() < 0.01);
+    assert!((at_192.height / at_96.height - 2.0).abs() < 0.01);
+}
+
+#[test]
+fn letter_spacing_normal_keeps_default_advances() {
+    setup_font_map();
+
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="200" height="100" font-family="Roboto" font-size="20">
+  <text id="normal" x="10" y="20" letter-spacing="normal">Hello</text>
+  <text id="zero" x="10" y="50" letter-spacing="0">Hello</text>
+  <text id="wide" x="10" y="80" letter-spacing="5px">Hello</text>
+</svg>
+"#,
+    )
+    .unwrap();
+
+    let renderer = CairoRenderer::new(&svg)
+        .test_mode()
+        .with_text_extents(TextExtents::Logical);
+
+    let (_, normal) = renderer.geometry_for_element(Some("#normal")).unwrap();
+    let (_, zero) = renderer.geometry_for_element(Some("#zero")).unwrap();
+    let (_, wide) = renderer.geometry_for_element(Some("#wide")).unwrap();
+
+    assert!((normal.width - zero.width).abs() < 0.01);
+
+    // At least four gaps between the five characters get wider
+    assert!(wide.width >= normal.width + 20.0 - 0.1);
+}
+
+#[test]
+fn letter_spacing_does_not_trail_the_last_character() {
+    setup_font_map();
+
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="200" height="100" font-family="Roboto" font-size="20">
+  <text id="normal" x="10" y="20">Hello</text>
+  <text id="wide" x="10" y="50" letter-spacing="5px">Hello</text>
+  <text id="spans" x="10" y="80" letter-spacing="5px">He<tspan fill="red">llo</tspan></text>
+</svg>
+"#,
+    )
+    .unwrap();
+
+    let renderer = CairoRenderer::new(&svg)
+        .test_mode()
+        .with_text_extents(TextExtents::Logical);
+
+    let (_, normal) = renderer.geometry_for_element(Some("#normal")).unwrap();
+    let (_, wide) = renderer.geometry_for_element(Some("#wide")).unwrap();
+    let (_, spans) = renderer.geometry_for_element(Some("#spans")).unwrap();
+
+    // Exactly four gaps between the five characters, and none after the last one
+    assert!((wide.width - (normal.width + 20.0)).abs() < 0.01);
+    assert!((spans.width - (normal.width + 20.0)).abs() < 0.01);
+
+    let length = renderer.computed_text_length("#wide").unwrap();
+    assert!((length - wide.width).abs() < 0.01);
+}
+
+#[test]
+fn letter_spacing_goes_along_vertical_text() {
+    setup_font_map();
+
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="200" height="200" font-family="Roboto" font-size="20">
+  <text id="normal" x="50" y="10" writing-mode="tb">&#x6F22;&#x5B57;&#x6F22;</text>
+  <text id="wide" x="150" y="10" writing-mode="tb" letter-spacing="10px">&#x6F22;&#x5B57;&#x6F22;</text>
+</svg>
+"#,
+    )
+    .unwrap();
+
+    let renderer = CairoRenderer::new(&svg)
+        .test_mode()
+        .with_text_extents(TextExtents::Logical);
+
+    let (_, normal) = renderer.geometry_for_element(Some("#normal")).unwrap();
+    let (_, wide) = renderer.geometry_for_element(Some("#wide")).unwrap();
+
+    // The two gaps between the three characters get taller, not wider
+    assert!(wide.height >= normal.height + 20.0 - 0.1);
+    assert!((wide.width - normal.width).abs() < 0.01);
+}
+
+#[test]
+fn percentage_baseline_shift_refers_to_line_height() {
+    setup_font_map();
+
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="200" height="100" font-family="Roboto" font-size="20">
+  <text id="short" x="10" y="50" style="line-height: 20px; baseline-shift: 50%">Hello</text>
+  <text id="tall" x="100" y="50" style="line-height: 40px; baseline-shift: 50%">Hello</text>
+</svg>
+"#,
+    )
+    .unwrap();
+
+    let renderer = CairoRenderer::new(&svg)
+        .test_mode()
+        .with_text_extents(TextExtents::Logical);
+
+    let (_, short) = renderer.geometry_for_element(Some("#short")).unwrap();
+    let (_, tall) = renderer.geometry_for_element(Some("#tall")).unwrap();
+
+    // Half of each line-height: 10px and 20px up from the baseline
+    assert!((short.y - tall.y - 10.0).abs() < 0.01);
+}
+
+#[test]
+fn unitless_line_height_scales_with_the_font_size_of_descendants() {
+    setup_font_map();
+
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="200" height="100" font-family="Roboto" font-size="10">
+  <text x="10" y="50" style="line-height: 2"><tspan id="inherited" style="font-size: 20px; baseline-shift: 50%">Hello</tspan></text>
+  <text x="100" y="50"><tspan id="explicit" style="font-size: 20px; line-height: 40px; baseline-shift: 50%">Hello</tspan></text>
+</svg>
+"#,
+    )
+    .unwrap();
+
+    let renderer = CairoRenderer::new(&svg)
+        .test_mode()
+        .with_text_extents(TextExtents::Logical);
+
+    let (_, inherited) = renderer.geometry_for_element(Some("#inherited")).unwrap();
+    let (_, explicit) = renderer.geometry_for_element(Some("#explicit")).unwrap();
+
+    // The tspan's line-height is twice its own font size, not twice the one of the <text>
+    assert!((inherited.y - explicit.y).abs() < 0.01);
+}
+
+#[test]
+fn link_regions_cover_each_line_of_linked_text() {
+    setup_font_map();
+
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink"
+     width="200" height="100" font-family="Roboto" font-size="20">
+  <a xlink:href="https://example.com/">
+    <text x="10" y="30">Hello<tspan x="10" y="60">world</tspan></text>
+  </a>
+  <text x="100" y="30">unlinked</text>
+</svg>
+"#,
+    )
+    .unwrap();
+
+    let renderer = CairoRenderer::new(&svg).test_mode();
+
+    let viewport = cairo::Rectangle {
+        x: 0.0,
+        y: 0.0,
+        width: 200.0,
+        height: 100.0,
+    };
+
+    let regions = renderer.link_regions(&viewport).unwrap();
+
+    assert_eq!(regions.len(), 2);
+
+    let (first, ref first_href) = regions[0];
+    let (second, ref second_href) = regions[1];
+
+    assert_eq!(first_href, "https://example.com/");
+    assert_eq!(second_href, "https://example.com/");
+
+    assert!(first.x >= 10.0 && first.y + first.height <= 40.0);
+    assert!(second.x >= 10.0 && second.y >= first.y + first.height - 1.0);
+    assert!(first.x + first.width < 100.0);
+}
+
+#[test]
+fn text_ink_rect_includes_stroke() {
+    setup_font_map();
+
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="200" height="200" font-family="Roboto" font-size="40">
+  <text id="plain" x="20" y="50">W</text>
+  <text id="stroked" x="20" y="120" stroke="black" stroke-width="10">W</text>
+  <text id="plain_vertical" x="150" y="20" writing-mode="tb">W</text>
+  <text id="stroked_vertical" x="150" y="100" writing-mode="tb" stroke="black" stroke-width="10">W</text>
+</svg>
+"#,
+    )
+    .unwrap();
+
+    let renderer = CairoRenderer::new(&svg).test_mode();
+
+    for &(plain, stroked) in &[
+        ("#plain", "#stroked"),
+        ("#plain_vertical", "#stroked_vertical"),
+    ] {
+        let (plain_ink, _) = renderer.geometry_for_element(Some(plain)).unwrap();
+        let (stroked_ink, _) = renderer.geometry_for_element(Some(stroked)).unwrap();
+
+        // Half the stroke width on each side, at least
+        assert!(stroked_ink.width >= plain_ink.width + 10.0 - 0.1);
+        assert!(stroked_ink.height >= plain_ink.height + 10.0 - 0.1);
+    }
+}
+
+#[test]
+fn long_words_wrap_only_with_break_all_or_break_word() {
+    setup_font_map();
+
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="400" height="400" font-family="Roboto" font-size="20">
+  <textArea id="normal" x="10" y="10" width="100">https://example.com/a/long/path</textArea>
+  <textArea id="break_all" x="10" y="110" width="100" word-break="break-all">https://example.com/a/long/path</textArea>
+  <textArea id="break_word" x="10" y="210" width="100" overflow-wrap="break-word">https://example.com/a/long/path</textArea>
+</svg>
+"#,
+    )
+    .unwrap();
+
+    let renderer = CairoRenderer::new(&svg).test_mode();
+
+    let (normal, _) = renderer.geometry_for_element(Some("#normal")).unwrap();
+    assert!(normal.width > 100.0);
+
+    for id in &["#break_all", "#break_word"] {
+        let (ink, _) = renderer.geometry_for_element(Some(id)).unwrap();
+        assert!(ink.width <= 100.0);
+        assert!(ink.height > normal.height * 1.5);
+    }
+}
+
+#[test]
+fn astral_characters_are_one_addressable_character() {
+    setup_font_map();
+
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="400" height="400" font-family="Roboto" font-size="20">
+  <text id="emoji" x="10 100 200" y="50">&#x1F600;ab</text>
+  <text id="cjk" x="10" y="100" dx="0 50">&#x20BB7;ab</text>
+</svg>
+"#,
+    )
+    .unwrap();
+
+    let renderer = CairoRenderer::new(&svg).test_mode();
+
+    // U+1F600 is two UTF-16 code units, but a single character
+    let (a, _) = renderer
+        .start_position_of_char("#emoji", 1)
+        .unwrap()
+        .unwrap();
+    let (b, _) = renderer
+        .start_position_of_char("#emoji", 2)
+        .unwrap()
+        .unwrap();
+    assert!((a - 100.0).abs() < 0.1);
+    assert!((b - 200.0).abs() < 0.1);
+
+    let (x0, _) = renderer.start_position_of_char("#cjk", 0).unwrap().unwrap();
+    let (x1, _) = renderer.start_position_of_char("#cjk", 1).unwrap().unwrap();
+    assert!(x1 - x0 > 50.0);
+    assert!(renderer
+        .start_position_of_char("#cjk", 3)
+        .unwrap()
+        .is_none());
+}
+
+#[test]
+fn zero_width_space_allows_a_break_but_adds_no_width() {
+    setup_font_map();
+
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="400" height="400" font-family="Roboto" font-size="20">
+  <text id="plain" x="10" y="50">HHHH</text>
+  <text id="zwsp" x="10" y="100">HH&#x200B;HH</text>
+  <text id="dx" x="10" y="150" dx="0 0 0 0 100">HH&#x200B;HH</text>
+  <textArea id="wrapped" x="10" y="200" width="40">HH&#x200B;HH</textArea>
+</svg>
+"#,
+    )
+    .unwrap();
+
+    let renderer = CairoRenderer::new(&svg)
+        .test_mode()
+        .with_text_extents(TextExtents::Logical);
+
+    let (_, plain) = renderer.geometry_for_element(Some("#plain")).unwrap();
+    let (_, zwsp) = renderer.geometry_for_element(Some("#zwsp")).unwrap();
+    let (_, dx) = renderer.geometry_for_element(Some("#dx")).unwrap();
+    let (_, wrapped) = renderer.geometry_for_element(Some("#wrapped")).unwrap();
+
+    assert!((zwsp.width - plain.width).abs() < 0.1);
+
+    // The zero width space is an addressable character of its own, so the fifth
+    // value of dx goes to the last H.
+    let (x, _) = renderer.start_position_of_char("#dx", 4).unwrap().unwrap();
+    let (third, _) = renderer.start_position_of_char("#dx", 3).unwrap().unwrap();
+    assert!(x - third > 100.0);
+    assert!((dx.width - plain.width - 100.0).abs() < 0.1);
+
+    assert!(wrapped.width <= 40.0);
+    assert!(wrapped.height > plain.height * 1.5);
+}
+
+#[test]
+fn soft_hyphens_are_break_opportunities_that_show_a_hyphen() {
+    setup_font_map();
+
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="400" height="400" font-family="Roboto" font-size="20">
+  <textArea id="unbroken" x="10" y="10">super&#173;cali</textArea>
+  <textArea id="wrapped" x="10" y="110" width="70">super&#173;cali</textArea>
+  <textArea id="clipped" x="10" y="210" width="70" height="30">super&#173;cali</textArea>
+  <textArea id="super" x="10" y="310">super</textArea>
+</svg>
+"#,
+    )
+    .unwrap();
+
+    let renderer = CairoRenderer::new(&svg).test_mode();
+
+    let (unbroken, _) = renderer.geometry_for_element(Some("#unbroken")).unwrap();
+    let (wrapped, _) = renderer.geometry_for_element(Some("#wrapped")).unwrap();
+    let (clipped, _) = renderer.geometry_for_element(Some("#clipped")).unwrap();
+    let (super_, _) = renderer.geometry_for_element(Some("#super")).unwrap();
+
+    // Without a break, the soft hyphen is invisible
+    assert!(unbroken.width > 70.0);
+    assert!(unbroken.height < super_.height * 1.5);
+
+    // The word breaks at the soft hyphen, and the first line ends with a hyphen
+    assert!(wrapped.width <= 70.0);
+    assert!(wrapped.height > super_.height * 1.5);
+    assert!(wrapped.width > super_.width + 2.0);
+
+    // The hyphen stays when the lines after it are removed
+    assert!((clipped.height - super_.height).abs() < 0.1);
+    assert!(clipped.width > super_.width + 2.0);
+}
+
+#[test]
+fn line_height_sets_the_distance_between_wrapped_lines() {
+    setup_font_map();
+
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="400" height="400" font-family="Roboto" font-size="20">
+  <textArea id="forty" x="10" y="10" width="40" line-height="40px">HH HH</textArea>
+  <textArea id="sixty" x="110" y="10" width="40" line-height="60px">HH HH</textArea>
+  <textArea id="number" x="210" y="10" width="40" line-height="2">HH HH</textArea>
+  <textArea id="percentage" x="310" y="10" width="40" line-height="300%">HH HH</textArea>
+</svg>
+"#,
+    )
+    .unwrap();
+
+    let renderer = CairoRenderer::new(&svg).test_mode();
+
+    let (forty, _) = renderer.geometry_for_element(Some("#forty")).unwrap();
+    let (sixty, _) = renderer.geometry_for_element(Some("#sixty")).unwrap();
+    let (number, _) = renderer.geometry_for_element(Some("#number")).unwrap();
+    let (percentage, _) = renderer.geometry_for_element(Some("#percentage")).unwrap();
+
+    // Both lines have the same glyphs, so the ink grows by the distance between baselines
+    assert!(forty.height > 40.0);
+    assert!(sixty.height - forty.height > 19.9 && sixty.height - forty.height < 20.1);
+
+    // Unitless numbers and percentages are relative to the font size
+    assert!((number.height - forty.height).abs() < 0.1);
+    assert!((percentage.height - sixty.height).abs() < 0.1);
+}
+
+#[test]
+fn tab_size_sets_the_distance_between_tab_stops() {
+    setup_font_map();
+
+    let svg = load_svg(
+        b"<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"400\" height=\"200\" font-family=\"Roboto\" font-size=\"20\">
+  <text id=\"eight\" x=\"10\" y=\"30\" white-space=\"pre\">a\tb</text>
+  <text id=\"four\" x=\"10\" y=\"60\" white-space=\"pre\" tab-size=\"4\">a\tb</text>
+  <text id=\"length\" x=\"10\" y=\"90\" white-space=\"pre\" tab-size=\"100px\">a\tb</text>
+  <text id=\"b\" x=\"110\" y=\"120\">b</text>
+</svg>
+",
+    )
+    .unwrap();
+
+    let renderer = CairoRenderer::new(&svg)
+        .test_mode()
+        .with_text_extents(TextExtents::Logical);
+
+    let (_, eight) = renderer.geometry_for_element(Some("#eight")).unwrap();
+    let (_, four) = renderer.geometry_for_element(Some("#four")).unwrap();
+    let (_, length) = renderer.geometry_for_element(Some("#length")).unwrap();
+    let (_, b) = renderer.geometry_for_element(Some("#b")).unwrap();
+
+    assert!(four.width < eight.width);
+
+    // The "b" starts at the first tab stop
+    assert!((length.width - (100.0 + b.width)).abs() < 0.1);
+}
+
+#[test]
+fn kerning_applies_across_tspans() {
+    setup_font_map();
+
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="200" height="100" font-family="Roboto" font-size="40">
+  <text id="whole" x="10" y="40">AV</text>
+  <text id="split" x="10" y="90">A<tspan id="v" fill="red">V</tspan></text>
+</svg>
+"#,
+    )
+    .unwrap();
+
+    let renderer = CairoRenderer::new(&svg)
+        .test_mode()
+        .with_text_extents(TextExtents::Logical);
+
+    let (_, whole) = renderer.geometry_for_element(Some("#whole")).unwrap();
+    let (_, split) = renderer.geometry_for_element(Some("#split")).unwrap();
+    let (_, v) = renderer.geometry_for_element(Some("#v")).unwrap();
+
+    assert!((whole.width - split.width).abs() < 0.01);
+
+    // The tspan only covers its own character
+    assert!(v.width < split.width);
+}
+
+#[test]
+fn stroke_linejoin_applies_to_glyph_outlines() {
+    setup_font_map();
+
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="400" height="200" font-family="Roboto" font-size="100">
+  <text id="miter" x="10" y="100" stroke="black" stroke-width="10" stroke-linejoin="miter" stroke-miterlimit="10">V</text>
+  <text id="round" x="210" y="100" stroke="black" stroke-width="10" stroke-linejoin="round">V</text>
+</svg>
+"#,
+    )
+    .unwrap();
+
+    let renderer = CairoRenderer::new(&svg).test_mode();
+
+    let (miter, _) = renderer.geometry_for_element(Some("#miter")).unwrap();
+    let (round, _) = renderer.geometry_for_element(Some("#round")).unwrap();
+
+    // The sharp bottom of the V sticks out further with a miter join than with a round one
+    assert!(miter.y + miter.height > round.y + round.height + 1.0);
+}
+
+#[test]
+fn ex_units_in_dx_scale_with_the_font() {
+    setup_font_map();
+
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="200" height="100" font-family="Roboto">
+  <text id="small" x="10" y="30" font-size="20" dx="1ex">x</text>
+  <text id="large" x="10" y="80" font-size="40" dx="1ex">x</text>
+</svg>
+"#,
+    )
+    .unwrap();
+
+    let renderer = CairoRenderer::new(&svg).test_mode();
+
+    let (small_x, _) = renderer
+        .start_position_of_char("#small", 0)
+        .unwrap()
+        .unwrap();
+    let (large_x, _) = renderer
+        .start_position_of_char("#large", 0)
+        .unwrap()
+        .unwrap();
+
+    // Roboto's "x" is 1082 units tall, with 2048 units per em
+    let x_height = 1082.0 / 2048.0;
+
+    assert!(((small_x - 10.0) - 20.0 * x_height).abs() < 0.01);
+    assert!(((large_x - 10.0) - 40.0 * x_height).abs() < 0.01);
+}
+
+#[test]
+fn ch_units_in_dx_are_advances_of_zero() {
+    setup_font_map();
+
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="200" height="100" font-family="Roboto" font-size="20">
+  <text id="zeros" x="10" y="30">000</text>
+  <text id="shifted" x="10" y="60" dx="2ch">0</text>
+</svg>
+"#,
+    )
+    .unwrap();
+
+    let renderer = CairoRenderer::new(&svg).test_mode();
+
+    let (third_zero_x, _) = renderer
+        .start_position_of_char("#zeros", 2)
+        .unwrap()
+        .unwrap();
+    let (shifted_x, _) = renderer
+        .start_position_of_char("#shifted", 0)
+        .unwrap()
+        .unwrap();
+
+    assert!((shifted_x - third_zero_x).abs() < 0.01);
+}
+
+#[test]
+fn rem_units_scale_with_the_root_font_size() {
+    setup_font_map();
+
+    fn rem_offsets(root_font_size: &str) -> (f64, f64) {
+        let svg = format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="200" height="100" font-family="Roboto" font-size="{}">
+  <g font-size="5">
+    <text id="dx" x="10" y="30" dx="2rem">a</text>
+    <text id="font-size" x="10" y="60" font-size="2rem">0000</text>
+  </g>
+</svg>
+"#,
+            root_font_size
+        );
+
+        let bytes = glib::Bytes::from_owned(svg.into_bytes());
+        let stream = gio::MemoryInputStream::from_bytes(&bytes);
+        let handle = Loader::new()
+            .read_stream(&stream, None::<&gio::File>, None::<&gio::Cancellable>)
+            .unwrap();
+
+        let renderer = CairoRenderer::new(&handle).test_mode();
+
+        let (x, _) = renderer.start_position_of_char("#dx", 0).unwrap().unwrap();
+        let (end, _) = renderer
+            .start_position_of_char("#font-size", 3)
+            .unwrap()
+            .unwrap();
+
+        (x - 10.0, end - 10.0)
+    }
+
+    let (small_dx, small_advance) = rem_offsets("10");
+    let (large_dx, large_advance) = rem_offsets("20");
+
+    assert!((small_dx - 20.0).abs() < 0.01);
+    assert!((large_dx - 40.0).abs() < 0.01);
+    assert!((large_advance / small_advance - 2.0).abs() < 0.05);
+}
+
+#[test]
+fn percentage_word_spacing_refers_to_the_space_advance() {
+    setup_font_map();
+
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="200" height="100" font-family="Roboto" font-size="20">
+  <text id="normal" x="10" y="30">a b</text>
+  <text id="spaced" x="10" y="60" word-spacing="50%">a b</text>
+</svg>
+"#,
+    )
+    .unwrap();
+
+    let renderer = CairoRenderer::new(&svg).test_mode();
+
+    let start = |id, index| {
+        renderer
+            .start_position_of_char(id, index)
+            .unwrap()
+            .unwrap()
+            .0
+    };
+
+    let space_advance = start("#normal", 2) - start("#normal", 1);
+    let extra = start("#spaced", 2) - start("#normal", 2);
+
+    assert!(space_advance > 0.0);
+    assert!((extra - space_advance / 2.0).abs() < 0.05);
+}
+
+#[test]
+fn number_of_chars_skips_collapsed_white_space() {
+    setup_font_map();
+
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="200" height="100" font-family="Roboto" font-size="20">
+  <text id="collapsed" x="10" y="30">a   b<tspan>  c</tspan></text>
+  <text id="preserved" x="10" y="60" xml:space="preserve">a   b<tspan>  c</tspan></text>
+</svg>
+"#,
+    )
+    .unwrap();
+
+    let renderer = CairoRenderer::new(&svg).test_mode();
+
+    // "a b c"
+    assert_eq!(renderer.number_of_chars("#collapsed").unwrap(), 5);
+
+    // "a   b  c"
+    assert_eq!(renderer.number_of_chars("#preserved").unwrap(), 8);
+
+    for id in &["#collapsed", "#preserved"] {
+        let n = renderer.number_of_chars(id).unwrap();
+
+        assert!(renderer
+            .start_position_of_char(id, n - 1)
+            .unwrap()
+            .is_some());
+        assert!(renderer.start_position_of_char(id, n).unwrap().is_none());
+    }
+}
+
+#[test]
+fn start_position_of_char_follows_baseline_shift() {
+    setup_font_map();
+
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="200" height="100" font-family="Roboto" font-size="20">
+  <text id="text" x="10" y="50">A<tspan baseline-shift="10px">B</tspan><tspan dy="5" baseline-shift="-0.5em">C</tspan></text>
+  <text id="length" x="10" y="80" textLength="150" letter-spacing="0.1em">A<tspan baseline-shift="1ex">B</tspan></text>
+</svg>
+"#,
+    )
+    .unwrap();
+
+    let renderer = CairoRenderer::new(&svg).test_mode();
+
+    let start = |id, i| renderer.start_position_of_char(id, i).unwrap().unwrap();
+
+    assert!((start("#text", 0).1 - 50.0).abs() < 0.01);
+    assert!((start("#text", 1).1 - 40.0).abs() < 0.01);
+    assert!((start("#text", 2).1 - 65.0).abs() < 0.01);
+
+    // Spans that get more letter spacing for textLength keep their baseline-shift
+    let length = renderer.computed_text_length("#length").unwrap();
+    assert!((length - 150.0).abs() < 0.01);
+    assert!(start("#length", 1).1 < 80.0 - 5.0);
+}
+
+#[test]
+fn start_position_of_char_follows_advances_and_dx() {
+    setup_font_map();
+
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="200" height="100" font-family="Roboto" font-size="20">
+  <text id="plain" x="10" y="30">ab   c</text>
+  <text id="shifted" x="10" y="60" dx="5">a<tspan dx="7">bc</tspan></text>
+</svg>
+"#,
+    )
+    .unwrap();
+
+    let renderer = CairoRenderer::new(&svg).test_mode();
+
+    let (x0, y0) = renderer
+        .start_position_of_char("#plain", 0)
+        .unwrap()
+        .unwrap();
+    assert!((x0 - 10.0).abs() < 0.01);
+    assert!((y0 - 30.0).abs() < 0.01);
+
+    let (x1, y1) = renderer
+        .start_position_of_char("#plain", 1)
+        .unwrap()
+        .unwrap();
+    assert!(x1 > x0);
+    assert!((y1 - 30.0).abs() < 0.01);
+
+    // The collapsed spaces count as a single character
+    let (x2, _) = renderer
+        .start_position_of_char("#plain", 2)
+        .unwrap()
+        .unwrap();
+    let (x3, _) = renderer
+        .start_position_of_char("#plain", 3)
+        .unwrap()
+        .unwrap();
+    assert!(x2 > x1);
+    assert!(x3 > x2);
+    assert!(renderer
+        .start_position_of_char("#plain", 4)
+        .unwrap()
+        .is_none());
+
+    let (sx0, sy0) = renderer
+        .start_position_of_char("#shifted", 0)
+        .unwrap()
+        .unwrap();
+    let (sx1, _) = renderer
+        .start_position_of_char("#shifted", 1)
+        .unwrap()
+        .unwrap();
+    let (sx2, _) = renderer
+        .start_position_of_char("#shifted", 2)
+        .unwrap()
+        .unwrap();
+
+    assert!((sx0 - 15.0).abs() < 0.01);
+    assert!((sy0 - 60.0).abs() < 0.01);
+
+    // The tspan's dx shifts its first character and everything after it
+    assert!((sx1 - (x1 + 5.0 + 7.0)).abs() < 0.01);
+    assert!((sx2 - (x2 + 5.0 + 7.0)).abs() < 0.1);
+}
+
+#[test]
+fn computed_text_length_sums_the_advances() {
+    setup_font_map();
+
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="200" height="200" font-family="Roboto" font-size="20">
+  <text id="plain" x="10" y="30">Hello</text>
+  <text id="split" x="10" y="60">He<tspan dx="10" dy="5">llo</tspan></text>
+  <text id="spacing" x="10" y="90" textLength="150">Hello</text>
+  <text id="glyphs" x="10" y="120" textLength="150" lengthAdjust="spacingAndGlyphs">Hello</text>
+  <text id="spans" x="10" y="150" textLength="150"><tspan>H</tspan><tspan>e</tspan><tspan>l</tspan><tspan>l</tspan><tspan>o</tspan></text>
+</svg>
+"#,
+    )
+    .unwrap();
+
+    let renderer = CairoRenderer::new(&svg)
+        .test_mode()
+        .with_text_extents(TextExtents::Logical);
+
+    let (_, logical) = renderer.geometry_for_element(Some("#plain")).unwrap();
+
+    let plain = renderer.computed_text_length("#plain").unwrap();
+    assert!((plain - logical.width).abs() < 0.01);
+
+    let split = renderer.computed_text_length("#split").unwrap();
+    assert!((split - plain).abs() < 0.01);
+
+    let spacing = renderer.computed_text_length("#spacing").unwrap();
+    assert!((spacing - 150.0).abs() < 0.01);
+
+    // Each span has its own layout, but the spacing goes between all the characters
+    let spans = renderer.computed_text_length("#spans").unwrap();
+    assert!((spans - 150.0).abs() < 0.01);
+
+    let glyphs = renderer.computed_text_length("#glyphs").unwrap();
+    assert!((glyphs - 150.0).abs() < 0.1);
+}
+
+#[test]
+fn sub_string_length_crosses_spans() {
+    setup_font_map();
+
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="200" height="100" font-family="Roboto" font-size="20">
+  <text id="text" x="10" y="30">ab<tspan fill="red">cd</tspan><tspan x="100">ef</tspan></text>
+</svg>
+"#,
+    )
+    .unwrap();
+
+    let renderer = CairoRenderer::new(&svg).test_mode();
+
+    let start = |i| {
+        renderer
+            .start_position_of_char("#text", i)
+            .unwrap()
+            .unwrap()
+            .0
+    };
+
+    let b_to_c = renderer.sub_string_length("#text", 1, 2).unwrap().unwrap();
+    assert!((b_to_c - (start(3) - start(1))).abs() < 0.01);
+
+    // Across the chunks, without the jump to x="100"
+    let whole = renderer.sub_string_length("#text", 0, 6).unwrap().unwrap();
+    let total = renderer.computed_text_length("#text").unwrap();
+    assert!((whole - total).abs() < 0.01);
+
+    let d_to_f = renderer.sub_string_length("#text", 3, 3).unwrap().unwrap();
+    let d = renderer.sub_string_length("#text", 3, 1).unwrap().unwrap();
+    let ef = renderer.sub_string_length("#text", 4, 2).unwrap().unwrap();
+    assert!((d_to_f - (d + ef)).abs() < 0.01);
+
+    // The range gets cut at the end of the text
+    let past_end = renderer.sub_string_length("#text", 4, 10).unwrap().unwrap();
+    assert!((past_end - ef).abs() < 0.01);
+
+    assert!(renderer.sub_string_length("#text", 6, 1).unwrap().is_none());
+}
+
+#[test]
+fn extent_of_char_covers_the_advance_and_the_line() {
+    setup_font_map();
+
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="200" height="100" font-family="Roboto" font-size="20">
+  <text id="word" x="10" y="30">Hello</text>
+  <text id="rotated" x="10" y="60" rotate="90">Hello</text>
+</svg>
+"#,
+    )
+    .unwrap();
+
+    let renderer = CairoRenderer::new(&svg)
+        .test_mode()
+        .with_text_extents(TextExtents::Logical);
+
+    let (_, logical) = renderer.geometry_for_element(Some("#word")).unwrap();
+
+    let first = renderer.extent_of_char("#word", 0).unwrap().unwrap();
+    let last = renderer.extent_of_char("#word", 4).unwrap().unwrap();
+
+    assert!((first.x - 10.0).abs() < 0.01);
+    assert!(first.y < 30.0 && first.y + first.height > 30.0);
+    assert!((first.height - logical.height).abs() < 0.01);
+
+    let h_advance = renderer.sub_string_length("#word", 0, 1).unwrap().unwrap();
+    assert!((first.width - h_advance).abs() < 0.01);
+
+    let o_advance = renderer.sub_string_length("#word", 4, 1).unwrap().unwrap();
+    assert!((last.x + last.width - (10.0 + logical.width)).abs() < 0.01);
+    assert!((last.width - o_advance).abs() < 0.01);
+
+    assert!(renderer.extent_of_char("#word", 5).unwrap().is_none());
+
+    // A glyph rotated by 90 degrees has its advance along the y axis
+    let rotated = renderer.extent_of_char("#rotated", 0).unwrap().unwrap();
+    assert!((rotated.height - h_advance).abs() < 0.01);
+    assert!((rotated.width - first.height).abs() < 0.01);
+}
+
+#[test]
+fn rotation_of_char_follows_the_rotate_attribute() {
+    setup_font_map();
+
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="200" height="100" font-family="Roboto" font-size="20">
+  <text id="plain" x="10" y="30">abc</text>
+  <text id="rotated" x="10" y="60" rotate="10 20">ab<tspan rotate="-5">cd</tspan></text>
+</svg>
+"#,
+    )
+    .unwrap();
+
+    let renderer = CairoRenderer::new(&svg).test_mode();
+
+    assert_eq!(renderer.rotation_of_char("#plain", 1).unwrap(), Some(0.0));
+
+    let rotations: Vec<_> = (0..4)
+        .map(|i| renderer.rotation_of_char("#rotated", i).unwrap().unwrap())
+        .collect();
+
+    assert_eq!(rotations, vec![10.0, 20.0, -5.0, -5.0]);
+
+    assert!(renderer.rotation_of_char("#rotated", 4).unwrap().is_none());
+}
+
+#[test]
+fn char_num_at_position_hits_the_character_cells() {
+    setup_font_map();
+
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="200" height="100" font-family="Roboto" font-size="20">
+  <text id="text" x="10" y="30">ab   <tspan fill="red">cd</tspan></text>
+</svg>
+"#,
+    )
+    .unwrap();
+
+    let renderer = CairoRenderer::new(&svg).test_mode();
+
+    for i in 0..5 {
+        let cell = renderer.extent_of_char("#text", i).unwrap().unwrap();
+        let (x, y) = (cell.x + cell.width / 2.0, cell.y + cell.height / 2.0);
+
+        assert_eq!(
+            renderer.char_num_at_position("#text", x, y).unwrap(),
+            Some(i)
+        );
+    }
+
+    // Outside of the text run
+    assert_eq!(
+        renderer.char_num_at_position("#text", 5.0, 25.0).unwrap(),
+        None
+    );
+    assert_eq!(
+        renderer.char_num_at_position("#text", 20.0, 90.0).unwrap(),
+        None
+    );
+    assert_eq!(
+        renderer.char_num_at_position("#text", 190.0, 25.0).unwrap(),
+        None
+    );
+}
+
+#[test]
+fn selection_extents_cover_a_range_of_characters() {
+    setup_font_map();
+
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="200" height="100" font-family="Roboto" font-size="20">
+  <text id="word" x="10" y="30">Hello</text>
+  <text id="chunks" x="10" y="60">ab<tspan fill="red">cd</tspan><tspan x="100">ef</tspan></text>
+</svg>
+"#,
+    )
+    .unwrap();
+
+    let renderer = CairoRenderer::new(&svg).test_mode();
+
+    // "ell" in the middle of the word
+    let rects = renderer.selection_extents("#word", 1, 4).unwrap();
+    assert_eq!(rects.len(), 1);
+
+    let e = renderer.extent_of_char("#word", 1).unwrap().unwrap();
+    let l = renderer.extent_of_char("#word", 3).unwrap().unwrap();
+
+    let selection = rects[0];
+    assert!((selection.x - e.x).abs() < 0.01);
+    assert!((selection.x + selection.width - (l.x + l.width)).abs() < 0.01);
+    assert!((selection.y - e.y).abs() < 0.01);
+    assert!((selection.height - e.height).abs() < 0.01);
+
+    // "bcde" crosses a span, and then the jump to a new chunk at x="100"
+    let rects = renderer.selection_extents("#chunks", 1, 5).unwrap();
+    assert_eq!(rects.len(), 2);
+
+    let b = renderer.extent_of_char("#chunks", 1).unwrap().unwrap();
+    let d = renderer.extent_of_char("#chunks", 3).unwrap().unwrap();
+    let e = renderer.extent_of_char("#chunks", 4).unwrap().unwrap();
+
+    assert!((rects[0].x - b.x).abs() < 0.01);
+    assert!((rects[0].x + rects[0].width - (d.x + d.width)).abs() < 0.01);
+    assert!((rects[1].x - 100.0).abs() < 0.01);
+    assert!((rects[1].width - e.width).abs() < 0.01);
+
+    // The range stops at the end of the text
+    assert_eq!(renderer.selection_extents("#word", 3, 10).unwrap().len(), 1);
+    assert!(renderer
+        .selection_extents("#word", 2, 2)
+        .unwrap()
+        .is_empty());
+    assert!(renderer
+        .selection_extents("#word", 5, 6)
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn deeply_nested_text_gets_cut_off() {
+    setup_font_map();
+
+    let depth = 150;
+
+    let svg = format!(
+        r##"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink"
+     width="200" height="100" font-family="Roboto" font-size="20">
+  <defs>
+    <g id="source">{open_groups}B{close_groups}</g>
+  </defs>
+  <text id="shallow" x="10" y="20">A</text>
+  <text id="tspans" x="10" y="50">A{open_tspans}B{close_tspans}</text>
+  <text id="tref" x="10" y="80">A<tref xlink:href="#source"/></text>
+</svg>
+"##,
+        open_groups = "<g>".repeat(depth),
+        close_groups = "</g>".repeat(depth),
+        open_tspans = "<tspan>".repeat(depth),
+        close_tspans = "</tspan>".repeat(depth),
+    );
+
+    let bytes = glib::Bytes::from_owned(svg.into_bytes());
+    let stream = gio::MemoryInputStream::from_bytes(&bytes);
+    let svg = Loader::new()
+        .read_stream(&stream, None::<&gio::File>, None::<&gio::Cancellable>)
+        .unwrap();
+
+    let renderer = CairoRenderer::new(&svg).test_mode();
+
+    let shallow = renderer.computed_text_length("#shallow").unwrap();
+
+    // The "B" is too deep inside the elements, so only the "A" remains
+    let tspans = renderer.computed_text_length("#tspans").unwrap();
+    assert!((tspans - shallow).abs() < 0.01);
+
+    let tref = renderer.computed_text_length("#tref").unwrap();
+    assert!((tref - shallow).abs() < 0.01);
+}
+
+#[test]
+fn tref_reference_cycles_get_broken() {
+    setup_font_map();
+
+    let svg = load_svg(
+        br##"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink"
+     width="200" height="100" font-family="Roboto" font-size="20">
+  <defs>
+    <text id="a">x<tref xlink:href="#b"/></text>
+    <text id="b">y<tref xlink:href="#a"/></text>
+  </defs>
+  <text id="cycle" x="10" y="30"><tref xlink:href="#a"/></text>
+  <text id="expected" x="10" y="60">xy</text>
+</svg>
+"##,
+    )
+    .unwrap();
+
+    let renderer = CairoRenderer::new(&svg).test_mode();
+
+    let cycle = renderer.computed_text_length("#cycle").unwrap();
+    let expected = renderer.computed_text_length("#expected").unwrap();
+
+    assert!((cycle - expected).abs() < 0.01);
+}
+
+#[test]
+fn bounding_box_for_element_is_in_the_nearest_viewport() {
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="200" height="100">
+  <defs>
+    <rect id="unused" x="10" y="20" width="30" height="40"/>
+  </defs>
+  <rect id="plain" x="10" y="20" width="30" height="40"/>
+  <g transform="translate(5, 5)">
+    <rect id="moved" x="10" y="20" width="30" height="40" stroke="black" stroke-width="2"/>
+  </g>
+  <svg x="100" y="0" width="100" height="100" viewBox="0 0 50 50">
+    <g transform="translate(5, 0)">
+      <rect id="nested" x="5" y="10" width="20" height="20"/>
+    </g>
+  </svg>
+</svg>
+"#,
+    )
+    .unwrap();
+
+    let renderer = CairoRenderer::new(&svg);
+
+    let viewport = cairo::Rectangle {
+        x: 0.0,
+        y: 0.0,
+        width: 200.0,
+        height: 100.0,
+    };
+
+    // In the toplevel SVG, the nearest viewport has the same coordinates as the canvas
+    for &id in &["#plain", "#moved"] {
+        assert_eq!(
+            renderer.bounding_box_for_element(id).unwrap(),
+            renderer.geometry_for_layer(Some(id), &viewport).unwrap()
+        );
+    }
+
+    let (_, logical_r) = renderer.bounding_box_for_element("#moved").unwrap();
+    assert_eq!(
+        logical_r,
+        cairo::Rectangle {
+            x: 15.0,
+            y: 25.0,
+            width: 30.0,
+            height: 40.0,
+        }
+    );
+
+    // The nested SVG's viewBox scales its contents by 2 on the canvas, but not in its own
+    // user space
+    let nested = cairo::Rectangle {
+        x: 10.0,
+        y: 10.0,
+        width: 20.0,
+        height: 20.0,
+    };
+    assert_eq!(
+        renderer.bounding_box_for_element("#nested").unwrap(),
+        (nested, nested)
+    );
+
+    let (_, nested_on_canvas) = renderer
+        .geometry_for_layer(Some("#nested"), &viewport)
+        .unwrap();
+    assert_eq!(
+        nested_on_canvas,
+        cairo::Rectangle {
+            x: 120.0,
+            y: 20.0,
+            width: 40.0,
+            height: 40.0,
+        }
+    );
+
+    let empty = cairo::Rectangle {
+        x: 0.0,
+        y: 0.0,
+        width: 0.0,
+        height: 0.0,
+    };
+    assert_eq!(
+        renderer.bounding_box_for_element("#unused").unwrap(),
+        (empty, empty)
+    );
+}
+
+#[test]
+fn registered_extension_predicate_picks_switch_branch() {
+    use std::cell::Cell;
+
+    let supported = Rc::new(Cell::new(true));
+    let s = supported.clone();
+
+    let bytes = glib::Bytes::from_static(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <switch>
+    <rect requiredExtensions="http://example.com/ext" width="50" height="100"/>
+    <rect x="50" width="50" height="100"/>
+  </switch>
+</svg>
+"#,
+    );
+    let stream = gio::MemoryInputStream::from_bytes(&bytes);
+
+    let svg = Loader::new()
+        .with_extension("http://example.com/ext", move || s.get())
+        .read_stream(&stream, None::<&gio::File>, None::<&gio::Cancellable>)
+        .unwrap();
+
+    let viewport = cairo::Rectangle {
+        x: 0.0,
+        y: 0.0,
+        width: 100.0,
+        height: 100.0,
+    };
+
+    let output = render_document(&svg, SurfaceSize(100, 100), |_| (), viewport).unwrap();
+    assert!(output.get_pixel(25, 50).a > 0);
+    assert_eq!(output.get_pixel(75, 50).a, 0);
+
+    // The predicate gets called again when rendering
+    supported.set(false);
+
+    let output = render_document(&svg, SurfaceSize(100, 100), |_| (), viewport).unwrap();
+    assert_eq!(output.get_pixel(25, 50).a, 0);
+    assert!(output.get_pixel(75, 50).a > 0);
+}
+
+#[test]
+fn explains_conditional_processing() {
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <switch>
+    <rect id="french" systemLanguage="fr" width="10" height="10"/>
+    <rect id="german" systemLanguage="de"
+          requiredFeatures="http://www.w3.org/TR/SVG11/feature#Shape" width="10" height="10"/>
+  </switch>
+  <rect width="10" height="10"/>
+</svg>
+"#,
+    )
+    .unwrap();
+
+    let language = Language::AcceptLanguage(AcceptLanguage::parse("de-CH, de").unwrap());
+    let elements = CairoRenderer::new(&svg)
+        .with_language(&language)
+        .conditional_elements();
+
+    assert_eq!(
+        elements,
+        vec![
+            ConditionalElement {
+                element_name: String::from("rect"),
+                id: Some(String::from("french")),
+                conditions: vec![ConditionResult {
+                    attribute: String::from("systemLanguage"),
+                    value: String::from("fr"),
+                    passed: false,
+                    reason: String::from("none of the languages match the user's languages"),
+                }],
+            },
+            ConditionalElement {
+                element_name: String::from("rect"),
+                id: Some(String::from("german")),
+                conditions: vec![
+                    ConditionResult {
+                        attribute: String::from("systemLanguage"),
+                        value: String::from("de"),
+                        passed: true,
+                        reason: String::from("one of the languages matches the user's languages"),
+                    },
+                    ConditionResult {
+                        attribute: String::from("requiredFeatures"),
+                        value: String::from("http://www.w3.org/TR/SVG11/feature#Shape"),
+                        passed: true,
+                        reason: String::from("all the features are supported"),
+                    },
+                ],
+            },
+        ]
+    );
+}
+
+#[test]
+fn switch_can_pick_the_best_language_match() {
+    let input = br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <switch>
+    <rect systemLanguage="en" x="0" width="25" height="100"/>
+    <rect systemLanguage="en-GB" x="25" width="25" height="100"/>
+    <rect systemLanguage="de" x="50" width="25" height="100"/>
+    <rect x="75" width="25" height="100"/>
+  </switch>
+</svg>
+"#;
+
+    let viewport = cairo::Rectangle {
+        x: 0.0,
+        y: 0.0,
+        width: 100.0,
+        height: 100.0,
+    };
+
+    // Returns the index of the rectangle that got rendered
+    let rendered = |best_language_match: bool, languages: &str| {
+        let bytes = glib::Bytes::from_static(input);
+        let stream = gio::MemoryInputStream::from_bytes(&bytes);
+
+        let svg = Loader::new()
+            .with_best_language_match(best_language_match)
+            .read_stream(&stream, None::<&gio::File>, None::<&gio::Cancellable>)
+            .unwrap();
+
+        let language = Language::AcceptLanguage(AcceptLanguage::parse(languages).unwrap());
+        let renderer = CairoRenderer::new(&svg).with_language(&language);
+
+        let output = cairo::ImageSurface::create(cairo::Format::ARgb32, 100, 100).unwrap();
+        {
+            let cr = cairo::Context::new(&output).unwrap();
+            renderer.render_document(&cr, &viewport).unwrap();
+        }
+        let output = SharedImageSurface::wrap(output, SurfaceType::SRgb).unwrap();
+
+        let drawn: Vec<usize> = (0..4)
+            .filter(|i| output.get_pixel(*i as u32 * 25 + 12, 50).a > 0)
+            .collect();
+        assert_eq!(drawn.len(), 1);
+        drawn[0]
+    };
+
+    // The first match wins by default
+    assert_eq!(rendered(false, "en-GB"), 0);
+
+    assert_eq!(rendered(true, "en-GB"), 1);
+    assert_eq!(rendered(true, "en-US"), 0);
+    assert_eq!(rendered(true, "de, en;q=0.5"), 2);
+    assert_eq!(rendered(true, "fr"), 3);
+}
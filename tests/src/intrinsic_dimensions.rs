@@ -48,6 +48,26 @@ fn has_intrinsic_dimensions() {
     );
 }
 
+#[test]
+fn intrinsic_dimensions_in_font_relative_units() {
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="10ch" height="2rem"/>
+"#,
+    )
+    .unwrap();
+
+    // The units that the API does not have get converted to em
+    assert_eq!(
+        CairoRenderer::new(&svg).intrinsic_dimensions(),
+        IntrinsicDimensions {
+            width: Some(Length::new(5.0, LengthUnit::Em)),
+            height: Some(Length::new(2.0, LengthUnit::Em)),
+            vbox: None,
+        }
+    );
+}
+
 #[test]
 fn intrinsic_size_in_pixels() {
     let svg = load_svg(
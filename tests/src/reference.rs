@@ -393,3 +393,842 @@ test_compare_render_output!(
   </svg>
     "##,
 );
+
+test_compare_render_output!(
+    text_decoration_thickness_from_font_is_default,
+    200,
+    100,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="200" height="100">
+      <text x="10" y="50" font-size="20" text-decoration="underline"
+            text-decoration-thickness="from-font" text-underline-offset="auto">XXXXXXXXXX</text>
+    </svg>"##,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="200" height="100">
+      <text x="10" y="50" font-size="20" text-decoration="underline">XXXXXXXXXX</text>
+    </svg>"##,
+);
+
+test_compare_render_output!(
+    text_decoration_thick_underline,
+    200,
+    100,
+    // The clip rectangle only leaves visible a part of the underline, which is below the
+    // baseline, where the "X" glyphs don't have any ink.
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="200" height="100">
+      <clipPath id="below-baseline">
+        <rect x="20" y="51" width="40" height="10"/>
+      </clipPath>
+      <text x="10" y="50" font-size="20" text-decoration="underline" clip-path="url(#below-baseline)"
+            text-decoration-thickness="4px" text-underline-offset="2px">XXXXXXXXXX</text>
+    </svg>"##,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="200" height="100">
+      <rect x="20" y="52" width="40" height="4"/>
+    </svg>"##,
+);
+
+test_compare_render_output!(
+    text_decoration_with_gradient,
+    200,
+    100,
+    // Same as above, but the underline comes from the <text>, so it needs to be painted
+    // with the text's gradient under the black <tspan> too.
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="200" height="100">
+      <linearGradient id="gradient" gradientUnits="userSpaceOnUse" x1="0" y1="0" x2="200" y2="0">
+        <stop offset="0" stop-color="lime"/>
+        <stop offset="1" stop-color="blue"/>
+      </linearGradient>
+      <clipPath id="below-baseline">
+        <rect x="60" y="51" width="40" height="10"/>
+      </clipPath>
+      <text x="10" y="50" font-size="20" text-decoration="underline" clip-path="url(#below-baseline)"
+            fill="url(#gradient)"
+            text-decoration-thickness="4px" text-underline-offset="2px">XX<tspan fill="black">XXXXXXXX</tspan></text>
+    </svg>"##,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="200" height="100">
+      <linearGradient id="gradient" gradientUnits="userSpaceOnUse" x1="0" y1="0" x2="200" y2="0">
+        <stop offset="0" stop-color="lime"/>
+        <stop offset="1" stop-color="blue"/>
+      </linearGradient>
+      <rect x="60" y="52" width="40" height="4" fill="url(#gradient)"/>
+    </svg>"##,
+);
+
+test_compare_render_output!(
+    text_decoration_of_ancestor_over_rotated_characters,
+    200,
+    100,
+    // Each rotated character gets measured on its own, and it must keep the underline of
+    // the <text>, just like the one of its own <tspan>.
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="200" height="100">
+      <text x="10" y="50" font-size="20" text-decoration="underline"
+            text-decoration-thickness="4px" text-underline-offset="2px"><tspan rotate="10">XXXX</tspan></text>
+    </svg>"##,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="200" height="100">
+      <text x="10" y="50" font-size="20"><tspan rotate="10" text-decoration="underline"
+            text-decoration-thickness="4px" text-underline-offset="2px">XXXX</tspan></text>
+    </svg>"##,
+);
+
+test_compare_render_output!(
+    text_length_spacing_and_glyphs,
+    300,
+    100,
+    // The underline gets scaled along with the glyphs, so it must span the whole textLength.
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="300" height="100">
+      <clipPath id="below-baseline">
+        <rect x="0" y="51" width="300" height="10"/>
+      </clipPath>
+      <text x="10" y="50" font-size="20" text-decoration="underline" clip-path="url(#below-baseline)"
+            text-decoration-thickness="4px" text-underline-offset="2px"
+            textLength="200" lengthAdjust="spacingAndGlyphs">XXXX</text>
+    </svg>"##,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="300" height="100">
+      <rect x="10" y="52" width="200" height="4"/>
+    </svg>"##,
+);
+
+test_compare_render_output!(
+    text_length_modes_end_at_the_same_place,
+    300,
+    100,
+    // The blocks are 12 units wide.  lengthAdjust="spacing" moves the last one to end at
+    // the textLength, while lengthAdjust="spacingAndGlyphs" stretches all of them into a
+    // single bar; only the ends of the text are visible.
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="300" height="100">
+      <clipPath id="ends">
+        <rect x="0" y="0" width="22" height="100"/>
+        <rect x="198" y="0" width="102" height="100"/>
+      </clipPath>
+      <text x="10" y="50" font-family="Rsvg Test Blocks" font-size="20" clip-path="url(#ends)"
+            textLength="200" lengthAdjust="spacing">XXXX</text>
+    </svg>"##,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="300" height="100">
+      <clipPath id="ends">
+        <rect x="0" y="0" width="22" height="100"/>
+        <rect x="198" y="0" width="102" height="100"/>
+      </clipPath>
+      <text x="10" y="50" font-family="Rsvg Test Blocks" font-size="20" clip-path="url(#ends)"
+            textLength="200" lengthAdjust="spacingAndGlyphs">XXXX</text>
+    </svg>"##,
+);
+
+test_compare_render_output!(
+    css_transform_overrides_transform_attribute,
+    100,
+    100,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+      <style>
+        #empty { transform: translate(20, 20); }
+        #translated { transform: translate(60, 60); }
+      </style>
+      <rect id="empty" transform="" x="0" y="0" width="10" height="10" fill="lime"/>
+      <rect id="translated" transform="translate(10, 10)" x="0" y="0" width="10" height="10" fill="blue"/>
+    </svg>"##,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+      <rect x="20" y="20" width="10" height="10" fill="lime"/>
+      <rect x="60" y="60" width="10" height="10" fill="blue"/>
+    </svg>"##,
+);
+
+test_compare_render_output!(
+    transform_attribute_without_css,
+    100,
+    100,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+      <style>
+        rect { fill: lime; }
+      </style>
+      <rect transform="translate(20, 20)" x="0" y="0" width="10" height="10"/>
+    </svg>"##,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+      <rect x="20" y="20" width="10" height="10" fill="lime"/>
+    </svg>"##,
+);
+
+test_compare_render_output!(
+    nested_baseline_shifts_add_up,
+    100,
+    100,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+      <text x="20" y="60" font-family="Roboto" font-size="20">A<tspan baseline-shift="super"><tspan baseline-shift="super">B</tspan></tspan></text>
+    </svg>"##,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+      <text x="20" y="60" font-family="Roboto" font-size="20">A<tspan baseline-shift="16px">B</tspan></text>
+    </svg>"##,
+);
+
+test_compare_render_output!(
+    small_tspan_sits_on_the_baseline_of_its_chunk,
+    100,
+    100,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+      <text x="20" y="60" font-family="Roboto" font-size="40">A<tspan font-size="15" dominant-baseline="central">b</tspan></text>
+    </svg>"##,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+      <text x="20" y="60" font-family="Roboto" font-size="40">A<tspan font-size="15">b</tspan></text>
+    </svg>"##,
+);
+
+test_compare_render_output!(
+    vertical_text_anchor_middle,
+    100,
+    200,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="100" height="200">
+      <text x="50" y="100" writing-mode="tb" text-anchor="middle" font-family="Roboto" font-size="30">HH</text>
+    </svg>"##,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="100" height="200">
+      <text x="50" y="100" writing-mode="tb" text-anchor="end" font-family="Roboto" font-size="30">H</text>
+      <text x="50" y="100" writing-mode="tb" font-family="Roboto" font-size="30">H</text>
+    </svg>"##,
+);
+
+test_compare_render_output!(
+    vertical_text_anchor_end,
+    100,
+    200,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="100" height="200">
+      <text x="50" y="100" writing-mode="tb" text-anchor="end" font-family="Roboto" font-size="30">HH</text>
+    </svg>"##,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="100" height="200">
+      <clipPath id="top">
+        <rect width="100" height="100"/>
+      </clipPath>
+      <!-- the first half of the centered text is where the end-anchored text goes -->
+      <text x="50" y="100" writing-mode="tb" text-anchor="middle" font-family="Roboto" font-size="30"
+            clip-path="url(#top)">HHHH</text>
+    </svg>"##,
+);
+
+test_compare_render_output!(
+    vertical_text_is_on_the_central_baseline_by_default,
+    100,
+    100,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+      <text x="50" y="20" writing-mode="tb" font-family="Rsvg Test Blocks" font-size="20">X&#x6587;x</text>
+    </svg>"##,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+      <!-- the em boxes of the sideways letters and of the upright ideograph are
+           centered on x; the tops of the letters point to the right -->
+      <rect x="44" y="20" width="14" height="12"/>
+      <rect x="40" y="32" width="20" height="20"/>
+      <rect x="44" y="52" width="10" height="12"/>
+    </svg>"##,
+);
+
+test_compare_render_output!(
+    underline_runs_along_the_left_side_of_vertical_text,
+    100,
+    100,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+      <text x="50" y="20" writing-mode="tb" font-family="Rsvg Test Blocks" font-size="20"
+            style="text-decoration: underline; text-decoration-thickness: 2px; text-underline-offset: 4px">&#x6587;&#x5B57;</text>
+    </svg>"##,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+      <text x="50" y="20" writing-mode="tb" font-family="Rsvg Test Blocks" font-size="20">&#x6587;&#x5B57;</text>
+      <!-- the line box goes from x=40 to x=60 -->
+      <rect x="36" y="20" width="2" height="40"/>
+    </svg>"##,
+);
+
+test_compare_render_output!(
+    vertical_tspan_in_horizontal_text,
+    100,
+    100,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+      <text x="20" y="10" font-family="Roboto" font-size="20"><tspan writing-mode="tb">AB</tspan><tspan x="60" y="50">CD</tspan></text>
+    </svg>"##,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+      <text x="20" y="10" font-family="Roboto" font-size="20" writing-mode="tb">AB</text>
+      <text x="60" y="50" font-family="Roboto" font-size="20">CD</text>
+    </svg>"##,
+);
+
+test_compare_render_output!(
+    text_anchor_of_mixed_horizontal_and_vertical_spans,
+    100,
+    100,
+    // The chunk advances 24 to the right and 40 down, so its end is at (80, 80).  The
+    // glyphs of the test font are rectangles; see tests/README.md.
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+      <text x="80" y="80" font-family="Rsvg Test Blocks" font-size="20" text-anchor="end">XX<tspan writing-mode="tb">&#x6587;&#x5B57;</tspan></text>
+    </svg>"##,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+      <rect x="56" y="26" width="24" height="14"/>
+      <rect x="70" y="40" width="20" height="40"/>
+    </svg>"##,
+);
+
+test_compare_render_output!(
+    combining_mark_is_one_addressable_character,
+    200,
+    100,
+    // "é" as a base "e" and U+0301 COMBINING ACUTE ACCENT; the second rotation must go
+    // to the "x", not to the accent.
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="200" height="100">
+      <text x="20" y="50" font-family="Roboto" font-size="20" rotate="0 90">e&#x301;x</text>
+    </svg>"##,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="200" height="100">
+      <text x="20" y="50" font-family="Roboto" font-size="20">e&#x301;<tspan rotate="90">x</tspan></text>
+    </svg>"##,
+);
+
+test_compare_render_output!(
+    emoji_zwj_sequence_is_one_addressable_character,
+    200,
+    100,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="200" height="100">
+      <text x="20" y="50" font-family="Roboto" font-size="20" rotate="0 90">&#x1F468;&#x200D;&#x1F469;&#x200D;&#x1F467;&#x200D;&#x1F466;x</text>
+    </svg>"##,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="200" height="100">
+      <text x="20" y="50" font-family="Roboto" font-size="20">&#x1F468;&#x200D;&#x1F469;&#x200D;&#x1F467;&#x200D;&#x1F466;<tspan rotate="90">x</tspan></text>
+    </svg>"##,
+);
+
+test_compare_render_output!(
+    pattern_fill_on_text,
+    200,
+    100,
+    // The rotated glyphs must not rotate the pattern, which is in the text's user space.
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="200" height="100">
+      <defs>
+        <pattern id="checks" patternUnits="userSpaceOnUse" width="8" height="8">
+          <rect width="4" height="4" fill="blue"/>
+          <rect x="4" y="4" width="4" height="4" fill="blue"/>
+        </pattern>
+      </defs>
+      <text x="20" y="70" font-family="Rsvg Test Blocks" font-size="60" rotate="20" fill="url(#checks)">XX</text>
+    </svg>"##,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="200" height="100">
+      <defs>
+        <pattern id="checks" patternUnits="userSpaceOnUse" width="8" height="8">
+          <rect width="4" height="4" fill="blue"/>
+          <rect x="4" y="4" width="4" height="4" fill="blue"/>
+        </pattern>
+        <clipPath id="word">
+          <text x="20" y="70" font-family="Rsvg Test Blocks" font-size="60" rotate="20">XX</text>
+        </clipPath>
+      </defs>
+      <rect width="200" height="100" fill="url(#checks)" clip-path="url(#word)"/>
+    </svg>"##,
+);
+
+test_compare_render_output!(
+    percentage_translate_refers_to_bounding_box,
+    100,
+    100,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+      <rect x="10" y="10" width="40" height="20" fill="lime" style="transform: translate(50%, 100%); transform-box: fill-box"/>
+    </svg>"##,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+      <rect x="30" y="30" width="40" height="20" fill="lime"/>
+    </svg>"##,
+);
+
+test_compare_render_output!(
+    transform_origin_in_fill_box,
+    100,
+    100,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+      <rect x="20" y="20" width="40" height="20" fill="lime"
+            style="transform: rotate(90); transform-origin: center; transform-box: fill-box"/>
+    </svg>"##,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+      <rect x="30" y="10" width="20" height="40" fill="lime"/>
+    </svg>"##,
+);
+
+test_compare_render_output!(
+    transform_origin_in_view_box,
+    100,
+    100,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+      <rect x="20" y="20" width="40" height="20" fill="lime"
+            style="transform: rotate(90); transform-origin: center"/>
+    </svg>"##,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+      <rect x="60" y="20" width="20" height="40" fill="lime"/>
+    </svg>"##,
+);
+
+test_compare_render_output!(
+    percentage_translate_refers_to_text_bounding_box,
+    100,
+    100,
+    // The glyph cells of "XX" go from (10, 34) to (34, 54), so the text moves by
+    // (12, 20).  The ink of the glyphs is from the baseline up to the cap height.
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+      <text x="10" y="50" font-family="Rsvg Test Blocks" font-size="20" fill="lime"
+            style="transform: translate(50%, 100%); transform-box: fill-box">XX</text>
+    </svg>"##,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+      <rect x="22" y="56" width="24" height="14" fill="lime"/>
+    </svg>"##,
+);
+
+test_compare_render_output!(
+    text_transform_origin_in_fill_box,
+    100,
+    100,
+    // Rotates around (22, 44), the center of the glyph cells.
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+      <text x="10" y="50" font-family="Rsvg Test Blocks" font-size="20" fill="lime"
+            style="transform: rotate(90); transform-origin: center; transform-box: fill-box">XX</text>
+    </svg>"##,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+      <rect x="16" y="32" width="14" height="24" fill="lime"/>
+    </svg>"##,
+);
+
+test_compare_render_output!(
+    text_transform_origin_in_view_box,
+    100,
+    100,
+    // Same as above, but rotates around (50, 50), the center of the viewport.
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+      <text x="10" y="50" font-family="Rsvg Test Blocks" font-size="20" fill="lime"
+            style="transform: rotate(90); transform-origin: center; transform-box: view-box">XX</text>
+    </svg>"##,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+      <rect x="50" y="10" width="14" height="24" fill="lime"/>
+    </svg>"##,
+);
+
+test_compare_render_output!(
+    conditional_attributes_apply_outside_switch,
+    100,
+    100,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+      <rect x="10" y="10" width="80" height="80" fill="lime"/>
+      <rect x="20" y="20" width="60" height="60" fill="red" systemLanguage="zz"/>
+      <g requiredExtensions="http://example.com/unsupported">
+        <rect x="20" y="20" width="60" height="60" fill="red"/>
+      </g>
+    </svg>"##,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+      <rect x="10" y="10" width="80" height="80" fill="lime"/>
+    </svg>"##,
+);
+
+test_compare_render_output!(
+    switch_renders_only_the_first_matching_child,
+    100,
+    100,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+      <switch>
+        <rect x="20" y="20" width="60" height="60" fill="red" systemLanguage="zz"/>
+        <rect x="10" y="10" width="40" height="80" fill="lime"/>
+        <rect x="50" y="10" width="40" height="80" fill="red" systemLanguage="*"/>
+        <rect x="10" y="10" width="80" height="80" fill="red"/>
+      </switch>
+    </svg>"##,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+      <rect x="10" y="10" width="40" height="80" fill="lime"/>
+    </svg>"##,
+);
+
+test_compare_render_output!(
+    dx_dy_apply_once_and_reset_at_new_chunks,
+    200,
+    100,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="200" height="100">
+      <text x="10" y="50" dx="30" dy="5">A<tspan dx="5">B<tspan x="100">C</tspan></tspan>D</text>
+    </svg>"##,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="200" height="100">
+      <text x="40" y="55">A<tspan dx="5">B</tspan></text>
+      <text x="100" y="55">CD</text>
+    </svg>"##,
+);
+
+test_compare_render_output!(
+    text_anchor_is_logical_for_rtl_text,
+    200,
+    200,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="200" height="200">
+      <g direction="rtl">
+        <text x="100" y="30" text-anchor="start">&#x05E9;&#x05DC;&#x05D5;&#x05DD;</text>
+        <text x="100" y="60" text-anchor="middle">&#x05E9;&#x05DC;&#x05D5;&#x05DD;</text>
+        <text x="100" y="90" text-anchor="end">&#x05E9;&#x05DC;&#x05D5;&#x05DD;</text>
+        <text x="100" y="120" text-anchor="start">&#x0633;&#x0644;&#x0627;&#x0645;</text>
+        <text x="100" y="150" text-anchor="middle">&#x0633;&#x0644;&#x0627;&#x0645;</text>
+        <text x="100" y="180" text-anchor="end">&#x0633;&#x0644;&#x0627;&#x0645;</text>
+      </g>
+    </svg>"##,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="200" height="200">
+      <text x="100" y="30" text-anchor="end">&#x05E9;&#x05DC;&#x05D5;&#x05DD;</text>
+      <text x="100" y="60" text-anchor="middle">&#x05E9;&#x05DC;&#x05D5;&#x05DD;</text>
+      <text x="100" y="90" text-anchor="start">&#x05E9;&#x05DC;&#x05D5;&#x05DD;</text>
+      <text x="100" y="120" text-anchor="end">&#x0633;&#x0644;&#x0627;&#x0645;</text>
+      <text x="100" y="150" text-anchor="middle">&#x0633;&#x0644;&#x0627;&#x0645;</text>
+      <text x="100" y="180" text-anchor="start">&#x0633;&#x0644;&#x0627;&#x0645;</text>
+    </svg>"##,
+);
+
+test_compare_render_output!(
+    rtl_chunks_advance_to_the_left,
+    200,
+    100,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="200" height="100" font-size="30">
+      <!-- the tspan starts a new chunk at the end of the first one, which is its left edge -->
+      <text x="190" y="60" direction="rtl">&#x05E9;&#x05DC;<tspan y="60">&#x05D5;&#x05DD;</tspan></text>
+    </svg>"##,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="200" height="100" font-size="30">
+      <text x="190" y="60" direction="rtl">&#x05E9;&#x05DC;&#x05D5;&#x05DD;</text>
+    </svg>"##,
+);
+
+test_compare_render_output!(
+    brackets_are_mirrored_in_rtl_text,
+    200,
+    100,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="200" height="100">
+      <text x="100" y="60" font-size="30" direction="rtl" text-anchor="middle">(&#x0633;&#x0644;&#x0627;&#x0645;)</text>
+    </svg>"##,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="200" height="100">
+      <!-- the parentheses take the left-to-right direction of the paragraph, so they are not mirrored -->
+      <text x="100" y="60" font-size="30" text-anchor="middle">(&#x0633;&#x0644;&#x0627;&#x0645;)</text>
+    </svg>"##,
+);
+
+test_compare_render_output!(
+    nested_bidi_overrides,
+    200,
+    100,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="200" height="100" font-family="Roboto" font-size="30">
+      <text x="10" y="40" unicode-bidi="bidi-override" direction="ltr">12<tspan unicode-bidi="bidi-override" direction="rtl">345</tspan>67</text>
+      <text x="10" y="80" unicode-bidi="bidi-override" direction="rtl" text-anchor="end">12<tspan unicode-bidi="bidi-override" direction="ltr">345</tspan>67</text>
+    </svg>"##,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="200" height="100" font-family="Roboto" font-size="30">
+      <text x="10" y="40">1254367</text>
+      <text x="10" y="80">7634521</text>
+    </svg>"##,
+);
+
+test_compare_render_output!(
+    rtl_word_reorders_across_tspans,
+    200,
+    100,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="200" height="100">
+      <text x="50" y="40" font-size="30">&#x05E9;&#x05DC;<tspan fill="red">&#x05D5;&#x05DD;</tspan></text>
+      <text x="50" y="80" font-size="30">&#x05E9;&#x05DC;<tspan>&#x05D5;&#x05DD;</tspan></text>
+    </svg>"##,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="200" height="100">
+      <text x="50" y="40" font-size="30">&#x05E9;&#x05DC;<tspan fill="none">&#x05D5;&#x05DD;</tspan></text>
+      <text x="50" y="40" font-size="30" fill="none">&#x05E9;&#x05DC;<tspan fill="red">&#x05D5;&#x05DD;</tspan></text>
+      <text x="50" y="80" font-size="30">&#x05E9;&#x05DC;&#x05D5;&#x05DD;</text>
+    </svg>"##,
+);
+
+test_compare_render_output!(
+    ligature_forms_across_tspans,
+    200,
+    100,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="200" height="100" font-family="Roboto" font-size="40">
+      <text x="20" y="40">f<tspan fill="red">i</tspan></text>
+      <text x="20" y="90">f<tspan>i</tspan></text>
+    </svg>"##,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="200" height="100" font-family="Roboto" font-size="40">
+      <text x="20" y="40">f<tspan fill="none">i</tspan></text>
+      <text x="20" y="40" fill="none">f<tspan fill="red">i</tspan></text>
+      <text x="20" y="90">fi</text>
+    </svg>"##,
+);
+
+test_compare_render_output!(
+    glyph_overhangs_are_not_cut_off_at_tspan_boundaries,
+    100,
+    100,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="100" height="100" font-family="Roboto" font-size="60">
+      <text x="20" y="70" font-style="italic">f<tspan fill="none">x</tspan></text>
+    </svg>"##,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="100" height="100" font-family="Roboto" font-size="60">
+      <text x="20" y="70" font-style="italic">f</text>
+    </svg>"##,
+);
+
+test_compare_render_output!(
+    rainbow_word_keeps_its_kerning,
+    200,
+    100,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="200" height="100" font-family="Roboto" font-size="40">
+      <text x="20" y="40"><tspan fill="red">W</tspan><tspan fill="orange">A</tspan><tspan fill="green">V</tspan><tspan fill="blue">E</tspan></text>
+      <text x="20" y="90"><tspan fill="black">W</tspan><tspan fill="black">A</tspan><tspan fill="black">V</tspan><tspan fill="black">E</tspan></text>
+    </svg>"##,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="200" height="100" font-family="Roboto" font-size="40">
+      <g fill="none">
+        <text x="20" y="40"><tspan fill="red">W</tspan>AVE</text>
+        <text x="20" y="40">W<tspan fill="orange">A</tspan>VE</text>
+        <text x="20" y="40">WA<tspan fill="green">V</tspan>E</text>
+        <text x="20" y="40">WAV<tspan fill="blue">E</tspan></text>
+      </g>
+      <text x="20" y="90">WAVE</text>
+    </svg>"##,
+);
+
+test_compare_render_output!(
+    oblique_angle_slants_upright_glyphs,
+    200,
+    100,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="200" height="100">
+      <g transform="translate(10 60)">
+        <text font-size="40" style="font-style: oblique 20deg">Hello</text>
+      </g>
+    </svg>"##,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="200" height="100">
+      <g transform="translate(10 60) skewX(-20)">
+        <text font-size="40">Hello</text>
+      </g>
+    </svg>"##,
+);
+
+test_compare_render_output!(
+    text_area_wraps_and_clips_lines,
+    200,
+    100,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="200" height="100" font-family="Roboto" font-size="20">
+      <textArea x="10" y="10" width="80" height="30">Hello world and more words</textArea>
+    </svg>"##,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="200" height="100" font-family="Roboto" font-size="20">
+      <textArea x="10" y="10">Hello</textArea>
+    </svg>"##,
+);
+
+test_compare_render_output!(
+    flow_root_lays_out_paragraphs_in_region,
+    200,
+    100,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg"
+         xmlns:sodipodi="http://sodipodi.sourceforge.net/DTD/sodipodi-0.dtd"
+         xmlns:inkscape="http://www.inkscape.org/namespaces/inkscape"
+         width="200" height="100" version="1.1">
+      <g inkscape:label="Layer 1" inkscape:groupmode="layer" id="layer1">
+        <flowRoot xml:space="preserve" id="flowRoot815"
+                  style="font-style:normal;font-weight:normal;font-size:20px;font-family:Roboto;fill:#000000;fill-opacity:1;stroke:none"><flowRegion
+             id="flowRegion817"><rect id="rect819" width="80" height="30" x="10" y="10" /></flowRegion><flowPara
+             id="flowPara821">Hello <flowSpan id="flowSpan823">world</flowSpan> and more words</flowPara></flowRoot>
+      </g>
+    </svg>"##,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="200" height="100" font-family="Roboto" font-size="20">
+      <textArea x="10" y="10" width="80" height="30">Hello world and more words</textArea>
+    </svg>"##,
+);
+
+test_compare_render_output!(
+    text_transform_changes_case_after_collapsing_spaces,
+    200,
+    120,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="200" height="120">
+      <text x="10" y="30" style="text-transform: uppercase">hello   world</text>
+      <text x="10" y="60" style="text-transform: lowercase">HELLO World</text>
+      <text x="10" y="90" style="text-transform: capitalize">
+        hello   world
+      </text>
+    </svg>"##,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="200" height="120">
+      <text x="10" y="30">HELLO WORLD</text>
+      <text x="10" y="60">hello world</text>
+      <text x="10" y="90">Hello World</text>
+    </svg>"##,
+);
+
+test_compare_render_output!(
+    small_caps_get_synthesized_for_fonts_without_smcp,
+    100,
+    100,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="100" height="100" font-family="Rsvg Test Blocks">
+      <text x="10" y="50" font-size="20" font-variant-caps="small-caps">Xx</text>
+    </svg>"##,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="100" height="100" font-family="Rsvg Test Blocks">
+      <text x="10" y="50" font-size="20">X<tspan font-size="14">X</tspan></text>
+    </svg>"##,
+);
+
+test_compare_render_output!(
+    capitalize_continues_words_across_spans,
+    200,
+    100,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="200" height="100" font-family="Roboto" font-size="20">
+      <text x="10" y="30" text-transform="capitalize">foo<tspan>bar</tspan></text>
+      <text x="10" y="60" text-transform="capitalize">foo <tspan>bar</tspan></text>
+    </svg>"##,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="200" height="100" font-family="Roboto" font-size="20">
+      <text x="10" y="30">Foobar</text>
+      <text x="10" y="60">Foo Bar</text>
+    </svg>"##,
+);
+
+test_compare_render_output!(
+    long_words_do_not_wrap_by_default,
+    300,
+    100,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="300" height="100" font-family="Roboto" font-size="20">
+      <textArea x="10" y="10" width="100">https://example.com/a/long/path</textArea>
+    </svg>"##,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="300" height="100" font-family="Roboto" font-size="20">
+      <textArea x="10" y="10">https://example.com/a/long/path</textArea>
+    </svg>"##,
+);
+
+test_compare_render_output!(
+    tspan_with_mix_blend_mode,
+    100,
+    100,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+      <rect width="100" height="100" fill="cyan"/>
+      <text x="10" y="60" font-family="Roboto" font-size="40">A<tspan fill="yellow" style="mix-blend-mode: multiply">BC</tspan></text>
+    </svg>"##,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+      <rect width="100" height="100" fill="cyan"/>
+      <text x="10" y="60" font-family="Roboto" font-size="40">A<tspan fill="lime">BC</tspan></text>
+    </svg>"##,
+);
+
+test_compare_render_output!(
+    text_in_use_gets_context_paints,
+    100,
+    100,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" width="100" height="100">
+      <defs>
+        <text id="template" x="10" y="60" font-family="Roboto" font-size="40" fill="context-fill" stroke="context-stroke">AB</text>
+      </defs>
+      <use xlink:href="#template" fill="lime" stroke="blue"/>
+    </svg>"##,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+      <text x="10" y="60" font-family="Roboto" font-size="40" fill="lime" stroke="blue">AB</text>
+    </svg>"##,
+);
+
+test_compare_render_output!(
+    translucent_tspan_is_a_group,
+    100,
+    100,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+      <text x="10" y="60" font-family="Roboto" font-size="40"><tspan opacity="0.5">A<tspan dx="-15">B</tspan></tspan></text>
+    </svg>"##,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+      <g opacity="0.5">
+        <text x="10" y="60" font-family="Roboto" font-size="40"><tspan>A<tspan dx="-15">B</tspan></tspan></text>
+      </g>
+    </svg>"##,
+);
+
+test_compare_render_output!(
+    non_scaling_stroke_on_text,
+    100,
+    100,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+      <g transform="scale(2)">
+        <text x="5" y="30" font-family="Roboto" font-size="20" fill="none" stroke="black" stroke-width="2" vector-effect="non-scaling-stroke">AB</text>
+      </g>
+    </svg>"##,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+      <text x="10" y="60" font-family="Roboto" font-size="40" fill="none" stroke="black" stroke-width="2">AB</text>
+    </svg>"##,
+);
+
+test_compare_render_output!(
+    text_paint_order_with_markers,
+    100,
+    100,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+      <text x="10" y="60" font-family="Roboto" font-size="40" fill="lime" stroke="blue" stroke-width="6" paint-order="markers stroke fill">AB</text>
+    </svg>"##,
+    br##"<?xml version="1.0" encoding="UTF-8"?>
+    <svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+      <text x="10" y="60" font-family="Roboto" font-size="40" fill="none" stroke="blue" stroke-width="6">AB</text>
+      <text x="10" y="60" font-family="Roboto" font-size="40" fill="lime">AB</text>
+    </svg>"##,
+);
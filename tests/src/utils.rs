@@ -69,6 +69,7 @@ mod pango_ft2 {
             "tests/resources/Roboto-Italic.ttf",
             "tests/resources/Roboto-Bold.ttf",
             "tests/resources/Roboto-BoldItalic.ttf",
+            "tests/resources/RsvgTestBlocks.ttf",
         ];
 
         let config = fontconfig::FcConfigCreate();